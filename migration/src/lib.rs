@@ -6,6 +6,16 @@ mod m20220101_000001_users;
 mod m20250411_134017_git_repos;
 
 mod m20250819_161131_sshes;
+mod m20251017_101204_add_user_ref_to_git_repos;
+mod m20251017_134522_add_user_ref_to_sshes;
+mod m20261017_090000_repo_insights;
+mod m20261017_120000_add_metadata_to_git_repos;
+mod m20261017_140000_personal_access_tokens;
+mod m20261017_160000_add_two_factor_to_users;
+mod m20261017_180000_add_login_throttling_to_users;
+mod m20261017_200000_sessions;
+mod m20261017_220000_unique_user_names;
+mod reference;
 pub struct Migrator;
 
 #[async_trait::async_trait]
@@ -15,6 +25,15 @@ impl MigratorTrait for Migrator {
             Box::new(m20220101_000001_users::Migration),
             Box::new(m20250411_134017_git_repos::Migration),
             Box::new(m20250819_161131_sshes::Migration),
+            Box::new(m20251017_101204_add_user_ref_to_git_repos::Migration),
+            Box::new(m20251017_134522_add_user_ref_to_sshes::Migration),
+            Box::new(m20261017_090000_repo_insights::Migration),
+            Box::new(m20261017_120000_add_metadata_to_git_repos::Migration),
+            Box::new(m20261017_140000_personal_access_tokens::Migration),
+            Box::new(m20261017_160000_add_two_factor_to_users::Migration),
+            Box::new(m20261017_180000_add_login_throttling_to_users::Migration),
+            Box::new(m20261017_200000_sessions::Migration),
+            Box::new(m20261017_220000_unique_user_names::Migration),
             // inject-above (do not remove this comment)
         ]
     }
@@ -0,0 +1,33 @@
+use sea_orm_migration::prelude::*;
+
+use crate::reference::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        add_user_reference(m, "git_repos").await?;
+
+        // Repositories created before they had owners go to the first user.
+        // Their directories are moved under that user's namespace by
+        // `cargo loco task namespace_repos`.
+        match first_user_id(m).await? {
+            Some(user_id) => assign_owner(m, "git_repos", user_id).await?,
+            None if count_ownerless(m, "git_repos").await? > 0 => {
+                return Err(DbErr::Migration(
+                    "git_repos has rows but there is no user to own them; create a user first"
+                        .to_string(),
+                ));
+            }
+            None => {}
+        }
+
+        require_user_reference(m, "git_repos").await
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        remove_user_reference(m, "git_repos").await
+    }
+}
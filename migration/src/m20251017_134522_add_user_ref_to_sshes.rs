@@ -0,0 +1,34 @@
+use sea_orm_migration::prelude::*;
+
+use crate::reference::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        add_user_reference(m, "sshes").await?;
+
+        // Keys added before keys had owners could reach every repository.
+        // Like those repositories, they go to the first user, which keeps
+        // their access to the repositories they used. `cargo loco task
+        // authorized_keys` then pins them to `gitcrab-serve`.
+        match first_user_id(m).await? {
+            Some(user_id) => assign_owner(m, "sshes", user_id).await?,
+            None if count_ownerless(m, "sshes").await? > 0 => {
+                return Err(DbErr::Migration(
+                    "sshes has rows but there is no user to own them; create a user first"
+                        .to_string(),
+                ));
+            }
+            None => {}
+        }
+
+        require_user_reference(m, "sshes").await
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        remove_user_reference(m, "sshes").await
+    }
+}
@@ -0,0 +1,70 @@
+use loco_rs::schema::*;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        create_table(m, "repo_insights",
+            &[
+            
+            ("id", ColType::PkAuto),
+            
+            ("head_commit", ColType::StringNull),
+            ("total_commits", ColType::Integer),
+            ],
+            &[
+            ("git_repos", ""),
+            ]
+        ).await?;
+        create_table(m, "repo_insight_weeks",
+            &[
+            
+            ("id", ColType::PkAuto),
+            
+            ("week_start", ColType::Date),
+            ("commits", ColType::Integer),
+            ],
+            &[
+            ("git_repos", ""),
+            ]
+        ).await?;
+        create_table(m, "repo_insight_contributors",
+            &[
+            
+            ("id", ColType::PkAuto),
+            
+            ("name", ColType::String),
+            ("email", ColType::String),
+            ("commits", ColType::Integer),
+            ("additions", ColType::BigInteger),
+            ("deletions", ColType::BigInteger),
+            ],
+            &[
+            ("git_repos", ""),
+            ]
+        ).await?;
+        create_table(m, "repo_insight_punchcards",
+            &[
+            
+            ("id", ColType::PkAuto),
+            
+            ("weekday", ColType::SmallInteger),
+            ("hour", ColType::SmallInteger),
+            ("commits", ColType::Integer),
+            ],
+            &[
+            ("git_repos", ""),
+            ]
+        ).await
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        drop_table(m, "repo_insight_punchcards").await?;
+        drop_table(m, "repo_insight_contributors").await?;
+        drop_table(m, "repo_insight_weeks").await?;
+        drop_table(m, "repo_insights").await
+    }
+}
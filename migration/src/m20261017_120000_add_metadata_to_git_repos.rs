@@ -0,0 +1,22 @@
+use loco_rs::schema::*;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        add_column(m, "git_repos", "description", ColType::TextNull).await?;
+        add_column(m, "git_repos", "topics", ColType::StringNull).await?;
+        add_column(m, "git_repos", "homepage", ColType::StringNull).await?;
+        add_column(m, "git_repos", "visibility", ColType::StringWithDefault("private".to_string())).await
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        remove_column(m, "git_repos", "visibility").await?;
+        remove_column(m, "git_repos", "homepage").await?;
+        remove_column(m, "git_repos", "topics").await?;
+        remove_column(m, "git_repos", "description").await
+    }
+}
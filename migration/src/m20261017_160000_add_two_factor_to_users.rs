@@ -0,0 +1,28 @@
+use loco_rs::schema::*;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        add_column(m, "users", "totp_secret", ColType::StringNull).await?;
+        add_column(m, "users", "totp_enabled_at", ColType::TimestampWithTimeZoneNull).await?;
+        add_column(m, "users", "totp_last_step", ColType::BigIntegerNull).await?;
+        add_column(m, "users", "totp_recovery_codes", ColType::TextNull).await?;
+        add_column(m, "users", "totp_challenge_token", ColType::StringNull).await?;
+        add_column(m, "users", "totp_challenge_expiration", ColType::TimestampWithTimeZoneNull).await?;
+        add_column(m, "users", "totp_challenge_attempts", ColType::IntegerWithDefault(0)).await
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        remove_column(m, "users", "totp_challenge_attempts").await?;
+        remove_column(m, "users", "totp_challenge_expiration").await?;
+        remove_column(m, "users", "totp_challenge_token").await?;
+        remove_column(m, "users", "totp_recovery_codes").await?;
+        remove_column(m, "users", "totp_last_step").await?;
+        remove_column(m, "users", "totp_enabled_at").await?;
+        remove_column(m, "users", "totp_secret").await
+    }
+}
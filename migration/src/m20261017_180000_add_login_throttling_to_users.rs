@@ -0,0 +1,18 @@
+use loco_rs::schema::*;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        add_column(m, "users", "failed_login_attempts", ColType::IntegerWithDefault(0)).await?;
+        add_column(m, "users", "locked_until", ColType::TimestampWithTimeZoneNull).await
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        remove_column(m, "users", "locked_until").await?;
+        remove_column(m, "users", "failed_login_attempts").await
+    }
+}
@@ -0,0 +1,44 @@
+use loco_rs::schema::*;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        create_table(m, "sessions",
+            &[
+            
+            ("id", ColType::PkAuto),
+            
+            ("pid", ColType::UuidUniq),
+            ("user_agent", ColType::StringNull),
+            ("ip_address", ColType::StringNull),
+            ("last_seen_at", ColType::TimestampWithTimeZone),
+            ("expires_at", ColType::TimestampWithTimeZone),
+            ],
+            &[
+            ("users", ""),
+            ]
+        ).await?;
+
+        // Data of the `axum_session` layer, keyed by the id in its cookie.
+        m.create_table(
+            Table::create()
+                .table(Alias::new("session_store"))
+                .if_not_exists()
+                .col(ColumnDef::new(Alias::new("id")).string().not_null().primary_key())
+                .col(ColumnDef::new(Alias::new("session")).text().not_null())
+                .col(ColumnDef::new(Alias::new("expires")).big_integer().not_null())
+                .to_owned(),
+        )
+        .await
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        m.drop_table(Table::drop().table(Alias::new("session_store")).to_owned())
+            .await?;
+        drop_table(m, "sessions").await
+    }
+}
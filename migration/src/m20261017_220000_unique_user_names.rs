@@ -0,0 +1,73 @@
+use std::collections::HashSet;
+
+use sea_orm_migration::{prelude::*, sea_orm::ConnectionTrait};
+
+const INDEX: &str = "idx-users-name";
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// Replaces every character a namespace cannot hold with `-`.
+fn to_namespace(name: &str) -> String {
+    name.trim()
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+        .collect()
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        // Names become the namespace of the user's repositories. Names that
+        // cannot be one, or that an older user already has, are rewritten and
+        // suffixed with the user id, oldest users keeping theirs.
+        let stmt = Query::select()
+            .columns([Alias::new("id"), Alias::new("name")])
+            .from(Alias::new("users"))
+            .order_by(Alias::new("id"), Order::Asc)
+            .to_owned();
+        let rows = m
+            .get_connection()
+            .query_all(m.get_database_backend().build(&stmt))
+            .await?;
+
+        let mut taken = HashSet::new();
+        for row in rows {
+            let id = row.try_get::<i32>("", "id")?;
+            let name = row.try_get::<String>("", "name")?;
+            let mut namespace = to_namespace(&name);
+            if namespace.chars().count() < 2 {
+                namespace = format!("user-{id}");
+            }
+            while taken.contains(&namespace) {
+                namespace = format!("{namespace}-{id}");
+            }
+            if namespace != name {
+                m.exec_stmt(
+                    Query::update()
+                        .table(Alias::new("users"))
+                        .value(Alias::new("name"), namespace.clone())
+                        .and_where(Expr::col(Alias::new("id")).eq(id))
+                        .to_owned(),
+                )
+                .await?;
+            }
+            taken.insert(namespace);
+        }
+
+        m.create_index(
+            Index::create()
+                .name(INDEX)
+                .table(Alias::new("users"))
+                .col(Alias::new("name"))
+                .unique()
+                .to_owned(),
+        )
+        .await
+    }
+
+    async fn down(&self, m: &SchemaManager) -> Result<(), DbErr> {
+        m.drop_index(Index::drop().name(INDEX).table(Alias::new("users")).to_owned())
+            .await
+    }
+}
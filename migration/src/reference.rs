@@ -0,0 +1,119 @@
+//! Adds an owner to tables that may already hold rows. `add_reference`
+//! creates a NOT NULL column without a default, which the database refuses
+//! as soon as the table is not empty, so the column is added nullable, filled
+//! in by the migration, and only then made NOT NULL.
+//!
+//! On SQLite the owner is not enforced by the database: SQLite can neither add
+//! a foreign key to an existing table nor make a column NOT NULL in place, and
+//! `add_reference` skips the foreign key there too. The column stays nullable
+//! and unconstrained, and only the application guarantees that every row has
+//! an existing owner. SQLite is meant for tests and development; production
+//! databases get both constraints.
+use loco_rs::schema::*;
+use sea_orm_migration::{
+    prelude::*,
+    sea_orm::{ConnectionTrait, DbBackend},
+};
+
+const USER_ID: &str = "user_id";
+
+/// Like `add_reference(m, table, "users", "")`, but the column accepts NULL
+/// until [`require_user_reference`] is called.
+///
+/// # Errors
+/// Returns the database error when the column or the foreign key cannot be added.
+pub async fn add_user_reference(m: &SchemaManager<'_>, table: &str) -> Result<(), DbErr> {
+    add_column(m, table, USER_ID, ColType::IntegerNull).await?;
+    // SQLite cannot add a foreign key to an existing table, see `add_reference`.
+    if m.get_database_backend() == DbBackend::Sqlite {
+        return Ok(());
+    }
+    m.create_foreign_key(
+        ForeignKey::create()
+            .name(format!("fk-{table}-{USER_ID}-to-users"))
+            .from(Alias::new(table), Alias::new(USER_ID))
+            .to(Alias::new("users"), Alias::new("id"))
+            .on_delete(ForeignKeyAction::Cascade)
+            .on_update(ForeignKeyAction::Cascade)
+            .to_owned(),
+    )
+    .await
+}
+
+/// Makes the column added by [`add_user_reference`] NOT NULL, once every row
+/// has an owner.
+///
+/// Does nothing on SQLite, see the module documentation.
+///
+/// # Errors
+/// Returns the database error when a row has no owner yet.
+pub async fn require_user_reference(m: &SchemaManager<'_>, table: &str) -> Result<(), DbErr> {
+    if m.get_database_backend() == DbBackend::Sqlite {
+        return Ok(());
+    }
+    m.alter_table(
+        alter(Alias::new(table))
+            .modify_column(ColumnDef::new(Alias::new(USER_ID)).integer().not_null())
+            .to_owned(),
+    )
+    .await
+}
+
+/// Drops the column added by [`add_user_reference`], with its foreign key.
+///
+/// # Errors
+/// Returns the database error when the foreign key or the column cannot be dropped.
+pub async fn remove_user_reference(m: &SchemaManager<'_>, table: &str) -> Result<(), DbErr> {
+    remove_reference(m, table, "users", "").await?;
+    remove_column(m, table, USER_ID).await
+}
+
+/// Finds the id of the oldest user, if there is any.
+///
+/// # Errors
+/// Returns the database error when `users` cannot be read.
+pub async fn first_user_id(m: &SchemaManager<'_>) -> Result<Option<i32>, DbErr> {
+    let stmt = Query::select()
+        .column(Alias::new("id"))
+        .from(Alias::new("users"))
+        .order_by(Alias::new("id"), Order::Asc)
+        .limit(1)
+        .to_owned();
+    let row = m
+        .get_connection()
+        .query_one(m.get_database_backend().build(&stmt))
+        .await?;
+    row.map(|row| row.try_get::<i32>("", "id")).transpose()
+}
+
+/// Counts the rows of `table` without an owner.
+///
+/// # Errors
+/// Returns the database error when `table` cannot be read.
+pub async fn count_ownerless(m: &SchemaManager<'_>, table: &str) -> Result<i64, DbErr> {
+    let stmt = Query::select()
+        .expr_as(Expr::col(Alias::new("id")).count(), Alias::new("count"))
+        .from(Alias::new(table))
+        .and_where(Expr::col(Alias::new(USER_ID)).is_null())
+        .to_owned();
+    let row = m
+        .get_connection()
+        .query_one(m.get_database_backend().build(&stmt))
+        .await?;
+    row.map_or(Ok(0), |row| row.try_get::<i64>("", "count"))
+}
+
+/// Gives every row of `table` without an owner to the given user.
+///
+/// # Errors
+/// Returns the database error when `table` cannot be updated.
+pub async fn assign_owner(m: &SchemaManager<'_>, table: &str, user_id: i32) -> Result<(), DbErr> {
+    m.exec_stmt(
+        Query::update()
+            .table(Alias::new(table))
+            .value(Alias::new(USER_ID), user_id)
+            .and_where(Expr::col(Alias::new(USER_ID)).is_null())
+            .to_owned(),
+    )
+    .await
+}
@@ -1,10 +1,11 @@
-use axum::{extract::Request, http:: StatusCode, middleware::Next, response::{IntoResponse, Response}};
+use axum::{extract::Request, http::{header, StatusCode}, middleware::Next, response::{IntoResponse, Response}};
 use async_trait::async_trait;
 use axum::{response::Redirect, Router};
 use loco_rs::{
     app::{AppContext, Hooks, Initializer},
     bgworker::{BackgroundWorker, Queue},
     boot::{create_app, BootResult, StartMode},
+    cache,
     config::Config,
     controller::AppRoutes,
     db::{self, truncate_table},
@@ -18,7 +19,8 @@ use std::path::Path;
 
 #[allow(unused_imports)]
 use crate::{
-    controllers, initializers, models::_entities::users, tasks, workers::downloader::DownloadWorker,
+    controllers, initializers, models::_entities::{git_repos, users}, tasks,
+    workers::{archive::ArchiveWorker, insights::InsightsWorker, search_index::SearchIndexWorker},
 };
 
 pub struct App;
@@ -49,6 +51,7 @@ impl Hooks for App {
     async fn initializers(_ctx: &AppContext) -> Result<Vec<Box<dyn Initializer>>> {
         Ok(vec![
             Box::new(initializers::view_engine::ViewEngineInitializer),
+            Box::new(initializers::csrf::CsrfInitializer),
             Box::new(initializers::axum_session::AxumSessionInitializer)
         ])
     }
@@ -56,16 +59,40 @@ impl Hooks for App {
     fn routes(_ctx: &AppContext) -> AppRoutes {
         AppRoutes::with_default_routes() // controller routes below
             .add_route(controllers::ssh::routes())
+            .add_route(controllers::token::routes())
+            .add_route(controllers::token::api_routes())
+            .add_route(controllers::two_factor::routes())
+            .add_route(controllers::two_factor::api_routes())
+            .add_route(controllers::sessions::routes())
+            .add_route(controllers::sessions::api_routes())
             .add_route(controllers::register::routes())
             .add_route(controllers::login::routes())
             .add_route(controllers::mysession::routes())
             .add_route(controllers::git_repo::routes())
+            .add_route(controllers::git_repo::explore_routes())
+            .add_route(controllers::refs::routes())
+            .add_route(controllers::refs::api_routes())
+            .add_route(controllers::search::routes())
+            .add_route(controllers::search::global_routes())
+            .add_route(controllers::insights::routes())
+            .add_route(controllers::insights::api_routes())
+            .add_route(controllers::git_http::routes())
             .add_route(controllers::auth::routes())
             .add_route(controllers::home::routes())
     }
     
+    async fn after_context(ctx: AppContext) -> Result<AppContext> {
+        // Keeps rendered content, such as highlighted files, in memory.
+        Ok(AppContext {
+            cache: cache::Cache::new(cache::drivers::inmem::new()).into(),
+            ..ctx
+        })
+    }
+
     async fn connect_workers(ctx: &AppContext, queue: &Queue) -> Result<()> {
-        queue.register(DownloadWorker::build(ctx)).await?;
+        queue.register(ArchiveWorker::build(ctx)).await?;
+        queue.register(SearchIndexWorker::build(ctx)).await?;
+        queue.register(InsightsWorker::build(ctx)).await?;
         Ok(())
     }
     async fn after_routes(router: Router, _ctx: &AppContext) -> Result<Router> {
@@ -79,9 +106,15 @@ impl Hooks for App {
 
     #[allow(unused_variables)]
     fn register_tasks(tasks: &mut Tasks) {
+        tasks.register(tasks::search_index::SearchIndex);
+        tasks.register(tasks::insights::Insights);
+        tasks.register(tasks::reset_two_factor::ResetTwoFactor);
+        tasks.register(tasks::namespace_repos::NamespaceRepos);
+        tasks.register(tasks::authorized_keys::AuthorizedKeys);
         // tasks-inject (do not remove)
     }
     async fn truncate(ctx: &AppContext) -> Result<()> {
+        truncate_table(&ctx.db, git_repos::Entity).await?;
         truncate_table(&ctx.db, users::Entity).await?;
         Ok(())
     }
@@ -99,7 +132,11 @@ async fn redirect_unauthorized(
 ) -> Response {
     let res = Next::run(next, req).await;
 
-    if res.status() == StatusCode::UNAUTHORIZED {
+    // Responses carrying a `WWW-Authenticate` challenge (git over HTTP) must reach
+    // the client untouched so it can prompt for credentials.
+    if res.status() == StatusCode::UNAUTHORIZED
+        && !res.headers().contains_key(header::WWW_AUTHENTICATE)
+    {
         Redirect::to("/login").into_response()
     } else {
         res
@@ -0,0 +1,154 @@
+//! Forced command for the `git` user's `authorized_keys`.
+//!
+//! Every key is written as `command="gitcrab-serve --key-id N" <key>`, so sshd
+//! runs this binary instead of whatever the client asked for. The requested git
+//! command arrives in `SSH_ORIGINAL_COMMAND`; it is only executed when the key's
+//! owner has the required access to the repository.
+//!
+//! After a successful push the repository's insights, and its search index when
+//! the default branch moved, are updated by detached `gitcrab-cli` tasks, which
+//! outlive the connection. `GITCRAB_CLI` points at `gitcrab-cli` when it is not
+//! installed next to this binary.
+use std::{
+    env,
+    fs::OpenOptions,
+    io::Write,
+    os::unix::process::CommandExt,
+    process::{exit, Command},
+};
+
+use chrono::Local;
+use gitcrab::services::{
+    git_access_service::{
+        authorize_ssh_key, cli_path, parse_ssh_command, post_push_tasks, spawn_task, GitCommand,
+        GitRequest,
+    },
+    search_index_service::default_branch_head,
+};
+use loco_rs::{
+    config::Config,
+    db,
+    environment::{resolve_from_env, Environment},
+};
+
+const LOG_FILE: &str = "/var/log/git-access.log";
+
+/// Appends a line to the git access log. Logging must never prevent a git
+/// operation, so failures are ignored.
+fn log_message(key_id: &str, message: &str) {
+    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(LOG_FILE) {
+        let _ = writeln!(
+            file,
+            "{} [key:{}] {}",
+            Local::now().format("%Y-%m-%d %H:%M:%S"),
+            key_id,
+            message
+        );
+    }
+}
+
+/// Prints `message` to the client, logs it and exits with a failure status.
+fn deny(key_id: &str, message: &str) -> ! {
+    log_message(key_id, &format!("ERROR: {}", message));
+    eprintln!("{}", message);
+    exit(1)
+}
+
+/// Reads the value of `--key-id N` from the command line arguments.
+fn parse_key_id(args: &[String]) -> Option<i32> {
+    args.iter()
+        .position(|a| a == "--key-id")
+        .and_then(|i| args.get(i + 1))
+        .and_then(|id| id.parse().ok())
+}
+
+/// Starts a `gitcrab-cli` task for the pushed repository in the background.
+/// The push itself already succeeded, so a failure is only reported: in the
+/// log and to the client, which shows it as a `remote:` line.
+fn run_task(key_id: &str, task: &str, request: &GitRequest) {
+    if let Err(e) = cli_path().and_then(|cli| spawn_task(&cli, task, request)) {
+        let message = format!("Failed to run task {}: {}", task, e);
+        log_message(key_id, &format!("WARN: {}", message));
+        eprintln!("warning: {}", message);
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let Some(key_id) = parse_key_id(&args) else {
+        deny("-", "Usage: gitcrab-serve --key-id <id>");
+    };
+    let key_label = key_id.to_string();
+
+    let original = env::var("SSH_ORIGINAL_COMMAND").unwrap_or_default();
+    log_message(
+        &key_label,
+        &format!("SSH connection established - Command: {}", original),
+    );
+    if original.trim().is_empty() {
+        deny(&key_label, "No command provided");
+    }
+
+    let request = match parse_ssh_command(&original) {
+        Ok(request) => request,
+        Err(e) => deny(
+            &key_label,
+            &format!(
+                "{}. Only git-upload-pack and git-receive-pack are supported.",
+                e
+            ),
+        ),
+    };
+
+    let environment: Environment = resolve_from_env().into();
+    let config = match Config::new(&environment) {
+        Ok(config) => config,
+        Err(e) => deny(&key_label, &format!("Failed to load configuration: {}", e)),
+    };
+    let conn = match db::connect(&config.database).await {
+        Ok(conn) => conn,
+        Err(e) => deny(&key_label, &format!("Failed to connect to database: {}", e)),
+    };
+
+    let (user, repo_path) = match authorize_ssh_key(&conn, key_id, &request).await {
+        Ok(authorized) => authorized,
+        Err(e) => deny(&key_label, &e.to_string()),
+    };
+    let _ = conn.close().await;
+
+    log_message(
+        &key_label,
+        &format!(
+            "{} granted to user {} for repository: {}",
+            request.command.as_str(),
+            user.pid,
+            request.display_path()
+        ),
+    );
+
+    if request.command == GitCommand::ReceivePack {
+        let head_before = default_branch_head(&repo_path);
+        let status = match Command::new(request.command.as_str()).arg(&repo_path).status() {
+            Ok(status) => status,
+            Err(e) => deny(
+                &key_label,
+                &format!("Failed to execute {}: {}", request.command.as_str(), e),
+            ),
+        };
+        if status.success() {
+            let moved = default_branch_head(&repo_path) != head_before;
+            for task in post_push_tasks(moved) {
+                run_task(&key_label, task, &request);
+            }
+        }
+        exit(status.code().unwrap_or(1));
+    }
+
+    // Replace this process so the pack protocol talks directly to the client.
+    let err = Command::new(request.command.as_str()).arg(&repo_path).exec();
+    deny(
+        &key_label,
+        &format!("Failed to execute {}: {}", request.command.as_str(), err),
+    );
+}
@@ -0,0 +1,170 @@
+use axum::{
+    extract::{FromRef, FromRequestParts},
+    http::{header, request::Parts, StatusCode},
+};
+use loco_rs::{
+    app::AppContext,
+    auth::jwt::UserClaims,
+    controller::{middleware::auth::extract_jwt_from_request_parts, ErrorDetail},
+    prelude::*,
+};
+use tracing::warn;
+
+use crate::models::{
+    personal_access_tokens::{self, Scope, TOKEN_PREFIX},
+    sessions, users,
+};
+
+/// The credentials a request was made with: either a logged-in session, or a
+/// personal access token sent as `Authorization: Bearer gcp_...`, which only
+/// grants its scopes.
+#[derive(Debug)]
+pub struct Auth {
+    /// The pid of the authenticated user.
+    pub pid: String,
+    /// The token used, or `None` for a session, which may do anything.
+    pub token: Option<personal_access_tokens::Model>,
+    /// The session the JWT was issued for, or `None` for a token.
+    pub session: Option<sessions::Model>,
+}
+
+impl Auth {
+    /// Whether the request may be used for `scope`.
+    #[must_use]
+    pub fn allows(&self, scope: Scope) -> bool {
+        self.token.as_ref().is_none_or(|token| token.allows(scope))
+    }
+
+    /// Fails with `403 Forbidden` if the request may not be used for `scope`.
+    ///
+    /// # Errors
+    /// When the request was made with a token lacking `scope`.
+    pub fn require(&self, scope: Scope) -> Result<()> {
+        if self.allows(scope) {
+            Ok(())
+        } else {
+            Err(Error::CustomError(
+                StatusCode::FORBIDDEN,
+                ErrorDetail::new(
+                    "forbidden",
+                    format!("This token does not have the {} scope", scope.as_str()).as_str(),
+                ),
+            ))
+        }
+    }
+
+    /// Loads the authenticated user, after checking the request may be used
+    /// for `scope`.
+    ///
+    /// # Errors
+    /// When the token lacks `scope`, or the user no longer exists.
+    pub async fn user(&self, ctx: &AppContext, scope: Scope) -> Result<users::Model> {
+        self.require(scope)?;
+        Ok(users::Model::find_by_pid(&ctx.db, &self.pid).await?)
+    }
+}
+
+/// The personal access token in an `Authorization: Bearer` header, if any.
+fn bearer_token(parts: &Parts) -> Option<&str> {
+    parts
+        .headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+        .filter(|token| token.starts_with(TOKEN_PREFIX))
+}
+
+/// Checks that the session a JWT was issued for was not revoked, recording
+/// its use.
+///
+/// # Errors
+/// When the JWT has no session, or it was revoked or expired.
+pub async fn authenticate_session(ctx: &AppContext, claims: &UserClaims) -> Result<sessions::Model> {
+    let invalid = || Error::Unauthorized("session is not valid".to_string());
+    let pid = claims
+        .claims
+        .get(sessions::JWT_CLAIM)
+        .and_then(|value| value.as_str())
+        .ok_or_else(invalid)?;
+    let session = sessions::Model::find_active(&ctx.db, pid)
+        .await
+        .map_err(|_| invalid())?;
+    match session.clone().touch(&ctx.db).await {
+        Ok(session) => Ok(session),
+        Err(e) => {
+            warn!("Failed to record the use of session {}: {}", session.id, e);
+            Ok(session)
+        }
+    }
+}
+
+/// Authenticates a personal access token, recording its use.
+///
+/// # Errors
+/// When the token is unknown or expired.
+pub async fn authenticate_token(ctx: &AppContext, token: &str) -> Result<(users::Model, personal_access_tokens::Model)> {
+    let invalid = || Error::Unauthorized("token is not valid".to_string());
+    let token = personal_access_tokens::Model::find_by_token(&ctx.db, token)
+        .await
+        .map_err(|_| invalid())?;
+    let user = users::Entity::find_by_id(token.user_id)
+        .one(&ctx.db)
+        .await?
+        .ok_or_else(invalid)?;
+    let token = match token.clone().touch(&ctx.db).await {
+        Ok(token) => token,
+        Err(e) => {
+            warn!("Failed to record the use of token {}: {}", token.id, e);
+            token
+        }
+    };
+    Ok((user, token))
+}
+
+impl<S> FromRequestParts<S> for Auth
+where
+    AppContext: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self> {
+        if let Some(token) = bearer_token(parts) {
+            let ctx = AppContext::from_ref(state);
+            let (user, token) = authenticate_token(&ctx, token).await?;
+            return Ok(Self {
+                pid: user.pid.to_string(),
+                token: Some(token),
+                session: None,
+            });
+        }
+        let jwt = extract_jwt_from_request_parts(parts, state)?;
+        let ctx = AppContext::from_ref(state);
+        let session = authenticate_session(&ctx, &jwt.claims).await?;
+        Ok(Self {
+            pid: jwt.claims.pid,
+            token: None,
+            session: Some(session),
+        })
+    }
+}
+
+/// The credentials of the caller when there are valid ones, for pages that
+/// anonymous visitors may see too. Missing, expired or otherwise invalid
+/// credentials make the caller anonymous rather than failing the request.
+#[derive(Debug)]
+pub struct OptionalAuth(pub Option<Auth>);
+
+impl<S> FromRequestParts<S> for OptionalAuth
+where
+    AppContext: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self> {
+        Ok(Self(Auth::from_request_parts(parts, state).await.ok()))
+    }
+}
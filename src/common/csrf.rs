@@ -0,0 +1,192 @@
+//! Protection against cross-site request forgery. Browsers attach the
+//! `auth-token` cookie to requests coming from any site, so a page elsewhere
+//! could otherwise post forms to this one on behalf of a logged-in user.
+//!
+//! Every session gets a random token, kept in the session (the synchronizer
+//! token) and in a cookie. Forms send it back in a hidden field, added with the
+//! `csrf_field()` Tera function, and scripts in the `X-CSRF-Token` header,
+//! taken from `csrf_token()`. A state-changing request authenticated by the
+//! cookie has to send the token matching both. Independently of credentials, a
+//! state-changing request whose `Origin` is another site is refused.
+use std::collections::HashMap;
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::Request,
+    http::{header, HeaderMap, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+use loco_rs::{controller::ErrorDetail, hash, Error};
+use serde_json::Value;
+use tracing::warn;
+
+use super::session_store::DbSession;
+
+/// Key of the token in the session.
+const SESSION_KEY: &str = "csrf_token";
+/// Cookie holding the token, for the double-submit check.
+pub const COOKIE_NAME: &str = "csrf-token";
+/// Header scripts send the token in.
+pub const HEADER_NAME: &str = "x-csrf-token";
+/// Form field the token is sent in.
+pub const FIELD_NAME: &str = "csrf_token";
+/// The cookie that makes a request authenticated without the client asking
+/// for it, and so needs the token.
+const AUTH_COOKIE: &str = "auth-token";
+const TOKEN_LENGTH: usize = 32;
+/// Form bodies are read up to this size to find the token.
+const MAX_FORM_BYTES: usize = 1024 * 1024;
+
+tokio::task_local! {
+    static TOKEN: String;
+}
+
+/// The token of the session the current request belongs to, for templates.
+#[must_use]
+pub fn current_token() -> Option<String> {
+    TOKEN.try_with(Clone::clone).ok()
+}
+
+fn forbidden(description: &str) -> Response {
+    warn!("Refused a request: {}", description);
+    Error::CustomError(StatusCode::FORBIDDEN, ErrorDetail::new("csrf", description)).into_response()
+}
+
+/// Whether the request may change anything.
+fn is_unsafe(method: &Method) -> bool {
+    !matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE)
+}
+
+/// Whether the `Origin` header, when sent, names this site.
+fn origin_matches(req: &Request) -> bool {
+    let Some(origin) = req.headers().get(header::ORIGIN) else {
+        return true;
+    };
+    let host = req
+        .uri()
+        .authority()
+        .map(ToString::to_string)
+        .or_else(|| {
+            req.headers()
+                .get(header::HOST)
+                .and_then(|host| host.to_str().ok())
+                .map(ToString::to_string)
+        });
+    let origin = origin
+        .to_str()
+        .ok()
+        .and_then(|origin| origin.split_once("://"))
+        .map(|(_, authority)| authority.trim_end_matches('/'));
+    matches!((origin, host), (Some(origin), Some(host)) if origin.eq_ignore_ascii_case(&host))
+}
+
+/// Whether the request is authenticated by the cookie alone; tokens sent in
+/// `Authorization` cannot be attached by another site.
+fn uses_auth_cookie(headers: &HeaderMap, jar: &CookieJar) -> bool {
+    jar.get(AUTH_COOKIE).is_some() && !headers.contains_key(header::AUTHORIZATION)
+}
+
+fn is_form(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/x-www-form-urlencoded"))
+}
+
+/// Takes the token the client sent from the header or, for forms, from the
+/// body, which is put back for the handler.
+async fn submitted_token(req: Request) -> Result<(Option<String>, Request), Response> {
+    if let Some(token) = req.headers().get(HEADER_NAME) {
+        let token = token.to_str().ok().map(ToString::to_string);
+        return Ok((token, req));
+    }
+    if !is_form(req.headers()) {
+        return Ok((None, req));
+    }
+    let (parts, body) = req.into_parts();
+    let bytes = to_bytes(body, MAX_FORM_BYTES)
+        .await
+        .map_err(|_| forbidden("The form is too large"))?;
+    let token = form_urlencoded::parse(&bytes)
+        .find(|(key, _)| key == FIELD_NAME)
+        .map(|(_, value)| value.into_owned());
+    Ok((token, Request::from_parts(parts, Body::from(bytes))))
+}
+
+/// Checks state-changing requests, and makes the token of the session
+/// available to templates while the request is handled.
+pub async fn protect(session: DbSession, req: Request, next: Next) -> Response {
+    let token = session.get::<String>(SESSION_KEY).unwrap_or_else(|| {
+        let token = hash::random_string(TOKEN_LENGTH);
+        session.set(SESSION_KEY, &token);
+        token
+    });
+    let jar = CookieJar::from_headers(req.headers());
+    let cookie_token = jar.get(COOKIE_NAME).map(|cookie| cookie.value().to_string());
+
+    let req = if is_unsafe(req.method()) {
+        if !origin_matches(&req) {
+            return forbidden("The request comes from another site");
+        }
+        if uses_auth_cookie(req.headers(), &jar) {
+            let (submitted, req) = match submitted_token(req).await {
+                Ok(submitted) => submitted,
+                Err(res) => return res,
+            };
+            let valid = submitted.is_some_and(|submitted| {
+                submitted == token && cookie_token.as_deref() == Some(submitted.as_str())
+            });
+            if !valid {
+                return forbidden("The form has expired, reload the page and try again");
+            }
+            req
+        } else {
+            req
+        }
+    } else {
+        req
+    };
+
+    let mut res = TOKEN.scope(token.clone(), next.run(req)).await;
+    if cookie_token.as_deref() != Some(token.as_str()) {
+        let cookie = Cookie::build((COOKIE_NAME, token))
+            .path("/")
+            .http_only(true)
+            .same_site(SameSite::Strict)
+            .build();
+        if let Ok(value) = cookie.to_string().parse() {
+            res.headers_mut().append(header::SET_COOKIE, value);
+        }
+    }
+    res
+}
+
+/// `csrf_token()` in templates: the token, for scripts to send in the
+/// `X-CSRF-Token` header.
+pub struct CsrfToken;
+
+impl tera::Function for CsrfToken {
+    fn call(&self, _args: &HashMap<String, Value>) -> tera::Result<Value> {
+        Ok(Value::String(current_token().unwrap_or_default()))
+    }
+}
+
+/// `csrf_field()` in templates: the hidden input every form posting to the
+/// site needs.
+pub struct CsrfField;
+
+impl tera::Function for CsrfField {
+    fn call(&self, _args: &HashMap<String, Value>) -> tera::Result<Value> {
+        let token = current_token().unwrap_or_default();
+        Ok(Value::String(format!(
+            r#"<input type="hidden" name="{FIELD_NAME}" value="{}" />"#,
+            tera::escape_html(&token)
+        )))
+    }
+
+    fn is_safe(&self) -> bool {
+        true
+    }
+}
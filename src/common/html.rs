@@ -0,0 +1,8 @@
+/// Escapes text for use in HTML element content and quoted attributes.
+#[must_use]
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,6 @@
+pub mod auth;
+pub mod client_ip;
+pub mod csrf;
+pub mod html;
+pub mod session_store;
+pub mod settings;
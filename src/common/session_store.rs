@@ -0,0 +1,176 @@
+//! Keeps the data of the `axum_session` layer in the application database,
+//! so it survives restarts and is shared between instances.
+use async_trait::async_trait;
+use axum_session::{DatabaseError, DatabasePool, Session};
+use chrono::Local;
+use sea_orm::{
+    sea_query::{Alias, Expr, OnConflict, Query},
+    ConnectionTrait, DatabaseConnection, StatementBuilder,
+};
+
+/// A session of the `axum_session` layer stored with [`SessionDbPool`].
+pub type DbSession = Session<SessionDbPool>;
+
+/// An `axum_session` store backed by the `SeaORM` connection of the app. The
+/// table is created by the migrations rather than by the store.
+#[derive(Debug, Clone)]
+pub struct SessionDbPool {
+    db: DatabaseConnection,
+}
+
+impl SessionDbPool {
+    #[must_use]
+    pub const fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    async fn execute<S: StatementBuilder>(&self, stmt: &S) -> Result<u64, String> {
+        let stmt = self.db.get_database_backend().build(stmt);
+        self.db
+            .execute(stmt)
+            .await
+            .map(|res| res.rows_affected())
+            .map_err(|e| e.to_string())
+    }
+
+    async fn ids<S: StatementBuilder>(&self, stmt: &S) -> Result<Vec<String>, DatabaseError> {
+        let stmt = self.db.get_database_backend().build(stmt);
+        let rows = self
+            .db
+            .query_all(stmt)
+            .await
+            .map_err(|e| DatabaseError::GenericSelectError(e.to_string()))?;
+        rows.iter()
+            .map(|row| row.try_get::<String>("", "id"))
+            .collect::<Result<_, _>>()
+            .map_err(|e| DatabaseError::GenericSelectError(e.to_string()))
+    }
+}
+
+fn now() -> i64 {
+    Local::now().timestamp()
+}
+
+#[async_trait]
+impl DatabasePool for SessionDbPool {
+    async fn initiate(&self, _table_name: &str) -> Result<(), DatabaseError> {
+        Ok(())
+    }
+
+    async fn count(&self, table_name: &str) -> Result<i64, DatabaseError> {
+        let stmt = Query::select()
+            .expr_as(Expr::col(Alias::new("id")).count(), Alias::new("count"))
+            .from(Alias::new(table_name))
+            .to_owned();
+        let stmt = self.db.get_database_backend().build(&stmt);
+        let row = self
+            .db
+            .query_one(stmt)
+            .await
+            .map_err(|e| DatabaseError::GenericSelectError(e.to_string()))?;
+        row.map_or(Ok(0), |row| row.try_get::<i64>("", "count"))
+            .map_err(|e| DatabaseError::GenericSelectError(e.to_string()))
+    }
+
+    async fn store(
+        &self,
+        id: &str,
+        session: &str,
+        expires: i64,
+        table_name: &str,
+    ) -> Result<(), DatabaseError> {
+        let stmt = Query::insert()
+            .into_table(Alias::new(table_name))
+            .columns([Alias::new("id"), Alias::new("session"), Alias::new("expires")])
+            .values_panic([id.into(), session.into(), expires.into()])
+            .on_conflict(
+                OnConflict::column(Alias::new("id"))
+                    .update_columns([Alias::new("session"), Alias::new("expires")])
+                    .to_owned(),
+            )
+            .to_owned();
+        self.execute(&stmt)
+            .await
+            .map(|_| ())
+            .map_err(DatabaseError::GenericInsertError)
+    }
+
+    async fn load(&self, id: &str, table_name: &str) -> Result<Option<String>, DatabaseError> {
+        let stmt = Query::select()
+            .column(Alias::new("session"))
+            .from(Alias::new(table_name))
+            .and_where(Expr::col(Alias::new("id")).eq(id))
+            .and_where(Expr::col(Alias::new("expires")).gt(now()))
+            .to_owned();
+        let stmt = self.db.get_database_backend().build(&stmt);
+        let row = self
+            .db
+            .query_one(stmt)
+            .await
+            .map_err(|e| DatabaseError::GenericSelectError(e.to_string()))?;
+        row.map(|row| row.try_get::<String>("", "session"))
+            .transpose()
+            .map_err(|e| DatabaseError::GenericSelectError(e.to_string()))
+    }
+
+    async fn delete_one_by_id(&self, id: &str, table_name: &str) -> Result<(), DatabaseError> {
+        let stmt = Query::delete()
+            .from_table(Alias::new(table_name))
+            .and_where(Expr::col(Alias::new("id")).eq(id))
+            .to_owned();
+        self.execute(&stmt)
+            .await
+            .map(|_| ())
+            .map_err(DatabaseError::GenericDeleteError)
+    }
+
+    async fn exists(&self, id: &str, table_name: &str) -> Result<bool, DatabaseError> {
+        let stmt = Query::select()
+            .column(Alias::new("id"))
+            .from(Alias::new(table_name))
+            .and_where(Expr::col(Alias::new("id")).eq(id))
+            .and_where(Expr::col(Alias::new("expires")).gt(now()))
+            .to_owned();
+        Ok(!self.ids(&stmt).await?.is_empty())
+    }
+
+    async fn delete_by_expiry(&self, table_name: &str) -> Result<Vec<String>, DatabaseError> {
+        let expired = Query::select()
+            .column(Alias::new("id"))
+            .from(Alias::new(table_name))
+            .and_where(Expr::col(Alias::new("expires")).lte(now()))
+            .to_owned();
+        let ids = self.ids(&expired).await?;
+        if !ids.is_empty() {
+            let stmt = Query::delete()
+                .from_table(Alias::new(table_name))
+                .and_where(Expr::col(Alias::new("id")).is_in(ids.clone()))
+                .to_owned();
+            self.execute(&stmt)
+                .await
+                .map_err(DatabaseError::GenericDeleteError)?;
+        }
+        Ok(ids)
+    }
+
+    async fn delete_all(&self, table_name: &str) -> Result<(), DatabaseError> {
+        let stmt = Query::delete().from_table(Alias::new(table_name)).to_owned();
+        self.execute(&stmt)
+            .await
+            .map(|_| ())
+            .map_err(DatabaseError::GenericDeleteError)
+    }
+
+    async fn get_ids(&self, table_name: &str) -> Result<Vec<String>, DatabaseError> {
+        let stmt = Query::select()
+            .column(Alias::new("id"))
+            .from(Alias::new(table_name))
+            .and_where(Expr::col(Alias::new("expires")).gt(now()))
+            .to_owned();
+        self.ids(&stmt).await
+    }
+
+    fn auto_handles_expiry(&self) -> bool {
+        false
+    }
+}
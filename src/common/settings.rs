@@ -0,0 +1,60 @@
+use loco_rs::prelude::*;
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::services::{
+    archive_service::ArchiveSettings,
+    diff_service::DiffLimits,
+    repo_retrive_service::FileLimits,
+    search_service::SearchSettings,
+    throttle_service::{RateLimitSettings, ThrottleSettings},
+};
+
+/// Application specific configuration, read from the `settings` section of the
+/// environment's config file. Every value has a default, so the section is optional.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub diff: DiffLimits,
+    pub files: FileLimits,
+    pub archive: ArchiveSettings,
+    pub search: SearchSettings,
+    pub auth: AuthSettings,
+}
+
+/// How users log in.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AuthSettings {
+    /// Users have to verify their email address before they can log in.
+    pub require_email_verification: bool,
+    pub throttle: ThrottleSettings,
+    pub rate_limits: RateLimitSettings,
+}
+
+impl Default for AuthSettings {
+    fn default() -> Self {
+        Self {
+            require_email_verification: true,
+            throttle: ThrottleSettings::default(),
+            rate_limits: RateLimitSettings::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// Reads the settings of the running application, falling back to the
+    /// defaults when the section is missing or invalid.
+    #[must_use]
+    pub fn from_context(ctx: &AppContext) -> Self {
+        ctx.config
+            .settings
+            .as_ref()
+            .and_then(|value| {
+                serde_json::from_value(value.clone())
+                    .map_err(|e| warn!("Invalid settings, using defaults: {}", e))
+                    .ok()
+            })
+            .unwrap_or_default()
+    }
+}
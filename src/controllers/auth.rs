@@ -1,16 +1,26 @@
 use crate::{
+    common::{
+        auth::Auth,
+        client_ip::{ClientIp, UserAgent},
+        settings::Settings,
+    },
     mailers::auth::AuthMailer,
     models::{
         _entities::users,
+        sessions,
         users::{LoginParams, RegisterParams},
     },
-    views::auth::{CurrentResponse, LoginResponse},
+    services::throttle_service::{self, Penalty, RateLimit, ThrottleSettings},
+    views::auth::{CurrentResponse, LoginResponse, TwoFactorChallengeResponse},
+};
+use axum::{
+    debug_handler,
+    http::{header, StatusCode},
 };
-use axum::debug_handler;
-use loco_rs::prelude::*;
+use loco_rs::{controller::ErrorDetail, prelude::*};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::sync::OnceLock;
+use std::{net::IpAddr, sync::OnceLock, time::Duration};
 
 pub static EMAIL_DOMAIN_RE: OnceLock<Regex> = OnceLock::new();
 
@@ -36,13 +46,112 @@ pub struct MagicLinkParams {
     pub email: String,
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TwoFactorLoginParams {
+    pub challenge_token: String,
+    /// A code from the authenticator app, or a recovery code.
+    pub code: String,
+}
+
+/// Refuses a request that came too soon, telling the client when to retry.
+fn too_many_requests(wait: Duration) -> Result<Response> {
+    format::render()
+        .status(StatusCode::TOO_MANY_REQUESTS)
+        .header(header::RETRY_AFTER, wait.as_secs().max(1).to_string())
+        .json(ErrorDetail::new("too_many_requests", "Too many attempts, try again later"))
+}
+
+/// Counts a request to `bucket` against the limit of the client address and,
+/// when given, of the email it is about.
+async fn rate_limited(
+    ctx: &AppContext,
+    bucket: &str,
+    limit: RateLimit,
+    ip: Option<IpAddr>,
+    email: Option<&str>,
+) -> Option<Duration> {
+    let ip = ip.map_or_else(|| "unknown".to_string(), |ip| ip.to_string());
+    if let Some(wait) = throttle_service::check_rate_limit(&ctx.cache, bucket, &ip, limit).await {
+        return Some(wait);
+    }
+    let email = email?.trim().to_lowercase();
+    throttle_service::check_rate_limit(&ctx.cache, bucket, &email, limit).await
+}
+
+/// Where a login comes from, recorded on the session it starts.
+struct Origin {
+    ip: Option<IpAddr>,
+    user_agent: Option<String>,
+}
+
+/// Starts a session for a user who proved who they are, and issues its JWT.
+async fn issue_jwt(ctx: &AppContext, user: &users::Model, origin: &Origin) -> Result<Response> {
+    let jwt_secret = ctx.config.get_jwt_config()?;
+
+    let session = sessions::ActiveModel::create_for_user(
+        &ctx.db,
+        user.id,
+        origin.user_agent.as_deref(),
+        origin.ip,
+        jwt_secret.expiration,
+    )
+    .await?;
+    let token = user
+        .generate_jwt(&jwt_secret.secret, jwt_secret.expiration, &session)
+        .or_else(|_| unauthorized("unauthorized!"))?;
+
+    format::json(LoginResponse::new(user, &token))
+}
+
+/// Finishes a login once the first factor checked out. Users with two-factor
+/// authentication get a short-lived challenge instead of the JWT, which they
+/// exchange for it at `/login/2fa` along with a code. Failed logins are only
+/// forgotten once every factor checked out.
+async fn complete_login(ctx: &AppContext, user: users::Model, origin: &Origin) -> Result<Response> {
+    if !user.two_factor_enabled() {
+        let user = forget_failed_logins(ctx, user).await?;
+        return issue_jwt(ctx, &user, origin).await;
+    }
+    let user = user.into_active_model().create_totp_challenge(&ctx.db).await?;
+    format::json(TwoFactorChallengeResponse::new(&user))
+}
+
+/// Resets the failed login counters of a user who fully logged in.
+async fn forget_failed_logins(ctx: &AppContext, user: users::Model) -> Result<users::Model> {
+    if user.failed_login_attempts > 0 || user.locked_until.is_some() {
+        Ok(user.into_active_model().clear_failed_logins(&ctx.db).await?)
+    } else {
+        Ok(user)
+    }
+}
+
+/// Counts a failed login, with a wrong password or second factor, against the
+/// account, and tells its owner when that locked it.
+async fn record_failed_login(ctx: &AppContext, user: users::Model, settings: &ThrottleSettings) -> Result<()> {
+    let (user, penalty) = user
+        .into_active_model()
+        .record_failed_login(&ctx.db, settings)
+        .await?;
+    if let Penalty::Lockout(_) = penalty {
+        tracing::warn!(user_pid = user.pid.to_string(), "account locked after failed logins");
+        AuthMailer::send_account_locked(ctx, &user).await?;
+    }
+    Ok(())
+}
+
 /// Register function creates a new user with the given parameters and sends a
 /// welcome email to the user
 #[debug_handler]
 async fn register(
     State(ctx): State<AppContext>,
+    ClientIp(ip): ClientIp,
     Json(params): Json<RegisterParams>,
 ) -> Result<Response> {
+    let limit = Settings::from_context(&ctx).auth.rate_limits.register;
+    if let Some(wait) = rate_limited(&ctx, "register", limit, ip, None).await {
+        return too_many_requests(wait);
+    }
+
     let res = users::Model::create_with_password(&ctx.db, &params).await;
 
     let user = match res {
@@ -67,8 +176,8 @@ async fn register(
     format::json(())
 }
 
-/// Verify register user. if the user not verified his email, he can't login to
-/// the system.
+/// Verify register user. Unless `settings.auth.require_email_verification` is
+/// turned off, users can't log in with their password before verifying.
 #[debug_handler]
 async fn verify(State(ctx): State<AppContext>, Path(token): Path<String>) -> Result<Response> {
     let user = users::Model::find_by_verification_token(&ctx.db, &token).await?;
@@ -91,8 +200,14 @@ async fn verify(State(ctx): State<AppContext>, Path(token): Path<String>) -> Res
 #[debug_handler]
 async fn forgot(
     State(ctx): State<AppContext>,
+    ClientIp(ip): ClientIp,
     Json(params): Json<ForgotParams>,
 ) -> Result<Response> {
+    let limit = Settings::from_context(&ctx).auth.rate_limits.forgot;
+    if let Some(wait) = rate_limited(&ctx, "forgot", limit, ip, Some(&params.email)).await {
+        return too_many_requests(wait);
+    }
+
     let Ok(user) = users::Model::find_by_email(&ctx.db, &params.email).await else {
         // we don't want to expose our users email. if the email is invalid we still
         // returning success to the caller
@@ -127,31 +242,109 @@ async fn reset(State(ctx): State<AppContext>, Json(params): Json<ResetParams>) -
 }
 
 /// Creates a user login and returns a token
+///
+/// Failed attempts are counted per client address and per account. Past a few
+/// of them the next attempt has to wait, and an account that keeps failing is
+/// locked for a while and its owner notified by email. While locked, the
+/// account answers like an unknown email.
 #[debug_handler]
-async fn login(State(ctx): State<AppContext>, Json(params): Json<LoginParams>) -> Result<Response> {
-    let user = users::Model::find_by_email(&ctx.db, &params.email).await?;
+async fn login(
+    State(ctx): State<AppContext>,
+    ClientIp(ip): ClientIp,
+    UserAgent(user_agent): UserAgent,
+    Json(params): Json<LoginParams>,
+) -> Result<Response> {
+    let settings = Settings::from_context(&ctx).auth;
+    if let Some(wait) = throttle_service::ip_wait(&ctx.cache, ip).await {
+        return too_many_requests(wait);
+    }
 
-    let valid = user.verify_password(&params.password);
+    let Ok(user) = users::Model::find_by_email(&ctx.db, &params.email).await else {
+        throttle_service::record_ip_failure(&ctx.cache, ip, &settings.throttle).await;
+        return unauthorized("unauthorized!");
+    };
 
-    if !valid {
+    // A locked account answers like an unknown email, so that neither reveals
+    // which emails are registered. Checking the password first would instead
+    // reveal whether a guess was right. The owner was emailed about the lockout.
+    if user.locked_for().is_some() {
+        throttle_service::record_ip_failure(&ctx.cache, ip, &settings.throttle).await;
         return unauthorized("unauthorized!");
     }
 
-    let jwt_secret = ctx.config.get_jwt_config()?;
+    if !user.verify_password(&params.password) {
+        throttle_service::record_ip_failure(&ctx.cache, ip, &settings.throttle).await;
+        record_failed_login(&ctx, user, &settings.throttle).await?;
+        return unauthorized("unauthorized!");
+    }
 
-    let token = user
-        .generate_jwt(&jwt_secret.secret, jwt_secret.expiration)
-        .or_else(|_| unauthorized("unauthorized!"))?;
+    if settings.require_email_verification && user.email_verified_at.is_none() {
+        return Err(Error::CustomError(
+            StatusCode::FORBIDDEN,
+            ErrorDetail::new("email_not_verified", "Verify your email address before logging in"),
+        ));
+    }
 
-    format::json(LoginResponse::new(&user, &token))
+    complete_login(&ctx, user, &Origin { ip, user_agent }).await
 }
 
+/// Second step of logging in with two-factor authentication: exchanges the
+/// challenge from the first step and a code for the JWT. A challenge only
+/// survives a few wrong codes, after which the password is needed again, and
+/// wrong codes count as failed logins, so they lead to the same lockout.
 #[debug_handler]
-async fn current(auth: auth::JWT, State(ctx): State<AppContext>) -> Result<Response> {
-    let user = users::Model::find_by_pid(&ctx.db, &auth.claims.pid).await?;
+async fn login_two_factor(
+    State(ctx): State<AppContext>,
+    ClientIp(ip): ClientIp,
+    UserAgent(user_agent): UserAgent,
+    Json(params): Json<TwoFactorLoginParams>,
+) -> Result<Response> {
+    if let Some(wait) = throttle_service::ip_wait(&ctx.cache, ip).await {
+        return too_many_requests(wait);
+    }
+
+    let Ok(user) = users::Model::find_by_totp_challenge(&ctx.db, &params.challenge_token).await
+    else {
+        return unauthorized("unauthorized!");
+    };
+    let settings = Settings::from_context(&ctx).auth;
+    // Wrong codes may have locked the account since the challenge was issued.
+    if user.locked_for().is_some() {
+        throttle_service::record_ip_failure(&ctx.cache, ip, &settings.throttle).await;
+        return unauthorized("unauthorized!");
+    }
+
+    match user.clone().redeem_second_factor(&ctx.db, &params.code).await? {
+        Some(user) => {
+            let user = user.into_active_model().clear_totp_challenge(&ctx.db).await?;
+            let user = forget_failed_logins(&ctx, user).await?;
+            issue_jwt(&ctx, &user, &Origin { ip, user_agent }).await
+        }
+        None => {
+            tracing::info!(user_pid = user.pid.to_string(), "invalid two-factor code");
+            throttle_service::record_ip_failure(&ctx.cache, ip, &settings.throttle).await;
+            let user = user.into_active_model().record_totp_failure(&ctx.db).await?;
+            record_failed_login(&ctx, user, &settings.throttle).await?;
+            unauthorized("unauthorized!")
+        }
+    }
+}
+
+#[debug_handler]
+async fn current(auth: Auth, State(ctx): State<AppContext>) -> Result<Response> {
+    let user = users::Model::find_by_pid(&ctx.db, &auth.pid).await?;
     format::json(CurrentResponse::new(&user))
 }
 
+/// Ends the session the request was made with; its JWT stops working.
+#[debug_handler]
+async fn logout(auth: Auth, State(ctx): State<AppContext>) -> Result<Response> {
+    if let Some(session) = &auth.session {
+        sessions::ActiveModel::revoke(&ctx.db, session.user_id, session.pid).await?;
+    }
+    format::json(())
+}
+
 /// Magic link authentication provides a secure and passwordless way to log in to the application.
 ///
 /// # Flow
@@ -162,14 +355,21 @@ async fn current(auth: auth::JWT, State(ctx): State<AppContext>) -> Result<Respo
 ///
 /// 2. **Click the Magic Link**:
 ///    The user clicks the link (/magic-link/{token}), which validates the token and its expiration.
-///    If valid, the server generates a JWT and responds with a [`LoginResponse`].
+///    If valid, the server generates a JWT and responds with a [`LoginResponse`], or with a
+///    [`TwoFactorChallengeResponse`] when the user enabled two-factor authentication.
 ///    If invalid or expired, an unauthorized response is returned.
 ///
 /// This flow enhances security by avoiding traditional passwords and providing a seamless login experience.
 async fn magic_link(
     State(ctx): State<AppContext>,
+    ClientIp(ip): ClientIp,
     Json(params): Json<MagicLinkParams>,
 ) -> Result<Response> {
+    let limit = Settings::from_context(&ctx).auth.rate_limits.magic_link;
+    if let Some(wait) = rate_limited(&ctx, "magic-link", limit, ip, Some(&params.email)).await {
+        return too_many_requests(wait);
+    }
+
     let email_regex = get_allow_email_domain_re();
     if !email_regex.is_match(&params.email) {
         tracing::debug!(
@@ -196,22 +396,26 @@ async fn magic_link(
 async fn magic_link_verify(
     Path(token): Path<String>,
     State(ctx): State<AppContext>,
+    ClientIp(ip): ClientIp,
+    UserAgent(user_agent): UserAgent,
 ) -> Result<Response> {
     let Ok(user) = users::Model::find_by_magic_token(&ctx.db, &token).await else {
         // we don't want to expose our users email. if the email is invalid we still
         // returning success to the caller
         return unauthorized("unauthorized!");
     };
+    // A link does not get around a lockout; see `login`.
+    if user.locked_for().is_some() {
+        return unauthorized("unauthorized!");
+    }
 
-    let user = user.into_active_model().clear_magic_link(&ctx.db).await?;
-
-    let jwt_secret = ctx.config.get_jwt_config()?;
-
-    let token = user
-        .generate_jwt(&jwt_secret.secret, jwt_secret.expiration)
-        .or_else(|_| unauthorized("unauthorized!"))?;
+    let mut user = user.into_active_model().clear_magic_link(&ctx.db).await?;
+    // Following a link sent to the address proves the user owns it.
+    if user.email_verified_at.is_none() {
+        user = user.into_active_model().verified(&ctx.db).await?;
+    }
 
-    format::json(LoginResponse::new(&user, &token))
+    complete_login(&ctx, user, &Origin { ip, user_agent }).await
 }
 
 
@@ -221,9 +425,11 @@ pub fn routes() -> Routes {
         .add("/register", post(register))
         .add("/verify/{token}", get(verify))
         .add("/login", post(login))
+        .add("/login/2fa", post(login_two_factor))
         .add("/forgot", post(forgot))
         .add("/reset", post(reset))
         .add("/current", get(current))
+        .add("/logout", post(logout))
         .add("/magic-link", post(magic_link))
         .add("/magic-link/{token}", get(magic_link_verify))
 
@@ -0,0 +1,268 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::unused_async)]
+//! Git smart HTTP transport: `git clone http://host/<owner>/<name>.git`.
+//!
+//! Credentials are sent with HTTP Basic, using a personal access token or the
+//! user's `api_key` as password.
+//! Requests without credentials are challenged only when the repository requires
+//! them, so git prompts for a password instead of following a login redirect.
+use axum::{
+    body::Body,
+    extract::Query,
+    debug_handler,
+    http::{header, HeaderMap, StatusCode},
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use loco_rs::prelude::*;
+use serde::Deserialize;
+use std::path::PathBuf;
+use tracing::{error, info, warn};
+
+use crate::{
+    common::auth::authenticate_token,
+    models::{
+        git_repos::{self, Access},
+        personal_access_tokens::{self, Scope, TOKEN_PREFIX},
+        users,
+    },
+    services::{
+        git_access_service::{authorize_request, parse_repository_path, GitAccessError, GitCommand, GitRequest},
+        git_http_service::{advertise_refs, advertisement_content_type, result_content_type, stateless_rpc},
+        git_service::GitServiceError,
+        search_index_service::default_branch_head,
+    },
+    workers::{
+        insights::{InsightsWorker, InsightsWorkerArgs},
+        search_index::{SearchIndexWorker, SearchIndexWorkerArgs},
+    },
+};
+
+const REALM: &str = "Basic realm=\"GitCrab\"";
+
+#[derive(Debug, Deserialize)]
+pub struct InfoRefsParams {
+    pub service: Option<String>,
+}
+
+/// Extracts the password of an `Authorization: Basic` header.
+fn basic_auth_password(headers: &HeaderMap) -> Option<String> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let encoded = value.strip_prefix("Basic ")?;
+    let decoded = String::from_utf8(STANDARD.decode(encoded.trim()).ok()?).ok()?;
+    let (_username, password) = decoded.split_once(':')?;
+    Some(password.to_string())
+}
+
+/// Resolves the user authenticated by HTTP Basic, if any, along with the
+/// personal access token they used. The `api_key` is not limited to scopes.
+///
+/// Returns `Err` with a challenge when credentials were sent but are not valid.
+async fn basic_auth_user(
+    ctx: &AppContext,
+    headers: &HeaderMap,
+) -> Result<Option<(users::Model, Option<personal_access_tokens::Model>)>> {
+    let Some(password) = basic_auth_password(headers) else {
+        return Ok(None);
+    };
+    if password.starts_with(TOKEN_PREFIX) {
+        let (user, token) = authenticate_token(ctx, &password).await?;
+        return Ok(Some((user, Some(token))));
+    }
+    match users::Model::find_by_api_key(&ctx.db, &password).await {
+        Ok(user) => Ok(Some((user, None))),
+        Err(_) => Err(Error::Unauthorized("invalid credentials".to_string())),
+    }
+}
+
+/// The scope a personal access token needs to run `command`.
+fn required_scope(command: GitCommand) -> Scope {
+    match command.required_access() {
+        Access::Write => Scope::RepoWrite,
+        _ => Scope::RepoRead,
+    }
+}
+
+fn challenge() -> Result<Response> {
+    format::render()
+        .status(StatusCode::UNAUTHORIZED)
+        .header(header::WWW_AUTHENTICATE, REALM)
+        .text("Authentication required")
+}
+
+fn not_found() -> Result<Response> {
+    format::render()
+        .status(StatusCode::NOT_FOUND)
+        .text("Repository not found")
+}
+
+/// Authenticates the caller and checks they may run `command` on `owner/repo`.
+///
+/// Returns the repository path, or the response to send back when access is refused.
+async fn authorize(
+    ctx: &AppContext,
+    headers: &HeaderMap,
+    owner: &str,
+    repo: &str,
+    command: GitCommand,
+) -> Result<std::result::Result<PathBuf, Response>> {
+    let Ok((owner, name)) = parse_repository_path(&format!("{}/{}", owner, repo)) else {
+        return Ok(Err(not_found()?));
+    };
+    let request = GitRequest { command, owner, name };
+
+    let (user, token) = match basic_auth_user(ctx, headers).await {
+        Ok(Some((user, token))) => (Some(user), token),
+        Ok(None) => (None, None),
+        Err(_) => return Ok(Err(challenge()?)),
+    };
+    let scope = required_scope(command);
+    if token.as_ref().is_some_and(|token| !token.allows(scope)) {
+        return Ok(Err(format::render()
+            .status(StatusCode::FORBIDDEN)
+            .text(&format!("This token does not have the {} scope", scope.as_str()))?));
+    }
+
+    match authorize_request(&ctx.db, user.as_ref(), &request).await {
+        Ok(path) => {
+            info!(
+                user = user.map(|u| u.pid.to_string()),
+                repo = request.display_path(),
+                command = command.as_str(),
+                "git http access granted"
+            );
+            Ok(Ok(path))
+        }
+        // Ask anonymous callers to log in before telling them anything.
+        Err(GitAccessError::RepositoryNotFound(_)) if user.is_none() => Ok(Err(challenge()?)),
+        Err(GitAccessError::RepositoryNotFound(_)) => Ok(Err(not_found()?)),
+        Err(e) => {
+            error!("Failed to authorize git http request: {}", e);
+            Err(Error::InternalServerError)
+        }
+    }
+}
+
+fn stream_response(content_type: String, body: Body) -> Result<Response> {
+    Response::builder()
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::CACHE_CONTROL, "no-cache")
+        .body(body)
+        .map_err(|e| Error::string(&e.to_string()))
+}
+
+#[debug_handler]
+pub async fn info_refs(
+    Path((owner, repo)): Path<(String, String)>,
+    Query(params): Query<InfoRefsParams>,
+    headers: HeaderMap,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    // Only the smart protocol is supported; dumb clients don't send `service`.
+    let Some(command) = params.service.as_deref().and_then(GitCommand::from_service) else {
+        return format::render()
+            .status(StatusCode::FORBIDDEN)
+            .text("Only the smart HTTP protocol is supported");
+    };
+
+    let repo_path = match authorize(&ctx, &headers, &owner, &repo, command).await? {
+        Ok(path) => path,
+        Err(response) => return Ok(response),
+    };
+
+    let body = advertise_refs(command, &repo_path).await.map_err(|e| {
+        error!("Failed to advertise refs: {}", e);
+        Error::InternalServerError
+    })?;
+    stream_response(advertisement_content_type(command), Body::from(body))
+}
+
+async fn rpc(
+    ctx: &AppContext,
+    headers: &HeaderMap,
+    owner: &str,
+    repo: &str,
+    command: GitCommand,
+    body: Body,
+) -> Result<Response> {
+    let repo_path = match authorize(ctx, headers, owner, repo, command).await? {
+        Ok(path) => path,
+        Err(response) => return Ok(response),
+    };
+
+    let gzip = headers
+        .get(header::CONTENT_ENCODING)
+        .is_some_and(|v| v.as_bytes().eq_ignore_ascii_case(b"gzip"));
+    let head_before = (command == GitCommand::ReceivePack)
+        .then(|| default_branch_head(&repo_path))
+        .flatten();
+    let (output, finished) = match stateless_rpc(command, repo_path.clone(), body, gzip).await {
+        Ok(rpc) => rpc,
+        Err(e @ GitServiceError::RequestTooLarge(_)) => {
+            return format::render().status(StatusCode::PAYLOAD_TOO_LARGE).text(&e.to_string());
+        }
+        Err(e) => {
+            error!("Failed to run {}: {}", command.as_str(), e);
+            return Err(Error::InternalServerError);
+        }
+    };
+    if command == GitCommand::ReceivePack {
+        let (ctx, owner, repo) = (ctx.clone(), owner.to_string(), repo.to_string());
+        tokio::spawn(async move {
+            if matches!(finished.await, Ok(true)) {
+                let reindex = default_branch_head(&repo_path) != head_before;
+                after_push(&ctx, &owner, &repo, reindex).await;
+            }
+        });
+    }
+    stream_response(result_content_type(command), output)
+}
+
+/// Queues the insights update of a repository that was pushed to, and the
+/// search index update when the push moved the default branch.
+async fn after_push(ctx: &AppContext, owner: &str, repo: &str, reindex: bool) {
+    let Ok((owner, name)) = parse_repository_path(&format!("{}/{}", owner, repo)) else {
+        return;
+    };
+    let item = match git_repos::Model::find_by_namespace(&ctx.db, &owner, &name).await {
+        Ok(item) => item,
+        Err(e) => {
+            warn!("Failed to find pushed repository {}/{}: {}", owner, name, e);
+            return;
+        }
+    };
+    if reindex {
+        if let Err(e) = SearchIndexWorker::perform_later(ctx, SearchIndexWorkerArgs { repo_id: item.id }).await {
+            warn!("Failed to queue search index update for {}/{}: {}", owner, name, e);
+        }
+    }
+    if let Err(e) = InsightsWorker::perform_later(ctx, InsightsWorkerArgs { repo_id: item.id }).await {
+        warn!("Failed to queue insights update for {}/{}: {}", owner, name, e);
+    }
+}
+
+#[debug_handler]
+pub async fn upload_pack(
+    Path((owner, repo)): Path<(String, String)>,
+    headers: HeaderMap,
+    State(ctx): State<AppContext>,
+    body: Body,
+) -> Result<Response> {
+    rpc(&ctx, &headers, &owner, &repo, GitCommand::UploadPack, body).await
+}
+
+#[debug_handler]
+pub async fn receive_pack(
+    Path((owner, repo)): Path<(String, String)>,
+    headers: HeaderMap,
+    State(ctx): State<AppContext>,
+    body: Body,
+) -> Result<Response> {
+    rpc(&ctx, &headers, &owner, &repo, GitCommand::ReceivePack, body).await
+}
+
+pub fn routes() -> Routes {
+    Routes::new()
+        .add("/{owner}/{repo}/info/refs", get(info_refs))
+        .add("/{owner}/{repo}/git-upload-pack", post(upload_pack))
+        .add("/{owner}/{repo}/git-receive-pack", post(receive_pack))
+}
@@ -1,178 +1,939 @@
 #![allow(clippy::missing_errors_doc)]
 #![allow(clippy::unnecessary_struct_initialization)]
 #![allow(clippy::unused_async)]
-use std::{path::PathBuf};
+use std::{collections::HashMap, path::PathBuf, time::Duration};
 use chrono::Local;
 use git2::Repository;
-use loco_rs::{controller::middleware, prelude::*};
+use loco_rs::{controller::ErrorDetail, prelude::*};
 use serde::{Deserialize, Serialize};
 use axum::response::Redirect;
 use axum_extra::extract::Form;
-use sea_orm::{sea_query::Order, QueryOrder};
-use axum::debug_handler;
+use axum::{
+    body::Body,
+    debug_handler,
+    extract::Query,
+    http::{header, HeaderMap, StatusCode},
+};
+use futures_util::{stream, StreamExt};
+use tokio_util::io::ReaderStream;
 use tracing::{error, info, warn};
 
 use crate::{
-    models::_entities::git_repos::{ActiveModel, Column, Entity, Model}, services::{git_service::GitService, repo_retrive_service::{count_files_in_structure, get_total_size_from_structure, read_git_repository_structure, RepoResponse}}, views
+    common::{auth::{Auth, OptionalAuth}, settings::Settings}, models::{_entities::git_repos::{ActiveModel, Model}, git_repos::{self, Access, Visibility}, personal_access_tokens::Scope, users}, services::{archive_service::{self, ArchiveFormat, ArchiveTarget}, blame_service::{self, BlameError}, diff_service::{self, DiffError}, git_service::GitService, highlight_service::{self, HighlightedFile}, language_stats_service::{self, LanguageStats}, markdown_service, raw_service::{self, ByteRange}, refs_service, repo_history_service::{self, clamp_per_page, RepoHistoryError}, search_index_service, repo_retrive_service::{count_files_in_structure, default_ref, get_total_size_from_structure, list_refs, find_git_blob_at, read_git_file_at, read_git_structure_at, resolve_commit, split_ref_path, FileContentParams, FileContentResponse, FileLimits, RepoResponse, TreeEntriesResponse, TreeParams}}, views, workers::archive::{ArchiveWorker, ArchiveWorkerArgs}
 };
 
 const USER : &str = "git";
 
+/// How long highlighted files are kept in the cache.
+const HIGHLIGHT_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// How long the language statistics of a commit are kept in the cache.
+const LANGUAGES_CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+/// How many repositories the list computes language statistics for at once,
+/// when they are not cached.
+const LANGUAGES_CONCURRENCY: usize = 4;
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Params {
     pub name: Option<String>,
-    pub path: Option<String>,
+    /// The branch HEAD points at; left unchanged when missing.
+    pub default_branch: Option<String>,
+    pub description: Option<String>,
+    /// Topics, separated by commas or whitespace.
+    pub topics: Option<String>,
+    pub homepage: Option<String>,
+    /// `private`, `internal` or `public`; left unchanged when missing.
+    pub visibility: Option<String>,
     }
 
 impl Params {
+    fn description(&self) -> Option<String> {
+        self.description
+            .as_deref()
+            .map(str::trim)
+            .filter(|d| !d.is_empty())
+            .map(str::to_string)
+    }
+
+    fn homepage(&self) -> Option<String> {
+        self.homepage
+            .as_deref()
+            .map(str::trim)
+            .filter(|h| !h.is_empty())
+            .map(str::to_string)
+    }
+
+    /// Checks the metadata fields, returning a message for the form.
+    fn validate(&self) -> std::result::Result<(), String> {
+        match self.homepage() {
+            Some(homepage) if !git_repos::is_valid_homepage(&homepage) => {
+                Err("The homepage must be an http or https URL".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+
     fn update(&self, item: &mut ActiveModel) {
       item.name = Set(self.name.clone());
-      item.path = Set(self.path.clone());
+      self.update_metadata(item);
       }
+
+    fn update_metadata(&self, item: &mut ActiveModel) {
+        let topics = git_repos::normalize_topics(self.topics.as_deref().unwrap_or_default());
+        item.description = Set(self.description());
+        item.topics = Set(Some(topics.join(",")).filter(|t| !t.is_empty()));
+        item.homepage = Set(self.homepage());
+        if let Some(visibility) = &self.visibility {
+            item.visibility = Set(Visibility::parse(visibility).as_str().to_string());
+        }
+    }
+}
+
+/// Resolves the logged-in user, or `None` for anonymous visitors. A token
+/// without the `repo:read` scope sees what anonymous visitors see, just like
+/// invalid credentials do.
+pub(crate) async fn load_viewer(ctx: &AppContext, auth: &OptionalAuth) -> Result<Option<users::Model>> {
+    match &auth.0 {
+        Some(auth) if auth.allows(Scope::RepoRead) => Ok(Some(auth.user(ctx, Scope::RepoRead).await?)),
+        _ => Ok(None),
+    }
+}
+
+/// Loads a repository `user` has at least `access` on, along with its owner.
+/// Repositories the user cannot read are reported as missing so their
+/// existence is not leaked, and anonymous visitors are asked to log in
+/// instead; readable ones they cannot write are forbidden.
+async fn load_item_with(
+    ctx: &AppContext,
+    user: Option<&users::Model>,
+    id: i32,
+    access: Access,
+) -> Result<(users::Model, Model)> {
+    let hidden = || match user {
+        Some(_) => Error::NotFound,
+        None => Error::Unauthorized("You need to log in to see this repository".to_string()),
+    };
+    let (item, owner) = match Model::find_with_owner(&ctx.db, id).await {
+        Ok(found) => found,
+        Err(ModelError::EntityNotFound) => return Err(hidden()),
+        Err(err) => return Err(err.into()),
+    };
+    let granted = item.access_for(user);
+    if granted < Access::Read {
+        return Err(hidden());
+    }
+    if granted < access {
+        return Err(Error::CustomError(
+            StatusCode::FORBIDDEN,
+            ErrorDetail::new("forbidden", "You cannot change this repository"),
+        ));
+    }
+    Ok((owner, item))
+}
+
+/// Loads a repository `user` can read, along with its owner. Public
+/// repositories can be read without logging in.
+pub(crate) async fn load_item(ctx: &AppContext, user: Option<&users::Model>, id: i32) -> Result<(users::Model, Model)> {
+    load_item_with(ctx, user, id, Access::Read).await
+}
+
+/// Loads a repository `user` can push to and change, along with its owner.
+pub(crate) async fn load_item_for_write(ctx: &AppContext, user: &users::Model, id: i32) -> Result<(users::Model, Model)> {
+    load_item_with(ctx, Some(user), id, Access::Write).await
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RawParams {
+    /// Forces `Content-Disposition: attachment` when set.
+    pub download: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HistoryParams {
+    pub page: Option<usize>,
+    pub per_page: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CommitParams {
+    /// Limits the diff to the files at or below this path.
+    pub path: Option<String>,
+}
+
+fn git_service() -> GitService {
+    GitService::new(PathBuf::new().join(env!("REPO_BASE_PATH")), USER)
 }
 
-async fn load_item(ctx: &AppContext, id: i32) -> Result<Model> {
-    let item = Entity::find_by_id(id).one(&ctx.db).await?;
-    item.ok_or_else(|| Error::NotFound)
+/// Opens the bare repository backing `item`, which belongs to `owner`.
+pub(crate) fn open_repository(owner: &users::Model, item: &Model) -> Result<Repository> {
+    let bare_repo_path = git_service()
+        .get_repository_path(&owner.namespace(), &item.name.clone().unwrap_or_default())
+        .map_err(|e| {
+            error!("Invalid repository path: {}", e);
+            Error::NotFound
+        })?;
+
+    if !bare_repo_path.exists() {
+        return Err(Error::NotFound);
+    }
+
+    Repository::open_bare(&bare_repo_path).map_err(|e| {
+        error!("Failed to open bare repository: {}", e);
+        Error::InternalServerError
+    })
+}
+
+/// Runs `f` on the repository backing `item` on a blocking thread. Walking
+/// trees and history can take a while on large repositories, which must not
+/// stall the async workers.
+pub(crate) async fn with_repository<T, F>(owner: &users::Model, item: &Model, f: F) -> Result<T>
+where
+    F: FnOnce(&Repository) -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    let (owner, item) = (owner.clone(), item.clone());
+    tokio::task::spawn_blocking(move || f(&open_repository(&owner, &item)?))
+        .await
+        .map_err(|e| Error::string(&format!("Failed to read repository: {}", e)))?
 }
 
 #[debug_handler]
 pub async fn list(
-    auth: middleware::auth::JWT,
+    auth: Auth,
     ViewEngine(v): ViewEngine<TeraView>,
     State(ctx): State<AppContext>,
 ) -> Result<Response> {
-    let item = Entity::find()
-        .order_by(Column::Id, Order::Desc)
-        .all(&ctx.db)
-        .await?;
-    views::git_repo::list(&v, &item)
+    let user = auth.user(&ctx, Scope::RepoRead).await?;
+    let item = Model::list_by_owner(&ctx.db, user.id).await?;
+    let (ctx_ref, user_ref) = (&ctx, &user);
+    let languages: HashMap<_, _> = stream::iter(item.clone())
+        .map(|repo| async move {
+            let stats = language_stats(ctx_ref, user_ref, &repo).await;
+            stats.map(|stats| (repo.id.to_string(), stats))
+        })
+        .buffer_unordered(LANGUAGES_CONCURRENCY)
+        .filter_map(|stats| async move { stats })
+        .collect()
+        .await;
+    views::git_repo::list(&v, &item, &languages, &user.namespace(), &ctx.config.server.full_url())
+}
+
+/// Lists every repository the visitor can read, with public ones visible
+/// without logging in.
+#[debug_handler]
+pub async fn explore(
+    auth: OptionalAuth,
+    ViewEngine(v): ViewEngine<TeraView>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let user = load_viewer(&ctx, &auth).await?;
+    let items = Model::list_visible_to(&ctx.db, user.as_ref()).await?;
+    views::git_repo::explore(&v, &items, &ctx.config.server.full_url())
 }
 
 #[debug_handler]
 pub async fn new(
-    auth: middleware::auth::JWT,
+    auth: Auth,
     ViewEngine(v): ViewEngine<TeraView>,
     State(_ctx): State<AppContext>,
 ) -> Result<Response> {
+    auth.require(Scope::RepoWrite)?;
     views::git_repo::create(&v)
 }
 
 #[debug_handler]
 pub async fn update(
-    auth: middleware::auth::JWT,
+    auth: Auth,
     Path(id): Path<i32>,
     State(ctx): State<AppContext>,
     Form(params): Form<Params>,
 
 ) -> Result<Redirect> {
 
-    let item = load_item(&ctx, id).await?;
+    let user = auth.user(&ctx, Scope::RepoWrite).await?;
+    let (owner, item) = load_item_for_write(&ctx, &user, id).await?;
+
+    if let Err(message) = params.validate() {
+        return Ok(Redirect::to(&format!("../git_repos?error={}", urlencoding::encode(&message))));
+    }
+
+    // HEAD is changed first, while the repository is still at its old path.
+    if let Some(branch) = params.default_branch.as_deref().filter(|b| !b.is_empty()) {
+        let repo = open_repository(&owner, &item)?;
+        if default_ref(&repo).as_deref() != Some(branch) {
+            if let Err(err) = refs_service::set_default_branch(&repo, branch) {
+                error!("Failed to change the default branch to '{}': {}", branch, err);
+                return Ok(Redirect::to(&format!("../git_repos?error={}", urlencoding::encode(&format!("Failed to change the default branch: {}", err)))));
+            }
+            info!("Changed the default branch of repository {} to '{}'", id, branch);
+        }
+    }
+
     let mut item = item.into_active_model();
     let old_name = item.name.clone().unwrap().unwrap_or_default();
     let new_name = params.name.clone().unwrap_or_default();
+    let owner = owner.namespace();
+    let git_service = git_service();
 
     if old_name == new_name {
-        info!("The repository name is unchanged; no action required.");
-        return Ok(Redirect::to("../git_repos"));
-    }
-    let git_service = GitService::new(PathBuf::new().join(env!("REPO_BASE_PATH")),USER);
-    if let Err(err) = git_service.rename_repository(&old_name, &new_name).await {
-        error!("Failed to rename repository '{}' to '{}': {}", old_name, new_name, err);
-        // Redirect with the error message in the URL
-        return Ok(Redirect::to(&format!("../git_repos?error={}", urlencoding::encode(&format!("Failed to rename repository: {}", err)))));
-    }
-    params.update(&mut item);
-    if let Err(err) = item.update(&ctx.db).await {
-        error!("Failed to update repository in the database: {}", err);
-        if let Err(rollback_err) = git_service.rename_repository(&new_name, &old_name).await {
-            error!("Failed to rollback filesystem rename: {}", rollback_err);
+        params.update_metadata(&mut item);
+        if let Err(err) = item.update(&ctx.db).await {
+            error!("Failed to update repository in the database: {}", err);
+            return Ok(Redirect::to(&format!("../git_repos?error={}", urlencoding::encode(&format!("Failed to update repository in the database: {}", err)))));
+        }
+    } else {
+        let new_path = match git_service.rename_repository(&owner, &old_name, &new_name).await {
+            Ok(path) => path,
+            Err(err) => {
+                error!("Failed to rename repository '{}' to '{}': {}", old_name, new_name, err);
+                // Redirect with the error message in the URL
+                return Ok(Redirect::to(&format!("../git_repos?error={}", urlencoding::encode(&format!("Failed to rename repository: {}", err)))));
+            }
+        };
+        params.update(&mut item);
+        item.path = Set(Some(new_path.to_string_lossy().to_string()));
+        if let Err(err) = item.update(&ctx.db).await {
+            error!("Failed to update repository in the database: {}", err);
+            if let Err(rollback_err) = git_service.rename_repository(&owner, &new_name, &old_name).await {
+                error!("Failed to rollback filesystem rename: {}", rollback_err);
+            }
+            return Ok(Redirect::to(&format!("../git_repos?error={}", urlencoding::encode(&format!("Failed to update repository in the database: {}", err)))));
         }
-        return Ok(Redirect::to(&format!("../git_repos?error={}", urlencoding::encode(&format!("Failed to update repository in the database: {}", err)))));
+        info!("Successfully renamed repository '{}' to '{}'", old_name, new_name);
     }
-    info!("Successfully updated repository '{}' to '{}'", old_name, new_name);
+
+    // The database is the source of truth; a stale file only affects git tools.
+    let description = params.description().unwrap_or_default();
+    if let Err(err) = git_service.write_description(&owner, &new_name, &description).await {
+        warn!("Failed to sync the description of repository '{}': {}", new_name, err);
+    }
+    info!("Successfully updated repository '{}'", new_name);
     Ok(Redirect::to("../git_repos"))
 
 }
 
 #[debug_handler]
 pub async fn edit(
-    auth: middleware::auth::JWT,
+    auth: Auth,
     Path(id): Path<i32>,
     ViewEngine(v): ViewEngine<TeraView>,
     State(ctx): State<AppContext>,
 ) -> Result<Response> {
-    let item = load_item(&ctx, id).await?;
+    let user = auth.user(&ctx, Scope::RepoWrite).await?;
+    let (owner, item) = load_item_for_write(&ctx, &user, id).await?;
+    // The form still works when the repository cannot be read; only the
+    // default branch cannot be changed then.
+    let repo = open_repository(&owner, &item).ok();
+    let branches = repo
+        .as_ref()
+        .and_then(|repo| list_refs(repo).ok())
+        .map(|refs| refs.branches)
+        .unwrap_or_default();
+    let default_branch = repo.as_ref().and_then(default_ref);
+
+    views::git_repo::edit(&v, &item, &branches, default_branch.as_deref())
+}
+
+/// Reads what the explorer shows for `path` at `reference`.
+fn tree_response(
+    item: &Model,
+    repo: &Repository,
+    reference: &str,
+    path: &str,
+    limits: &FileLimits,
+) -> Result<RepoResponse> {
+    let commit = resolve_commit(repo, reference)?;
+    let structure = read_git_structure_at(repo, &commit, path)?;
+    let refs = list_refs(repo)?;
+    let readme = commit.tree().ok().and_then(|tree| {
+        markdown_service::render_readme(
+            repo,
+            &tree,
+            path,
+            &item.id.to_string(),
+            reference,
+            limits.max_content_bytes,
+        )
+    });
 
-    views::git_repo::edit(&v, &item)
+    let total_files = count_files_in_structure(&structure);
+    let total_size = get_total_size_from_structure(&structure);
+    Ok(RepoResponse {
+        id: item.id.to_string(),
+        name: item.name.clone().unwrap_or_default(),
+        reference: reference.to_string(),
+        path: path.trim_matches('/').to_string(),
+        refs,
+        readme,
+        structure,
+        total_files,
+        total_size,
+    })
 }
 
 #[debug_handler]
 pub async fn show(
-    auth: middleware::auth::JWT,
+    auth: OptionalAuth,
     Path(id): Path<i32>,
     ViewEngine(v): ViewEngine<TeraView>,
     State(ctx): State<AppContext>,
 ) -> Result<Response> {
 
-    let item = load_item(&ctx, id).await?;
-     info!("Fetching repository structure for repo: {}", item.name.clone().unwrap());
-    
-    // In a real application, you might fetch repo info from database
-    // For now, we'll assume the repo_id corresponds to a directory path
-    let bare_repo_path = PathBuf::new().join(env!("REPO_BASE_PATH")).join(format!("{}.git",&item.name.clone().unwrap()));
-    
-    info!("Repo Path : {}", bare_repo_path.to_str().unwrap());
-    if !bare_repo_path.exists() {
-        return Err(Error::NotFound);
+    let user = load_viewer(&ctx, &auth).await?;
+    let (owner, item) = load_item(&ctx, user.as_ref(), id).await?;
+    info!("Fetching repository structure for repo: {}", item.name.clone().unwrap_or_default());
+    let languages = language_stats(&ctx, &owner, &item).await;
+
+    let limits = Settings::from_context(&ctx).files;
+    let repo_item = item.clone();
+    let response = with_repository(&owner, &item, move |repo| {
+        default_ref(repo)
+            .map(|reference| tree_response(&repo_item, repo, &reference, "", &limits))
+            .transpose()
+    })
+    .await?;
+    // Nothing has been pushed yet: explain how to, instead of failing on HEAD.
+    let Some(response) = response else {
+        return views::git_repo::empty(&v, &item, &owner.namespace(), &ctx.config.server.full_url());
+    };
+
+    views::git_repo::show(&v, &item, response, languages.as_ref(), &owner.namespace(), &ctx.config.server.full_url())
+}
+
+#[debug_handler]
+pub async fn tree(
+    auth: OptionalAuth,
+    Path((id, spec)): Path<(i32, String)>,
+    ViewEngine(v): ViewEngine<TeraView>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let user = load_viewer(&ctx, &auth).await?;
+    let (owner, item) = load_item(&ctx, user.as_ref(), id).await?;
+    let limits = Settings::from_context(&ctx).files;
+    let repo_item = item.clone();
+    let response = with_repository(&owner, &item, move |repo| {
+        let (reference, path) = split_ref_path(repo, &spec)?;
+        tree_response(&repo_item, repo, &reference, &path, &limits)
+    })
+    .await?;
+    views::git_repo::show(&v, &item, response, None, &owner.namespace(), &ctx.config.server.full_url())
+}
+
+#[debug_handler]
+pub async fn blob(
+    auth: OptionalAuth,
+    Path((id, spec)): Path<(i32, String)>,
+    ViewEngine(v): ViewEngine<TeraView>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let user = load_viewer(&ctx, &auth).await?;
+    let (owner, item) = load_item(&ctx, user.as_ref(), id).await?;
+    let limits = Settings::from_context(&ctx).files;
+
+    let (repo_id, file_limits) = (item.id.to_string(), limits.clone());
+    let (reference, mut file, refs) = with_repository(&owner, &item, move |repo| {
+        let (reference, path) = split_ref_path(repo, &spec)?;
+        let commit = resolve_commit(repo, &reference)?;
+        let mut file = read_git_file_at(repo, &commit, &path, &repo_id, &file_limits)?;
+        file.html = commit
+            .tree()
+            .ok()
+            .and_then(|tree| markdown_service::render_file(&file, &tree, &reference));
+        Ok((reference, file, list_refs(repo)?))
+    })
+    .await?;
+    file.highlighted = highlight_file(&ctx, &file, &limits).await;
+
+    views::git_repo::blob(&v, &item, &file, &reference, &refs)
+}
+
+/// Shows which commit last changed each line of a file.
+#[debug_handler]
+pub async fn blame(
+    auth: OptionalAuth,
+    Path((id, spec)): Path<(i32, String)>,
+    ViewEngine(v): ViewEngine<TeraView>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let user = load_viewer(&ctx, &auth).await?;
+    let (owner, item) = load_item(&ctx, user.as_ref(), id).await?;
+    let limits = Settings::from_context(&ctx).files;
+
+    let (blame, reference, refs) = with_repository(&owner, &item, move |repo| {
+        let (reference, path) = split_ref_path(repo, &spec)?;
+        let commit = resolve_commit(repo, &reference)?;
+        let blame = match blame_service::blame_file(repo, &commit, &path, &limits, Local::now().timestamp()) {
+            Ok(blame) => blame,
+            Err(BlameError::NotFound(_)) => return Err(Error::NotFound),
+            Err(e) => {
+                error!("Failed to blame {}: {}", path, e);
+                return Err(Error::InternalServerError);
+            }
+        };
+        Ok((blame, reference, list_refs(repo)?))
+    })
+    .await?;
+
+    views::git_repo::blame(&v, &item, &blame, &reference, &refs)
+}
+
+/// Serves the bytes of a file as they are stored, for downloads and embedding.
+///
+/// The content type is guessed conservatively and the response is locked down
+/// with `nosniff` and a sandboxing CSP, so files from a repository can never
+/// run scripts on our origin. Single byte ranges are supported for resumable
+/// downloads and media seeking.
+#[debug_handler]
+pub async fn raw(
+    auth: OptionalAuth,
+    Path((id, spec)): Path<(i32, String)>,
+    Query(params): Query<RawParams>,
+    headers: HeaderMap,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let user = load_viewer(&ctx, &auth).await?;
+    let (owner, item) = load_item(&ctx, user.as_ref(), id).await?;
+    let (repo_path, path, oid, size) = with_repository(&owner, &item, move |repo| {
+        let (reference, path) = split_ref_path(repo, &spec)?;
+        let commit = resolve_commit(repo, &reference)?;
+        let (oid, size) = find_git_blob_at(repo, &commit, &path)?;
+        Ok((repo.path().to_path_buf(), path, oid, size))
+    })
+    .await?;
+
+    let name = path.rsplit('/').next().unwrap_or(&path);
+    let etag = format!("\"{}\"", oid);
+
+    let range = headers.get(header::RANGE).and_then(|v| v.to_str().ok());
+    // A range is only honoured if the file is still the one the client started with.
+    let range_valid = headers
+        .get(header::IF_RANGE)
+        .and_then(|v| v.to_str().ok())
+        .is_none_or(|tag| tag == etag);
+    let range = raw_service::parse_range(range.filter(|_| range_valid), size);
+
+    let window = match &range {
+        ByteRange::Full => 0..size,
+        ByteRange::Partial(range) => *range.start()..*range.end() + 1,
+        ByteRange::Unsatisfiable => 0..0,
+    };
+    let (content_type, body) = raw_service::stream_blob(repo_path, oid, name.to_string(), window);
+    let content_type = content_type.await.map_err(|_| Error::InternalServerError)?;
+    let attachment = params.download.is_some() || !raw_service::is_inline_type(content_type);
+
+    let response = Response::builder()
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::CONTENT_DISPOSITION, raw_service::content_disposition(name, attachment))
+        .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff")
+        .header(header::CONTENT_SECURITY_POLICY, raw_service::RAW_CONTENT_SECURITY_POLICY)
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::ETAG, &etag)
+        .header(header::CACHE_CONTROL, "private, no-cache");
+
+    let response = match range {
+        ByteRange::Full => response
+            .header(header::CONTENT_LENGTH, size)
+            .body(body),
+        ByteRange::Partial(range) => {
+            let (start, end) = (*range.start(), *range.end());
+            response
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header(header::CONTENT_RANGE, format!("bytes {}-{}/{}", start, end, size))
+                .header(header::CONTENT_LENGTH, end - start + 1)
+                .body(body)
+        }
+        ByteRange::Unsatisfiable => response
+            .status(StatusCode::RANGE_NOT_SATISFIABLE)
+            .header(header::CONTENT_RANGE, format!("bytes */{}", size))
+            .body(Body::empty()),
+    };
+    response.map_err(|e| Error::string(&e.to_string()))
+}
+
+/// Highlights a source file for display. Results are cached by blob oid, so
+/// a file is only highlighted again once it changes.
+async fn highlight_file(
+    ctx: &AppContext,
+    file: &FileContentResponse,
+    limits: &FileLimits,
+) -> Option<HighlightedFile> {
+    if file.is_binary || file.too_large || file.html.is_some() {
+        return None;
     }
 
-    // Open the bare repository directly
-    let repo = Repository::open_bare(&bare_repo_path)
+    // The same blob may be stored under names of different languages.
+    let language = highlight_service::language_for(&file.file_path, &file.content).unwrap_or("text");
+    let key = format!("highlight:{}:{}", file.oid, language);
+    if let Ok(Some(cached)) = ctx.cache.get(&key).await {
+        if let Ok(highlighted) = serde_json::from_str(&cached) {
+            return Some(highlighted);
+        }
+    }
+
+    let (path, content, max_bytes) = (file.file_path.clone(), file.content.clone(), limits.max_highlight_bytes);
+    let highlighted = tokio::task::spawn_blocking(move || highlight_service::highlight(&path, &content, max_bytes))
+        .await
+        .map_err(|e| error!("Failed to highlight {}: {}", file.file_path, e))
+        .ok()?;
+    if let Ok(json) = serde_json::to_string(&highlighted) {
+        if let Err(e) = ctx.cache.insert_with_expiry(&key, &json, HIGHLIGHT_CACHE_TTL).await {
+            warn!("Failed to cache highlighted {}: {}", file.file_path, e);
+        }
+    }
+    Some(highlighted)
+}
+
+/// Computes the language statistics of the default branch of `item`. Results
+/// are cached by commit, so they are only computed again after a push.
+async fn language_stats(ctx: &AppContext, owner: &users::Model, item: &Model) -> Option<LanguageStats> {
+    let commit = with_repository(owner, item, |repo| {
+        let reference = default_ref(repo).ok_or(Error::NotFound)?;
+        Ok(resolve_commit(repo, &reference)?.id())
+    })
+    .await
+    .ok()?;
+    let key = format!("languages:{}", commit);
+    if let Ok(Some(cached)) = ctx.cache.get(&key).await {
+        if let Ok(stats) = serde_json::from_str(&cached) {
+            return Some(stats);
+        }
+    }
+
+    let stats = with_repository(owner, item, move |repo| {
+        let commit = repo.find_commit(commit).map_err(|_| Error::NotFound)?;
+        language_stats_service::language_stats(repo, &commit)
+            .map_err(|e| Error::string(&e.to_string()))
+    })
+    .await
+    .map_err(|e| error!("Failed to compute the languages of {}: {}", commit, e))
+    .ok()?;
+    if let Ok(json) = serde_json::to_string(&stats) {
+        if let Err(e) = ctx.cache.insert_with_expiry(&key, &json, LANGUAGES_CACHE_TTL).await {
+            warn!("Failed to cache the languages of {}: {}", commit, e);
+        }
+    }
+    Some(stats)
+}
+
+/// Resolves an optional `ref` query parameter, defaulting to the default branch.
+fn reference_or_default(repo: &Repository, reference: Option<String>) -> Result<String> {
+    reference
+        .filter(|r| !r.is_empty())
+        .or_else(|| default_ref(repo))
+        .ok_or(Error::NotFound)
+}
+
+/// Lists one directory level as JSON, for the explorer to expand lazily.
+#[debug_handler]
+pub async fn entries(
+    auth: OptionalAuth,
+    Path(id): Path<i32>,
+    Query(params): Query<TreeParams>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let user = load_viewer(&ctx, &auth).await?;
+    let (owner, item) = load_item(&ctx, user.as_ref(), id).await?;
+    let entries = with_repository(&owner, &item, move |repo| {
+        let reference = reference_or_default(repo, params.reference)?;
+        let path = params.path.unwrap_or_default().trim_matches('/').to_string();
+        let commit = resolve_commit(repo, &reference)?;
+        let structure = read_git_structure_at(repo, &commit, &path)?;
+        Ok(TreeEntriesResponse {
+            reference,
+            path,
+            entries: structure.children,
+        })
+    })
+    .await?;
+
+    format::json(entries)
+}
+
+/// Serves the content of a single file as JSON.
+///
+/// The `ETag` is the blob oid, so unchanged files are revalidated with a 304
+/// whatever ref they were requested through.
+#[debug_handler]
+pub async fn file(
+    auth: OptionalAuth,
+    Path(id): Path<i32>,
+    Query(params): Query<FileContentParams>,
+    headers: HeaderMap,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let user = load_viewer(&ctx, &auth).await?;
+    let (owner, item) = load_item(&ctx, user.as_ref(), id).await?;
+    let limits = Settings::from_context(&ctx).files;
+
+    let (repo_id, file_limits) = (item.id.to_string(), limits.clone());
+    let mut file = with_repository(&owner, &item, move |repo| {
+        let reference = reference_or_default(repo, params.reference)?;
+        let commit = resolve_commit(repo, &reference)?;
+        let mut file = read_git_file_at(repo, &commit, &params.path, &repo_id, &file_limits)?;
+        file.html = commit
+            .tree()
+            .ok()
+            .and_then(|tree| markdown_service::render_file(&file, &tree, &reference));
+        Ok(file)
+    })
+    .await?;
+
+    let etag = format!("\"{}\"", file.oid);
+    let cached = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.split(',').any(|tag| tag.trim() == etag || tag.trim() == "*"));
+    if cached {
+        return format::render()
+            .status(StatusCode::NOT_MODIFIED)
+            .header(header::ETAG, &etag)
+            .empty();
+    }
+
+    file.highlighted = highlight_file(&ctx, &file, &limits).await;
+    format::render()
+        .header(header::ETAG, &etag)
+        .header(header::CACHE_CONTROL, "private, no-cache")
+        .json(file)
+}
+
+#[debug_handler]
+pub async fn commits(
+    auth: OptionalAuth,
+    Path(id): Path<i32>,
+    Query(params): Query<HistoryParams>,
+    ViewEngine(v): ViewEngine<TeraView>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let user = load_viewer(&ctx, &auth).await?;
+    let (owner, item) = load_item(&ctx, user.as_ref(), id).await?;
+    let page = with_repository(&owner, &item, move |repo| {
+        repo_history_service::list_commits(
+            repo,
+            params.page.unwrap_or(1),
+            clamp_per_page(params.per_page),
+        )
+        .map_err(|e| {
+            error!("Failed to list commits: {}", e);
+            Error::InternalServerError
+        })
+    })
+    .await?;
+
+    views::git_repo::commits(&v, &item, &page)
+}
+
+/// Lists the commits that changed a file or directory, following renames.
+#[debug_handler]
+pub async fn history(
+    auth: OptionalAuth,
+    Path((id, spec)): Path<(i32, String)>,
+    Query(params): Query<HistoryParams>,
+    ViewEngine(v): ViewEngine<TeraView>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let user = load_viewer(&ctx, &auth).await?;
+    let (owner, item) = load_item(&ctx, user.as_ref(), id).await?;
+    let (page, reference) = with_repository(&owner, &item, move |repo| {
+        let (reference, path) = split_ref_path(repo, &spec)?;
+        let commit = resolve_commit(repo, &reference)?;
+        let page = repo_history_service::list_path_commits(
+            repo,
+            &commit,
+            &path,
+            params.page.unwrap_or(1),
+            clamp_per_page(params.per_page),
+        )
         .map_err(|e| {
-            error!("Failed to open bare repository: {}", e);
+            error!("Failed to list commits of {}: {}", path, e);
             Error::InternalServerError
         })?;
+        Ok((page, reference))
+    })
+    .await?;
 
-    // Read the repository structure directly from Git objects
-    match read_git_repository_structure(&repo) {
+    views::git_repo::history(&v, &item, &page, &reference)
+}
 
-        Ok(structure) => {
-            let total_files = count_files_in_structure(&structure);
-            let total_size = get_total_size_from_structure(&structure);
-            let response = RepoResponse {
-                id: item.id.to_string(),
-                name: item.name.clone().unwrap(),
-                structure,
-                total_files,
-                total_size,
-            };
-            
-            info!("Successfully fetched repository structure");
-            views::git_repo::show(&v, &item, response)
+#[debug_handler]
+pub async fn commit(
+    auth: OptionalAuth,
+    Path((id, oid)): Path<(i32, String)>,
+    Query(params): Query<CommitParams>,
+    ViewEngine(v): ViewEngine<TeraView>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let user = load_viewer(&ctx, &auth).await?;
+    let (owner, item) = load_item(&ctx, user.as_ref(), id).await?;
+    let limits = Settings::from_context(&ctx).diff;
+    let path = params
+        .path
+        .as_deref()
+        .map(|p| p.trim_matches('/').to_string())
+        .filter(|p| !p.is_empty());
+    let filter = path.clone();
+    let detail = with_repository(&owner, &item, move |repo| {
+        match repo_history_service::commit_detail_at(repo, &oid, filter.as_deref(), &limits) {
+            Ok(detail) => Ok(detail),
+            Err(RepoHistoryError::InvalidOid(_) | RepoHistoryError::CommitNotFound(_)) => {
+                Err(Error::NotFound)
+            }
+            Err(e) => {
+                error!("Failed to load commit {}: {}", oid, e);
+                Err(Error::InternalServerError)
+            }
         }
-        Err(e) => {
-            error!("Failed to read repository structure: {}", e);
-            Err(Error::InternalServerError)
+    })
+    .await?;
+
+    views::git_repo::commit(&v, &item, &detail, path.as_deref())
+}
+
+#[debug_handler]
+pub async fn compare(
+    auth: OptionalAuth,
+    Path((id, spec)): Path<(i32, String)>,
+    ViewEngine(v): ViewEngine<TeraView>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let user = load_viewer(&ctx, &auth).await?;
+    let (owner, item) = load_item(&ctx, user.as_ref(), id).await?;
+    let (base, head) = diff_service::parse_compare_spec(&spec)
+        .map_err(|e| Error::BadRequest(e.to_string()))?;
+    let limits = Settings::from_context(&ctx).diff;
+    let comparison = with_repository(&owner, &item, move |repo| {
+        match diff_service::compare(repo, &base, &head, &limits) {
+            Ok(comparison) => Ok(comparison),
+            Err(DiffError::RevisionNotFound(_)) => Err(Error::NotFound),
+            Err(e) => {
+                error!("Failed to compare {}: {}", spec, e);
+                Err(Error::InternalServerError)
+            }
+        }
+    })
+    .await?;
+
+    views::git_repo::compare(&v, &item, &comparison)
+}
+
+/// Serves a snapshot of a ref as a `.tar.gz` or `.zip` archive.
+///
+/// Archives already in the cache are sent from disk. Small trees are streamed
+/// while they are archived; larger ones are built by the `ArchiveWorker` and
+/// the client is asked to retry until the cached archive is ready.
+#[debug_handler]
+pub async fn archive(
+    auth: OptionalAuth,
+    Path((id, spec)): Path<(i32, String)>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let user = load_viewer(&ctx, &auth).await?;
+    let (owner, item) = load_item(&ctx, user.as_ref(), id).await?;
+    let settings = Settings::from_context(&ctx).archive;
+
+    let (reference, format) = ArchiveFormat::from_file_name(&spec).ok_or(Error::NotFound)?;
+    let (reference, name, cache_dir) = (
+        reference.to_string(),
+        item.name.clone().unwrap_or_default(),
+        settings.cache_dir.clone(),
+    );
+    let (target, cache_path, size) = with_repository(&owner, &item, move |repo| {
+        let commit = resolve_commit(repo, &reference)?;
+        let target = ArchiveTarget {
+            repo_path: repo.path().to_path_buf(),
+            commit: commit.id().to_string(),
+            prefix: archive_service::archive_prefix(&name, &commit.id().to_string()),
+            format,
+        };
+        let cache_path = archive_service::cache_path(&cache_dir, &target);
+        // Cached archives are served whatever their size.
+        let size = if cache_path.exists() {
+            0
+        } else {
+            commit
+                .tree()
+                .map_err(archive_service::ArchiveError::from)
+                .and_then(|tree| archive_service::tree_size(repo, &tree))
+                .map_err(|e| {
+                    error!("Failed to size archive {}: {}", target.file_name(), e);
+                    Error::InternalServerError
+                })?
+        };
+        Ok((target, cache_path, size))
+    })
+    .await?;
+
+    let response = Response::builder()
+        .header(header::CONTENT_TYPE, target.format.content_type())
+        .header(
+            header::CONTENT_DISPOSITION,
+            raw_service::content_disposition(&target.file_name(), true),
+        )
+        .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff");
+
+    if !cache_path.exists() {
+        if size <= settings.max_stream_bytes {
+            return response
+                .body(archive_service::stream_archive(target))
+                .map_err(|e| Error::string(&e.to_string()));
+        }
+
+        if !archive_service::is_building(&cache_path) {
+            archive_service::mark_building(&cache_path).map_err(|e| {
+                error!("Failed to prepare archive cache {:?}: {}", cache_path, e);
+                Error::InternalServerError
+            })?;
+            ArchiveWorker::perform_later(
+                &ctx,
+                ArchiveWorkerArgs {
+                    target: target.clone(),
+                    cache_path: cache_path.clone(),
+                },
+            )
+            .await?;
+        }
+        // Workers running in the foreground have finished the archive by now.
+        if !cache_path.exists() {
+            return format::render()
+                .status(StatusCode::ACCEPTED)
+                .header(header::RETRY_AFTER, "10")
+                .json(serde_json::json!({
+                    "status": "building",
+                    "file_name": target.file_name(),
+                }));
         }
     }
 
-    
+    let file = tokio::fs::File::open(&cache_path).await.map_err(|e| {
+        error!("Failed to open cached archive {:?}: {}", cache_path, e);
+        Error::InternalServerError
+    })?;
+    let length = file.metadata().await.map(|m| m.len()).unwrap_or_default();
+    response
+        .header(header::CONTENT_LENGTH, length)
+        .body(Body::from_stream(ReaderStream::new(file)))
+        .map_err(|e| Error::string(&e.to_string()))
 }
 
 #[debug_handler]
 
 pub async fn add(
-    auth: middleware::auth::JWT,
+    auth: Auth,
     State(ctx): State<AppContext>,
     Form(params): Form<Params>,
 ) -> Result<Redirect> {
 
-    let service = GitService::new(PathBuf::new().join(env!("REPO_BASE_PATH")), USER);
+    let user = auth.user(&ctx, Scope::RepoWrite).await?;
+    let owner = user.namespace();
+    let service = git_service();
     let repo_name = params.name.clone().unwrap_or_default();
 
+    if let Err(message) = params.validate() {
+        return Ok(Redirect::to(&format!("git_repos?error={}", urlencoding::encode(&message))));
+    }
+
     // Handle the Result from create_bare_repository
-    let path = match service.create_bare_repository(&repo_name).await {
+    let path = match service.create_bare_repository(&owner, &repo_name).await {
         Ok(path) => path,
         Err(err) => {
             error!("Failed to create repository '{}': {}", repo_name, err);
@@ -183,38 +944,53 @@ pub async fn add(
     };
 
     let local_now = Local::now();
-    let item = ActiveModel { 
+    let mut item = ActiveModel { 
         created_at: ActiveValue::set(local_now.with_timezone(local_now.offset())), 
         updated_at: ActiveValue::set(local_now.with_timezone(local_now.offset())), 
         id: ActiveValue::NotSet,
         name: ActiveValue::set(params.name.clone()), 
-        path: ActiveValue::set(Some(path.to_string_lossy().to_string()))
+        path: ActiveValue::set(Some(path.to_string_lossy().to_string())),
+        user_id: ActiveValue::set(user.id),
+        visibility: ActiveValue::set(Visibility::default().as_str().to_string()),
+        ..Default::default()
     };
+    params.update_metadata(&mut item);
 
     // Handle database insertion error as well
     if let Err(err) = item.insert(&ctx.db).await {
         error!("Failed to insert repository '{}' into database: {}", repo_name, err);
 
         // You might want to clean up the created repository here if needed
-        let _ = service.delete_repository(&repo_name).await;
+        let _ = service.delete_repository(&owner, &repo_name).await;
         // service.delete_repository(&repo_name).await;
         return Ok(Redirect::to(&format!("git_repos?error={}", 
             urlencoding::encode(&format!("Failed to save repository to database: {}", err)))));
     }
+    if let Some(description) = params.description() {
+        if let Err(err) = service.write_description(&owner, &repo_name, &description).await {
+            warn!("Failed to write the description of repository '{}': {}", repo_name, err);
+        }
+    }
     info!("Successfully created repository '{}'", repo_name);
     Ok(Redirect::to("git_repos"))
 }
 
 #[debug_handler]
 
-pub async fn remove(Path(id): Path<i32>, State(ctx): State<AppContext>) -> Result<Response> {
+pub async fn remove(
+    auth: Auth,
+    Path(id): Path<i32>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
 
-    let service = GitService::new(PathBuf::new().join(env!("REPO_BASE_PATH")), USER);
-    let item = load_item(&ctx, id).await?;
+    let user = auth.user(&ctx, Scope::RepoWrite).await?;
+    let service = git_service();
+    let (owner, item) = load_item_for_write(&ctx, &user, id).await?;
     let repo_name = item.name.clone().unwrap_or_default();
+    let repo_id = item.id;
 
     // Handle the Result from delete_repository
-    if let Err(err) = service.delete_repository(&repo_name).await {
+    if let Err(err) = service.delete_repository(&owner.namespace(), &repo_name).await {
         error!("Failed to delete repository '{}': {}", repo_name, err);
         // Return redirect with error message
         return Ok(Redirect::to(&format!("git_repos?error={}", 
@@ -231,6 +1007,10 @@ pub async fn remove(Path(id): Path<i32>, State(ctx): State<AppContext>) -> Resul
             .into_response());
 
     }
+    let index_dir = Settings::from_context(&ctx).search.index_dir;
+    if let Err(err) = search_index_service::remove_index(&index_dir, repo_id) {
+        warn!("Failed to remove the search index of repository '{}': {}", repo_name, err);
+    }
     info!("Successfully deleted repository '{}'", repo_name);
     format::empty()
 
@@ -244,6 +1024,21 @@ pub fn routes() -> Routes {
         .add("new", get(new))
         .add("{id}", get(show))
         .add("{id}/edit", get(edit))
+        .add("{id}/entries", get(entries))
+        .add("{id}/file", get(file))
+        .add("{id}/tree/{*spec}", get(tree))
+        .add("{id}/blob/{*spec}", get(blob))
+        .add("{id}/raw/{*spec}", get(raw))
+        .add("{id}/blame/{*spec}", get(blame))
+        .add("{id}/commits", get(commits))
+        .add("{id}/history/{*spec}", get(history))
+        .add("{id}/commit/{oid}", get(commit))
+        .add("{id}/compare/{*spec}", get(compare))
+        .add("{id}/archive/{*spec}", get(archive))
         .add("{id}", delete(remove))
         .add("{id}", post(update))
 }
+
+pub fn explore_routes() -> Routes {
+    Routes::new().add("/explore", get(explore))
+}
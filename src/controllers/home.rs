@@ -1,14 +1,14 @@
 #![allow(clippy::missing_errors_doc)]
 #![allow(clippy::unnecessary_struct_initialization)]
 #![allow(clippy::unused_async)]
-use loco_rs::{controller::middleware, prelude::*};
+use loco_rs::prelude::*;
 
 use axum::debug_handler;
 
-use crate::views;
+use crate::{common::auth::Auth, views};
 #[debug_handler]
 pub async fn dashboard(
-    auth: middleware::auth::JWT,
+    _auth: Auth,
     ViewEngine(v): ViewEngine<TeraView>,
     State(_): State<AppContext>,
 ) -> Result<Response> {
@@ -0,0 +1,78 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::unused_async)]
+use axum::debug_handler;
+use loco_rs::prelude::*;
+
+use crate::{
+    common::auth::OptionalAuth,
+    controllers::git_repo::{load_item, load_viewer, with_repository},
+    models::{_entities::git_repos, repo_insights, users},
+    services::{insights_service::Insights, repo_retrive_service::default_ref},
+    views,
+    workers::insights::InsightsWorker,
+};
+
+/// Loads the stored insights of a repository. When there are none yet, or
+/// they predate the last push, an update is queued unless one already is, and
+/// `true` is returned along with whatever was stored.
+async fn load_insights(
+    ctx: &AppContext,
+    owner: &users::Model,
+    item: &git_repos::Model,
+) -> Result<(Option<(repo_insights::Model, Insights)>, bool)> {
+    let head = with_repository(owner, item, |repo| {
+        Ok(default_ref(repo)
+            .and_then(|branch| repo.revparse_single(&format!("refs/heads/{}", branch)).ok())
+            .map(|object| object.id().to_string()))
+    })
+    .await?;
+    let stored = repo_insights::Model::find_by_repo(&ctx.db, item.id).await?;
+
+    let outdated = stored
+        .as_ref()
+        .is_none_or(|(row, _)| row.head_commit != head);
+    if outdated {
+        InsightsWorker::queue_if_idle(ctx, item.id).await;
+    }
+    Ok((stored, outdated))
+}
+
+/// Shows commit activity, contributors and the punchcard of a repository, as
+/// computed after its last push.
+#[debug_handler]
+pub async fn show(
+    auth: OptionalAuth,
+    Path(id): Path<i32>,
+    ViewEngine(v): ViewEngine<TeraView>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let user = load_viewer(&ctx, &auth).await?;
+    let (owner, item) = load_item(&ctx, user.as_ref(), id).await?;
+    let (stored, outdated) = load_insights(&ctx, &owner, &item).await?;
+    views::insights::show(&v, &item, stored.as_ref(), outdated)
+}
+
+#[debug_handler]
+pub async fn api_show(
+    auth: OptionalAuth,
+    Path(id): Path<i32>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let user = load_viewer(&ctx, &auth).await?;
+    let (owner, item) = load_item(&ctx, user.as_ref(), id).await?;
+    let (stored, _) = load_insights(&ctx, &owner, &item).await?;
+    let (_, insights) = stored.ok_or(Error::NotFound)?;
+    format::json(insights)
+}
+
+pub fn routes() -> Routes {
+    Routes::new()
+        .prefix("git_repos/")
+        .add("{id}/insights", get(show))
+}
+
+pub fn api_routes() -> Routes {
+    Routes::new()
+        .prefix("/api/git_repos/")
+        .add("{id}/insights", get(api_show))
+}
@@ -1,9 +1,16 @@
 pub mod auth;
 pub mod home;
 pub mod git_repo;
+pub mod git_http;
+pub mod refs;
+pub mod search;
+pub mod insights;
 
 pub mod mysession;
 pub mod login;
 pub mod register;
 
 pub mod ssh;
+pub mod token;
+pub mod two_factor;
+pub mod sessions;
@@ -3,9 +3,10 @@
 #![allow(clippy::unused_async)]
 use loco_rs::prelude::*;
 use axum::extract::State;
-use axum_session::{Session, SessionNullPool};
 use serde::Serialize;
 
+use crate::common::session_store::DbSession;
+
 #[derive(Serialize)]
 pub struct SessionInfo {
     count: i32,
@@ -13,7 +14,7 @@ pub struct SessionInfo {
 
 pub async fn get_session(
     State(_ctx): State<AppContext>,
-    session: Session<SessionNullPool>,
+    session: DbSession,
 ) -> Result<Response> {
     // Get or initialize counter
     let count: i32 = session.get("counter").unwrap_or(0);
@@ -0,0 +1,262 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::unused_async)]
+use axum::{debug_handler, http::StatusCode, response::Redirect};
+use axum_extra::extract::Form;
+use git2::Signature;
+use loco_rs::prelude::*;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::{
+    common::auth::{Auth, OptionalAuth},
+    controllers::git_repo::{load_item, load_item_for_write, load_viewer, with_repository},
+    models::{git_repos::Access, personal_access_tokens::Scope, users},
+    services::refs_service::{self, RefsError},
+    views,
+};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BranchParams {
+    pub name: String,
+    /// The branch, tag or commit the new branch starts at.
+    pub from: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TagParams {
+    pub name: String,
+    /// The branch, tag or commit to tag.
+    pub target: String,
+    /// Creates an annotated tag when present, a lightweight one otherwise.
+    pub message: Option<String>,
+}
+
+/// Maps a `RefsError` to the response of a JSON endpoint.
+fn refs_error(e: RefsError) -> Error {
+    match e {
+        RefsError::NotFound(_) => Error::NotFound,
+        RefsError::GitError(e) => {
+            error!("Failed to update references: {}", e);
+            Error::InternalServerError
+        }
+        e => Error::BadRequest(e.to_string()),
+    }
+}
+
+/// Redirects a form back to `to`, with the error shown if there is one.
+fn redirect_after(to: &str, result: Result<(), RefsError>) -> Redirect {
+    match result {
+        Ok(()) => Redirect::to(to),
+        Err(e) => {
+            if let RefsError::GitError(e) = &e {
+                error!("Failed to update references: {}", e);
+            }
+            Redirect::to(&format!("{}?error={}", to, urlencoding::encode(&e.to_string())))
+        }
+    }
+}
+
+fn signature(user: &users::Model) -> Result<Signature<'static>> {
+    Signature::now(&user.name, &user.email).map_err(|e| Error::BadRequest(e.to_string()))
+}
+
+#[debug_handler]
+pub async fn branches(
+    auth: OptionalAuth,
+    Path(id): Path<i32>,
+    ViewEngine(v): ViewEngine<TeraView>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let user = load_viewer(&ctx, &auth).await?;
+    let (owner, item) = load_item(&ctx, user.as_ref(), id).await?;
+    let list = with_repository(&owner, &item, |repo| {
+        refs_service::list_branches(repo).map_err(refs_error)
+    })
+    .await?;
+    views::git_repo::branches(&v, &item, &list, item.access_for(user.as_ref()) >= Access::Write)
+}
+
+#[debug_handler]
+pub async fn create_branch(
+    auth: Auth,
+    Path(id): Path<i32>,
+    State(ctx): State<AppContext>,
+    Form(params): Form<BranchParams>,
+) -> Result<Redirect> {
+    let user = auth.user(&ctx, Scope::RepoWrite).await?;
+    let (owner, item) = load_item_for_write(&ctx, &user, id).await?;
+    let (name, from) = (params.name.trim().to_string(), params.from.trim().to_string());
+    let result = with_repository(&owner, &item, move |repo| {
+        Ok(refs_service::create_branch(repo, &name, &from))
+    })
+    .await?;
+    if result.is_ok() {
+        info!("Created branch '{}' in repository {}", params.name, id);
+    }
+    Ok(redirect_after(&format!("/git_repos/{}/branches", id), result))
+}
+
+/// Deletes a branch that is fully merged into the default branch. Shared by
+/// the branches page and the API.
+#[debug_handler]
+pub async fn delete_branch(
+    auth: Auth,
+    Path((id, name)): Path<(i32, String)>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let user = auth.user(&ctx, Scope::RepoWrite).await?;
+    let (owner, item) = load_item_for_write(&ctx, &user, id).await?;
+    let branch = name.clone();
+    with_repository(&owner, &item, move |repo| {
+        refs_service::delete_branch(repo, &branch).map_err(refs_error)
+    })
+    .await?;
+    info!("Deleted branch '{}' in repository {}", name, id);
+    format::empty()
+}
+
+#[debug_handler]
+pub async fn tags(
+    auth: OptionalAuth,
+    Path(id): Path<i32>,
+    ViewEngine(v): ViewEngine<TeraView>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let user = load_viewer(&ctx, &auth).await?;
+    let (owner, item) = load_item(&ctx, user.as_ref(), id).await?;
+    let (tags, branches) = with_repository(&owner, &item, |repo| {
+        Ok((
+            refs_service::list_tags(repo).map_err(refs_error)?,
+            refs_service::list_branches(repo).map_err(refs_error)?,
+        ))
+    })
+    .await?;
+    views::git_repo::tags(&v, &item, &tags, &branches, item.access_for(user.as_ref()) >= Access::Write)
+}
+
+#[debug_handler]
+pub async fn create_tag(
+    auth: Auth,
+    Path(id): Path<i32>,
+    State(ctx): State<AppContext>,
+    Form(params): Form<TagParams>,
+) -> Result<Redirect> {
+    let user = auth.user(&ctx, Scope::RepoWrite).await?;
+    let (owner, item) = load_item_for_write(&ctx, &user, id).await?;
+    let result = with_repository(&owner, &item, move |repo| {
+        Ok(refs_service::create_tag(
+            repo,
+            params.name.trim(),
+            params.target.trim(),
+            params.message.as_deref(),
+            &signature(&user)?,
+        )
+        .map(|_| info!("Created tag '{}' in repository {}", params.name, id)))
+    })
+    .await?;
+    Ok(redirect_after(&format!("/git_repos/{}/tags", id), result))
+}
+
+#[debug_handler]
+pub async fn api_branches(
+    auth: OptionalAuth,
+    Path(id): Path<i32>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let user = load_viewer(&ctx, &auth).await?;
+    let (owner, item) = load_item(&ctx, user.as_ref(), id).await?;
+    format::json(
+        with_repository(&owner, &item, |repo| refs_service::list_branches(repo).map_err(refs_error))
+            .await?,
+    )
+}
+
+#[debug_handler]
+pub async fn api_create_branch(
+    auth: Auth,
+    Path(id): Path<i32>,
+    State(ctx): State<AppContext>,
+    Json(params): Json<BranchParams>,
+) -> Result<Response> {
+    let user = auth.user(&ctx, Scope::RepoWrite).await?;
+    let (owner, item) = load_item_for_write(&ctx, &user, id).await?;
+    let branch = with_repository(&owner, &item, move |repo| {
+        let name = params.name.trim();
+        refs_service::create_branch(repo, name, params.from.trim()).map_err(refs_error)?;
+        info!("Created branch '{}' in repository {}", name, id);
+
+        refs_service::list_branches(repo)
+            .map_err(refs_error)?
+            .branches
+            .into_iter()
+            .find(|b| b.name == name)
+            .ok_or(Error::NotFound)
+    })
+    .await?;
+    format::render().status(StatusCode::CREATED).json(branch)
+}
+
+#[debug_handler]
+pub async fn api_tags(
+    auth: OptionalAuth,
+    Path(id): Path<i32>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let user = load_viewer(&ctx, &auth).await?;
+    let (owner, item) = load_item(&ctx, user.as_ref(), id).await?;
+    format::json(
+        with_repository(&owner, &item, |repo| refs_service::list_tags(repo).map_err(refs_error))
+            .await?,
+    )
+}
+
+#[debug_handler]
+pub async fn api_create_tag(
+    auth: Auth,
+    Path(id): Path<i32>,
+    State(ctx): State<AppContext>,
+    Json(params): Json<TagParams>,
+) -> Result<Response> {
+    let user = auth.user(&ctx, Scope::RepoWrite).await?;
+    let (owner, item) = load_item_for_write(&ctx, &user, id).await?;
+    let tag = with_repository(&owner, &item, move |repo| {
+        let name = params.name.trim();
+        refs_service::create_tag(
+            repo,
+            name,
+            params.target.trim(),
+            params.message.as_deref(),
+            &signature(&user)?,
+        )
+        .map_err(refs_error)?;
+        info!("Created tag '{}' in repository {}", name, id);
+
+        refs_service::list_tags(repo)
+            .map_err(refs_error)?
+            .into_iter()
+            .find(|t| t.name == name)
+            .ok_or(Error::NotFound)
+    })
+    .await?;
+    format::render().status(StatusCode::CREATED).json(tag)
+}
+
+pub fn routes() -> Routes {
+    Routes::new()
+        .prefix("git_repos/")
+        .add("{id}/branches", get(branches))
+        .add("{id}/branches", post(create_branch))
+        .add("{id}/branches/{*name}", delete(delete_branch))
+        .add("{id}/tags", get(tags))
+        .add("{id}/tags", post(create_tag))
+}
+
+pub fn api_routes() -> Routes {
+    Routes::new()
+        .prefix("/api/git_repos/")
+        .add("{id}/branches", get(api_branches))
+        .add("{id}/branches", post(api_create_branch))
+        .add("{id}/branches/{*name}", delete(delete_branch))
+        .add("{id}/tags", get(api_tags))
+        .add("{id}/tags", post(api_create_tag))
+}
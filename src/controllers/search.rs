@@ -0,0 +1,213 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::unused_async)]
+use axum::{debug_handler, extract::Query};
+use loco_rs::prelude::*;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::{
+    common::{auth::OptionalAuth, settings::Settings},
+    controllers::git_repo::{load_item, load_viewer, open_repository, with_repository},
+    models::{
+        git_repos::{self, Access},
+        users,
+    },
+    services::{
+        git_service::GitService,
+        repo_retrive_service::{default_ref, resolve_commit},
+        search_index_service::{self, IndexQuery, IndexedRepo, SearchIndexError},
+        search_service::{self, SearchError, SearchMode, SearchOutcome, SearchQuery},
+    },
+    views,
+};
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct SearchParams {
+    #[serde(default)]
+    pub q: String,
+    /// The branch, tag or commit to search; the default branch when missing.
+    #[serde(rename = "ref")]
+    pub reference: Option<String>,
+    /// `literal`, `regex`, or `files` to only match file names.
+    pub mode: Option<String>,
+    /// Matches case-sensitively when set.
+    pub case: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct GlobalSearchParams {
+    #[serde(default)]
+    pub q: String,
+    /// Only searches the repository with this name, or `owner/name`.
+    pub repo: Option<String>,
+    /// Only searches files matching this glob, e.g. `*.rs` or `src/**`.
+    pub path: Option<String>,
+    /// Only searches files in this language, e.g. `Rust`.
+    pub lang: Option<String>,
+}
+
+fn search_error(e: SearchError) -> Error {
+    match e {
+        SearchError::InvalidPattern(msg) => Error::BadRequest(msg),
+        SearchError::GitError(e) => {
+            error!("Failed to search repository: {}", e);
+            Error::InternalServerError
+        }
+    }
+}
+
+/// Searches the files of a repository at a ref, either their lines or, in
+/// `files` mode, their names.
+#[debug_handler]
+pub async fn search(
+    auth: OptionalAuth,
+    Path(id): Path<i32>,
+    Query(params): Query<SearchParams>,
+    ViewEngine(v): ViewEngine<TeraView>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let user = load_viewer(&ctx, &auth).await?;
+    let (owner, item) = load_item(&ctx, user.as_ref(), id).await?;
+    let settings = Settings::from_context(&ctx).search;
+
+    let reference = match &params.reference {
+        Some(reference) => reference.clone(),
+        None => default_ref(&open_repository(&owner, &item)?).ok_or(Error::NotFound)?,
+    };
+    if params.q.trim().is_empty() {
+        return views::search::repo(&v, &item, &params, &reference, None, None);
+    }
+
+    // Searches may take seconds, so they run off the async workers.
+    let (repo_owner, repo_item, query_ref) = (owner.clone(), item.clone(), reference.clone());
+    let pattern = params.q.clone();
+    let mode = params.mode.clone();
+    let case_sensitive = params.case.is_some();
+    let outcome = tokio::task::spawn_blocking(move || -> Result<_> {
+        let repo = open_repository(&repo_owner, &repo_item)?;
+        let commit = resolve_commit(&repo, &query_ref)?;
+        let mode = match mode.as_deref() {
+            Some("files") => {
+                return Ok(search_service::find_files(&commit, &pattern, settings.max_files)
+                    .map(|files| SearchOutcome::Files { files }));
+            }
+            Some("regex") => SearchMode::Regex,
+            _ => SearchMode::Literal,
+        };
+        let query = SearchQuery {
+            pattern,
+            mode,
+            case_sensitive,
+        };
+        Ok(search_service::search_code(&repo, &commit, &query, &settings).map(SearchOutcome::Code))
+    })
+    .await
+    .map_err(|e| Error::string(&format!("Search failed: {}", e)))??;
+
+    match outcome {
+        Ok(outcome) => views::search::repo(&v, &item, &params, &reference, Some(&outcome), None),
+        // A mistyped regex is shown next to the search box rather than as an error page.
+        Err(SearchError::InvalidPattern(msg)) => {
+            views::search::repo(&v, &item, &params, &reference, None, Some(&msg))
+        }
+        Err(e) => Err(search_error(e)),
+    }
+}
+
+/// Lists the files whose names match a fuzzy query, for "Go to file".
+#[debug_handler]
+pub async fn find(
+    auth: OptionalAuth,
+    Path(id): Path<i32>,
+    Query(params): Query<SearchParams>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let user = load_viewer(&ctx, &auth).await?;
+    let (owner, item) = load_item(&ctx, user.as_ref(), id).await?;
+    let settings = Settings::from_context(&ctx).search;
+
+    let hits = with_repository(&owner, &item, move |repo| {
+        let reference = match params.reference {
+            Some(reference) => reference,
+            None => default_ref(repo).ok_or(Error::NotFound)?,
+        };
+        let commit = resolve_commit(repo, &reference)?;
+        search_service::find_files(&commit, &params.q, settings.max_files).map_err(search_error)
+    })
+    .await?;
+    format::json(hits)
+}
+
+/// Searches the indexed default branches of every repository the user can
+/// read, which for anonymous visitors are the public ones.
+#[debug_handler]
+pub async fn global(
+    auth: OptionalAuth,
+    Query(params): Query<GlobalSearchParams>,
+    ViewEngine(v): ViewEngine<TeraView>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let user = load_viewer(&ctx, &auth).await?;
+    if params.q.trim().is_empty() {
+        return views::search::global(&v, &params, None, None);
+    }
+    let settings = Settings::from_context(&ctx).search;
+
+    let service = GitService::new(env!("REPO_BASE_PATH").into(), env!("GIT_USER"));
+    let wanted = params.repo.as_deref().map(str::trim).filter(|r| !r.is_empty());
+    let mut repos = Vec::new();
+    for (item, owner) in git_repos::Entity::find()
+        .find_also_related(users::Entity)
+        .all(&ctx.db)
+        .await?
+    {
+        let Some(owner) = owner else { continue };
+        if item.access_for(user.as_ref()) < Access::Read {
+            continue;
+        }
+        let name = item.name.clone().unwrap_or_default();
+        let full_name = format!("{}/{}", owner.name, name);
+        if wanted.is_some_and(|wanted| wanted != name && wanted != full_name) {
+            continue;
+        }
+        let Ok(path) = service.get_repository_path(&owner.namespace(), &name) else {
+            continue;
+        };
+        repos.push(IndexedRepo {
+            id: item.id,
+            name: full_name,
+            path,
+        });
+    }
+
+    let query = IndexQuery {
+        text: params.q.clone(),
+        path_glob: params.path.clone(),
+        language: params.lang.clone().filter(|lang| !lang.trim().is_empty()),
+    };
+    let results = tokio::task::spawn_blocking(move || {
+        search_index_service::search_index(&settings.index_dir, &repos, &query, &settings)
+    })
+    .await
+    .map_err(|e| Error::string(&format!("Search failed: {}", e)))?;
+
+    match results {
+        Ok(results) => views::search::global(&v, &params, Some(&results), None),
+        Err(SearchIndexError::InvalidQuery(msg)) => views::search::global(&v, &params, None, Some(&msg)),
+        Err(e) => {
+            error!("Failed to search the index: {}", e);
+            Err(Error::InternalServerError)
+        }
+    }
+}
+
+pub fn routes() -> Routes {
+    Routes::new()
+        .prefix("git_repos/")
+        .add("{id}/search", get(search))
+        .add("{id}/find", get(find))
+}
+
+pub fn global_routes() -> Routes {
+    Routes::new().add("/search", get(global))
+}
@@ -0,0 +1,101 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::unused_async)]
+//! The sessions a user is logged in with, so they can spot the ones they do
+//! not recognise and end them.
+use axum::{debug_handler, response::Redirect};
+use loco_rs::prelude::*;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{
+    common::auth::Auth,
+    models::{personal_access_tokens::Scope, sessions},
+    views::{self, session::SessionResponse},
+};
+
+/// The session the request was made with, if it was made with one rather
+/// than with a personal access token.
+fn current_pid(auth: &Auth) -> Option<Uuid> {
+    auth.session.as_ref().map(|session| session.pid)
+}
+
+/// Revokes every session of the user but the current one, or all of them
+/// when the request was made with a token.
+async fn revoke_others(ctx: &AppContext, auth: &Auth, user_id: i32) -> Result<u64> {
+    let revoked = match current_pid(auth) {
+        Some(keep) => sessions::ActiveModel::revoke_others(&ctx.db, user_id, keep).await?,
+        None => sessions::ActiveModel::revoke_all(&ctx.db, user_id).await?,
+    };
+    info!("Revoked {} other sessions of user {}", revoked, auth.pid);
+    Ok(revoked)
+}
+
+#[debug_handler]
+pub async fn list(
+    auth: Auth,
+    ViewEngine(v): ViewEngine<TeraView>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let user = auth.user(&ctx, Scope::Admin).await?;
+    let items = sessions::Model::list_by_owner(&ctx.db, user.id).await?;
+    views::session::list(&v, &items, current_pid(&auth))
+}
+
+/// Signs out everywhere else, e.g. after using a shared computer.
+#[debug_handler]
+pub async fn sign_out_others(auth: Auth, State(ctx): State<AppContext>) -> Result<Response> {
+    let user = auth.user(&ctx, Scope::Admin).await?;
+    revoke_others(&ctx, &auth, user.id).await?;
+    Ok(Redirect::to("/sessions").into_response())
+}
+
+/// Revokes one session; its JWT stops working immediately.
+#[debug_handler]
+pub async fn remove(
+    auth: Auth,
+    Path(pid): Path<Uuid>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let user = auth.user(&ctx, Scope::Admin).await?;
+    if !sessions::ActiveModel::revoke(&ctx.db, user.id, pid).await? {
+        return Err(Error::NotFound);
+    }
+    info!("Revoked session {} of user {}", pid, user.pid);
+    format::empty()
+}
+
+#[debug_handler]
+pub async fn api_list(auth: Auth, State(ctx): State<AppContext>) -> Result<Response> {
+    let user = auth.user(&ctx, Scope::Admin).await?;
+    let items = sessions::Model::list_by_owner(&ctx.db, user.id).await?;
+    let current = current_pid(&auth);
+    format::json(
+        items
+            .iter()
+            .map(|item| SessionResponse::new(item, current))
+            .collect::<Vec<_>>(),
+    )
+}
+
+#[debug_handler]
+pub async fn api_sign_out_others(auth: Auth, State(ctx): State<AppContext>) -> Result<Response> {
+    let user = auth.user(&ctx, Scope::Admin).await?;
+    let revoked = revoke_others(&ctx, &auth, user.id).await?;
+    format::json(serde_json::json!({ "revoked": revoked }))
+}
+
+pub fn routes() -> Routes {
+    Routes::new()
+        .prefix("sessions/")
+        .add("/", get(list))
+        .add("sign_out_others", post(sign_out_others))
+        .add("{pid}", delete(remove))
+}
+
+pub fn api_routes() -> Routes {
+    Routes::new()
+        .prefix("/api/sessions/")
+        .add("/", get(api_list))
+        .add("sign-out-others", post(api_sign_out_others))
+        .add("{pid}", delete(remove))
+}
@@ -5,11 +5,11 @@ use loco_rs::prelude::*;
 use serde::{Deserialize, Serialize};
 use axum::response::Redirect;
 use axum_extra::extract::Form;
-use sea_orm::{sea_query::Order, QueryOrder};
 use axum::debug_handler;
 
 use crate::{
-    models::_entities::sshes::{ActiveModel, Column, Entity, Model}, services::ssh_service::SshKeyService, views
+    common::auth::Auth,
+    models::{_entities::sshes::{ActiveModel, Model}, personal_access_tokens::Scope, users}, services::ssh_service::{validate_public_key, SshKeyService}, views
 };
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -25,39 +25,50 @@ impl Params {
       }
 }
 
-async fn load_item(ctx: &AppContext, id: i32) -> Result<Model> {
-    let item = Entity::find_by_id(id).one(&ctx.db).await?;
-    item.ok_or_else(|| Error::NotFound)
+/// Loads a key owned by `user`. Keys of other users are reported as missing.
+async fn load_item(ctx: &AppContext, user: &users::Model, id: i32) -> Result<Model> {
+    match Model::find_by_owner(&ctx.db, user.id, id).await {
+        Ok(item) => Ok(item),
+        Err(ModelError::EntityNotFound) => Err(Error::NotFound),
+        Err(err) => Err(err.into()),
+    }
 }
 
 #[debug_handler]
 pub async fn list(
+    auth: Auth,
     ViewEngine(v): ViewEngine<TeraView>,
     State(ctx): State<AppContext>,
 ) -> Result<Response> {
-    let item = Entity::find()
-        .order_by(Column::Id, Order::Desc)
-        .all(&ctx.db)
-        .await?;
+    let user = auth.user(&ctx, Scope::SshKeys).await?;
+    let item = Model::list_by_owner(&ctx.db, user.id).await?;
     views::ssh::list(&v, &item)
 }
 
 #[debug_handler]
 pub async fn new(
+    auth: Auth,
     ViewEngine(v): ViewEngine<TeraView>,
-    State(_ctx): State<AppContext>,
+    State(ctx): State<AppContext>,
 ) -> Result<Response> {
+    auth.user(&ctx, Scope::SshKeys).await?;
     views::ssh::create(&v)
 }
 
 #[debug_handler]
 pub async fn update(
+    auth: Auth,
     Path(id): Path<i32>,
     State(ctx): State<AppContext>,
     Form(params): Form<Params>,
 ) -> Result<Redirect> {
-    let item = load_item(&ctx, id).await?;
-    let saved = load_item(&ctx, id).await?;
+    let user = auth.user(&ctx, Scope::SshKeys).await?;
+    if let Err(err) = validate_public_key(&params.public_key.clone().unwrap_or_default()) {
+        return Ok(Redirect::to(&format!("../sshes?error={}",
+            urlencoding::encode(&format!("Invalid public key: {err}")))));
+    }
+    let item = load_item(&ctx, &user, id).await?;
+    let saved = item.clone();
     let mut item = item.into_active_model();
     params.update(&mut item);
     let item = item.update(&ctx.db).await?;
@@ -70,30 +81,41 @@ pub async fn update(
 
 #[debug_handler]
 pub async fn edit(
+    auth: Auth,
     Path(id): Path<i32>,
     ViewEngine(v): ViewEngine<TeraView>,
     State(ctx): State<AppContext>,
 ) -> Result<Response> {
-    let item = load_item(&ctx, id).await?;
+    let user = auth.user(&ctx, Scope::SshKeys).await?;
+    let item = load_item(&ctx, &user, id).await?;
     views::ssh::edit(&v, &item)
 }
 
 #[debug_handler]
 pub async fn show(
+    auth: Auth,
     Path(id): Path<i32>,
     ViewEngine(v): ViewEngine<TeraView>,
     State(ctx): State<AppContext>,
 ) -> Result<Response> {
-    let item = load_item(&ctx, id).await?;
+    let user = auth.user(&ctx, Scope::SshKeys).await?;
+    let item = load_item(&ctx, &user, id).await?;
     views::ssh::show(&v, &item)
 }
 
 #[debug_handler]
 pub async fn add(
+    auth: Auth,
     State(ctx): State<AppContext>,
     Form(params): Form<Params>,
 ) -> Result<Redirect> {
+    let user = auth.user(&ctx, Scope::SshKeys).await?;
+    if let Err(err) = validate_public_key(&params.public_key.clone().unwrap_or_default()) {
+        return Ok(Redirect::to(&format!("sshes?error={}",
+            urlencoding::encode(&format!("Invalid public key: {err}")))));
+    }
     let mut item = ActiveModel {
+        user_id: Set(user.id),
         ..Default::default()
     };
     params.update(&mut item);
@@ -105,8 +127,13 @@ pub async fn add(
 }
 
 #[debug_handler]
-pub async fn remove(Path(id): Path<i32>, State(ctx): State<AppContext>) -> Result<Response> {
-    let saved = load_item(&ctx, id).await?;
+pub async fn remove(
+    auth: Auth,
+    Path(id): Path<i32>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let user = auth.user(&ctx, Scope::SshKeys).await?;
+    let saved = load_item(&ctx, &user, id).await?;
     let service = SshKeyService::new(env!("GIT_HOME")); 
     service.remove_key(&saved)
         .map_err(|e| Error::Message(format!("Failed to remove key to authorized_keys: {e}")))?;
@@ -0,0 +1,164 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::unused_async)]
+//! Personal access tokens, for scripts and git clients that should not hold
+//! the user's password or full-power session.
+use axum::{debug_handler, http::StatusCode, response::Redirect};
+use axum_extra::extract::Form;
+use chrono::{Days, Local};
+use loco_rs::prelude::*;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::{
+    common::auth::Auth,
+    models::{
+        personal_access_tokens::{self, parse_scopes, Model, Scope},
+        users,
+    },
+    views::{self, token::TokenResponse},
+};
+
+const MAX_NAME_LENGTH: usize = 100;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TokenParams {
+    pub name: String,
+    /// Any of `repo:read`, `repo:write`, `ssh_keys` and `admin`.
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    /// Days until the token expires; it never expires when missing.
+    pub expires_in_days: Option<u64>,
+}
+
+/// The form of the tokens page, where "never" expires is sent as an empty value.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TokenForm {
+    pub name: String,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    #[serde(default)]
+    pub expires_in_days: String,
+}
+
+impl From<TokenForm> for TokenParams {
+    fn from(form: TokenForm) -> Self {
+        Self {
+            name: form.name,
+            scopes: form.scopes,
+            expires_in_days: form.expires_in_days.trim().parse().ok(),
+        }
+    }
+}
+
+/// Creates a token for `user`, returning it with its secret, or a message
+/// explaining what is wrong with `params`.
+async fn create_token(
+    ctx: &AppContext,
+    user: &users::Model,
+    params: &TokenParams,
+) -> Result<std::result::Result<(Model, String), String>> {
+    let name = params.name.trim();
+    if name.is_empty() || name.len() > MAX_NAME_LENGTH {
+        return Ok(Err(format!("The name must be 1 to {} characters long", MAX_NAME_LENGTH)));
+    }
+    let scopes = parse_scopes(&params.scopes);
+    if scopes.is_empty() {
+        return Ok(Err("Select at least one scope".to_string()));
+    }
+    let expires_at = match params.expires_in_days {
+        Some(days) => match Local::now().checked_add_days(Days::new(days)) {
+            Some(expires_at) if days > 0 => Some(expires_at.fixed_offset()),
+            _ => return Ok(Err("The expiry must be a positive number of days".to_string())),
+        },
+        None => None,
+    };
+
+    let created = personal_access_tokens::ActiveModel::create_for_user(&ctx.db, user.id, name, &scopes, expires_at).await?;
+    info!("Created personal access token {} for user {}", created.0.id, user.pid);
+    Ok(Ok(created))
+}
+
+async fn load_item(ctx: &AppContext, user: &users::Model, id: i32) -> Result<Model> {
+    match Model::find_by_owner(&ctx.db, user.id, id).await {
+        Ok(item) => Ok(item),
+        Err(ModelError::EntityNotFound) => Err(Error::NotFound),
+        Err(err) => Err(err.into()),
+    }
+}
+
+#[debug_handler]
+pub async fn list(
+    auth: Auth,
+    ViewEngine(v): ViewEngine<TeraView>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let user = auth.user(&ctx, Scope::Admin).await?;
+    let items = Model::list_by_owner(&ctx.db, user.id).await?;
+    views::token::list(&v, &items, &Scope::ALL)
+}
+
+/// Creates a token and shows its secret, which is not stored and so can only
+/// be seen this once.
+#[debug_handler]
+pub async fn add(
+    auth: Auth,
+    ViewEngine(v): ViewEngine<TeraView>,
+    State(ctx): State<AppContext>,
+    Form(form): Form<TokenForm>,
+) -> Result<Response> {
+    let user = auth.user(&ctx, Scope::Admin).await?;
+    match create_token(&ctx, &user, &form.into()).await? {
+        Ok((item, secret)) => views::token::created(&v, &item, &secret),
+        Err(message) => Ok(Redirect::to(&format!("tokens?error={}", urlencoding::encode(&message))).into_response()),
+    }
+}
+
+/// Revokes a token; it stops working immediately.
+#[debug_handler]
+pub async fn remove(
+    auth: Auth,
+    Path(id): Path<i32>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let user = auth.user(&ctx, Scope::Admin).await?;
+    let item = load_item(&ctx, &user, id).await?;
+    item.delete(&ctx.db).await?;
+    info!("Revoked personal access token {} of user {}", id, user.pid);
+    format::empty()
+}
+
+#[debug_handler]
+pub async fn api_list(auth: Auth, State(ctx): State<AppContext>) -> Result<Response> {
+    let user = auth.user(&ctx, Scope::Admin).await?;
+    let items = Model::list_by_owner(&ctx.db, user.id).await?;
+    format::json(items.iter().map(TokenResponse::new).collect::<Vec<_>>())
+}
+
+#[debug_handler]
+pub async fn api_create(
+    auth: Auth,
+    State(ctx): State<AppContext>,
+    Json(params): Json<TokenParams>,
+) -> Result<Response> {
+    let user = auth.user(&ctx, Scope::Admin).await?;
+    let (item, secret) = create_token(&ctx, &user, &params).await?.map_err(Error::BadRequest)?;
+    format::render()
+        .status(StatusCode::CREATED)
+        .json(TokenResponse::with_secret(&item, secret))
+}
+
+pub fn routes() -> Routes {
+    Routes::new()
+        .prefix("tokens/")
+        .add("/", get(list))
+        .add("/", post(add))
+        .add("{id}", delete(remove))
+}
+
+pub fn api_routes() -> Routes {
+    Routes::new()
+        .prefix("/api/tokens/")
+        .add("/", get(api_list))
+        .add("/", post(api_create))
+        .add("{id}", delete(remove))
+}
@@ -0,0 +1,236 @@
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::unused_async)]
+//! Enrolling an authenticator app for two-factor authentication, and managing
+//! it afterwards. Logging in with a second factor lives in `controllers::auth`.
+use axum::{debug_handler, response::Redirect};
+use axum_extra::extract::Form;
+use loco_rs::prelude::*;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::{
+    common::auth::Auth,
+    models::{personal_access_tokens::Scope, users},
+    services::totp_service,
+    views::{
+        self,
+        two_factor::{RecoveryCodesResponse, TwoFactorSetupResponse, TwoFactorStatusResponse},
+    },
+};
+
+/// Issuer shown next to the account in authenticator apps.
+const ISSUER: &str = "GitCrab";
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CodeParams {
+    /// A code from the authenticator app, or a recovery code where accepted.
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DisableParams {
+    pub password: String,
+    pub code: String,
+}
+
+/// Either the outcome of a request, or a message explaining why it was refused.
+type Outcome<T> = std::result::Result<T, String>;
+
+fn setup_response(user: &users::Model) -> Result<TwoFactorSetupResponse> {
+    let secret = user.totp_secret.clone().unwrap_or_default();
+    let provisioning_uri = totp_service::provisioning_uri(&secret, &user.email, ISSUER);
+    let qr_code_svg =
+        totp_service::qr_code_svg(&provisioning_uri).map_err(|e| Error::string(&e.to_string()))?;
+    Ok(TwoFactorSetupResponse {
+        secret,
+        provisioning_uri,
+        qr_code_svg,
+    })
+}
+
+/// Generates a new secret to enroll, replacing any enrollment in progress.
+async fn start_setup(ctx: &AppContext, user: users::Model) -> Result<Outcome<TwoFactorSetupResponse>> {
+    if user.two_factor_enabled() {
+        return Ok(Err("Two-factor authentication is already enabled".to_string()));
+    }
+    let user = user.into_active_model().start_totp_enrollment(&ctx.db).await?;
+    Ok(Ok(setup_response(&user)?))
+}
+
+/// Enables two-factor authentication once the user proved their app generates
+/// the right codes, returning their recovery codes.
+async fn confirm_setup(ctx: &AppContext, user: users::Model, code: &str) -> Result<Outcome<Vec<String>>> {
+    if user.two_factor_enabled() {
+        return Ok(Err("Two-factor authentication is already enabled".to_string()));
+    }
+    if user.totp_secret.is_none() {
+        return Ok(Err("Set up two-factor authentication first".to_string()));
+    }
+    let Some(step) = user.verify_totp(code)? else {
+        return Ok(Err("The code is not valid".to_string()));
+    };
+    let (user, codes) = user.into_active_model().enable_totp(&ctx.db, step).await?;
+    info!(user_pid = user.pid.to_string(), "two-factor authentication enabled");
+    Ok(Ok(codes))
+}
+
+async fn regenerate_codes(ctx: &AppContext, user: users::Model, code: &str) -> Result<Outcome<Vec<String>>> {
+    if !user.two_factor_enabled() {
+        return Ok(Err("Two-factor authentication is not enabled".to_string()));
+    }
+    let Some(user) = user.redeem_second_factor(&ctx.db, code).await? else {
+        return Ok(Err("The code is not valid".to_string()));
+    };
+    let (_, codes) = user.into_active_model().regenerate_recovery_codes(&ctx.db).await?;
+    Ok(Ok(codes))
+}
+
+/// Turns two-factor authentication off, which takes both the password and a
+/// second factor, so a stolen session alone cannot do it.
+async fn disable(ctx: &AppContext, user: users::Model, params: &DisableParams) -> Result<Outcome<()>> {
+    if !user.two_factor_enabled() {
+        return Ok(Err("Two-factor authentication is not enabled".to_string()));
+    }
+    if !user.verify_password(&params.password) {
+        return Ok(Err("The password or code is not valid".to_string()));
+    }
+    let Some(user) = user.redeem_second_factor(&ctx.db, &params.code).await? else {
+        return Ok(Err("The password or code is not valid".to_string()));
+    };
+    let user = user.into_active_model().disable_totp(&ctx.db).await?;
+    info!(user_pid = user.pid.to_string(), "two-factor authentication disabled");
+    Ok(Ok(()))
+}
+
+fn redirect_with_error(message: &str) -> Result<Response> {
+    Ok(Redirect::to(&format!("/two_factor?error={}", urlencoding::encode(message))).into_response())
+}
+
+#[debug_handler]
+pub async fn show(
+    auth: Auth,
+    ViewEngine(v): ViewEngine<TeraView>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let user = auth.user(&ctx, Scope::Admin).await?;
+    views::two_factor::show(&v, &TwoFactorStatusResponse::new(&user))
+}
+
+#[debug_handler]
+pub async fn setup(
+    auth: Auth,
+    ViewEngine(v): ViewEngine<TeraView>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let user = auth.user(&ctx, Scope::Admin).await?;
+    match start_setup(&ctx, user).await? {
+        Ok(setup) => views::two_factor::setup(&v, &setup),
+        Err(message) => redirect_with_error(&message),
+    }
+}
+
+#[debug_handler]
+pub async fn enable(
+    auth: Auth,
+    ViewEngine(v): ViewEngine<TeraView>,
+    State(ctx): State<AppContext>,
+    Form(params): Form<CodeParams>,
+) -> Result<Response> {
+    let user = auth.user(&ctx, Scope::Admin).await?;
+    match confirm_setup(&ctx, user, &params.code).await? {
+        Ok(codes) => views::two_factor::recovery_codes(&v, &codes),
+        Err(message) => redirect_with_error(&message),
+    }
+}
+
+#[debug_handler]
+pub async fn recovery_codes(
+    auth: Auth,
+    ViewEngine(v): ViewEngine<TeraView>,
+    State(ctx): State<AppContext>,
+    Form(params): Form<CodeParams>,
+) -> Result<Response> {
+    let user = auth.user(&ctx, Scope::Admin).await?;
+    match regenerate_codes(&ctx, user, &params.code).await? {
+        Ok(codes) => views::two_factor::recovery_codes(&v, &codes),
+        Err(message) => redirect_with_error(&message),
+    }
+}
+
+#[debug_handler]
+pub async fn remove(
+    auth: Auth,
+    State(ctx): State<AppContext>,
+    Form(params): Form<DisableParams>,
+) -> Result<Response> {
+    let user = auth.user(&ctx, Scope::Admin).await?;
+    match disable(&ctx, user, &params).await? {
+        Ok(()) => Ok(Redirect::to("/two_factor").into_response()),
+        Err(message) => redirect_with_error(&message),
+    }
+}
+
+#[debug_handler]
+pub async fn api_show(auth: Auth, State(ctx): State<AppContext>) -> Result<Response> {
+    let user = auth.user(&ctx, Scope::Admin).await?;
+    format::json(TwoFactorStatusResponse::new(&user))
+}
+
+#[debug_handler]
+pub async fn api_setup(auth: Auth, State(ctx): State<AppContext>) -> Result<Response> {
+    let user = auth.user(&ctx, Scope::Admin).await?;
+    format::json(start_setup(&ctx, user).await?.map_err(Error::BadRequest)?)
+}
+
+#[debug_handler]
+pub async fn api_enable(
+    auth: Auth,
+    State(ctx): State<AppContext>,
+    Json(params): Json<CodeParams>,
+) -> Result<Response> {
+    let user = auth.user(&ctx, Scope::Admin).await?;
+    let codes = confirm_setup(&ctx, user, &params.code).await?.map_err(Error::BadRequest)?;
+    format::json(RecoveryCodesResponse { recovery_codes: codes })
+}
+
+#[debug_handler]
+pub async fn api_recovery_codes(
+    auth: Auth,
+    State(ctx): State<AppContext>,
+    Json(params): Json<CodeParams>,
+) -> Result<Response> {
+    let user = auth.user(&ctx, Scope::Admin).await?;
+    let codes = regenerate_codes(&ctx, user, &params.code).await?.map_err(Error::BadRequest)?;
+    format::json(RecoveryCodesResponse { recovery_codes: codes })
+}
+
+#[debug_handler]
+pub async fn api_disable(
+    auth: Auth,
+    State(ctx): State<AppContext>,
+    Json(params): Json<DisableParams>,
+) -> Result<Response> {
+    let user = auth.user(&ctx, Scope::Admin).await?;
+    disable(&ctx, user, &params).await?.map_err(Error::BadRequest)?;
+    format::empty_json()
+}
+
+pub fn routes() -> Routes {
+    Routes::new()
+        .prefix("two_factor/")
+        .add("/", get(show))
+        .add("setup", post(setup))
+        .add("enable", post(enable))
+        .add("recovery_codes", post(recovery_codes))
+        .add("disable", post(remove))
+}
+
+pub fn api_routes() -> Routes {
+    Routes::new()
+        .prefix("/api/auth/2fa/")
+        .add("/", get(api_show))
+        .add("setup", post(api_setup))
+        .add("enable", post(api_enable))
+        .add("recovery-codes", post(api_recovery_codes))
+        .add("disable", post(api_disable))
+}
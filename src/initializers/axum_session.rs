@@ -2,7 +2,10 @@ use async_trait::async_trait;
 use axum::Router;
 
 use loco_rs::app::{AppContext, Initializer};
-use loco_rs::Result;
+use loco_rs::{Error, Result};
+
+use crate::common::session_store::SessionDbPool;
+
 #[allow(clippy::module_name_repetitions)]
 pub struct AxumSessionInitializer;
 #[async_trait]
@@ -11,14 +14,15 @@ impl Initializer for AxumSessionInitializer {
         "axum-session".to_string()
     }
 
-    async fn after_routes(&self, router: Router, _ctx: &AppContext) -> Result<Router> {
+    async fn after_routes(&self, router: Router, ctx: &AppContext) -> Result<Router> {
         let session_config =
-            axum_session::SessionConfig::default().with_table_name("sessions_table");
-        let session_store =
-            axum_session::SessionStore::<axum_session::SessionNullPool>::new(None, session_config)
-                .await
-                .unwrap();
+            axum_session::SessionConfig::default().with_table_name("session_store");
+        let session_store = axum_session::SessionStore::<SessionDbPool>::new(
+            Some(SessionDbPool::new(ctx.db.clone())),
+            session_config,
+        )
+        .await
+        .map_err(|e| Error::string(&e.to_string()))?;
         Ok(router.layer(axum_session::SessionLayer::new(session_store)))
     }
 }
-
@@ -0,0 +1,23 @@
+use async_trait::async_trait;
+use axum::Router;
+
+use loco_rs::app::{AppContext, Initializer};
+use loco_rs::Result;
+
+use crate::common::csrf;
+
+/// Checks state-changing requests for a CSRF token. It needs the session, so
+/// it has to be listed before the `axum-session` initializer, whose layer
+/// then runs first.
+#[allow(clippy::module_name_repetitions)]
+pub struct CsrfInitializer;
+#[async_trait]
+impl Initializer for CsrfInitializer {
+    fn name(&self) -> String {
+        "csrf".to_string()
+    }
+
+    async fn after_routes(&self, router: Router, _ctx: &AppContext) -> Result<Router> {
+        Ok(router.layer(axum::middleware::from_fn(csrf::protect)))
+    }
+}
@@ -1,2 +1,3 @@
 pub mod view_engine;
 pub mod axum_session;
+pub mod csrf;
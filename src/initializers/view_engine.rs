@@ -8,6 +8,8 @@ use loco_rs::{
 };
 use tracing::info;
 
+use crate::common::csrf;
+
 const I18N_DIR: &str = "assets/i18n";
 const I18N_SHARED: &str = "assets/i18n/shared.ftl";
 #[allow(clippy::module_name_repetitions)]
@@ -42,6 +44,19 @@ impl Initializer for ViewEngineInitializer {
             info!("locales loaded");
         }
 
+        #[cfg(debug_assertions)]
+        {
+            let mut tera = tera_engine.tera.lock().expect("lock");
+            tera.register_function("csrf_token", csrf::CsrfToken);
+            tera.register_function("csrf_field", csrf::CsrfField);
+        }
+
+        #[cfg(not(debug_assertions))]
+        {
+            tera_engine.tera.register_function("csrf_token", csrf::CsrfToken);
+            tera_engine.tera.register_function("csrf_field", csrf::CsrfField);
+        }
+
         Ok(router.layer(Extension(ViewEngine::from(tera_engine))))
     }
 }
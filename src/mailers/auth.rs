@@ -9,6 +9,7 @@ use crate::models::users;
 static welcome: Dir<'_> = include_dir!("src/mailers/auth/welcome");
 static forgot: Dir<'_> = include_dir!("src/mailers/auth/forgot");
 static magic_link: Dir<'_> = include_dir!("src/mailers/auth/magic_link");
+static locked: Dir<'_> = include_dir!("src/mailers/auth/locked");
 // #[derive(Mailer)] // -- disabled for faster build speed. it works. but lets
 // move on for now.
 
@@ -89,4 +90,28 @@ impl AuthMailer {
 
         Ok(())
     }
+
+    /// Tells the user their account was locked after too many failed logins.
+    ///
+    /// # Errors
+    ///
+    /// When email sending is failed
+    pub async fn send_account_locked(ctx: &AppContext, user: &users::Model) -> Result<()> {
+        Self::mail_template(
+            ctx,
+            &locked,
+            mailer::Args {
+                to: user.email.to_string(),
+                locals: json!({
+                  "name": user.name,
+                  "lockedUntil": user.locked_until.map(|until| until.to_rfc2822()),
+                  "domain": ctx.config.server.full_url()
+                }),
+                ..Default::default()
+            },
+        )
+        .await?;
+
+        Ok(())
+    }
 }
@@ -0,0 +1,12 @@
+;<html>
+
+<body>
+  Hey {{name}},
+  Someone entered a wrong password for your account too many times, so we locked it until {{lockedUntil}}.
+  If this was you, you can log in again after that, or reset your password now:
+  <a href="{{domain}}/reset">Reset Your Password</a>
+  If this wasn't you, consider changing your password and enabling two-factor authentication.
+  Best regards,<br>The Loco Team</br>
+</body>
+
+</html>
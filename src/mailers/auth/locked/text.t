@@ -0,0 +1,3 @@
+Someone entered a wrong password for your account too many times, so we locked it until {{lockedUntil}}.
+
+If this wasn't you, consider changing your password and enabling two-factor authentication.
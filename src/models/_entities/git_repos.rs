@@ -12,7 +12,60 @@ pub struct Model {
     pub id: i32,
     pub name: Option<String>,
     pub path: Option<String>,
+    pub user_id: i32,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub description: Option<String>,
+    pub topics: Option<String>,
+    pub homepage: Option<String>,
+    pub visibility: String,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-pub enum Relation {}
+pub enum Relation {
+    #[sea_orm(has_many = "super::repo_insight_contributors::Entity")]
+    RepoInsightContributors,
+    #[sea_orm(has_many = "super::repo_insight_punchcards::Entity")]
+    RepoInsightPunchcards,
+    #[sea_orm(has_many = "super::repo_insight_weeks::Entity")]
+    RepoInsightWeeks,
+    #[sea_orm(has_many = "super::repo_insights::Entity")]
+    RepoInsights,
+    #[sea_orm(
+        belongs_to = "super::users::Entity",
+        from = "Column::UserId",
+        to = "super::users::Column::Id",
+        on_update = "Cascade",
+        on_delete = "Cascade"
+    )]
+    Users,
+}
+
+impl Related<super::repo_insight_contributors::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::RepoInsightContributors.def()
+    }
+}
+
+impl Related<super::repo_insight_punchcards::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::RepoInsightPunchcards.def()
+    }
+}
+
+impl Related<super::repo_insight_weeks::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::RepoInsightWeeks.def()
+    }
+}
+
+impl Related<super::repo_insights::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::RepoInsights.def()
+    }
+}
+
+impl Related<super::users::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Users.def()
+    }
+}
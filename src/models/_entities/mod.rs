@@ -3,5 +3,11 @@
 pub mod prelude;
 
 pub mod git_repos;
+pub mod personal_access_tokens;
+pub mod repo_insight_contributors;
+pub mod repo_insight_punchcards;
+pub mod repo_insight_weeks;
+pub mod repo_insights;
+pub mod sessions;
 pub mod sshes;
 pub mod users;
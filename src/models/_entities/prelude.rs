@@ -1,5 +1,11 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.8
 
 pub use super::git_repos::Entity as GitRepos;
+pub use super::personal_access_tokens::Entity as PersonalAccessTokens;
+pub use super::repo_insight_contributors::Entity as RepoInsightContributors;
+pub use super::repo_insight_punchcards::Entity as RepoInsightPunchcards;
+pub use super::repo_insight_weeks::Entity as RepoInsightWeeks;
+pub use super::repo_insights::Entity as RepoInsights;
+pub use super::sessions::Entity as Sessions;
 pub use super::sshes::Entity as Sshes;
 pub use super::users::Entity as Users;
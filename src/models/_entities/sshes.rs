@@ -12,6 +12,7 @@ pub struct Model {
     pub id: i32,
     pub public_key: Option<String>,
     pub title: Option<String>,
+    pub user_id: i32,
 }
 impl Model {
     pub(crate) fn as_bytes(&self) -> &[u8] {
@@ -20,4 +21,19 @@ impl Model {
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-pub enum Relation {}
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::users::Entity",
+        from = "Column::UserId",
+        to = "super::users::Column::Id",
+        on_update = "Cascade",
+        on_delete = "Cascade"
+    )]
+    Users,
+}
+
+impl Related<super::users::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Users.def()
+    }
+}
@@ -16,6 +16,7 @@ pub struct Model {
     pub password: String,
     #[sea_orm(unique)]
     pub api_key: String,
+    #[sea_orm(unique)]
     pub name: String,
     pub reset_token: Option<String>,
     pub reset_sent_at: Option<DateTimeWithTimeZone>,
@@ -24,7 +25,50 @@ pub struct Model {
     pub email_verified_at: Option<DateTimeWithTimeZone>,
     pub magic_link_token: Option<String>,
     pub magic_link_expiration: Option<DateTimeWithTimeZone>,
+    pub totp_secret: Option<String>,
+    pub totp_enabled_at: Option<DateTimeWithTimeZone>,
+    pub totp_last_step: Option<i64>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub totp_recovery_codes: Option<String>,
+    pub totp_challenge_token: Option<String>,
+    pub totp_challenge_expiration: Option<DateTimeWithTimeZone>,
+    pub totp_challenge_attempts: i32,
+    pub failed_login_attempts: i32,
+    pub locked_until: Option<DateTimeWithTimeZone>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-pub enum Relation {}
+pub enum Relation {
+    #[sea_orm(has_many = "super::git_repos::Entity")]
+    GitRepos,
+    #[sea_orm(has_many = "super::personal_access_tokens::Entity")]
+    PersonalAccessTokens,
+    #[sea_orm(has_many = "super::sessions::Entity")]
+    Sessions,
+    #[sea_orm(has_many = "super::sshes::Entity")]
+    Sshes,
+}
+
+impl Related<super::git_repos::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::GitRepos.def()
+    }
+}
+
+impl Related<super::personal_access_tokens::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PersonalAccessTokens.def()
+    }
+}
+
+impl Related<super::sessions::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Sessions.def()
+    }
+}
+
+impl Related<super::sshes::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Sshes.def()
+    }
+}
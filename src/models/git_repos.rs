@@ -1,7 +1,54 @@
-use sea_orm::entity::prelude::*;
-pub use super::_entities::git_repos::{ActiveModel, Model, Entity};
+use loco_rs::prelude::*;
+use serde::{Deserialize, Serialize};
+use sea_orm::{sea_query::Order, Condition, QueryOrder};
+pub use super::_entities::git_repos::{ActiveModel, Column, Model, Entity};
+use super::users;
 pub type GitRepos = Entity;
 
+/// The level of access a user has on a repository. Variants are ordered so that
+/// `access >= Access::Read` reads naturally.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Access {
+    None,
+    Read,
+    Write,
+}
+
+/// Who can see a repository, besides its owner.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    /// Only the owner.
+    #[default]
+    Private,
+    /// Every logged-in user.
+    Internal,
+    /// Everyone, including anonymous visitors.
+    Public,
+}
+
+impl Visibility {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Private => "private",
+            Self::Internal => "internal",
+            Self::Public => "public",
+        }
+    }
+
+    /// Parses a stored or submitted visibility. Unknown values are treated as
+    /// private, so a repository is never exposed by mistake.
+    #[must_use]
+    pub fn parse(value: &str) -> Self {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "public" => Self::Public,
+            "internal" => Self::Internal,
+            _ => Self::Private,
+        }
+    }
+}
+
 #[async_trait::async_trait]
 impl ActiveModelBehavior for ActiveModel {
     async fn before_save<C>(self, _db: &C, insert: bool) -> std::result::Result<Self, DbErr>
@@ -19,7 +66,157 @@ impl ActiveModelBehavior for ActiveModel {
 }
 
 // implement your read-oriented logic here
-impl Model {}
+impl Model {
+    /// Returns the access `user` has on this repository. Only the owner can
+    /// push; who else can read depends on the visibility of the repository.
+    #[must_use]
+    pub fn access_for(&self, user: Option<&users::Model>) -> Access {
+        match (user, self.visibility()) {
+            (Some(user), _) if user.id == self.user_id => Access::Write,
+            (_, Visibility::Public) | (Some(_), Visibility::Internal) => Access::Read,
+            _ => Access::None,
+        }
+    }
+
+    #[must_use]
+    pub fn visibility(&self) -> Visibility {
+        Visibility::parse(&self.visibility)
+    }
+
+    /// finds a repository by id, along with its owner
+    ///
+    /// # Errors
+    ///
+    /// When the repository does not exist or DB query error
+    pub async fn find_with_owner(db: &DatabaseConnection, id: i32) -> ModelResult<(Self, users::Model)> {
+        let item = Entity::find_by_id(id)
+            .find_also_related(users::Entity)
+            .one(db)
+            .await?;
+        match item {
+            Some((item, Some(owner))) => Ok((item, owner)),
+            _ => Err(ModelError::EntityNotFound),
+        }
+    }
+
+    /// finds a repository by its owner namespace and name, as they appear in clone URLs
+    ///
+    /// # Errors
+    ///
+    /// When the owner or the repository does not exist or DB query error
+    pub async fn find_by_namespace(
+        db: &DatabaseConnection,
+        namespace: &str,
+        name: &str,
+    ) -> ModelResult<Self> {
+        let owner = users::Model::find_by_username(db, namespace).await?;
+        let item = Entity::find()
+            .filter(
+                model::query::condition()
+                    .eq(Column::UserId, owner.id)
+                    .eq(Column::Name, name)
+                    .build(),
+            )
+            .one(db)
+            .await?;
+        item.ok_or_else(|| ModelError::EntityNotFound)
+    }
+
+    /// finds a repository by id, restricted to the repositories owned by the given user
+    ///
+    /// # Errors
+    ///
+    /// When the repository does not exist, belongs to another user or DB query error
+    pub async fn find_by_owner(db: &DatabaseConnection, user_id: i32, id: i32) -> ModelResult<Self> {
+        let item = Entity::find_by_id(id)
+            .filter(
+                model::query::condition()
+                    .eq(Column::UserId, user_id)
+                    .build(),
+            )
+            .one(db)
+            .await?;
+        item.ok_or_else(|| ModelError::EntityNotFound)
+    }
+
+    /// lists the repositories `user` can read, along with their owners, most
+    /// recently updated first. Anonymous visitors only see public repositories.
+    ///
+    /// # Errors
+    ///
+    /// When DB query error
+    pub async fn list_visible_to(
+        db: &DatabaseConnection,
+        user: Option<&users::Model>,
+    ) -> ModelResult<Vec<(Self, users::Model)>> {
+        let mut visible = Condition::any().add(Column::Visibility.eq(Visibility::Public.as_str()));
+        if let Some(user) = user {
+            visible = visible
+                .add(Column::Visibility.eq(Visibility::Internal.as_str()))
+                .add(Column::UserId.eq(user.id));
+        }
+        Ok(Entity::find()
+            .filter(visible)
+            .find_also_related(users::Entity)
+            .order_by(Column::UpdatedAt, Order::Desc)
+            .all(db)
+            .await?
+            .into_iter()
+            .filter_map(|(item, owner)| owner.map(|owner| (item, owner)))
+            .collect())
+    }
+
+    /// lists the repositories owned by the given user, newest first
+    ///
+    /// # Errors
+    ///
+    /// When DB query error
+    pub async fn list_by_owner(db: &DatabaseConnection, user_id: i32) -> ModelResult<Vec<Self>> {
+        Ok(Entity::find()
+            .filter(
+                model::query::condition()
+                    .eq(Column::UserId, user_id)
+                    .build(),
+            )
+            .order_by(Column::Id, Order::Desc)
+            .all(db)
+            .await?)
+    }
+}
+
+/// Normalizes topics entered as a comma or whitespace separated list: topics
+/// are lowercased, may only contain letters, digits and `-`, and are kept once
+/// each, in the order given.
+#[must_use]
+pub fn normalize_topics(input: &str) -> Vec<String> {
+    let mut topics: Vec<String> = Vec::new();
+    for topic in input.split(|c: char| c == ',' || c.is_whitespace()) {
+        let topic: String = topic
+            .trim()
+            .to_lowercase()
+            .chars()
+            .filter(|c| c.is_alphanumeric() || *c == '-')
+            .collect();
+        let topic = topic.trim_matches('-').to_string();
+        if !topic.is_empty() && topic.len() <= 50 && !topics.contains(&topic) {
+            topics.push(topic);
+        }
+    }
+    topics
+}
+
+/// Whether `url` can be linked to as the homepage of a repository: an
+/// absolute `http` or `https` URL with a host and no whitespace.
+#[must_use]
+pub fn is_valid_homepage(url: &str) -> bool {
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"));
+    rest.is_some_and(|rest| {
+        let host = rest.split(['/', '?', '#']).next().unwrap_or_default();
+        !host.is_empty() && !url.chars().any(char::is_whitespace)
+    })
+}
 
 // implement your write-oriented logic here
 impl ActiveModel {}
@@ -2,3 +2,9 @@ pub mod _entities;
 pub mod users;
 pub mod git_repos;
 pub mod sshes;
+pub mod repo_insights;
+pub mod repo_insight_weeks;
+pub mod repo_insight_contributors;
+pub mod repo_insight_punchcards;
+pub mod personal_access_tokens;
+pub mod sessions;
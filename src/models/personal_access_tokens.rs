@@ -0,0 +1,215 @@
+use chrono::{DateTime, FixedOffset, Local};
+use loco_rs::prelude::*;
+use sea_orm::{sea_query::Order, QueryOrder};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+pub use super::_entities::personal_access_tokens::{ActiveModel, Column, Entity, Model};
+pub type PersonalAccessTokens = Entity;
+
+/// Prefix of every personal access token, so they can be told apart from
+/// session tokens and API keys, and found by secret scanners.
+pub const TOKEN_PREFIX: &str = "gcp_";
+
+/// `last_used_at` is only written again once it is older than this, so busy
+/// clients do not write on every request.
+const LAST_USED_PRECISION_SECS: i64 = 60;
+
+/// What a personal access token may be used for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Scope {
+    /// Browse, search and clone the repositories the user can read.
+    #[serde(rename = "repo:read")]
+    RepoRead,
+    /// Push to, and create, change or delete the user's repositories.
+    #[serde(rename = "repo:write")]
+    RepoWrite,
+    /// Manage the user's SSH keys.
+    #[serde(rename = "ssh_keys")]
+    SshKeys,
+    /// Everything, including managing personal access tokens.
+    #[serde(rename = "admin")]
+    Admin,
+}
+
+impl Scope {
+    pub const ALL: [Self; 4] = [Self::RepoRead, Self::RepoWrite, Self::SshKeys, Self::Admin];
+
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::RepoRead => "repo:read",
+            Self::RepoWrite => "repo:write",
+            Self::SshKeys => "ssh_keys",
+            Self::Admin => "admin",
+        }
+    }
+
+    #[must_use]
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|scope| scope.as_str() == value.trim())
+    }
+}
+
+/// Parses scopes, ignoring unknown ones and keeping each once.
+#[must_use]
+pub fn parse_scopes<S: AsRef<str>>(values: &[S]) -> Vec<Scope> {
+    let mut scopes = Vec::new();
+    for scope in values.iter().filter_map(|value| Scope::parse(value.as_ref())) {
+        if !scopes.contains(&scope) {
+            scopes.push(scope);
+        }
+    }
+    scopes
+}
+
+/// Hashes a token for storage. Tokens are long random strings, so a fast
+/// hash is enough; there is nothing to brute force.
+#[must_use]
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(self, _db: &C, insert: bool) -> std::result::Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if !insert && self.updated_at.is_unchanged() {
+            let mut this = self;
+            this.updated_at = sea_orm::ActiveValue::Set(chrono::Utc::now().into());
+            Ok(this)
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+// implement your read-oriented logic here
+impl Model {
+    #[must_use]
+    pub fn scopes(&self) -> Vec<Scope> {
+        parse_scopes(&self.scopes.split(',').collect::<Vec<_>>())
+    }
+
+    /// Whether the token may be used for `scope`. `admin` grants everything,
+    /// and `repo:write` implies `repo:read`.
+    #[must_use]
+    pub fn allows(&self, scope: Scope) -> bool {
+        let scopes = self.scopes();
+        scopes.contains(&scope)
+            || scopes.contains(&Scope::Admin)
+            || (scope == Scope::RepoRead && scopes.contains(&Scope::RepoWrite))
+    }
+
+    #[must_use]
+    pub fn is_expired(&self, now: DateTime<FixedOffset>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    /// finds the token a client presented, as long as it has not expired
+    ///
+    /// # Errors
+    ///
+    /// When the token is unknown or expired, or DB query error
+    pub async fn find_by_token(db: &DatabaseConnection, token: &str) -> ModelResult<Self> {
+        if !token.starts_with(TOKEN_PREFIX) {
+            return Err(ModelError::EntityNotFound);
+        }
+        let item = Entity::find()
+            .filter(Column::TokenHash.eq(hash_token(token)))
+            .one(db)
+            .await?
+            .ok_or(ModelError::EntityNotFound)?;
+        if item.is_expired(Local::now().fixed_offset()) {
+            return Err(ModelError::EntityNotFound);
+        }
+        Ok(item)
+    }
+
+    /// finds a token by id, restricted to the tokens of the given user
+    ///
+    /// # Errors
+    ///
+    /// When the token does not exist, belongs to another user or DB query error
+    pub async fn find_by_owner(db: &DatabaseConnection, user_id: i32, id: i32) -> ModelResult<Self> {
+        let item = Entity::find_by_id(id)
+            .filter(Column::UserId.eq(user_id))
+            .one(db)
+            .await?;
+        item.ok_or_else(|| ModelError::EntityNotFound)
+    }
+
+    /// lists the tokens of the given user, newest first
+    ///
+    /// # Errors
+    ///
+    /// When DB query error
+    pub async fn list_by_owner(db: &DatabaseConnection, user_id: i32) -> ModelResult<Vec<Self>> {
+        Ok(Entity::find()
+            .filter(Column::UserId.eq(user_id))
+            .order_by(Column::Id, Order::Desc)
+            .all(db)
+            .await?)
+    }
+
+    /// records that the token was just used
+    ///
+    /// # Errors
+    ///
+    /// When DB query error
+    pub async fn touch(self, db: &DatabaseConnection) -> ModelResult<Self> {
+        let now = Local::now().fixed_offset();
+        let recent = self
+            .last_used_at
+            .is_some_and(|used| (now - used).num_seconds() < LAST_USED_PRECISION_SECS);
+        if recent {
+            return Ok(self);
+        }
+        let mut item = self.into_active_model();
+        item.last_used_at = ActiveValue::set(Some(now));
+        Ok(item.update(db).await?)
+    }
+}
+
+// implement your write-oriented logic here
+impl ActiveModel {
+    /// creates a token for the given user, returning it along with the only
+    /// copy of its secret
+    ///
+    /// # Errors
+    ///
+    /// When DB query error
+    pub async fn create_for_user(
+        db: &DatabaseConnection,
+        user_id: i32,
+        name: &str,
+        scopes: &[Scope],
+        expires_at: Option<DateTime<FixedOffset>>,
+    ) -> ModelResult<(Model, String)> {
+        let secret = format!(
+            "{}{}{}",
+            TOKEN_PREFIX,
+            Uuid::new_v4().simple(),
+            Uuid::new_v4().simple()
+        );
+        let scopes: Vec<&str> = scopes.iter().map(|scope| scope.as_str()).collect();
+        let item = Self {
+            name: ActiveValue::set(name.trim().to_string()),
+            token_hash: ActiveValue::set(hash_token(&secret)),
+            token_hint: ActiveValue::set(secret[secret.len() - 4..].to_string()),
+            scopes: ActiveValue::set(scopes.join(",")),
+            expires_at: ActiveValue::set(expires_at),
+            user_id: ActiveValue::set(user_id),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?;
+        Ok((item, secret))
+    }
+}
+
+// implement your custom finders, selectors oriented logic here
+impl Entity {}
@@ -0,0 +1,185 @@
+use loco_rs::prelude::*;
+use sea_orm::{sea_query::Order, QueryOrder, TransactionTrait};
+pub use super::_entities::repo_insights::{ActiveModel, Column, Model, Entity};
+use super::{repo_insight_contributors, repo_insight_punchcards, repo_insight_weeks};
+use crate::services::insights_service::{Contributor, Insights, PunchcardCell, WeekActivity};
+pub type RepoInsights = Entity;
+
+/// Rows inserted per statement, well below the bind parameter limits of the
+/// databases.
+const INSERT_CHUNK: usize = 500;
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(self, _db: &C, insert: bool) -> std::result::Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if !insert && self.updated_at.is_unchanged() {
+            let mut this = self;
+            this.updated_at = sea_orm::ActiveValue::Set(chrono::Utc::now().into());
+            Ok(this)
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+// implement your read-oriented logic here
+impl Model {
+    /// loads the stored insights of a repository, with the row recording when
+    /// they were computed
+    ///
+    /// # Errors
+    ///
+    /// When DB query error
+    pub async fn find_by_repo(
+        db: &DatabaseConnection,
+        git_repo_id: i32,
+    ) -> ModelResult<Option<(Self, Insights)>> {
+        let Some(item) = Entity::find()
+            .filter(Column::GitRepoId.eq(git_repo_id))
+            .one(db)
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        let weeks = repo_insight_weeks::Entity::find()
+            .filter(repo_insight_weeks::Column::GitRepoId.eq(git_repo_id))
+            .order_by(repo_insight_weeks::Column::WeekStart, Order::Asc)
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|week| WeekActivity {
+                week_start: week.week_start,
+                commits: week.commits,
+            })
+            .collect();
+        let contributors = repo_insight_contributors::Entity::find()
+            .filter(repo_insight_contributors::Column::GitRepoId.eq(git_repo_id))
+            .order_by(repo_insight_contributors::Column::Id, Order::Asc)
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|contributor| Contributor {
+                name: contributor.name,
+                email: contributor.email,
+                commits: contributor.commits,
+                additions: contributor.additions,
+                deletions: contributor.deletions,
+            })
+            .collect();
+        let punchcard = repo_insight_punchcards::Entity::find()
+            .filter(repo_insight_punchcards::Column::GitRepoId.eq(git_repo_id))
+            .order_by(repo_insight_punchcards::Column::Weekday, Order::Asc)
+            .order_by(repo_insight_punchcards::Column::Hour, Order::Asc)
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|cell| PunchcardCell {
+                weekday: cell.weekday,
+                hour: cell.hour,
+                commits: cell.commits,
+            })
+            .collect();
+
+        let insights = Insights {
+            head_commit: item.head_commit.clone(),
+            total_commits: item.total_commits,
+            weeks,
+            contributors,
+            punchcard,
+        };
+        Ok(Some((item, insights)))
+    }
+}
+
+// implement your write-oriented logic here
+impl ActiveModel {
+    /// replaces the stored insights of a repository in one transaction
+    ///
+    /// # Errors
+    ///
+    /// When DB query error
+    pub async fn replace(
+        db: &DatabaseConnection,
+        git_repo_id: i32,
+        insights: &Insights,
+    ) -> ModelResult<Model> {
+        let txn = db.begin().await?;
+
+        Entity::delete_many()
+            .filter(Column::GitRepoId.eq(git_repo_id))
+            .exec(&txn)
+            .await?;
+        repo_insight_weeks::Entity::delete_many()
+            .filter(repo_insight_weeks::Column::GitRepoId.eq(git_repo_id))
+            .exec(&txn)
+            .await?;
+        repo_insight_contributors::Entity::delete_many()
+            .filter(repo_insight_contributors::Column::GitRepoId.eq(git_repo_id))
+            .exec(&txn)
+            .await?;
+        repo_insight_punchcards::Entity::delete_many()
+            .filter(repo_insight_punchcards::Column::GitRepoId.eq(git_repo_id))
+            .exec(&txn)
+            .await?;
+
+        let item = Self {
+            git_repo_id: ActiveValue::set(git_repo_id),
+            head_commit: ActiveValue::set(insights.head_commit.clone()),
+            total_commits: ActiveValue::set(insights.total_commits),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+
+        let weeks: Vec<_> = insights
+            .weeks
+            .iter()
+            .map(|week| repo_insight_weeks::ActiveModel {
+                git_repo_id: ActiveValue::set(git_repo_id),
+                week_start: ActiveValue::set(week.week_start),
+                commits: ActiveValue::set(week.commits),
+                ..Default::default()
+            })
+            .collect();
+        for chunk in weeks.chunks(INSERT_CHUNK) {
+            repo_insight_weeks::Entity::insert_many(chunk.to_vec()).exec(&txn).await?;
+        }
+        let contributors: Vec<_> = insights
+            .contributors
+            .iter()
+            .map(|contributor| repo_insight_contributors::ActiveModel {
+                git_repo_id: ActiveValue::set(git_repo_id),
+                name: ActiveValue::set(contributor.name.clone()),
+                email: ActiveValue::set(contributor.email.clone()),
+                commits: ActiveValue::set(contributor.commits),
+                additions: ActiveValue::set(contributor.additions),
+                deletions: ActiveValue::set(contributor.deletions),
+                ..Default::default()
+            })
+            .collect();
+        for chunk in contributors.chunks(INSERT_CHUNK) {
+            repo_insight_contributors::Entity::insert_many(chunk.to_vec()).exec(&txn).await?;
+        }
+        let punchcard: Vec<_> = insights
+            .punchcard
+            .iter()
+            .map(|cell| repo_insight_punchcards::ActiveModel {
+                git_repo_id: ActiveValue::set(git_repo_id),
+                weekday: ActiveValue::set(cell.weekday),
+                hour: ActiveValue::set(cell.hour),
+                commits: ActiveValue::set(cell.commits),
+                ..Default::default()
+            })
+            .collect();
+        for chunk in punchcard.chunks(INSERT_CHUNK) {
+            repo_insight_punchcards::Entity::insert_many(chunk.to_vec()).exec(&txn).await?;
+        }
+
+        txn.commit().await?;
+        Ok(item)
+    }
+}
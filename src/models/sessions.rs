@@ -0,0 +1,177 @@
+use std::net::IpAddr;
+
+use chrono::{DateTime, Duration, FixedOffset, Local};
+use loco_rs::prelude::*;
+use sea_orm::{sea_query::Order, QueryOrder};
+use uuid::Uuid;
+
+pub use super::_entities::sessions::{ActiveModel, Column, Entity, Model};
+pub type Sessions = Entity;
+
+/// The JWT claim holding the pid of the session the token was issued for.
+pub const JWT_CLAIM: &str = "sid";
+
+/// `last_seen_at` is only written again once it is older than this, so busy
+/// clients do not write on every request.
+const LAST_SEEN_PRECISION_SECS: i64 = 60;
+
+/// User agents are kept for display only, and cut to this many characters.
+const USER_AGENT_MAX_CHARS: usize = 255;
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(self, _db: &C, insert: bool) -> std::result::Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if !insert && self.updated_at.is_unchanged() {
+            let mut this = self;
+            this.updated_at = sea_orm::ActiveValue::Set(chrono::Utc::now().into());
+            Ok(this)
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+// implement your read-oriented logic here
+impl Model {
+    #[must_use]
+    pub fn is_expired(&self, now: DateTime<FixedOffset>) -> bool {
+        self.expires_at <= now
+    }
+
+    /// finds the session a JWT was issued for, as long as it was not revoked
+    /// and has not expired
+    ///
+    /// # Errors
+    ///
+    /// When the session is unknown, revoked or expired, or DB query error
+    pub async fn find_active(db: &DatabaseConnection, pid: &str) -> ModelResult<Self> {
+        let pid = Uuid::parse_str(pid).map_err(|_| ModelError::EntityNotFound)?;
+        let item = Entity::find()
+            .filter(Column::Pid.eq(pid))
+            .one(db)
+            .await?
+            .ok_or(ModelError::EntityNotFound)?;
+        if item.is_expired(Local::now().fixed_offset()) {
+            return Err(ModelError::EntityNotFound);
+        }
+        Ok(item)
+    }
+
+    /// lists the sessions of the given user that can still be used, most
+    /// recently active first
+    ///
+    /// # Errors
+    ///
+    /// When DB query error
+    pub async fn list_by_owner(db: &DatabaseConnection, user_id: i32) -> ModelResult<Vec<Self>> {
+        Ok(Entity::find()
+            .filter(Column::UserId.eq(user_id))
+            .filter(Column::ExpiresAt.gt(Local::now().fixed_offset()))
+            .order_by(Column::LastSeenAt, Order::Desc)
+            .all(db)
+            .await?)
+    }
+
+    /// records that the session was just used
+    ///
+    /// # Errors
+    ///
+    /// When DB query error
+    pub async fn touch(self, db: &DatabaseConnection) -> ModelResult<Self> {
+        let now = Local::now().fixed_offset();
+        if (now - self.last_seen_at).num_seconds() < LAST_SEEN_PRECISION_SECS {
+            return Ok(self);
+        }
+        let mut item = self.into_active_model();
+        item.last_seen_at = ActiveValue::set(now);
+        Ok(item.update(db).await?)
+    }
+}
+
+// implement your write-oriented logic here
+impl ActiveModel {
+    /// starts a session for the given user, lasting as long as the JWT issued
+    /// for it, and forgets the user's sessions that expired meanwhile
+    ///
+    /// # Errors
+    ///
+    /// When DB query error
+    pub async fn create_for_user(
+        db: &DatabaseConnection,
+        user_id: i32,
+        user_agent: Option<&str>,
+        ip: Option<IpAddr>,
+        expiration_secs: u64,
+    ) -> ModelResult<Model> {
+        let now = Local::now().fixed_offset();
+        Entity::delete_many()
+            .filter(Column::UserId.eq(user_id))
+            .filter(Column::ExpiresAt.lte(now))
+            .exec(db)
+            .await?;
+
+        let lifetime = Duration::seconds(i64::try_from(expiration_secs).unwrap_or(i64::MAX));
+        let item = Self {
+            pid: ActiveValue::set(Uuid::new_v4()),
+            user_agent: ActiveValue::set(
+                user_agent.map(|agent| agent.chars().take(USER_AGENT_MAX_CHARS).collect()),
+            ),
+            ip_address: ActiveValue::set(ip.map(|ip| ip.to_string())),
+            last_seen_at: ActiveValue::set(now),
+            expires_at: ActiveValue::set(now.checked_add_signed(lifetime).unwrap_or(now)),
+            user_id: ActiveValue::set(user_id),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?;
+        Ok(item)
+    }
+
+    /// revokes one session of the given user, returning whether it existed
+    ///
+    /// # Errors
+    ///
+    /// When DB query error
+    pub async fn revoke(db: &DatabaseConnection, user_id: i32, pid: Uuid) -> ModelResult<bool> {
+        let res = Entity::delete_many()
+            .filter(Column::UserId.eq(user_id))
+            .filter(Column::Pid.eq(pid))
+            .exec(db)
+            .await?;
+        Ok(res.rows_affected > 0)
+    }
+
+    /// revokes every session of the given user except `keep`, returning how
+    /// many were revoked
+    ///
+    /// # Errors
+    ///
+    /// When DB query error
+    pub async fn revoke_others(db: &DatabaseConnection, user_id: i32, keep: Uuid) -> ModelResult<u64> {
+        let res = Entity::delete_many()
+            .filter(Column::UserId.eq(user_id))
+            .filter(Column::Pid.ne(keep))
+            .exec(db)
+            .await?;
+        Ok(res.rows_affected)
+    }
+
+    /// revokes every session of the given user, returning how many were revoked
+    ///
+    /// # Errors
+    ///
+    /// When DB query error
+    pub async fn revoke_all(db: &DatabaseConnection, user_id: i32) -> ModelResult<u64> {
+        let res = Entity::delete_many()
+            .filter(Column::UserId.eq(user_id))
+            .exec(db)
+            .await?;
+        Ok(res.rows_affected)
+    }
+}
+
+// implement your custom finders, selectors oriented logic here
+impl Entity {}
@@ -1,5 +1,6 @@
-use sea_orm::entity::prelude::*;
-pub use super::_entities::sshes::{ActiveModel, Model, Entity};
+use loco_rs::prelude::*;
+use sea_orm::{sea_query::Order, QueryOrder};
+pub use super::_entities::sshes::{ActiveModel, Column, Model, Entity};
 pub type Sshes = Entity;
 
 #[async_trait::async_trait]
@@ -19,7 +20,41 @@ impl ActiveModelBehavior for ActiveModel {
 }
 
 // implement your read-oriented logic here
-impl Model {}
+impl Model {
+    /// finds a key by id, restricted to the keys owned by the given user
+    ///
+    /// # Errors
+    ///
+    /// When the key does not exist, belongs to another user or DB query error
+    pub async fn find_by_owner(db: &DatabaseConnection, user_id: i32, id: i32) -> ModelResult<Self> {
+        let item = Entity::find_by_id(id)
+            .filter(
+                model::query::condition()
+                    .eq(Column::UserId, user_id)
+                    .build(),
+            )
+            .one(db)
+            .await?;
+        item.ok_or_else(|| ModelError::EntityNotFound)
+    }
+
+    /// lists the keys owned by the given user, newest first
+    ///
+    /// # Errors
+    ///
+    /// When DB query error
+    pub async fn list_by_owner(db: &DatabaseConnection, user_id: i32) -> ModelResult<Vec<Self>> {
+        Ok(Entity::find()
+            .filter(
+                model::query::condition()
+                    .eq(Column::UserId, user_id)
+                    .build(),
+            )
+            .order_by(Column::Id, Order::Desc)
+            .all(db)
+            .await?)
+    }
+}
 
 // implement your write-oriented logic here
 impl ActiveModel {}
@@ -4,11 +4,22 @@ use loco_rs::{auth::jwt, hash, prelude::*};
 use serde::{Deserialize, Serialize};
 use serde_json::Map;
 use uuid::Uuid;
+use validator::ValidationError;
 
 pub use super::_entities::users::{self, ActiveModel, Entity, Model};
+use super::sessions;
+use crate::services::{
+    throttle_service::{self, Penalty, ThrottleSettings},
+    totp_service::{self, TotpError},
+};
 
 pub const MAGIC_LINK_LENGTH: i8 = 32;
 pub const MAGIC_LINK_EXPIRATION_MIN: i8 = 5;
+pub const TOTP_CHALLENGE_LENGTH: i8 = 32;
+pub const TOTP_CHALLENGE_EXPIRATION_MIN: i8 = 5;
+/// Wrong codes allowed per challenge; after that the password has to be
+/// entered again.
+pub const TOTP_CHALLENGE_MAX_ATTEMPTS: i32 = 5;
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct LoginParams {
@@ -25,12 +36,26 @@ pub struct RegisterParams {
 
 #[derive(Debug, Validate, Deserialize)]
 pub struct Validator {
-    #[validate(length(min = 2, message = "Name must be at least 2 characters long."))]
+    #[validate(
+        length(min = 2, message = "Name must be at least 2 characters long."),
+        custom(function = "is_valid_username")
+    )]
     pub name: String,
     #[validate(custom(function = "validation::is_valid_email"))]
     pub email: String,
 }
 
+/// The name is the user's namespace in repository paths and clone URLs, so it
+/// follows the same rules as repository names.
+fn is_valid_username(name: &str) -> Result<(), ValidationError> {
+    if name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') {
+        Ok(())
+    } else {
+        Err(ValidationError::new("invalid username")
+            .with_message("Name may only contain letters, digits, `-` and `_`.".into()))
+    }
+}
+
 impl Validatable for ActiveModel {
     fn validator(&self) -> Box<dyn Validate> {
         Box::new(Validator {
@@ -186,6 +211,23 @@ impl Model {
         user.ok_or_else(|| ModelError::EntityNotFound)
     }
 
+    /// finds a user by the provided name, which is also their namespace
+    ///
+    /// # Errors
+    ///
+    /// When could not find user  or DB query error
+    pub async fn find_by_username(db: &DatabaseConnection, name: &str) -> ModelResult<Self> {
+        let user = users::Entity::find()
+            .filter(
+                model::query::condition()
+                    .eq(users::Column::Name, name)
+                    .build(),
+            )
+            .one(db)
+            .await?;
+        user.ok_or_else(|| ModelError::EntityNotFound)
+    }
+
     /// finds a user by the provided api key
     ///
     /// # Errors
@@ -203,6 +245,14 @@ impl Model {
         user.ok_or_else(|| ModelError::EntityNotFound)
     }
 
+    /// Returns the namespace directory under which the user's repositories are
+    /// stored, i.e. `<base>/<namespace>/<name>.git`. It is the user's name, so
+    /// clone URLs read `/<name>/<repo>.git`.
+    #[must_use]
+    pub fn namespace(&self) -> String {
+        self.name.clone()
+    }
+
     /// Verifies whether the provided plain password matches the hashed password
     ///
     /// # Errors
@@ -238,6 +288,20 @@ impl Model {
             return Err(ModelError::EntityAlreadyExists {});
         }
 
+        // Names are namespaces, so two users cannot share one.
+        if users::Entity::find()
+            .filter(
+                model::query::condition()
+                    .eq(users::Column::Name, &params.name)
+                    .build(),
+            )
+            .one(&txn)
+            .await?
+            .is_some()
+        {
+            return Err(ModelError::msg("name is already taken"));
+        }
+
         let password_hash =
             hash::hash_password(&params.password).map_err(|e| ModelError::Any(e.into()))?;
         let user = users::ActiveModel {
@@ -254,13 +318,125 @@ impl Model {
         Ok(user)
     }
 
-    /// Creates a JWT
+    /// finds a user by the two-factor challenge handed out at login, as long as
+    /// it has not expired
+    ///
+    /// # Errors
+    ///
+    /// When could not find user by the given token or DB query error or token expired
+    pub async fn find_by_totp_challenge(db: &DatabaseConnection, token: &str) -> ModelResult<Self> {
+        let user = users::Entity::find()
+            .filter(
+                query::condition()
+                    .eq(users::Column::TotpChallengeToken, token)
+                    .build(),
+            )
+            .one(db)
+            .await?;
+
+        let user = user.ok_or_else(|| ModelError::EntityNotFound)?;
+        match user.totp_challenge_expiration {
+            Some(expired_at) if expired_at >= Local::now() => Ok(user),
+            _ => {
+                tracing::debug!(
+                    user_pid = user.pid.to_string(),
+                    "two-factor challenge expired for the user."
+                );
+                Err(ModelError::msg("two-factor challenge expired"))
+            }
+        }
+    }
+
+    /// Whether logging in needs a second factor
+    #[must_use]
+    pub fn two_factor_enabled(&self) -> bool {
+        self.totp_enabled_at.is_some() && self.totp_secret.is_some()
+    }
+
+    fn recovery_code_hashes(&self) -> Vec<&str> {
+        self.totp_recovery_codes
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .filter(|hash| !hash.is_empty())
+            .collect()
+    }
+
+    /// Number of recovery codes that were not used yet
+    #[must_use]
+    pub fn recovery_codes_left(&self) -> usize {
+        self.recovery_code_hashes().len()
+    }
+
+    /// Checks a code from the authenticator app against the secret being
+    /// enrolled or already enabled, returning its time step.
+    ///
+    /// # Errors
+    ///
+    /// When the user has no secret, or the stored secret is invalid
+    pub fn verify_totp(&self, code: &str) -> ModelResult<Option<u64>> {
+        let secret = self
+            .totp_secret
+            .as_deref()
+            .ok_or_else(|| ModelError::msg("two-factor authentication is not set up"))?;
+        let now = u64::try_from(Local::now().timestamp()).unwrap_or_default();
+        let last_step = self.totp_last_step.and_then(|step| u64::try_from(step).ok());
+        totp_service::verify(secret, code, now, last_step).map_err(|e: TotpError| ModelError::Any(e.into()))
+    }
+
+    /// Checks a second factor, either a code from the authenticator app or a
+    /// recovery code, and records its use so it cannot be replayed. Returns
+    /// `None` when the code is not valid.
+    ///
+    /// # Errors
+    ///
+    /// When two-factor authentication is not enabled or DB query error
+    pub async fn redeem_second_factor(self, db: &DatabaseConnection, code: &str) -> ModelResult<Option<Self>> {
+        if !self.two_factor_enabled() {
+            return Err(ModelError::msg("two-factor authentication is not enabled"));
+        }
+
+        if let Some(step) = self.verify_totp(code)? {
+            let mut user = self.into_active_model();
+            user.totp_last_step = ActiveValue::set(i64::try_from(step).ok());
+            return Ok(Some(user.update(db).await?));
+        }
+
+        let hash = totp_service::hash_recovery_code(code);
+        let hashes = self.recovery_code_hashes();
+        if !hashes.contains(&hash.as_str()) {
+            return Ok(None);
+        }
+        let left: Vec<&str> = hashes.into_iter().filter(|h| *h != hash).collect();
+        let left = left.join(",");
+        tracing::info!(user_pid = self.pid.to_string(), "recovery code used");
+        let mut user = self.into_active_model();
+        user.totp_recovery_codes = ActiveValue::set(Some(left));
+        Ok(Some(user.update(db).await?))
+    }
+
+    /// How long the account still refuses logins after failed attempts, if at all
+    #[must_use]
+    pub fn locked_for(&self) -> Option<std::time::Duration> {
+        let until = self.locked_until?;
+        (until.with_timezone(&Local) - Local::now()).to_std().ok().filter(|left| !left.is_zero())
+    }
+
+    /// Creates a JWT for the given session, which stops being accepted once
+    /// the session is revoked
     ///
     /// # Errors
     ///
     /// when could not convert user claims to jwt token
-    pub fn generate_jwt(&self, secret: &str, expiration: u64) -> ModelResult<String> {
-        Ok(jwt::JWT::new(secret).generate_token(expiration, self.pid.to_string(), Map::new())?)
+    pub fn generate_jwt(
+        &self,
+        secret: &str,
+        expiration: u64,
+        session: &sessions::Model,
+    ) -> ModelResult<String> {
+        let mut claims = Map::new();
+        claims.insert(sessions::JWT_CLAIM.to_string(), session.pid.to_string().into());
+        Ok(jwt::JWT::new(secret).generate_token(expiration, self.pid.to_string(), claims)?)
     }
 }
 
@@ -319,7 +495,8 @@ impl ActiveModel {
     /// updates it in the database.
     ///
     /// This method hashes the provided password and sets it as the new password
-    /// for the user.
+    /// for the user. Every session of the user is revoked, so whoever knew the
+    /// old password is logged out.
     ///
     /// # Errors
     ///
@@ -333,7 +510,9 @@ impl ActiveModel {
             ActiveValue::set(hash::hash_password(password).map_err(|e| ModelError::Any(e.into()))?);
         self.reset_token = ActiveValue::Set(None);
         self.reset_sent_at = ActiveValue::Set(None);
-        Ok(self.update(db).await?)
+        let user = self.update(db).await?;
+        sessions::ActiveModel::revoke_all(db, user.id).await?;
+        Ok(user)
     }
 
     /// Creates a magic link token for passwordless authentication.
@@ -364,4 +543,136 @@ impl ActiveModel {
         self.magic_link_expiration = ActiveValue::set(None);
         Ok(self.update(db).await?)
     }
+
+    /// Starts enrolling an authenticator app by generating a new secret. Two
+    /// factor authentication is only enabled once the user confirms a code
+    /// from the app, see [`ActiveModel::enable_totp`].
+    ///
+    /// # Errors
+    /// - Returns an error if database update fails
+    pub async fn start_totp_enrollment(mut self, db: &DatabaseConnection) -> ModelResult<Model> {
+        self.totp_secret = ActiveValue::set(Some(totp_service::generate_secret()));
+        self.totp_enabled_at = ActiveValue::set(None);
+        self.totp_last_step = ActiveValue::set(None);
+        self.totp_recovery_codes = ActiveValue::set(None);
+        Ok(self.update(db).await?)
+    }
+
+    /// Enables two-factor authentication once the first code, of time step
+    /// `step`, was confirmed. Returns the user along with the only copy of
+    /// their recovery codes.
+    ///
+    /// # Errors
+    /// - Returns an error if database update fails
+    pub async fn enable_totp(mut self, db: &DatabaseConnection, step: u64) -> ModelResult<(Model, Vec<String>)> {
+        self.totp_enabled_at = ActiveValue::set(Some(Local::now().into()));
+        self.totp_last_step = ActiveValue::set(i64::try_from(step).ok());
+        self.regenerate_recovery_codes(db).await
+    }
+
+    /// Replaces the recovery codes with new ones, returning the only copy of
+    /// them.
+    ///
+    /// # Errors
+    /// - Returns an error if database update fails
+    pub async fn regenerate_recovery_codes(mut self, db: &DatabaseConnection) -> ModelResult<(Model, Vec<String>)> {
+        let codes = totp_service::generate_recovery_codes();
+        let hashes: Vec<String> = codes.iter().map(|code| totp_service::hash_recovery_code(code)).collect();
+        self.totp_recovery_codes = ActiveValue::set(Some(hashes.join(",")));
+        Ok((self.update(db).await?, codes))
+    }
+
+    /// Turns two-factor authentication off, forgetting the secret and the
+    /// recovery codes.
+    ///
+    /// # Errors
+    /// - Returns an error if database update fails
+    pub async fn disable_totp(mut self, db: &DatabaseConnection) -> ModelResult<Model> {
+        self.totp_secret = ActiveValue::set(None);
+        self.totp_enabled_at = ActiveValue::set(None);
+        self.totp_last_step = ActiveValue::set(None);
+        self.totp_recovery_codes = ActiveValue::set(None);
+        self.totp_challenge_token = ActiveValue::set(None);
+        self.totp_challenge_expiration = ActiveValue::set(None);
+        self.totp_challenge_attempts = ActiveValue::set(0);
+        Ok(self.update(db).await?)
+    }
+
+    /// Creates the short-lived challenge a user with two-factor authentication
+    /// exchanges, along with a code, for a JWT after entering their password.
+    ///
+    /// # Errors
+    /// - Returns an error if database update fails
+    pub async fn create_totp_challenge(mut self, db: &DatabaseConnection) -> ModelResult<Model> {
+        let random_str = hash::random_string(TOTP_CHALLENGE_LENGTH as usize);
+        let expired = Local::now() + Duration::minutes(TOTP_CHALLENGE_EXPIRATION_MIN.into());
+
+        self.totp_challenge_token = ActiveValue::set(Some(random_str));
+        self.totp_challenge_expiration = ActiveValue::set(Some(expired.into()));
+        self.totp_challenge_attempts = ActiveValue::set(0);
+        Ok(self.update(db).await?)
+    }
+
+    /// Invalidates the two-factor challenge once it was used.
+    ///
+    /// # Errors
+    /// - Returns an error if database update fails
+    pub async fn clear_totp_challenge(mut self, db: &DatabaseConnection) -> ModelResult<Model> {
+        self.totp_challenge_token = ActiveValue::set(None);
+        self.totp_challenge_expiration = ActiveValue::set(None);
+        self.totp_challenge_attempts = ActiveValue::set(0);
+        Ok(self.update(db).await?)
+    }
+
+    /// Counts a wrong code against the two-factor challenge, invalidating it
+    /// after [`TOTP_CHALLENGE_MAX_ATTEMPTS`] of them.
+    ///
+    /// # Errors
+    /// - Returns an error if database update fails
+    pub async fn record_totp_failure(mut self, db: &DatabaseConnection) -> ModelResult<Model> {
+        let attempts = self.totp_challenge_attempts.as_ref() + 1;
+        if attempts >= TOTP_CHALLENGE_MAX_ATTEMPTS {
+            return self.clear_totp_challenge(db).await;
+        }
+        self.totp_challenge_attempts = ActiveValue::set(attempts);
+        Ok(self.update(db).await?)
+    }
+
+    /// Counts a failed login against the account and applies the resulting
+    /// [`Penalty`]: logins are refused until the backoff or the lockout is over.
+    ///
+    /// # Errors
+    /// - Returns an error if database update fails
+    pub async fn record_failed_login(
+        mut self,
+        db: &DatabaseConnection,
+        settings: &ThrottleSettings,
+    ) -> ModelResult<(Model, Penalty)> {
+        let failures = self.failed_login_attempts.as_ref().saturating_add(1);
+        let penalty = throttle_service::account_penalty(u32::try_from(failures).unwrap_or_default(), settings);
+        let wait = match penalty {
+            Penalty::None => None,
+            Penalty::Backoff(wait) | Penalty::Lockout(wait) => Some(wait),
+        };
+        if let Some(wait) = wait.and_then(|wait| Duration::from_std(wait).ok()) {
+            self.locked_until = ActiveValue::set(Some((Local::now() + wait).into()));
+        }
+        if matches!(penalty, Penalty::Lockout(_)) {
+            // Start over once the lockout ends, rather than locking again on the next failure.
+            self.failed_login_attempts = ActiveValue::set(0);
+        } else {
+            self.failed_login_attempts = ActiveValue::set(failures);
+        }
+        Ok((self.update(db).await?, penalty))
+    }
+
+    /// Forgets failed logins after a successful one.
+    ///
+    /// # Errors
+    /// - Returns an error if database update fails
+    pub async fn clear_failed_logins(mut self, db: &DatabaseConnection) -> ModelResult<Model> {
+        self.failed_login_attempts = ActiveValue::set(0);
+        self.locked_until = ActiveValue::set(None);
+        Ok(self.update(db).await?)
+    }
 }
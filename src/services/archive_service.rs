@@ -0,0 +1,377 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use axum::body::Body;
+use chrono::{DateTime, Datelike, Timelike};
+use flate2::{write::GzEncoder, Compression};
+use git2::{ObjectType, Oid, Repository, TreeWalkMode, TreeWalkResult};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio_util::io::{ReaderStream, SyncIoBridge};
+use tracing::{error, warn};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+/// A cache entry being written for longer than this is assumed to be left
+/// over from a crashed worker and is rebuilt.
+const STALE_BUILD: Duration = Duration::from_secs(60 * 60);
+
+/// Represents a custom error for building repository archives.
+#[derive(Debug, Error)]
+pub enum ArchiveError {
+    #[error("Git error: {0}")]
+    GitError(#[from] git2::Error),
+    #[error("IO error: {0}")]
+    IoError(#[from] io::Error),
+    #[error("Zip error: {0}")]
+    ZipError(#[from] zip::result::ZipError),
+}
+
+/// Where archives are cached, and how large an archive may get before it is
+/// built by a background worker instead of being streamed right away.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArchiveSettings {
+    pub cache_dir: PathBuf,
+    /// Total size, in bytes, of the files in a tree above which archives are
+    /// built in the background.
+    pub max_stream_bytes: u64,
+}
+
+impl Default for ArchiveSettings {
+    fn default() -> Self {
+        let base = Path::new(env!("REPO_BASE_PATH"));
+        Self {
+            cache_dir: base.parent().unwrap_or(base).join("archives"),
+            max_stream_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveFormat {
+    TarGz,
+    Zip,
+}
+
+impl ArchiveFormat {
+    /// Splits a requested file name such as `main.tar.gz` into the ref and format.
+    #[must_use]
+    pub fn from_file_name(name: &str) -> Option<(&str, Self)> {
+        let (reference, format) = if let Some(reference) = name.strip_suffix(".tar.gz") {
+            (reference, Self::TarGz)
+        } else if let Some(reference) = name.strip_suffix(".tgz") {
+            (reference, Self::TarGz)
+        } else {
+            (name.strip_suffix(".zip")?, Self::Zip)
+        };
+        (!reference.is_empty()).then_some((reference, format))
+    }
+
+    #[must_use]
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::TarGz => "tar.gz",
+            Self::Zip => "zip",
+        }
+    }
+
+    #[must_use]
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::TarGz => "application/gzip",
+            Self::Zip => "application/zip",
+        }
+    }
+}
+
+/// Everything needed to build one archive, independent of any open repository
+/// so it can be handed to a blocking task or a background worker.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveTarget {
+    pub repo_path: PathBuf,
+    pub commit: String,
+    /// The directory every entry is placed in, e.g. `api-1a2b3c4`.
+    pub prefix: String,
+    pub format: ArchiveFormat,
+}
+
+impl ArchiveTarget {
+    /// The file name offered to the client, e.g. `api-1a2b3c4.tar.gz`.
+    #[must_use]
+    pub fn file_name(&self) -> String {
+        format!("{}.{}", self.prefix, self.format.extension())
+    }
+}
+
+/// The top-level directory of an archive: `{repo}-{shortsha}`.
+#[must_use]
+pub fn archive_prefix(repo_name: &str, commit: &str) -> String {
+    format!("{}-{}", repo_name, &commit[..commit.len().min(7)])
+}
+
+/// Where the archive of `target` is cached. The top-level directory names the
+/// commit, so archives are keyed by commit oid.
+#[must_use]
+pub fn cache_path(cache_dir: &Path, target: &ArchiveTarget) -> PathBuf {
+    cache_dir.join(&target.commit).join(target.file_name())
+}
+
+/// Whether a background build of `path` is currently in progress.
+#[must_use]
+pub fn is_building(path: &Path) -> bool {
+    partial_path(path)
+        .metadata()
+        .and_then(|m| m.modified())
+        .is_ok_and(|modified| {
+            SystemTime::now()
+                .duration_since(modified)
+                .is_ok_and(|age| age < STALE_BUILD)
+        })
+}
+
+/// Marks `path` as being built, so requests arriving before the worker starts
+/// do not queue the same archive again.
+///
+/// # Errors
+/// Returns `ArchiveError::IoError` if the cache directory cannot be written.
+pub fn mark_building(path: &Path) -> Result<(), ArchiveError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::File::create(partial_path(path))?;
+    Ok(())
+}
+
+/// Sums the sizes of all files in `tree`, read from the object headers.
+///
+/// # Errors
+/// Returns `ArchiveError::GitError` if the tree cannot be walked.
+pub fn tree_size(repo: &Repository, tree: &git2::Tree) -> Result<u64, ArchiveError> {
+    let odb = repo.odb()?;
+    let mut total = 0u64;
+    let mut failed = None;
+    tree.walk(TreeWalkMode::PreOrder, |_, entry| {
+        if entry.kind() == Some(ObjectType::Blob) {
+            match odb.read_header(entry.id()) {
+                Ok((size, _)) => total += size as u64,
+                Err(e) => {
+                    failed = Some(e);
+                    return TreeWalkResult::Abort;
+                }
+            }
+        }
+        TreeWalkResult::Ok
+    })?;
+    match failed {
+        Some(e) => Err(e.into()),
+        None => Ok(total),
+    }
+}
+
+/// Writes the archive of `target` into `out`, one blob at a time.
+///
+/// # Errors
+/// Returns an `ArchiveError` if the repository cannot be read or `out` fails.
+pub fn write_archive<W: Write>(target: &ArchiveTarget, out: W) -> Result<W, ArchiveError> {
+    let repo = Repository::open_bare(&target.repo_path)?;
+    let commit = repo.find_commit(Oid::from_str(&target.commit)?)?;
+    let tree = commit.tree()?;
+    let mtime = commit.time().seconds();
+
+    // Collect the entry list first: tree walks cannot propagate write errors.
+    let mut entries = Vec::new();
+    tree.walk(TreeWalkMode::PreOrder, |root, entry| {
+        let path = format!("{}{}", root, entry.name().unwrap_or_default());
+        match entry.kind() {
+            Some(ObjectType::Tree) => entries.push(Entry::Directory(path)),
+            Some(ObjectType::Blob) => entries.push(Entry::File {
+                path,
+                oid: entry.id(),
+                mode: entry.filemode(),
+            }),
+            // Submodules are not part of this repository's content.
+            _ => {}
+        }
+        TreeWalkResult::Ok
+    })?;
+
+    match target.format {
+        ArchiveFormat::TarGz => write_tar_gz(&repo, &target.prefix, &entries, mtime, out),
+        ArchiveFormat::Zip => write_zip(&repo, &target.prefix, &entries, mtime, out),
+    }
+}
+
+/// Streams the archive of `target` as a response body. The archive is written
+/// by a blocking task as the client reads it, never held in memory as a whole.
+#[must_use]
+pub fn stream_archive(target: ArchiveTarget) -> Body {
+    let (reader, writer) = tokio::io::duplex(64 * 1024);
+    tokio::task::spawn_blocking(move || {
+        let bridge = SyncIoBridge::new(writer);
+        if let Err(e) = write_archive(&target, bridge).and_then(|mut w| Ok(w.flush()?)) {
+            // The client sees a truncated download; there is no way to report
+            // an error once the response has started.
+            warn!("Failed to stream archive {}: {}", target.file_name(), e);
+        }
+    });
+    Body::from_stream(ReaderStream::new(reader))
+}
+
+/// Builds the archive of `target` into the cache at `path`.
+///
+/// The archive is written next to its final location and renamed into place,
+/// so a cached file is always complete.
+///
+/// # Errors
+/// Returns an `ArchiveError` if the archive cannot be built or written.
+pub fn build_cached_archive(target: &ArchiveTarget, path: &Path) -> Result<(), ArchiveError> {
+    if path.exists() {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let partial = partial_path(path);
+    let result = fs::File::create(&partial)
+        .map_err(ArchiveError::from)
+        .and_then(|file| write_archive(target, io::BufWriter::new(file)))
+        .and_then(|writer| {
+            writer.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+            Ok(fs::rename(&partial, path)?)
+        });
+    if let Err(e) = &result {
+        error!("Failed to build archive {:?}: {}", path, e);
+        let _ = fs::remove_file(&partial);
+    }
+    result
+}
+
+enum Entry {
+    Directory(String),
+    File { path: String, oid: Oid, mode: i32 },
+}
+
+const MODE_SYMLINK: i32 = 0o120_000;
+const MODE_EXECUTABLE: i32 = 0o100_755;
+
+fn partial_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".partial");
+    path.with_file_name(name)
+}
+
+fn write_tar_gz<W: Write>(
+    repo: &Repository,
+    prefix: &str,
+    entries: &[Entry],
+    mtime: i64,
+    out: W,
+) -> Result<W, ArchiveError> {
+    let mut builder = tar::Builder::new(GzEncoder::new(out, Compression::default()));
+    let mtime = u64::try_from(mtime).unwrap_or_default();
+
+    append_tar_directory(&mut builder, format!("{}/", prefix), mtime)?;
+    for entry in entries {
+        match entry {
+            Entry::Directory(path) => {
+                append_tar_directory(&mut builder, format!("{}/{}/", prefix, path), mtime)?;
+            }
+            Entry::File { path, oid, mode } => {
+                let blob = repo.find_blob(*oid)?;
+                let mut header = tar::Header::new_gnu();
+                header.set_mtime(mtime);
+                let path = format!("{}/{}", prefix, path);
+                if *mode == MODE_SYMLINK {
+                    header.set_entry_type(tar::EntryType::Symlink);
+                    header.set_mode(0o777);
+                    header.set_size(0);
+                    let target = String::from_utf8_lossy(blob.content()).to_string();
+                    builder.append_link(&mut header, path, target)?;
+                } else {
+                    header.set_entry_type(tar::EntryType::Regular);
+                    header.set_mode(if *mode == MODE_EXECUTABLE { 0o755 } else { 0o644 });
+                    header.set_size(blob.size() as u64);
+                    builder.append_data(&mut header, path, blob.content())?;
+                }
+            }
+        }
+    }
+
+    Ok(builder.into_inner()?.finish()?)
+}
+
+fn append_tar_directory<W: Write>(
+    builder: &mut tar::Builder<W>,
+    path: String,
+    mtime: u64,
+) -> io::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(tar::EntryType::Directory);
+    header.set_mode(0o755);
+    header.set_mtime(mtime);
+    header.set_size(0);
+    builder.append_data(&mut header, path, io::empty())
+}
+
+fn write_zip<W: Write>(
+    repo: &Repository,
+    prefix: &str,
+    entries: &[Entry],
+    mtime: i64,
+    out: W,
+) -> Result<W, ArchiveError> {
+    let mut zip = ZipWriter::new_stream(out);
+    let mut options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    if let Some(time) = zip_time(mtime) {
+        options = options.last_modified_time(time);
+    }
+
+    zip.add_directory(format!("{}/", prefix), options.unix_permissions(0o755))?;
+    for entry in entries {
+        match entry {
+            Entry::Directory(path) => {
+                zip.add_directory(format!("{}/{}/", prefix, path), options.unix_permissions(0o755))?;
+            }
+            Entry::File { path, oid, mode } => {
+                let blob = repo.find_blob(*oid)?;
+                let path = format!("{}/{}", prefix, path);
+                if *mode == MODE_SYMLINK {
+                    let target = String::from_utf8_lossy(blob.content()).to_string();
+                    zip.add_symlink(path, target, options)?;
+                } else {
+                    let permissions = if *mode == MODE_EXECUTABLE { 0o755 } else { 0o644 };
+                    zip.start_file(
+                        path,
+                        options
+                            .unix_permissions(permissions)
+                            .large_file(blob.size() as u64 >= u64::from(u32::MAX)),
+                    )?;
+                    zip.write_all(blob.content())?;
+                }
+            }
+        }
+    }
+
+    Ok(zip.finish()?.into_inner())
+}
+
+/// Converts a commit timestamp to a zip timestamp. Zip cannot represent dates
+/// before 1980, which are left at the format's default.
+fn zip_time(seconds: i64) -> Option<zip::DateTime> {
+    let time = DateTime::from_timestamp(seconds, 0)?;
+    zip::DateTime::from_date_and_time(
+        u16::try_from(time.year()).ok()?,
+        u8::try_from(time.month()).ok()?,
+        u8::try_from(time.day()).ok()?,
+        u8::try_from(time.hour()).ok()?,
+        u8::try_from(time.minute()).ok()?,
+        u8::try_from(time.second()).ok()?,
+    )
+    .ok()
+}
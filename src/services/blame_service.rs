@@ -0,0 +1,158 @@
+use std::{collections::HashMap, path::Path};
+
+use git2::{BlameOptions, Commit, ErrorCode, ObjectType, Oid, Repository};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::services::{
+    highlight_service::highlight,
+    repo_history_service::{relative_time, summarize, CommitSummary},
+    repo_retrive_service::FileLimits,
+};
+
+/// Represents a custom error for blaming a file.
+#[derive(Debug, Error)]
+pub enum BlameError {
+    #[error("File not found: {0}")]
+    NotFound(String),
+    #[error("Git error: {0}")]
+    GitError(#[from] git2::Error),
+}
+
+/// A run of consecutive lines last changed by the same commit.
+#[derive(Debug, Serialize)]
+pub struct BlameHunk {
+    pub commit: CommitSummary,
+    /// How long before the blame was computed the commit was authored.
+    pub relative_date: String,
+    /// The number of the first line of the hunk, counting from 1.
+    pub start_line: usize,
+    /// The highlighted lines of the hunk.
+    pub lines: Vec<String>,
+    /// The number of the same line in `commit`, which is where to look in the
+    /// blame of its parent.
+    pub original_start_line: usize,
+    /// The path of the file in `commit`, which differs when it was renamed since.
+    pub original_path: String,
+    /// The first parent of `commit`, when the file already existed there, to
+    /// continue blaming from the state before this change.
+    pub parent: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Blame {
+    pub path: String,
+    pub size: u64,
+    pub is_binary: bool,
+    pub too_large: bool,
+    /// The name of the grammar used for highlighting, if any.
+    pub language: Option<String>,
+    pub hunks: Vec<BlameHunk>,
+}
+
+/// Finds the commit that last changed each line of the file at `path`, as of
+/// `commit`, grouping consecutive lines of the same commit into hunks.
+///
+/// Binary files and files over `limits.max_content_bytes` are returned
+/// without hunks, with `is_binary` or `too_large` set.
+///
+/// # Errors
+/// Returns `BlameError::NotFound` if `path` is not a file in `commit`, and
+/// `BlameError::GitError` if the history cannot be read.
+pub fn blame_file(
+    repo: &Repository,
+    commit: &Commit,
+    path: &str,
+    limits: &FileLimits,
+    now: i64,
+) -> Result<Blame, BlameError> {
+    let path = path.trim_matches('/');
+    let entry = commit
+        .tree()?
+        .get_path(Path::new(path))
+        .ok()
+        .filter(|entry| entry.kind() == Some(ObjectType::Blob))
+        .ok_or_else(|| BlameError::NotFound(path.to_string()))?;
+    let (size, _) = repo.odb()?.read_header(entry.id())?;
+
+    let mut blame = Blame {
+        path: path.to_string(),
+        size: size as u64,
+        is_binary: false,
+        too_large: false,
+        language: None,
+        hunks: Vec::new(),
+    };
+    // Blaming walks the whole history of the file, so the size is checked first.
+    if blame.size > limits.max_content_bytes {
+        blame.too_large = true;
+        return Ok(blame);
+    }
+    let blob = repo.find_blob(entry.id())?;
+    if blob.is_binary() {
+        blame.is_binary = true;
+        return Ok(blame);
+    }
+
+    let content = String::from_utf8_lossy(blob.content());
+    let highlighted = highlight(path, &content, limits.max_highlight_bytes);
+    blame.language = highlighted.language;
+    let mut lines = highlighted.lines.into_iter();
+
+    let mut options = BlameOptions::new();
+    options.newest_commit(commit.id());
+    let file_blame = match repo.blame_file(Path::new(path), Some(&mut options)) {
+        Ok(file_blame) => file_blame,
+        Err(e) if e.code() == ErrorCode::NotFound => {
+            return Err(BlameError::NotFound(path.to_string()))
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut summaries: HashMap<Oid, (CommitSummary, Option<String>)> = HashMap::new();
+    for hunk in file_blame.iter() {
+        let oid = hunk.final_commit_id();
+        let original_path = hunk
+            .path()
+            .and_then(Path::to_str)
+            .unwrap_or(path)
+            .to_string();
+        let hunk_lines: Vec<String> = lines.by_ref().take(hunk.lines_in_hunk()).collect();
+
+        // libgit2 may report one change as several adjacent hunks.
+        if let Some(last) = blame.hunks.last_mut() {
+            if last.commit.oid == oid.to_string() && last.original_path == original_path {
+                last.lines.extend(hunk_lines);
+                continue;
+            }
+        }
+
+        let (summary, parent) = match summaries.get(&oid) {
+            Some(cached) => cached.clone(),
+            None => {
+                let hunk_commit = repo.find_commit(oid)?;
+                let cached = (summarize(&hunk_commit), parent_with_file(&hunk_commit, &original_path));
+                summaries.insert(oid, cached.clone());
+                cached
+            }
+        };
+        blame.hunks.push(BlameHunk {
+            relative_date: relative_time(summary.timestamp, now),
+            commit: summary,
+            start_line: hunk.final_start_line(),
+            lines: hunk_lines,
+            original_start_line: hunk.orig_start_line(),
+            original_path,
+            parent,
+        });
+    }
+
+    Ok(blame)
+}
+
+/// The first parent of `commit`, if `path` exists in it.
+fn parent_with_file(commit: &Commit, path: &str) -> Option<String> {
+    let parent = commit.parent(0).ok()?;
+    parent.tree().ok()?.get_path(Path::new(path)).ok()?;
+    Some(parent.id().to_string())
+}
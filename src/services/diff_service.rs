@@ -0,0 +1,393 @@
+use git2::{Commit, Delta, DiffFindOptions, DiffOptions, Oid, Patch, Repository, Sort, Tree};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::services::repo_history_service::{summarize, CommitSummary};
+
+/// Most commits listed on a comparison page.
+const MAX_COMPARE_COMMITS: usize = 250;
+
+/// Represents a custom error for computing diffs.
+#[derive(Debug, Error)]
+pub enum DiffError {
+    #[error("Revision '{0}' not found")]
+    RevisionNotFound(String),
+    #[error("Invalid comparison: {0}")]
+    InvalidComparison(String),
+    #[error("Git error: {0}")]
+    GitError(#[from] git2::Error),
+}
+
+/// Size limits for rendering a diff. Files over a per-file limit are listed
+/// without their hunks; once a per-diff limit is reached the remaining files
+/// are left out and the diff is marked as truncated.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiffLimits {
+    pub max_file_bytes: u64,
+    pub max_file_lines: usize,
+    pub max_total_lines: usize,
+    pub max_files: usize,
+}
+
+impl Default for DiffLimits {
+    fn default() -> Self {
+        Self {
+            max_file_bytes: 1024 * 1024,
+            max_file_lines: 2_000,
+            max_total_lines: 20_000,
+            max_files: 300,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiffLine {
+    /// One of `context`, `addition` or `deletion`.
+    pub kind: &'static str,
+    pub old_lineno: Option<u32>,
+    pub new_lineno: Option<u32>,
+    pub content: String,
+}
+
+/// A row of the side-by-side view: removed lines on the left, added lines on
+/// the right, context lines on both sides.
+#[derive(Debug, Serialize)]
+pub struct SplitRow {
+    pub left: Option<DiffLine>,
+    pub right: Option<DiffLine>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DiffHunk {
+    pub header: String,
+    pub lines: Vec<DiffLine>,
+    pub rows: Vec<SplitRow>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FileDiff {
+    pub path: String,
+    /// The previous path, when the file was renamed or copied.
+    pub old_path: Option<String>,
+    pub status: String,
+    pub additions: usize,
+    pub deletions: usize,
+    pub is_binary: bool,
+    /// Set when the file is over a per-file limit and its hunks were not rendered.
+    pub too_large: bool,
+    pub hunks: Vec<DiffHunk>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DiffResult {
+    pub files: Vec<FileDiff>,
+    pub total_additions: usize,
+    pub total_deletions: usize,
+    /// Set when a per-diff limit was reached and some files were left out.
+    pub truncated: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Comparison {
+    pub base: String,
+    pub head: String,
+    pub base_oid: String,
+    pub head_oid: String,
+    pub merge_base: Option<String>,
+    /// Commits reachable from `head` but not from `base`, newest first.
+    pub commits: Vec<CommitSummary>,
+    pub diff: DiffResult,
+}
+
+/// Diffs two trees, detecting renames and copies. A missing `old` tree is
+/// treated as empty.
+///
+/// # Errors
+/// Returns `DiffError::GitError` if the diff cannot be computed.
+pub fn diff_trees(
+    repo: &Repository,
+    old: Option<&Tree>,
+    new: Option<&Tree>,
+    limits: &DiffLimits,
+) -> Result<DiffResult, DiffError> {
+    diff_trees_at(repo, old, new, None, limits)
+}
+
+/// Like [`diff_trees`], but keeps only the files at or below `path`, before or
+/// after a rename, when a path is given.
+fn diff_trees_at(
+    repo: &Repository,
+    old: Option<&Tree>,
+    new: Option<&Tree>,
+    path: Option<&str>,
+    limits: &DiffLimits,
+) -> Result<DiffResult, DiffError> {
+    let mut options = DiffOptions::new();
+    let mut diff = repo.diff_tree_to_tree(old, new, Some(&mut options))?;
+    diff.find_similar(Some(DiffFindOptions::new().renames(true).copies(true)))?;
+
+    let odb = repo.odb()?;
+    let blob_size = |oid: Oid| {
+        if oid.is_zero() {
+            0
+        } else {
+            odb.read_header(oid).map_or(0, |(size, _)| size as u64)
+        }
+    };
+
+    let mut result = DiffResult {
+        files: Vec::new(),
+        total_additions: 0,
+        total_deletions: 0,
+        truncated: false,
+    };
+    let mut rendered_lines = 0usize;
+
+    for (index, delta) in diff.deltas().enumerate() {
+        if let Some(path) = path {
+            let matches = |file: git2::DiffFile| file.path().is_some_and(|p| p.starts_with(path));
+            if !matches(delta.old_file()) && !matches(delta.new_file()) {
+                continue;
+            }
+        }
+        if result.files.len() >= limits.max_files || rendered_lines >= limits.max_total_lines {
+            result.truncated = true;
+            break;
+        }
+
+        let new_path = delta.new_file().path().map(|p| p.to_string_lossy().to_string());
+        let old_path = delta.old_file().path().map(|p| p.to_string_lossy().to_string());
+        let mut file = FileDiff {
+            path: new_path.clone().or_else(|| old_path.clone()).unwrap_or_default(),
+            old_path: match delta.status() {
+                Delta::Renamed | Delta::Copied => old_path,
+                _ => None,
+            },
+            status: delta_status(delta.status()).to_string(),
+            additions: 0,
+            deletions: 0,
+            is_binary: false,
+            too_large: false,
+            hunks: Vec::new(),
+        };
+
+        let size = blob_size(delta.old_file().id()).max(blob_size(delta.new_file().id()));
+        if size > limits.max_file_bytes {
+            file.too_large = true;
+            result.files.push(file);
+            continue;
+        }
+
+        let patch = Patch::from_diff(&diff, index)?;
+        let binary = delta.flags().is_binary()
+            || delta.old_file().is_binary()
+            || delta.new_file().is_binary();
+        let Some(patch) = patch.filter(|_| !binary) else {
+            file.is_binary = true;
+            result.files.push(file);
+            continue;
+        };
+
+        let (_context, additions, deletions) = patch.line_stats()?;
+        file.additions = additions;
+        file.deletions = deletions;
+        result.total_additions += additions;
+        result.total_deletions += deletions;
+
+        let lines: usize = (0..patch.num_hunks())
+            .map(|h| patch.num_lines_in_hunk(h).unwrap_or(0))
+            .sum();
+        if lines > limits.max_file_lines {
+            file.too_large = true;
+        } else {
+            file.hunks = read_hunks(&patch)?;
+            rendered_lines += lines;
+        }
+        result.files.push(file);
+    }
+
+    Ok(result)
+}
+
+/// Diffs `commit` against its first parent, or against the empty tree for a
+/// root commit.
+///
+/// # Errors
+/// Returns `DiffError::GitError` if the diff cannot be computed.
+pub fn diff_commit(
+    repo: &Repository,
+    commit: &Commit,
+    limits: &DiffLimits,
+) -> Result<DiffResult, DiffError> {
+    diff_commit_at(repo, commit, None, limits)
+}
+
+/// Like [`diff_commit`], but keeps only the files at or below `path` when a
+/// path is given. Renamed files are kept when either name matches.
+///
+/// # Errors
+/// Returns `DiffError::GitError` if the diff cannot be computed.
+pub fn diff_commit_at(
+    repo: &Repository,
+    commit: &Commit,
+    path: Option<&str>,
+    limits: &DiffLimits,
+) -> Result<DiffResult, DiffError> {
+    let tree = commit.tree()?;
+    let parent_tree = match commit.parent(0) {
+        Ok(parent) => Some(parent.tree()?),
+        Err(_) => None,
+    };
+    diff_trees_at(repo, parent_tree.as_ref(), Some(&tree), path, limits)
+}
+
+/// Splits a `base...head` comparison spec into its two revisions.
+///
+/// # Errors
+/// Returns `DiffError::InvalidComparison` unless both sides are present.
+pub fn parse_compare_spec(spec: &str) -> Result<(String, String), DiffError> {
+    match spec.split_once("...") {
+        Some((base, head)) if !base.is_empty() && !head.is_empty() => {
+            Ok((base.to_string(), head.to_string()))
+        }
+        _ => Err(DiffError::InvalidComparison(spec.to_string())),
+    }
+}
+
+/// Compares `head` with `base` the way a merge would: the diff goes from their
+/// merge base to `head`, and lists the commits `head` would bring in.
+///
+/// Both revisions may be anything `revparse_single` understands: branch and
+/// tag names or full and abbreviated commit ids.
+///
+/// # Errors
+/// Returns `DiffError::RevisionNotFound` if a revision does not resolve to a
+/// commit and `DiffError::GitError` if the comparison cannot be computed.
+pub fn compare(
+    repo: &Repository,
+    base: &str,
+    head: &str,
+    limits: &DiffLimits,
+) -> Result<Comparison, DiffError> {
+    let resolve = |rev: &str| {
+        repo.revparse_single(rev)
+            .and_then(|object| object.peel_to_commit())
+            .map_err(|_| DiffError::RevisionNotFound(rev.to_string()))
+    };
+    let base_commit = resolve(base)?;
+    let head_commit = resolve(head)?;
+
+    // Unrelated histories have no merge base; compare the tips directly then.
+    let merge_base = repo.merge_base(base_commit.id(), head_commit.id()).ok();
+    let from = match merge_base {
+        Some(oid) => repo.find_commit(oid)?,
+        None => base_commit.clone(),
+    };
+
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)?;
+    revwalk.push(head_commit.id())?;
+    revwalk.hide(base_commit.id())?;
+    let mut commits = Vec::new();
+    for oid in revwalk.take(MAX_COMPARE_COMMITS) {
+        commits.push(summarize(&repo.find_commit(oid?)?));
+    }
+
+    let diff = diff_trees(repo, Some(&from.tree()?), Some(&head_commit.tree()?), limits)?;
+
+    Ok(Comparison {
+        base: base.to_string(),
+        head: head.to_string(),
+        base_oid: base_commit.id().to_string(),
+        head_oid: head_commit.id().to_string(),
+        merge_base: merge_base.map(|oid| oid.to_string()),
+        commits,
+        diff,
+    })
+}
+
+fn read_hunks(patch: &Patch) -> Result<Vec<DiffHunk>, DiffError> {
+    let mut hunks = Vec::with_capacity(patch.num_hunks());
+    for h in 0..patch.num_hunks() {
+        let (hunk, count) = patch.hunk(h)?;
+        let mut lines = Vec::with_capacity(count);
+        for l in 0..count {
+            let line = patch.line_in_hunk(h, l)?;
+            let kind = match line.origin() {
+                '+' => "addition",
+                '-' => "deletion",
+                ' ' => "context",
+                // End-of-file newline markers carry no content of their own.
+                _ => continue,
+            };
+            lines.push(DiffLine {
+                kind,
+                old_lineno: line.old_lineno(),
+                new_lineno: line.new_lineno(),
+                content: String::from_utf8_lossy(line.content())
+                    .trim_end_matches(['\n', '\r'])
+                    .to_string(),
+            });
+        }
+        hunks.push(DiffHunk {
+            header: String::from_utf8_lossy(hunk.header()).trim_end().to_string(),
+            rows: split_rows(&lines),
+            lines,
+        });
+    }
+    Ok(hunks)
+}
+
+/// Pairs each run of deleted lines with the run of added lines following it.
+fn split_rows(lines: &[DiffLine]) -> Vec<SplitRow> {
+    let mut rows = Vec::new();
+    let mut deletions: Vec<DiffLine> = Vec::new();
+    let mut additions: Vec<DiffLine> = Vec::new();
+
+    let flush = |rows: &mut Vec<SplitRow>, deletions: &mut Vec<DiffLine>, additions: &mut Vec<DiffLine>| {
+        let count = deletions.len().max(additions.len());
+        let mut left = deletions.drain(..);
+        let mut right = additions.drain(..);
+        for _ in 0..count {
+            rows.push(SplitRow {
+                left: left.next(),
+                right: right.next(),
+            });
+        }
+    };
+
+    for line in lines {
+        match line.kind {
+            "deletion" => {
+                if !additions.is_empty() {
+                    flush(&mut rows, &mut deletions, &mut additions);
+                }
+                deletions.push(line.clone());
+            }
+            "addition" => additions.push(line.clone()),
+            _ => {
+                flush(&mut rows, &mut deletions, &mut additions);
+                rows.push(SplitRow {
+                    left: Some(line.clone()),
+                    right: Some(line.clone()),
+                });
+            }
+        }
+    }
+    flush(&mut rows, &mut deletions, &mut additions);
+    rows
+}
+
+/// A lowercase, human readable name of a diff status.
+#[must_use]
+pub fn delta_status(status: Delta) -> &'static str {
+    match status {
+        Delta::Added => "added",
+        Delta::Deleted => "deleted",
+        Delta::Renamed => "renamed",
+        Delta::Copied => "copied",
+        Delta::Typechange => "typechange",
+        _ => "modified",
+    }
+}
@@ -0,0 +1,254 @@
+use std::{
+    env, io,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+};
+
+use sea_orm::{DatabaseConnection, DbErr, EntityTrait};
+use thiserror::Error;
+use tracing::{debug, warn};
+
+use crate::{
+    models::{
+        git_repos::{self, Access},
+        sshes, users,
+    },
+    services::git_service::GitService,
+};
+
+/// Environment variable naming the `gitcrab-cli` binary that SSH pushes run
+/// their follow-up tasks with. Needed when `gitcrab-serve` is installed in a
+/// different directory than `gitcrab-cli`, as in the container image.
+pub const CLI_ENV: &str = "GITCRAB_CLI";
+
+/// Represents a custom error for git transport authorization.
+#[derive(Debug, Error)]
+pub enum GitAccessError {
+    #[error("Invalid git command: {0}")]
+    InvalidCommand(String),
+    #[error("Invalid repository path: {0}")]
+    InvalidRepositoryPath(String),
+    #[error("Unknown SSH key: {0}")]
+    UnknownKey(i32),
+    #[error("Repository '{0}' not found")]
+    RepositoryNotFound(String),
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] DbErr),
+}
+
+/// The git pack services we are willing to run on behalf of a client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GitCommand {
+    UploadPack,
+    ReceivePack,
+}
+
+impl GitCommand {
+    /// Parses a service name such as `git-upload-pack`.
+    ///
+    /// # Returns
+    /// * `Some(GitCommand)` for the supported pack services, `None` otherwise.
+    #[must_use]
+    pub fn from_service(service: &str) -> Option<Self> {
+        match service {
+            "git-upload-pack" => Some(Self::UploadPack),
+            "git-receive-pack" => Some(Self::ReceivePack),
+            _ => None,
+        }
+    }
+
+    /// The executable (and service) name of the command.
+    #[must_use]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::UploadPack => "git-upload-pack",
+            Self::ReceivePack => "git-receive-pack",
+        }
+    }
+
+    /// The repository access a user needs to run the command: fetching only
+    /// reads, pushing writes.
+    #[must_use]
+    pub fn required_access(&self) -> Access {
+        match self {
+            Self::UploadPack => Access::Read,
+            Self::ReceivePack => Access::Write,
+        }
+    }
+}
+
+/// A pack command addressed to `<owner>/<name>.git`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitRequest {
+    pub command: GitCommand,
+    pub owner: String,
+    pub name: String,
+}
+
+impl GitRequest {
+    /// The repository as it appears in clone URLs, e.g. `owner/name.git`.
+    #[must_use]
+    pub fn display_path(&self) -> String {
+        format!("{}/{}.git", self.owner, self.name)
+    }
+}
+
+/// Parses the `SSH_ORIGINAL_COMMAND` sent by a git client, e.g.
+/// `git-upload-pack '/owner/name.git'`.
+///
+/// # Errors
+/// Returns `GitAccessError::InvalidCommand` for anything but `git-upload-pack`
+/// and `git-receive-pack`, and `GitAccessError::InvalidRepositoryPath` when the
+/// argument is not an `owner/name` path.
+pub fn parse_ssh_command(original: &str) -> Result<GitRequest, GitAccessError> {
+    let (service, argument) = original
+        .trim()
+        .split_once(' ')
+        .ok_or_else(|| GitAccessError::InvalidCommand(original.to_string()))?;
+
+    let command = GitCommand::from_service(service)
+        .ok_or_else(|| GitAccessError::InvalidCommand(original.to_string()))?;
+
+    let argument = argument.trim();
+    let path = argument
+        .strip_prefix('\'')
+        .and_then(|a| a.strip_suffix('\''))
+        .unwrap_or(argument);
+
+    let (owner, name) = parse_repository_path(path)?;
+    Ok(GitRequest {
+        command,
+        owner,
+        name,
+    })
+}
+
+/// Splits a repository path such as `/owner/name.git` or `owner/name` into its
+/// owner namespace and repository name.
+///
+/// # Errors
+/// Returns `GitAccessError::InvalidRepositoryPath` unless the path has exactly two
+/// segments made of alphanumerics, `-` and `_`.
+pub fn parse_repository_path(path: &str) -> Result<(String, String), GitAccessError> {
+    let trimmed = path.trim_start_matches('/').trim_end_matches('/');
+    let trimmed = trimmed.strip_suffix(".git").unwrap_or(trimmed);
+
+    let segments: Vec<&str> = trimmed.split('/').collect();
+    let valid = |s: &str| {
+        !s.is_empty() && s.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    };
+
+    match segments.as_slice() {
+        [owner, name] if valid(owner) && valid(name) => {
+            Ok(((*owner).to_string(), (*name).to_string()))
+        }
+        _ => Err(GitAccessError::InvalidRepositoryPath(path.to_string())),
+    }
+}
+
+/// Resolves the repository addressed by `request` and checks that `user` may run
+/// the requested command on it.
+///
+/// Repositories the user cannot access are reported as not found, so their
+/// existence is not leaked.
+///
+/// # Returns
+/// The on-disk path of the bare repository.
+///
+/// # Errors
+/// Returns `GitAccessError::RepositoryNotFound` if the repository does not exist or
+/// the user lacks the required access.
+pub async fn authorize_request(
+    db: &DatabaseConnection,
+    user: Option<&users::Model>,
+    request: &GitRequest,
+) -> Result<PathBuf, GitAccessError> {
+    let not_found = || GitAccessError::RepositoryNotFound(request.display_path());
+
+    let repo = git_repos::Model::find_by_namespace(db, &request.owner, &request.name)
+        .await
+        .map_err(|e| {
+            debug!("Repository lookup for {} failed: {}", request.display_path(), e);
+            not_found()
+        })?;
+
+    let access = repo.access_for(user);
+    if access < request.command.required_access() {
+        warn!(
+            user = user.map(|u| u.pid.to_string()),
+            repo = request.display_path(),
+            command = request.command.as_str(),
+            "access denied"
+        );
+        return Err(not_found());
+    }
+
+    GitService::new(PathBuf::from(env!("REPO_BASE_PATH")), env!("GIT_USER"))
+        .get_repository_path(&request.owner, &request.name)
+        .map_err(|_| not_found())
+}
+
+/// Authorizes a request made over SSH with the key stored as `key_id`.
+///
+/// # Returns
+/// The key owner and the on-disk path of the bare repository.
+///
+/// # Errors
+/// Returns `GitAccessError::UnknownKey` if the key (or its owner) no longer exists,
+/// otherwise see [`authorize_request`].
+pub async fn authorize_ssh_key(
+    db: &DatabaseConnection,
+    key_id: i32,
+    request: &GitRequest,
+) -> Result<(users::Model, PathBuf), GitAccessError> {
+    let key = sshes::Entity::find_by_id(key_id)
+        .one(db)
+        .await?
+        .ok_or(GitAccessError::UnknownKey(key_id))?;
+    let user = users::Entity::find_by_id(key.user_id)
+        .one(db)
+        .await?
+        .ok_or(GitAccessError::UnknownKey(key_id))?;
+
+    let path = authorize_request(db, Some(&user), request).await?;
+    Ok((user, path))
+}
+
+/// Locates the `gitcrab-cli` binary: the path in [`CLI_ENV`] when it is set,
+/// otherwise `gitcrab-cli` next to the running executable.
+///
+/// # Errors
+/// Returns the `io::Error` when the running executable cannot be located.
+pub fn cli_path() -> io::Result<PathBuf> {
+    match env::var_os(CLI_ENV) {
+        Some(cli) if !cli.is_empty() => Ok(PathBuf::from(cli)),
+        _ => env::current_exe().map(|exe| exe.with_file_name("gitcrab-cli")),
+    }
+}
+
+/// The `gitcrab-cli` tasks to run after a successful push. The search index
+/// follows the default branch, so it is only updated when that branch moved.
+#[must_use]
+pub fn post_push_tasks(default_branch_moved: bool) -> Vec<&'static str> {
+    if default_branch_moved {
+        vec!["search_index", "insights"]
+    } else {
+        vec!["insights"]
+    }
+}
+
+/// Starts `<cli> task <task> repo:<owner>/<name>` in the background, without
+/// waiting for it.
+///
+/// # Errors
+/// Returns the `io::Error` when the process cannot be started.
+pub fn spawn_task(cli: &Path, task: &str, request: &GitRequest) -> io::Result<Child> {
+    Command::new(cli)
+        .arg("task")
+        .arg(task)
+        .arg(format!("repo:{}/{}", request.owner, request.name))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+}
@@ -0,0 +1,174 @@
+use std::{io::Read, path::{Path, PathBuf}, process::Stdio};
+
+use axum::body::{Body, Bytes};
+use flate2::read::GzDecoder;
+use futures_util::StreamExt;
+use tokio::{io::AsyncWriteExt, process::Command, task::JoinHandle};
+use tokio_util::io::ReaderStream;
+use tracing::{debug, error, warn};
+
+use crate::services::{
+    git_access_service::GitCommand,
+    git_service::{GitService, GitServiceError},
+};
+
+/// Upper bound for a gzip-compressed request once inflated. Git only compresses
+/// fetch negotiation requests, which are small compared to packs.
+const MAX_INFLATED_REQUEST: usize = 64 * 1024 * 1024;
+
+/// Encodes `data` as a git pkt-line: four hex digits of length followed by the payload.
+#[must_use]
+pub fn pkt_line(data: &str) -> Vec<u8> {
+    format!("{:04x}{}", data.len() + 4, data).into_bytes()
+}
+
+/// The `Content-Type` of the reference advertisement for `command`.
+#[must_use]
+pub fn advertisement_content_type(command: GitCommand) -> String {
+    format!("application/x-{}-advertisement", command.as_str())
+}
+
+/// The `Content-Type` of the result of a stateless RPC call to `command`.
+#[must_use]
+pub fn result_content_type(command: GitCommand) -> String {
+    format!("application/x-{}-result", command.as_str())
+}
+
+/// Runs `command --stateless-rpc --advertise-refs` and prefixes its output with
+/// the `# service=...` pkt-line the smart HTTP protocol expects.
+///
+/// # Errors
+/// Returns `GitServiceError::GitError` if the command cannot be run or fails.
+pub async fn advertise_refs(command: GitCommand, repo_path: &Path) -> Result<Vec<u8>, GitServiceError> {
+    let output = Command::new(command.as_str())
+        .arg("--stateless-rpc")
+        .arg("--advertise-refs")
+        .arg(repo_path)
+        .output()
+        .await
+        .map_err(|e| GitServiceError::GitError(format!("Failed to run {}: {:?}", command.as_str(), e)))?;
+
+    if !output.status.success() {
+        error!(
+            "{} --advertise-refs failed: {}",
+            command.as_str(),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        return Err(GitServiceError::GitError(
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        ));
+    }
+
+    let mut body = pkt_line(&format!("# service={}\n", command.as_str()));
+    body.extend_from_slice(b"0000");
+    body.extend_from_slice(&output.stdout);
+    Ok(body)
+}
+
+/// Runs a stateless RPC call of `command` against the repository, streaming the
+/// request body into the process and its output back as the response body.
+///
+/// When `gzip` is set the request is inflated first. After a `git-receive-pack`
+/// finishes, the repository ownership is handed back to the git user.
+///
+/// Also returns a handle that resolves once the process exited, to `true` if it
+/// succeeded.
+///
+/// # Errors
+/// Returns `GitServiceError::GitError` if the process cannot be spawned or a
+/// compressed request cannot be inflated, and `GitServiceError::RequestTooLarge`
+/// if it inflates past the limit.
+pub async fn stateless_rpc(
+    command: GitCommand,
+    repo_path: PathBuf,
+    request: Body,
+    gzip: bool,
+) -> Result<(Body, JoinHandle<bool>), GitServiceError> {
+    let mut child = Command::new(command.as_str())
+        .arg("--stateless-rpc")
+        .arg(&repo_path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| GitServiceError::GitError(format!("Failed to run {}: {:?}", command.as_str(), e)))?;
+
+    let mut stdin = child.stdin.take().ok_or_else(|| {
+        GitServiceError::UnexpectedError("child process has no stdin".to_string())
+    })?;
+    let stdout = child.stdout.take().ok_or_else(|| {
+        GitServiceError::UnexpectedError("child process has no stdout".to_string())
+    })?;
+
+    if gzip {
+        let compressed = axum::body::to_bytes(request, MAX_INFLATED_REQUEST)
+            .await
+            .map_err(|e| GitServiceError::GitError(format!("Failed to read request: {}", e)))?;
+        let inflated = inflate(&compressed)?;
+        tokio::spawn(async move {
+            if let Err(e) = stdin.write_all(&inflated).await {
+                warn!("Failed to write request to {}: {:?}", command.as_str(), e);
+            }
+        });
+    } else {
+        let mut stream = request.into_data_stream();
+        tokio::spawn(async move {
+            while let Some(chunk) = stream.next().await {
+                let written = match chunk {
+                    Ok(chunk) => stdin.write_all(&chunk).await,
+                    Err(e) => {
+                        warn!("Failed to read request body: {}", e);
+                        return;
+                    }
+                };
+                if let Err(e) = written {
+                    warn!("Failed to write request to {}: {:?}", command.as_str(), e);
+                    return;
+                }
+            }
+        });
+    }
+
+    // Reap the process once it is done so the response stream ends cleanly.
+    let finished = tokio::spawn(async move {
+        let success = match child.wait().await {
+            Ok(status) if status.success() => {
+                debug!("{} finished for {:?}", command.as_str(), repo_path);
+                true
+            }
+            Ok(status) => {
+                warn!("{} exited with {}", command.as_str(), status);
+                false
+            }
+            Err(e) => {
+                error!("Failed to wait for {}: {:?}", command.as_str(), e);
+                false
+            }
+        };
+        if command == GitCommand::ReceivePack {
+            let service = GitService::new(PathBuf::from(env!("REPO_BASE_PATH")), env!("GIT_USER"));
+            if let Err(e) = service.set_ownership(&repo_path) {
+                warn!("Failed to restore ownership of {:?}: {}", repo_path, e);
+            }
+        }
+        success
+    });
+
+    Ok((Body::from_stream(ReaderStream::new(stdout)), finished))
+}
+
+/// Inflates a gzip-compressed request body, refusing bodies that inflate past
+/// `MAX_INFLATED_REQUEST`.
+fn inflate(compressed: &Bytes) -> Result<Vec<u8>, GitServiceError> {
+    let mut inflated = Vec::new();
+    // One byte past the limit tells a request that fits exactly from one that does not.
+    GzDecoder::new(compressed.as_ref())
+        .take(MAX_INFLATED_REQUEST as u64 + 1)
+        .read_to_end(&mut inflated)
+        .map_err(|e| GitServiceError::GitError(format!("Invalid gzip request: {}", e)))?;
+    if inflated.len() > MAX_INFLATED_REQUEST {
+        return Err(GitServiceError::RequestTooLarge(MAX_INFLATED_REQUEST));
+    }
+    Ok(inflated)
+}
@@ -1,5 +1,5 @@
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::fs::{self, create_dir_all};
 use std::process::Command;
 use tracing::{error, info, warn, debug};
@@ -16,8 +16,14 @@ pub enum GitServiceError {
     InvalidRepositoryName(String),
     #[error("Unexpected error: {0}")]
     UnexpectedError(String),
+    #[error("Request larger than {0} bytes")]
+    RequestTooLarge(usize),
 }
 
+/// The `description` file `git init` creates, which git tools treat as no description.
+pub const DEFAULT_DESCRIPTION: &str =
+    "Unnamed repository; edit this file 'description' to name the repository.\n";
+
 /// A service for managing Git repositories on the filesystem.
 pub struct GitService {
     base_path: PathBuf,
@@ -37,41 +43,28 @@ impl GitService {
         Self { base_path, user: user.to_string()}
     }
 
-    /// Constructs the repository path based on the repository name.
+    /// Constructs the repository path based on the owner namespace and repository name.
     ///
     /// # Arguments
+    /// * `owner` - The namespace of the user owning the repository.
     /// * `name` - The name of the repository.
     ///
     /// # Returns
-    /// A `PathBuf` representing the repository path if the name is valid.
+    /// A `PathBuf` of the form `<base>/<owner>/<name>.git` if both segments are valid.
     ///
     /// # Errors
-    /// Returns `GitServiceError::InvalidRepositoryName` if the name is empty or contains invalid characters..
-    fn get_repository_path(&self, name: &str) -> Result<PathBuf, GitServiceError> {
-        if name.trim().is_empty() {
-            return Err(GitServiceError::InvalidRepositoryName(
-                "Repository name cannot be empty".into(),
-            ));
-        }
-
-        // Basic validation for repository name.
-        let sanitized_name = name
-            .chars()
-            .filter(|c| c.is_alphanumeric() || *c == '-' || *c == '_')
-            .collect::<String>();
+    /// Returns `GitServiceError::InvalidRepositoryName` if the name or owner is empty or contains invalid characters..
+    pub fn get_repository_path(&self, owner: &str, name: &str) -> Result<PathBuf, GitServiceError> {
+        validate_path_segment(owner, "Repository owner")?;
+        validate_path_segment(name, "Repository name")?;
 
-        if sanitized_name != name {
-            return Err(GitServiceError::InvalidRepositoryName(
-                "Repository name contains invalid characters".into(),
-            ));
-        }
-
-        Ok(self.base_path.join(format!("{}.git", sanitized_name)))
+        Ok(self.base_path.join(owner).join(format!("{}.git", name)))
     }
 
     /// Creates a bare Git repository.
     ///
     /// # Arguments
+    /// * `owner` - The namespace of the user owning the repository.
     /// * `name` - The name of the repository to create.
     ///
     /// # Returns
@@ -79,8 +72,8 @@ impl GitService {
     ///
     /// # Errors
     /// Returns `GitServiceError::FilesystemError` if the repository already exists or if directory creation fails.
-    pub async fn create_bare_repository(&self, name: &str) -> Result<PathBuf, GitServiceError> {
-        let repo_path = self.get_repository_path(name)?;
+    pub async fn create_bare_repository(&self, owner: &str, name: &str) -> Result<PathBuf, GitServiceError> {
+        let repo_path = self.get_repository_path(owner, name)?;
 
         // Check if the repository already exists.
         if repo_path.exists() {
@@ -94,9 +87,13 @@ impl GitService {
         let mut rollback_steps = vec![];
         debug!("Creating bare git repository at {:?}", repo_path);
 
-        // Ensure the parent directory exists.
-        match create_dir_all(&repo_path.parent().unwrap()) {
-            Ok(_) => rollback_steps.push(format!("delete-parent:{:?}", repo_path.parent())),
+        // Ensure the owner directory exists. It is only rolled back when we created it,
+        // otherwise a failure would wipe the owner's other repositories.
+        let owner_path = repo_path.parent().unwrap();
+        let owner_existed = owner_path.exists();
+        match create_dir_all(owner_path) {
+            Ok(_) if !owner_existed => rollback_steps.push(format!("delete-parent:{}", owner_path.display())),
+            Ok(_) => {}
             Err(e) => {
                 error!("Failed to create parent directory: {:?}", e);
                 return Err(GitServiceError::FilesystemError(format!(
@@ -114,26 +111,12 @@ impl GitService {
             .output()
             .await;
         // Set proper ownership
-        let chown_output = match Command::new("chown")
-                    .args(["-R", &format!("{}:{}", &self.user, &self.user)])
-                    .arg(&repo_path)
-                    .output() {
-            Ok(it) => it,
-            Err(e) => return Err(GitServiceError::FilesystemError(format!(
-                    "Failed to set ownership: {:?}",
-                    e
-                ))),
-        };
-
-        if !chown_output.status.success() {
-            warn!("Failed to set repository ownership: {}", 
-                  String::from_utf8_lossy(&chown_output.stderr));
-        }
+        self.set_ownership(&repo_path)?;
 
         match git_init_result {
             Ok(output) if output.status.success() => {
                 info!("Successfully created bare repository at {:?}", repo_path);
-                rollback_steps.push(format!("delete:{:?}", repo_path));
+                rollback_steps.push(format!("delete:{}", repo_path.display()));
             }
             Ok(output) => {
                 error!(
@@ -158,9 +141,37 @@ impl GitService {
         Ok(repo_path)
     }
 
+    /// Hands a repository back to the git user, so that objects written by this
+    /// process (e.g. a push over HTTP) stay writable for pushes over SSH.
+    ///
+    /// # Arguments
+    /// * `repo_path` - The path of the bare repository.
+    ///
+    /// # Errors
+    /// Returns `GitServiceError::FilesystemError` if `chown` cannot be executed.
+    pub fn set_ownership(&self, repo_path: &Path) -> Result<(), GitServiceError> {
+        let chown_output = match Command::new("chown")
+                    .args(["-R", &format!("{}:{}", &self.user, &self.user)])
+                    .arg(repo_path)
+                    .output() {
+            Ok(it) => it,
+            Err(e) => return Err(GitServiceError::FilesystemError(format!(
+                    "Failed to set ownership: {:?}",
+                    e
+                ))),
+        };
+
+        if !chown_output.status.success() {
+            warn!("Failed to set repository ownership: {}", 
+                  String::from_utf8_lossy(&chown_output.stderr));
+        }
+        Ok(())
+    }
+
     /// Deletes a Git repository.
     ///
     /// # Arguments
+    /// * `owner` - The namespace of the user owning the repository.
     /// * `name` - The name of the repository to delete.
     ///
     /// # Returns
@@ -168,8 +179,8 @@ impl GitService {
     ///
     /// # Errors
     /// Returns `GitServiceError::FilesystemError` if the repository does not exist or if deletion fails.
-    pub async fn delete_repository(&self, name: &str) -> Result<(), GitServiceError> {
-        let repo_path = self.get_repository_path(name)?;
+    pub async fn delete_repository(&self, owner: &str, name: &str) -> Result<(), GitServiceError> {
+        let repo_path = self.get_repository_path(owner, name)?;
 
         if !repo_path.exists() {
             warn!(
@@ -202,21 +213,23 @@ impl GitService {
     /// Renames or moves a Git repository.
     ///
     /// # Arguments
+    /// * `owner` - The namespace of the user owning the repository.
     /// * `old_name` - The current name of the repository.
     /// * `new_name` - The new name for the repository.
     ///
     /// # Returns
-    /// `Ok(())` if the repository is successfully renamed.
+    /// The `PathBuf` of the renamed repository.
     ///
     /// # Errors
     /// Returns `GitServiceError::FilesystemError` if the source repository does not exist, the target already exists, or the rename operation fails.
     pub async fn rename_repository(
         &self,
+        owner: &str,
         old_name: &str,
         new_name: &str,
-    ) -> Result<(), GitServiceError> {
-        let old_path = self.get_repository_path(old_name)?;
-        let new_path = self.get_repository_path(new_name)?;
+    ) -> Result<PathBuf, GitServiceError> {
+        let old_path = self.get_repository_path(owner, old_name)?;
+        let new_path = self.get_repository_path(owner, new_name)?;
 
         // Ensure the source exists.
         if !old_path.exists() {
@@ -245,7 +258,7 @@ impl GitService {
                     "Successfully renamed repository from {:?} to {:?}",
                     old_path, new_path
                 );
-                Ok(())
+                Ok(new_path)
             }
             Err(e) => {
                 error!(
@@ -260,6 +273,105 @@ impl GitService {
         }
     }
 
+    /// Moves a repository into its owner's namespace, from the namespace it
+    /// was stored under before, or from `<base>/<name>.git`, where repositories
+    /// were stored before they had owners.
+    ///
+    /// # Arguments
+    /// * `from` - The previous namespace of the repository, `None` for the flat layout.
+    /// * `owner` - The namespace of the user owning the repository.
+    /// * `name` - The name of the repository.
+    ///
+    /// # Returns
+    /// The `PathBuf` of the moved repository, or `None` if there is nothing to move.
+    ///
+    /// # Errors
+    /// Returns `GitServiceError::FilesystemError` if both locations exist or the move fails.
+    pub async fn move_into_namespace(
+        &self,
+        from: Option<&str>,
+        owner: &str,
+        name: &str,
+    ) -> Result<Option<PathBuf>, GitServiceError> {
+        let new_path = self.get_repository_path(owner, name)?;
+        let old_path = match from {
+            Some(from) => self.get_repository_path(from, name)?,
+            None => self.base_path.join(format!("{}.git", name)),
+        };
+        if old_path == new_path {
+            return Ok(None);
+        }
+
+        if !old_path.exists() {
+            return Ok(None);
+        }
+        if new_path.exists() {
+            return Err(GitServiceError::FilesystemError(format!(
+                "Both {:?} and {:?} exist",
+                old_path, new_path
+            )));
+        }
+
+        debug!("Moving repository from {:?} to {:?}", old_path, new_path);
+        let owner_path = new_path.parent().unwrap();
+        let moved = match tokio::fs::create_dir_all(owner_path).await {
+            Ok(_) => tokio::fs::rename(&old_path, &new_path).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = moved {
+            error!("Failed to move repository {:?}: {:?}", old_path, e);
+            return Err(GitServiceError::FilesystemError(format!(
+                "Failed to move repository: {:?}",
+                e
+            )));
+        }
+        self.set_ownership(owner_path)?;
+        info!("Moved repository from {:?} to {:?}", old_path, new_path);
+        Ok(Some(new_path))
+    }
+
+    /// Writes the `description` file of a repository, which gitweb and other
+    /// git tools show. An empty description restores the placeholder that
+    /// `git init` writes.
+    ///
+    /// # Arguments
+    /// * `owner` - The namespace of the user owning the repository.
+    /// * `name` - The name of the repository.
+    /// * `description` - The description to write.
+    ///
+    /// # Errors
+    /// Returns `GitServiceError::FilesystemError` if the repository does not exist or the file cannot be written.
+    pub async fn write_description(
+        &self,
+        owner: &str,
+        name: &str,
+        description: &str,
+    ) -> Result<(), GitServiceError> {
+        let repo_path = self.get_repository_path(owner, name)?;
+        if !repo_path.exists() {
+            return Err(GitServiceError::FilesystemError(format!(
+                "Repository does not exist: {:?}",
+                repo_path
+            )));
+        }
+
+        let description = description.trim();
+        let content = if description.is_empty() {
+            DEFAULT_DESCRIPTION.to_string()
+        } else {
+            format!("{}\n", description)
+        };
+        tokio::fs::write(repo_path.join("description"), content)
+            .await
+            .map_err(|e| {
+                error!("Failed to write the description of {:?}: {:?}", repo_path, e);
+                GitServiceError::FilesystemError(format!(
+                    "Failed to write the repository description: {:?}",
+                    e
+                ))
+            })
+    }
+
     /// Rolls back a sequence of operations in case of failure.
     ///
     /// # Arguments
@@ -294,4 +406,28 @@ impl GitService {
     }
 }
 
+/// Validates a single path segment (owner namespace or repository name).
+///
+/// # Errors
+/// Returns `GitServiceError::InvalidRepositoryName` if the segment is empty or contains
+/// characters other than alphanumerics, `-` and `_`.
+fn validate_path_segment(segment: &str, label: &str) -> Result<(), GitServiceError> {
+    if segment.trim().is_empty() {
+        return Err(GitServiceError::InvalidRepositoryName(format!(
+            "{} cannot be empty",
+            label
+        )));
+    }
 
+    if !segment
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(GitServiceError::InvalidRepositoryName(format!(
+            "{} contains invalid characters",
+            label
+        )));
+    }
+
+    Ok(())
+}
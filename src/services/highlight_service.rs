@@ -0,0 +1,137 @@
+use std::sync::LazyLock;
+
+use serde::{Deserialize, Serialize};
+use syntect::{
+    html::{line_tokens_to_classed_spans, ClassStyle},
+    parsing::{ParseState, Scope, ScopeStack, SyntaxReference, SyntaxSet},
+    util::LinesWithEndings,
+};
+
+use crate::{
+    common::html::escape_html,
+    services::repo_retrive_service::get_file_extension_from_name,
+};
+
+/// Prefix of every CSS class in highlighted code, matching `/static/highlight.css`.
+pub const CLASS_PREFIX: &str = "hl-";
+
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: CLASS_PREFIX };
+
+/// The bundled grammars, loaded on first use. These include TOML, Dockerfile
+/// and other grammars missing from syntect's own set.
+static SYNTAXES: LazyLock<SyntaxSet> = LazyLock::new(two_face::syntax::extra_newlines);
+
+/// A file split into lines of HTML, ready to be shown next to line numbers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HighlightedFile {
+    /// The name of the grammar used, or `None` for plain text.
+    pub language: Option<String>,
+    /// One entry per line, without the line break. Every line is a complete
+    /// HTML fragment: spans left open at its end are closed and reopened on
+    /// the next line.
+    pub lines: Vec<String>,
+}
+
+/// File names that identify a language without an extension.
+const KNOWN_FILE_NAMES: &[(&str, &str)] = &[
+    ("Dockerfile", "dockerfile"),
+    ("Containerfile", "dockerfile"),
+    ("Makefile", "make"),
+    ("GNUmakefile", "make"),
+    ("makefile", "make"),
+    ("Cargo.lock", "toml"),
+    ("Pipfile", "toml"),
+    ("Gemfile", "ruby"),
+    ("Rakefile", "ruby"),
+    ("Vagrantfile", "ruby"),
+    ("PKGBUILD", "bash"),
+    (".bashrc", "bash"),
+    (".bash_profile", "bash"),
+    (".profile", "bash"),
+    (".zshrc", "bash"),
+    (".envrc", "bash"),
+    ("CMakeLists.txt", "cmake"),
+];
+
+/// Finds the grammar for a file from its name, its extension or, for
+/// scripts, the shebang on its first line.
+#[must_use]
+pub fn detect_syntax(path: &str, first_line: &str) -> Option<&'static SyntaxReference> {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let by_name = KNOWN_FILE_NAMES
+        .iter()
+        .find(|(known, _)| *known == name || name.starts_with(&format!("{}.", known)))
+        .and_then(|(_, token)| SYNTAXES.find_syntax_by_token(token));
+
+    by_name
+        .or_else(|| {
+            get_file_extension_from_name(name)
+                .and_then(|extension| SYNTAXES.find_syntax_by_extension(&extension))
+        })
+        .or_else(|| SYNTAXES.find_syntax_by_first_line(first_line))
+        .filter(|syntax| syntax.name != "Plain Text")
+}
+
+/// The name of the grammar `highlight` would use for a file, used as part of
+/// cache keys since the same blob may be stored under different names.
+#[must_use]
+pub fn language_for(path: &str, content: &str) -> Option<&'static str> {
+    detect_syntax(path, content.lines().next().unwrap_or_default()).map(|s| s.name.as_str())
+}
+
+/// Highlights `content` as class-based HTML, one fragment per line.
+///
+/// Files without a known grammar, larger than `max_bytes` or that fail to
+/// parse are escaped as plain text, still split into lines.
+#[must_use]
+pub fn highlight(path: &str, content: &str, max_bytes: u64) -> HighlightedFile {
+    let syntax = if content.len() as u64 > max_bytes {
+        None
+    } else {
+        detect_syntax(path, content.lines().next().unwrap_or_default())
+    };
+
+    if let Some(syntax) = syntax {
+        if let Some(lines) = highlight_lines(syntax, content) {
+            return HighlightedFile {
+                language: Some(syntax.name.clone()),
+                lines,
+            };
+        }
+    }
+
+    HighlightedFile {
+        language: None,
+        lines: content.lines().map(escape_html).collect(),
+    }
+}
+
+fn highlight_lines(syntax: &SyntaxReference, content: &str) -> Option<Vec<String>> {
+    let mut state = ParseState::new(syntax);
+    let mut stack = ScopeStack::new();
+    let mut lines = Vec::new();
+
+    for line in LinesWithEndings::from(content) {
+        // Reopen the scopes still open from the previous line.
+        let mut html: String = stack.as_slice().iter().map(|scope| open_span(*scope)).collect();
+        let ops = state.parse_line(line, &SYNTAXES).ok()?;
+        let (spans, _) = line_tokens_to_classed_spans(line, &ops, CLASS_STYLE, &mut stack).ok()?;
+        html.push_str(&spans);
+        // Line breaks are dropped, also from inside spans; the table row is the line.
+        html.retain(|c| c != '\n' && c != '\r');
+        for _ in 0..stack.len() {
+            html.push_str("</span>");
+        }
+        lines.push(html);
+    }
+    Some(lines)
+}
+
+fn open_span(scope: Scope) -> String {
+    let classes: Vec<String> = scope
+        .build_string()
+        .split('.')
+        .map(|atom| format!("{}{}", CLASS_PREFIX, atom))
+        .collect();
+    format!("<span class=\"{}\">", classes.join(" "))
+}
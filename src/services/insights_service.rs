@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Datelike, Days, NaiveDate, NaiveDateTime, Timelike};
+use git2::{Commit, Repository, Sort};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::services::repo_retrive_service::default_ref;
+
+/// Weeks of commit activity kept, the current one included.
+pub const ACTIVITY_WEEKS: u64 = 52;
+
+/// Represents a custom error for computing repository insights.
+#[derive(Debug, Error)]
+pub enum InsightsError {
+    #[error("Git error: {0}")]
+    GitError(#[from] git2::Error),
+}
+
+/// The number of commits authored in the week starting on `week_start`, a Monday.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WeekActivity {
+    pub week_start: NaiveDate,
+    pub commits: i32,
+}
+
+/// Someone who committed to the default branch, under the identity the
+/// `.mailmap` maps them to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Contributor {
+    pub name: String,
+    pub email: String,
+    pub commits: i32,
+    pub additions: i64,
+    pub deletions: i64,
+}
+
+/// The number of commits authored on a weekday, counting from Monday as 0, at
+/// an hour of the author's local time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PunchcardCell {
+    pub weekday: i16,
+    pub hour: i16,
+    pub commits: i32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Insights {
+    /// The commit of the default branch the insights were computed at, or
+    /// `None` for an empty repository.
+    pub head_commit: Option<String>,
+    pub total_commits: i32,
+    /// Every week of the last year, oldest first, including weeks without commits.
+    pub weeks: Vec<WeekActivity>,
+    /// Contributors by number of commits, most first. Merge commits are not
+    /// counted here, as they repeat changes made elsewhere.
+    pub contributors: Vec<Contributor>,
+    /// The weekday and hour slots that have commits.
+    pub punchcard: Vec<PunchcardCell>,
+}
+
+/// The Monday of the week `date` falls in.
+#[must_use]
+pub fn week_start(date: NaiveDate) -> NaiveDate {
+    date - Days::new(u64::from(date.weekday().num_days_from_monday()))
+}
+
+/// The time a commit was authored, in the author's own timezone.
+fn local_time(commit: &Commit) -> Option<NaiveDateTime> {
+    let when = commit.author().when();
+    let seconds = when.seconds() + i64::from(when.offset_minutes()) * 60;
+    DateTime::from_timestamp(seconds, 0).map(|time| time.naive_utc())
+}
+
+/// Computes commit activity, contributors and the punchcard of the default
+/// branch of `repo`, with the weeks counted back from `now`.
+///
+/// Authors are unified through the repository's `.mailmap`. Lines changed
+/// are taken from the diff of every commit against its first parent.
+///
+/// # Errors
+/// Returns `InsightsError::GitError` if the history cannot be read.
+pub fn compute_insights(repo: &Repository, now: i64) -> Result<Insights, InsightsError> {
+    let current_week = week_start(
+        DateTime::from_timestamp(now, 0)
+            .unwrap_or_default()
+            .date_naive(),
+    );
+    let first_week = current_week - Days::new((ACTIVITY_WEEKS - 1) * 7);
+    let mut weeks: Vec<WeekActivity> = (0..ACTIVITY_WEEKS)
+        .map(|week| WeekActivity {
+            week_start: first_week + Days::new(week * 7),
+            commits: 0,
+        })
+        .collect();
+    let mut insights = Insights::default();
+
+    let head = default_ref(repo)
+        .and_then(|branch| repo.revparse_single(&format!("refs/heads/{}", branch)).ok())
+        .and_then(|object| object.peel_to_commit().ok());
+    let Some(head) = head else {
+        insights.weeks = weeks;
+        return Ok(insights);
+    };
+    insights.head_commit = Some(head.id().to_string());
+
+    let mailmap = repo.mailmap()?;
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(Sort::TIME)?;
+    revwalk.push(head.id())?;
+
+    let mut contributors: HashMap<String, Contributor> = HashMap::new();
+    let mut punchcard: HashMap<(i16, i16), i32> = HashMap::new();
+    for oid in revwalk {
+        let commit = repo.find_commit(oid?)?;
+        insights.total_commits += 1;
+
+        if let Some(time) = local_time(&commit) {
+            let date = time.date();
+            if date >= first_week {
+                let index = (date - first_week).num_days() / 7;
+                if let Some(week) = usize::try_from(index).ok().and_then(|i| weeks.get_mut(i)) {
+                    week.commits += 1;
+                }
+            }
+            let weekday = i16::try_from(date.weekday().num_days_from_monday()).unwrap_or_default();
+            let hour = i16::try_from(time.hour()).unwrap_or_default();
+            *punchcard.entry((weekday, hour)).or_default() += 1;
+        }
+
+        if commit.parent_count() > 1 {
+            continue;
+        }
+        let author = commit.author_with_mailmap(&mailmap)?;
+        let email = author.email().unwrap_or_default().to_string();
+        let parent_tree = commit.parent(0).ok().map(|parent| parent.tree()).transpose()?;
+        let stats = repo
+            .diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)?
+            .stats()?;
+        // Walking from the newest commit, the most recent name is kept.
+        let contributor = contributors
+            .entry(email.to_lowercase())
+            .or_insert_with(|| Contributor {
+                name: author.name().unwrap_or_default().to_string(),
+                email,
+                commits: 0,
+                additions: 0,
+                deletions: 0,
+            });
+        contributor.commits += 1;
+        contributor.additions += i64::try_from(stats.insertions()).unwrap_or_default();
+        contributor.deletions += i64::try_from(stats.deletions()).unwrap_or_default();
+    }
+
+    insights.weeks = weeks;
+    insights.contributors = contributors.into_values().collect();
+    insights.contributors.sort_by(|a, b| {
+        b.commits
+            .cmp(&a.commits)
+            .then_with(|| (b.additions + b.deletions).cmp(&(a.additions + a.deletions)))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    insights.punchcard = punchcard
+        .into_iter()
+        .map(|((weekday, hour), commits)| PunchcardCell { weekday, hour, commits })
+        .collect();
+    insights.punchcard.sort_by_key(|cell| (cell.weekday, cell.hour));
+    Ok(insights)
+}
+
+/// Lays the punchcard out as seven rows of 24 hours, Monday first.
+#[must_use]
+pub fn punchcard_grid(cells: &[PunchcardCell]) -> Vec<Vec<i32>> {
+    let mut grid = vec![vec![0; 24]; 7];
+    for cell in cells {
+        let row = usize::try_from(cell.weekday).ok().and_then(|weekday| grid.get_mut(weekday));
+        if let Some(slot) = row.and_then(|row| usize::try_from(cell.hour).ok().and_then(|hour| row.get_mut(hour))) {
+            *slot += cell.commits;
+        }
+    }
+    grid
+}
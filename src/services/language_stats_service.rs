@@ -0,0 +1,280 @@
+use std::path::Path;
+
+use git2::{Commit, ObjectType, Repository, TreeWalkMode, TreeWalkResult};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::services::search_index_service::glob_to_regex;
+
+/// Languages beyond this many are summed up as "Other".
+const MAX_LANGUAGES: usize = 8;
+
+const OTHER_COLOR: &str = "#8b949e";
+
+/// Represents a custom error for computing language statistics.
+#[derive(Debug, Error)]
+pub enum LanguageStatsError {
+    #[error("Git error: {0}")]
+    GitError(#[from] git2::Error),
+}
+
+/// A language and the color it is shown in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Language {
+    pub name: &'static str,
+    pub color: &'static str,
+}
+
+const fn language(name: &'static str, color: &'static str) -> Language {
+    Language { name, color }
+}
+
+/// Languages by file extension, with the colors GitHub uses for them.
+const EXTENSIONS: &[(&str, Language)] = &[
+    ("rs", language("Rust", "#dea584")),
+    ("py", language("Python", "#3572a5")),
+    ("pyi", language("Python", "#3572a5")),
+    ("js", language("JavaScript", "#f1e05a")),
+    ("mjs", language("JavaScript", "#f1e05a")),
+    ("cjs", language("JavaScript", "#f1e05a")),
+    ("jsx", language("JavaScript", "#f1e05a")),
+    ("ts", language("TypeScript", "#3178c6")),
+    ("tsx", language("TypeScript", "#3178c6")),
+    ("go", language("Go", "#00add8")),
+    ("c", language("C", "#555555")),
+    ("h", language("C", "#555555")),
+    ("cc", language("C++", "#f34b7d")),
+    ("cpp", language("C++", "#f34b7d")),
+    ("cxx", language("C++", "#f34b7d")),
+    ("hpp", language("C++", "#f34b7d")),
+    ("hh", language("C++", "#f34b7d")),
+    ("cs", language("C#", "#178600")),
+    ("java", language("Java", "#b07219")),
+    ("kt", language("Kotlin", "#a97bff")),
+    ("kts", language("Kotlin", "#a97bff")),
+    ("scala", language("Scala", "#c22d40")),
+    ("swift", language("Swift", "#f05138")),
+    ("m", language("Objective-C", "#438eff")),
+    ("rb", language("Ruby", "#701516")),
+    ("php", language("PHP", "#4f5d95")),
+    ("pl", language("Perl", "#0298c3")),
+    ("lua", language("Lua", "#000080")),
+    ("hs", language("Haskell", "#5e5086")),
+    ("ex", language("Elixir", "#6e4a7e")),
+    ("exs", language("Elixir", "#6e4a7e")),
+    ("erl", language("Erlang", "#b83998")),
+    ("clj", language("Clojure", "#db5855")),
+    ("ml", language("OCaml", "#ef7a08")),
+    ("zig", language("Zig", "#ec915c")),
+    ("dart", language("Dart", "#00b4ab")),
+    ("r", language("R", "#198ce7")),
+    ("sql", language("SQL", "#e38c00")),
+    ("sh", language("Shell", "#89e051")),
+    ("bash", language("Shell", "#89e051")),
+    ("zsh", language("Shell", "#89e051")),
+    ("ps1", language("PowerShell", "#012456")),
+    ("html", language("HTML", "#e34c26")),
+    ("htm", language("HTML", "#e34c26")),
+    ("css", language("CSS", "#563d7c")),
+    ("scss", language("SCSS", "#c6538c")),
+    ("sass", language("Sass", "#a53b70")),
+    ("less", language("Less", "#1d365d")),
+    ("vue", language("Vue", "#41b883")),
+    ("svelte", language("Svelte", "#ff3e00")),
+    ("md", language("Markdown", "#083fa1")),
+    ("markdown", language("Markdown", "#083fa1")),
+    ("json", language("JSON", "#292929")),
+    ("yaml", language("YAML", "#cb171e")),
+    ("yml", language("YAML", "#cb171e")),
+    ("toml", language("TOML", "#9c4221")),
+    ("xml", language("XML", "#0060ac")),
+    ("proto", language("Protocol Buffer", "#6a7fd2")),
+    ("tf", language("HCL", "#844fba")),
+    ("nix", language("Nix", "#7e7eff")),
+    ("cmake", language("CMake", "#da3434")),
+    ("dockerfile", language("Dockerfile", "#384d54")),
+];
+
+/// Languages of files recognized by their whole name.
+const FILENAMES: &[(&str, Language)] = &[
+    ("Makefile", language("Makefile", "#427819")),
+    ("GNUmakefile", language("Makefile", "#427819")),
+    ("Dockerfile", language("Dockerfile", "#384d54")),
+    ("CMakeLists.txt", language("CMake", "#da3434")),
+    ("Rakefile", language("Ruby", "#701516")),
+    ("Gemfile", language("Ruby", "#701516")),
+    ("Jenkinsfile", language("Groovy", "#4298b8")),
+];
+
+/// The share of one language in a repository.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LanguageShare {
+    pub name: String,
+    pub color: String,
+    pub bytes: u64,
+    /// The percentage of all classified bytes, rounded to one decimal.
+    pub percentage: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LanguageStats {
+    /// The commit the statistics were computed at.
+    pub commit: String,
+    /// The bytes of all files with a known language.
+    pub total_bytes: u64,
+    /// The languages by size, largest first, with the smallest ones summed up
+    /// as "Other".
+    pub languages: Vec<LanguageShare>,
+}
+
+/// The language of a file, from its name or else its extension.
+#[must_use]
+pub fn classify(path: &str) -> Option<Language> {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    if let Some((_, language)) = FILENAMES.iter().find(|(filename, _)| *filename == name) {
+        return Some(*language);
+    }
+    let extension = Path::new(name).extension()?.to_str()?.to_ascii_lowercase();
+    EXTENSIONS
+        .iter()
+        .find(|(ext, _)| *ext == extension)
+        .map(|(_, language)| *language)
+}
+
+/// Whether `path` is vendored or built code that does not count towards the
+/// languages of a repository: anything under `vendor/` or `target/`, and lock
+/// files.
+#[must_use]
+pub fn is_excluded(path: &str) -> bool {
+    let in_dir = |dir: &str| path.starts_with(&format!("{}/", dir)) || path.contains(&format!("/{}/", dir));
+    in_dir("vendor") || in_dir("target") || path.ends_with(".lock")
+}
+
+/// A `linguist-generated` or `linguist-vendored` line of a `.gitattributes`
+/// file.
+struct AttributeRule {
+    /// The directory of the `.gitattributes` file, with a trailing `/`.
+    dir: String,
+    pattern: Regex,
+    excluded: bool,
+}
+
+/// Reads the rules of a `.gitattributes` file in `dir` that mark files as
+/// generated or vendored, or unmark them.
+fn parse_attributes(dir: &str, content: &str) -> Vec<AttributeRule> {
+    let mut rules = Vec::new();
+    for line in content.lines() {
+        let mut fields = line.split_whitespace();
+        let Some(pattern) = fields.next().filter(|p| !p.starts_with('#')) else {
+            continue;
+        };
+        let mut excluded = None;
+        for attribute in fields {
+            let (name, value) = match attribute.split_once('=') {
+                Some((name, value)) => (name, value != "false"),
+                None => match attribute.strip_prefix('-').or_else(|| attribute.strip_prefix('!')) {
+                    Some(name) => (name, false),
+                    None => (attribute, true),
+                },
+            };
+            if name == "linguist-generated" || name == "linguist-vendored" {
+                excluded = Some(value);
+            }
+        }
+        let (Some(excluded), Ok(pattern)) = (excluded, glob_to_regex(pattern)) else {
+            continue;
+        };
+        rules.push(AttributeRule {
+            dir: dir.to_string(),
+            pattern,
+            excluded,
+        });
+    }
+    rules
+}
+
+/// Whether the attribute rules mark `path` as generated or vendored. Later
+/// rules win, and the rules are ordered from the root directory down, so a
+/// `.gitattributes` file overrides those of its parents.
+fn excluded_by_attributes(rules: &[AttributeRule], path: &str) -> bool {
+    rules
+        .iter()
+        .rev()
+        .find(|rule| {
+            path.strip_prefix(&rule.dir)
+                .is_some_and(|relative| rule.pattern.is_match(relative))
+        })
+        .is_some_and(|rule| rule.excluded)
+}
+
+/// Computes how many bytes of each language the files at `commit` contain.
+///
+/// Files are classified by [`classify`]; files without a known language,
+/// files excluded by [`is_excluded`], and files marked `linguist-generated` or
+/// `linguist-vendored` in a `.gitattributes` file are left out.
+///
+/// # Errors
+/// Returns `LanguageStatsError::GitError` if the tree cannot be read.
+pub fn language_stats(repo: &Repository, commit: &Commit) -> Result<LanguageStats, LanguageStatsError> {
+    let mut files = Vec::new();
+    let mut attribute_files = Vec::new();
+    commit.tree()?.walk(TreeWalkMode::PreOrder, |root, entry| {
+        if entry.kind() != Some(ObjectType::Blob) {
+            return TreeWalkResult::Ok;
+        }
+        let name = entry.name().unwrap_or_default();
+        if name == ".gitattributes" {
+            attribute_files.push((root.to_string(), entry.id()));
+        } else if let Some(language) = classify(name) {
+            files.push((format!("{}{}", root, name), entry.id(), language));
+        }
+        TreeWalkResult::Ok
+    })?;
+
+    // Deeper files override shallower ones, so they have to come last.
+    attribute_files.sort_by_key(|(dir, _)| dir.matches('/').count());
+    let mut rules = Vec::new();
+    for (dir, oid) in attribute_files {
+        let blob = repo.find_blob(oid)?;
+        rules.extend(parse_attributes(&dir, &String::from_utf8_lossy(blob.content())));
+    }
+
+    let odb = repo.odb()?;
+    let mut sizes: Vec<(Language, u64)> = Vec::new();
+    for (path, oid, language) in files {
+        if is_excluded(&path) || excluded_by_attributes(&rules, &path) {
+            continue;
+        }
+        let (size, _) = odb.read_header(oid)?;
+        match sizes.iter_mut().find(|(known, _)| known.name == language.name) {
+            Some((_, total)) => *total += size as u64,
+            None => sizes.push((language, size as u64)),
+        }
+    }
+    sizes.retain(|(_, bytes)| *bytes > 0);
+    sizes.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.name.cmp(b.0.name)));
+
+    let total_bytes: u64 = sizes.iter().map(|(_, bytes)| bytes).sum();
+    let share = |name: &str, color: &str, bytes: u64| LanguageShare {
+        name: name.to_string(),
+        color: color.to_string(),
+        bytes,
+        percentage: (bytes as f64 * 1000.0 / total_bytes as f64).round() / 10.0,
+    };
+    let mut languages: Vec<LanguageShare> = sizes
+        .iter()
+        .take(MAX_LANGUAGES)
+        .map(|(language, bytes)| share(language.name, language.color, *bytes))
+        .collect();
+    let other: u64 = sizes.iter().skip(MAX_LANGUAGES).map(|(_, bytes)| bytes).sum();
+    if other > 0 {
+        languages.push(share("Other", OTHER_COLOR, other));
+    }
+
+    Ok(LanguageStats {
+        commit: commit.id().to_string(),
+        total_bytes,
+        languages,
+    })
+}
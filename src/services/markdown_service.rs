@@ -0,0 +1,346 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use git2::{ObjectType, Repository, Tree};
+use pulldown_cmark::{html, CowStr, Event, Options, Parser, Tag, TagEnd};
+use serde::Serialize;
+
+use crate::{
+    common::html::escape_html,
+    services::repo_retrive_service::{get_file_extension_from_name, FileContentResponse},
+};
+
+/// Prefix given to every `id` in rendered Markdown, so headings of a README
+/// can never clash with element ids of the page around it.
+const ID_PREFIX: &str = "user-content-";
+
+/// Where a Markdown file lives, used to turn its relative links into links to
+/// the file browser.
+pub struct LinkContext<'a> {
+    pub repo_id: &'a str,
+    /// The branch, tag or commit being browsed.
+    pub reference: &'a str,
+    /// The directory of the Markdown file, relative to the repository root.
+    pub base_dir: &'a str,
+    /// The root tree of the browsed commit, used to tell files from directories.
+    pub tree: Option<&'a Tree<'a>>,
+}
+
+/// A README rendered for display below a directory listing.
+#[derive(Debug, Serialize)]
+pub struct Readme {
+    pub name: String,
+    pub path: String,
+    pub html: String,
+}
+
+/// Whether `name` is a Markdown file by its extension.
+#[must_use]
+pub fn is_markdown(name: &str) -> bool {
+    matches!(
+        get_file_extension_from_name(name).as_deref(),
+        Some("md" | "markdown" | "mdown" | "mkd" | "mkdn")
+    )
+}
+
+/// Finds the README of a directory: `README.md` and other Markdown READMEs
+/// first, then any other `readme` or `readme.*` file.
+#[must_use]
+pub fn find_readme(tree: &Tree) -> Option<String> {
+    let candidates: Vec<String> = tree
+        .iter()
+        .filter(|entry| entry.kind() == Some(ObjectType::Blob))
+        .filter_map(|entry| entry.name().map(str::to_string))
+        .filter(|name| {
+            let lower = name.to_lowercase();
+            lower == "readme" || lower.starts_with("readme.")
+        })
+        .collect();
+
+    candidates
+        .iter()
+        .find(|name| name.eq_ignore_ascii_case("readme.md"))
+        .or_else(|| candidates.iter().find(|name| is_markdown(name)))
+        .or_else(|| candidates.first())
+        .cloned()
+}
+
+/// Renders the README of the directory `dir` in `tree`, if it has one.
+///
+/// READMEs larger than `max_bytes` or stored as binary are skipped. Markdown
+/// is rendered with [`render_markdown`], anything else is shown as plain text.
+#[must_use]
+pub fn render_readme(
+    repo: &Repository,
+    tree: &Tree,
+    dir: &str,
+    repo_id: &str,
+    reference: &str,
+    max_bytes: u64,
+) -> Option<Readme> {
+    let dir = dir.trim_matches('/');
+    let dir_tree = if dir.is_empty() {
+        tree.clone()
+    } else {
+        repo.find_tree(tree.get_path(Path::new(dir)).ok()?.id()).ok()?
+    };
+    let name = find_readme(&dir_tree)?;
+    let entry = dir_tree.get_name(&name)?;
+    let (size, _) = repo.odb().ok()?.read_header(entry.id()).ok()?;
+    if size as u64 > max_bytes {
+        return None;
+    }
+    let blob = repo.find_blob(entry.id()).ok()?;
+    if blob.is_binary() {
+        return None;
+    }
+
+    let source = String::from_utf8_lossy(blob.content());
+    let html = if is_markdown(&name) {
+        let context = LinkContext {
+            repo_id,
+            reference,
+            base_dir: dir,
+            tree: Some(tree),
+        };
+        render_markdown(&source, &context)
+    } else {
+        format!("<pre>{}</pre>", escape_html(&source))
+    };
+
+    Some(Readme {
+        path: join_path(dir, &name),
+        name,
+        html,
+    })
+}
+
+/// Renders a Markdown file that is being viewed, or returns `None` for other
+/// files and for content that was left out.
+#[must_use]
+pub fn render_file(file: &FileContentResponse, tree: &Tree, reference: &str) -> Option<String> {
+    if !is_markdown(&file.file_path) || file.is_binary || file.too_large {
+        return None;
+    }
+    let base_dir = file.file_path.rsplit_once('/').map_or("", |(dir, _)| dir);
+    let context = LinkContext {
+        repo_id: &file.repo_id,
+        reference,
+        base_dir,
+        tree: Some(tree),
+    };
+    Some(render_markdown(&file.content, &context))
+}
+
+/// Renders GitHub flavoured Markdown to sanitized HTML.
+///
+/// Tables, task lists and strikethrough are supported. Headings get anchors,
+/// relative links point to the file browser and relative images to the raw
+/// file, both resolved from the directory of the Markdown file.
+#[must_use]
+pub fn render_markdown(source: &str, context: &LinkContext) -> String {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_GFM;
+    let events: Vec<Event> = Parser::new_ext(source, options).collect();
+
+    let mut slugs = HashMap::new();
+    let mut output = Vec::with_capacity(events.len());
+    for (i, event) in events.iter().enumerate() {
+        match event {
+            Event::Start(Tag::Heading { level, classes, attrs, .. }) => {
+                let end = events[i..]
+                    .iter()
+                    .position(|e| matches!(e, Event::End(TagEnd::Heading(_))))
+                    .map_or(events.len(), |offset| i + offset);
+                let text: String = events[i + 1..end]
+                    .iter()
+                    .filter_map(|e| match e {
+                        Event::Text(text) | Event::Code(text) => Some(text.as_ref()),
+                        _ => None,
+                    })
+                    .collect();
+                let slug = unique_slug(&mut slugs, &text);
+                output.push(Event::Start(Tag::Heading {
+                    level: *level,
+                    id: Some(CowStr::from(slug.clone())),
+                    classes: classes.clone(),
+                    attrs: attrs.clone(),
+                }));
+                output.push(Event::InlineHtml(CowStr::from(format!(
+                    "<a class=\"anchor\" href=\"#{}{}\">#</a> ",
+                    ID_PREFIX, slug
+                ))));
+            }
+            Event::Start(Tag::Link { link_type, dest_url, title, id }) => {
+                output.push(Event::Start(Tag::Link {
+                    link_type: *link_type,
+                    dest_url: CowStr::from(rewrite_url(dest_url, context, false)),
+                    title: title.clone(),
+                    id: id.clone(),
+                }));
+            }
+            Event::Start(Tag::Image { link_type, dest_url, title, id }) => {
+                output.push(Event::Start(Tag::Image {
+                    link_type: *link_type,
+                    dest_url: CowStr::from(rewrite_url(dest_url, context, true)),
+                    title: title.clone(),
+                    id: id.clone(),
+                }));
+            }
+            event => output.push(event.clone()),
+        }
+    }
+
+    let mut unsafe_html = String::new();
+    html::push_html(&mut unsafe_html, output.into_iter());
+    sanitize(&unsafe_html)
+}
+
+/// Removes everything from rendered HTML that could run scripts, load
+/// third-party content unasked or break out of the page layout.
+fn sanitize(html: &str) -> String {
+    ammonia::Builder::default()
+        .add_tags(["input"])
+        .add_tag_attributes("input", ["type", "checked", "disabled"])
+        .add_tag_attributes("h1", ["id"])
+        .add_tag_attributes("h2", ["id"])
+        .add_tag_attributes("h3", ["id"])
+        .add_tag_attributes("h4", ["id"])
+        .add_tag_attributes("h5", ["id"])
+        .add_tag_attributes("h6", ["id"])
+        .add_allowed_classes("a", ["anchor"])
+        .id_prefix(Some(ID_PREFIX))
+        .link_rel(Some("noopener noreferrer nofollow"))
+        // Task list checkboxes are the only inputs Markdown produces.
+        .attribute_filter(|element, attribute, value| {
+            if element == "input" && attribute == "type" && value != "checkbox" {
+                None
+            } else {
+                Some(value.into())
+            }
+        })
+        .clean(html)
+        .to_string()
+}
+
+/// Turns heading text into a GitHub style anchor, e.g. `Getting Started!`
+/// into `getting-started`, numbering repeated headings.
+fn unique_slug(slugs: &mut HashMap<String, usize>, text: &str) -> String {
+    let slug: String = text
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect();
+    let count = slugs.entry(slug.clone()).or_insert(0);
+    *count += 1;
+    if *count == 1 {
+        slug
+    } else {
+        format!("{}-{}", slug, *count - 1)
+    }
+}
+
+/// Rewrites a link found in Markdown. Absolute URLs are kept, anchors get the
+/// id prefix and repository paths become links to the file browser, or to the
+/// raw file for images.
+fn rewrite_url(url: &str, context: &LinkContext, image: bool) -> String {
+    if url.is_empty() || url.starts_with("//") || has_scheme(url) {
+        return url.to_string();
+    }
+    if let Some(anchor) = url.strip_prefix('#') {
+        return format!("#{}{}", ID_PREFIX, anchor);
+    }
+
+    let (path, suffix) = url
+        .find(['?', '#'])
+        .map_or((url, ""), |index| url.split_at(index));
+    let Some(path) = resolve_path(context.base_dir, path) else {
+        return url.to_string();
+    };
+
+    let view = if image {
+        "raw"
+    } else if path.is_empty() || is_directory(context.tree, &path) {
+        "tree"
+    } else {
+        "blob"
+    };
+    let suffix = match suffix.strip_prefix('#') {
+        Some(anchor) if view == "blob" => format!("#{}{}", ID_PREFIX, anchor),
+        _ => suffix.to_string(),
+    };
+    let mut link = format!(
+        "/git_repos/{}/{}/{}",
+        context.repo_id,
+        view,
+        encode_path(context.reference)
+    );
+    if !path.is_empty() {
+        link.push('/');
+        link.push_str(&encode_path(&path));
+    }
+    link.push_str(&suffix);
+    link
+}
+
+/// Resolves `link` against `base_dir`, or against the repository root when it
+/// starts with `/`. Returns `None` for paths leaving the repository.
+fn resolve_path(base_dir: &str, link: &str) -> Option<String> {
+    let link = urlencoding::decode(link).map_or_else(|_| link.to_string(), |l| l.into_owned());
+    let mut segments: Vec<String> = if link.starts_with('/') {
+        Vec::new()
+    } else {
+        base_dir
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .map(str::to_string)
+            .collect()
+    };
+    for segment in link.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop()?;
+            }
+            segment => segments.push(segment.to_string()),
+        }
+    }
+    Some(segments.join("/"))
+}
+
+fn is_directory(tree: Option<&Tree>, path: &str) -> bool {
+    tree.and_then(|tree| tree.get_path(Path::new(path)).ok())
+        .is_some_and(|entry| entry.kind() == Some(ObjectType::Tree))
+}
+
+fn has_scheme(url: &str) -> bool {
+    url.split_once(':').is_some_and(|(scheme, _)| {
+        !scheme.is_empty()
+            && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    })
+}
+
+fn encode_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| urlencoding::encode(segment).into_owned())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn join_path(dir: &str, name: &str) -> String {
+    if dir.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", dir, name)
+    }
+}
@@ -1,3 +1,19 @@
 pub mod git_service;
 pub mod ssh_service;
 pub mod repo_retrive_service;
+pub mod git_access_service;
+pub mod git_http_service;
+pub mod repo_history_service;
+pub mod diff_service;
+pub mod raw_service;
+pub mod archive_service;
+pub mod refs_service;
+pub mod markdown_service;
+pub mod highlight_service;
+pub mod language_stats_service;
+pub mod insights_service;
+pub mod blame_service;
+pub mod search_service;
+pub mod search_index_service;
+pub mod totp_service;
+pub mod throttle_service;
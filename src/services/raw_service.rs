@@ -0,0 +1,199 @@
+use std::{
+    io::{self, Write},
+    ops::{Range, RangeInclusive},
+    path::{Path, PathBuf},
+};
+
+use axum::body::Body;
+use git2::{Oid, Repository};
+use tokio::sync::oneshot;
+use tokio_util::io::{ReaderStream, SyncIoBridge};
+use tracing::warn;
+
+use crate::services::repo_retrive_service::get_file_extension_from_name;
+
+/// Policy sent with every raw file: nothing in it may load resources or run
+/// scripts, and it is rendered in a sandbox with its own opaque origin.
+pub const RAW_CONTENT_SECURITY_POLICY: &str =
+    "default-src 'none'; img-src 'self' data:; media-src 'self'; style-src 'unsafe-inline'; sandbox";
+
+/// Guesses the `Content-Type` of a file from its name and content.
+///
+/// Only types a browser renders without executing anything are reported as
+/// such. Text is always served as `text/plain`, so an `.html` or `.js` file in
+/// a repository is shown as source instead of being run on our origin.
+#[must_use]
+pub fn guess_content_type(name: &str, content: &[u8]) -> &'static str {
+    let extension = get_file_extension_from_name(name);
+    let known = match extension.as_deref() {
+        Some("png") => Some("image/png"),
+        Some("jpg" | "jpeg") => Some("image/jpeg"),
+        Some("gif") => Some("image/gif"),
+        Some("webp") => Some("image/webp"),
+        Some("ico") => Some("image/x-icon"),
+        Some("bmp") => Some("image/bmp"),
+        Some("svg") => Some("image/svg+xml"),
+        Some("pdf") => Some("application/pdf"),
+        Some("mp3") => Some("audio/mpeg"),
+        Some("ogg") => Some("audio/ogg"),
+        Some("wav") => Some("audio/wav"),
+        Some("mp4") => Some("video/mp4"),
+        Some("webm") => Some("video/webm"),
+        Some("zip") => Some("application/zip"),
+        Some("gz" | "tgz") => Some("application/gzip"),
+        Some("tar") => Some("application/x-tar"),
+        Some("wasm") => Some("application/wasm"),
+        _ => None,
+    };
+    if let Some(content_type) = known {
+        return content_type;
+    }
+
+    if looks_like_text(content) {
+        "text/plain; charset=utf-8"
+    } else {
+        "application/octet-stream"
+    }
+}
+
+/// Whether the browser can show a file of `content_type` itself; anything else
+/// is sent as an attachment.
+#[must_use]
+pub fn is_inline_type(content_type: &str) -> bool {
+    content_type.starts_with("text/plain")
+        || content_type.starts_with("image/")
+        || content_type.starts_with("audio/")
+        || content_type.starts_with("video/")
+        || content_type == "application/pdf"
+}
+
+/// Builds a `Content-Disposition` header value. The file name is sent both as
+/// a plain ASCII fallback and RFC 5987 encoded, so any name survives.
+#[must_use]
+pub fn content_disposition(name: &str, attachment: bool) -> String {
+    let fallback: String = name
+        .chars()
+        .map(|c| {
+            if (c.is_ascii_graphic() && c != '"' && c != '\\') || c == ' ' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!(
+        "{}; filename=\"{}\"; filename*=UTF-8''{}",
+        if attachment { "attachment" } else { "inline" },
+        fallback,
+        urlencoding::encode(name)
+    )
+}
+
+/// The outcome of reading a `Range` header against a file of known size.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ByteRange {
+    /// No usable range was requested; send the whole file.
+    Full,
+    /// Send only these bytes.
+    Partial(RangeInclusive<u64>),
+    /// The range lies outside the file.
+    Unsatisfiable,
+}
+
+/// Parses a single `bytes=` range, e.g. `bytes=0-99`, `bytes=100-` or `bytes=-100`.
+///
+/// Multiple ranges and other units are not supported and fall back to the
+/// whole file, which is always a valid answer to a range request.
+#[must_use]
+pub fn parse_range(header: Option<&str>, size: u64) -> ByteRange {
+    let Some(spec) = header.and_then(|h| h.trim().strip_prefix("bytes=")) else {
+        return ByteRange::Full;
+    };
+    if spec.contains(',') {
+        return ByteRange::Full;
+    }
+    let Some((start, end)) = spec.split_once('-') else {
+        return ByteRange::Full;
+    };
+    let (start, end) = (start.trim(), end.trim());
+
+    let range = match (start.parse::<u64>(), end.parse::<u64>()) {
+        // bytes=-N: the last N bytes.
+        (Err(_), Ok(suffix)) if start.is_empty() => {
+            if suffix == 0 || size == 0 {
+                return ByteRange::Unsatisfiable;
+            }
+            size.saturating_sub(suffix)..=size - 1
+        }
+        // bytes=N-: from N to the end.
+        (Ok(first), Err(_)) if end.is_empty() => first..=size.saturating_sub(1),
+        (Ok(first), Ok(last)) if first <= last => first..=last.min(size.saturating_sub(1)),
+        _ => return ByteRange::Full,
+    };
+
+    if *range.start() >= size {
+        ByteRange::Unsatisfiable
+    } else {
+        ByteRange::Partial(range)
+    }
+}
+
+/// Streams the bytes in `window` of the blob `oid` as a response body.
+///
+/// The blob is read by a blocking task and written to the body straight from
+/// the buffer git loaded it into, so large files neither stall the runtime nor
+/// get copied; a range request only writes the requested bytes. The content
+/// type, which depends on the first bytes, is sent on the returned receiver
+/// before anything is written; it is dropped if the blob cannot be read.
+#[must_use]
+pub fn stream_blob(
+    repo_path: PathBuf,
+    oid: Oid,
+    name: String,
+    window: Range<u64>,
+) -> (oneshot::Receiver<&'static str>, Body) {
+    let (content_type, content_type_rx) = oneshot::channel();
+    let (reader, writer) = tokio::io::duplex(64 * 1024);
+    tokio::task::spawn_blocking(move || {
+        let bridge = SyncIoBridge::new(writer);
+        if let Err(e) = write_blob(&repo_path, oid, &name, window, content_type, bridge) {
+            // The client sees a truncated download; there is no way to report
+            // an error once the response has started.
+            warn!("Failed to stream blob {}: {}", oid, e);
+        }
+    });
+    (content_type_rx, Body::from_stream(ReaderStream::new(reader)))
+}
+
+fn write_blob<W: Write>(
+    repo_path: &Path,
+    oid: Oid,
+    name: &str,
+    window: Range<u64>,
+    content_type: oneshot::Sender<&'static str>,
+    mut out: W,
+) -> io::Result<()> {
+    let repo = Repository::open_bare(repo_path).map_err(io::Error::other)?;
+    let blob = repo.find_blob(oid).map_err(io::Error::other)?;
+    let content = blob.content();
+    // The handler is gone when this fails, and nobody reads the body.
+    let _ = content_type.send(guess_content_type(name, content));
+
+    let clamp = |offset: u64| usize::try_from(offset).unwrap_or(usize::MAX).min(content.len());
+    let (start, end) = (clamp(window.start), clamp(window.end));
+    out.write_all(&content[start..end.max(start)])?;
+    out.flush()
+}
+
+/// Text is valid UTF-8 without NUL bytes, judged on the first 8000 bytes like git does.
+fn looks_like_text(content: &[u8]) -> bool {
+    let head = &content[..content.len().min(8000)];
+    if head.contains(&0) {
+        return false;
+    }
+    match std::str::from_utf8(head) {
+        Ok(_) => true,
+        // The sample may end in the middle of a multi-byte character.
+        Err(e) => e.error_len().is_none(),
+    }
+}
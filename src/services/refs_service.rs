@@ -0,0 +1,230 @@
+use git2::{BranchType, ErrorCode, Oid, Repository, Signature};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::services::{
+    repo_history_service::{format_time, summarize, CommitSummary},
+    repo_retrive_service::default_ref,
+};
+
+/// Represents a custom error for reading and changing branches and tags.
+#[derive(Debug, Error)]
+pub enum RefsError {
+    #[error("Reference not found: {0}")]
+    NotFound(String),
+    #[error("Invalid reference name: {0}")]
+    InvalidName(String),
+    #[error("Reference already exists: {0}")]
+    AlreadyExists(String),
+    #[error("Branch {0} is not merged into the default branch")]
+    NotMerged(String),
+    #[error("Branch {0} is the default branch")]
+    DefaultBranch(String),
+    #[error("Git error: {0}")]
+    GitError(#[from] git2::Error),
+}
+
+#[derive(Debug, Serialize)]
+pub struct BranchInfo {
+    pub name: String,
+    pub commit: CommitSummary,
+    pub is_default: bool,
+    /// Commits on this branch that the default branch does not have.
+    pub ahead: usize,
+    /// Commits on the default branch that this branch does not have.
+    pub behind: usize,
+    /// Whether every commit of this branch is on the default branch, so it
+    /// can be deleted without losing work.
+    pub merged: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TagInfo {
+    pub name: String,
+    pub commit: CommitSummary,
+    pub annotated: bool,
+    /// The message of an annotated tag.
+    pub message: Option<String>,
+    pub tagger_name: Option<String>,
+    pub tagger_email: Option<String>,
+    pub date: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BranchList {
+    pub default_branch: Option<String>,
+    pub branches: Vec<BranchInfo>,
+}
+
+/// Lists the local branches, the default branch first and the rest by name,
+/// with their distance from the default branch.
+///
+/// # Errors
+/// Returns `RefsError::GitError` if the branches cannot be read.
+pub fn list_branches(repo: &Repository) -> Result<BranchList, RefsError> {
+    let default_branch = default_ref(repo);
+    let default_oid = default_branch
+        .as_deref()
+        .and_then(|name| repo.find_branch(name, BranchType::Local).ok())
+        .and_then(|branch| branch.get().target());
+
+    let mut branches = Vec::new();
+    for branch in repo.branches(Some(BranchType::Local))? {
+        let (branch, _) = branch?;
+        let Some(name) = branch.name()?.map(str::to_string) else {
+            continue;
+        };
+        let commit = branch.get().peel_to_commit()?;
+        let (ahead, behind) = match default_oid {
+            Some(default_oid) => repo.graph_ahead_behind(commit.id(), default_oid)?,
+            None => (0, 0),
+        };
+        branches.push(BranchInfo {
+            is_default: default_branch.as_deref() == Some(name.as_str()),
+            commit: summarize(&commit),
+            name,
+            ahead,
+            behind,
+            merged: default_oid.is_some() && ahead == 0,
+        });
+    }
+    branches.sort_by(|a, b| b.is_default.cmp(&a.is_default).then_with(|| a.name.cmp(&b.name)));
+
+    Ok(BranchList {
+        default_branch,
+        branches,
+    })
+}
+
+/// Lists the tags by name, with the commit each points to.
+///
+/// # Errors
+/// Returns `RefsError::GitError` if the tags cannot be read.
+pub fn list_tags(repo: &Repository) -> Result<Vec<TagInfo>, RefsError> {
+    let mut tags = Vec::new();
+    for name in repo.tag_names(None)?.iter().flatten() {
+        let object = repo.revparse_single(&format!("refs/tags/{}", name))?;
+        // Tags of trees or blobs have no commit to show.
+        let Ok(commit) = object.peel_to_commit() else {
+            continue;
+        };
+        let tag = object.as_tag();
+        let tagger = tag.and_then(git2::Tag::tagger);
+        tags.push(TagInfo {
+            name: name.to_string(),
+            commit: summarize(&commit),
+            annotated: tag.is_some(),
+            message: tag.and_then(|t| t.message()).map(|m| m.trim_end().to_string()),
+            tagger_name: tagger.as_ref().and_then(|t| t.name().map(str::to_string)),
+            tagger_email: tagger.as_ref().and_then(|t| t.email().map(str::to_string)),
+            date: tagger.as_ref().map(|t| format_time(&t.when())),
+        });
+    }
+    tags.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(tags)
+}
+
+/// Creates the branch `name` at the commit `from` resolves to.
+///
+/// # Errors
+/// Returns `RefsError::InvalidName` for names git rejects,
+/// `RefsError::AlreadyExists` if the branch exists and
+/// `RefsError::NotFound` if `from` does not resolve to a commit.
+pub fn create_branch(repo: &Repository, name: &str, from: &str) -> Result<(), RefsError> {
+    if !git2::Branch::name_is_valid(name)? {
+        return Err(RefsError::InvalidName(name.to_string()));
+    }
+    let commit = resolve(repo, from)?;
+    match repo.branch(name, &commit, false) {
+        Ok(_) => Ok(()),
+        Err(e) if e.code() == ErrorCode::Exists => Err(RefsError::AlreadyExists(name.to_string())),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Deletes the branch `name` if all of its commits are on the default branch.
+///
+/// # Errors
+/// Returns `RefsError::NotFound` if there is no such branch,
+/// `RefsError::DefaultBranch` for the default branch and
+/// `RefsError::NotMerged` if deleting it would lose commits.
+pub fn delete_branch(repo: &Repository, name: &str) -> Result<(), RefsError> {
+    let mut branch = find_branch(repo, name)?;
+    let default_branch = default_ref(repo);
+    if default_branch.as_deref() == Some(name) {
+        return Err(RefsError::DefaultBranch(name.to_string()));
+    }
+
+    let default_oid = default_branch
+        .as_deref()
+        .and_then(|default| repo.find_branch(default, BranchType::Local).ok())
+        .and_then(|default| default.get().target())
+        .ok_or_else(|| RefsError::NotMerged(name.to_string()))?;
+    let oid = branch.get().peel_to_commit()?.id();
+    if oid != default_oid && !repo.graph_descendant_of(default_oid, oid)? {
+        return Err(RefsError::NotMerged(name.to_string()));
+    }
+
+    branch.delete()?;
+    Ok(())
+}
+
+/// Creates the tag `name` at the commit `target` resolves to. The tag is
+/// annotated when a message is given and lightweight otherwise.
+///
+/// # Errors
+/// Returns `RefsError::InvalidName` for names git rejects,
+/// `RefsError::AlreadyExists` if the tag exists and
+/// `RefsError::NotFound` if `target` does not resolve to a commit.
+pub fn create_tag(
+    repo: &Repository,
+    name: &str,
+    target: &str,
+    message: Option<&str>,
+    tagger: &Signature,
+) -> Result<Oid, RefsError> {
+    if !git2::Tag::is_valid_name(name) {
+        return Err(RefsError::InvalidName(name.to_string()));
+    }
+    let commit = resolve(repo, target)?;
+    let result = match message.map(str::trim).filter(|m| !m.is_empty()) {
+        Some(message) => repo.tag(name, commit.as_object(), tagger, message, false),
+        None => repo.tag_lightweight(name, commit.as_object(), false),
+    };
+    match result {
+        Ok(oid) => Ok(oid),
+        Err(e) if e.code() == ErrorCode::Exists => Err(RefsError::AlreadyExists(name.to_string())),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Points HEAD, and with it the default branch, at the existing branch `name`.
+///
+/// # Errors
+/// Returns `RefsError::NotFound` if there is no such branch.
+pub fn set_default_branch(repo: &Repository, name: &str) -> Result<(), RefsError> {
+    let branch = find_branch(repo, name)?;
+    let refname = branch
+        .get()
+        .name()
+        .ok_or_else(|| RefsError::NotFound(name.to_string()))?
+        .to_string();
+    repo.set_head(&refname)?;
+    Ok(())
+}
+
+fn find_branch<'r>(repo: &'r Repository, name: &str) -> Result<git2::Branch<'r>, RefsError> {
+    match repo.find_branch(name, BranchType::Local) {
+        Ok(branch) => Ok(branch),
+        Err(e) if e.code() == ErrorCode::NotFound || e.code() == ErrorCode::InvalidSpec => {
+            Err(RefsError::NotFound(name.to_string()))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn resolve<'r>(repo: &'r Repository, revision: &str) -> Result<git2::Commit<'r>, RefsError> {
+    repo.revparse_single(revision)
+        .and_then(|object| object.peel_to_commit())
+        .map_err(|_| RefsError::NotFound(revision.to_string()))
+}
@@ -0,0 +1,352 @@
+use chrono::{DateTime, FixedOffset, TimeZone};
+use std::path::Path;
+
+use git2::{Commit, Delta, DiffFindOptions, ErrorCode, Oid, Repository, Sort, Tree};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::services::diff_service::{diff_commit_at, DiffError, DiffLimits, DiffResult};
+
+/// Number of commits shown per page when the caller does not ask for a size.
+pub const DEFAULT_PER_PAGE: usize = 30;
+/// Upper bound for the page size, to keep a single request cheap.
+pub const MAX_PER_PAGE: usize = 100;
+
+/// Represents a custom error for reading repository history.
+#[derive(Debug, Error)]
+pub enum RepoHistoryError {
+    #[error("Invalid commit id: {0}")]
+    InvalidOid(String),
+    #[error("Commit '{0}' not found")]
+    CommitNotFound(String),
+    #[error("Git error: {0}")]
+    GitError(#[from] git2::Error),
+    #[error("Diff error: {0}")]
+    DiffError(#[from] DiffError),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CommitSummary {
+    pub oid: String,
+    pub short_oid: String,
+    pub author_name: String,
+    pub author_email: String,
+    pub date: String,
+    pub timestamp: i64,
+    pub summary: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CommitPage {
+    pub commits: Vec<CommitSummary>,
+    pub page: usize,
+    pub per_page: usize,
+    pub has_previous: bool,
+    pub has_next: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CommitDetail {
+    #[serde(flatten)]
+    pub summary: CommitSummary,
+    pub message: String,
+    pub committer_name: String,
+    pub committer_email: String,
+    pub committer_date: String,
+    pub parents: Vec<String>,
+    pub diff: DiffResult,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PathCommit {
+    #[serde(flatten)]
+    pub summary: CommitSummary,
+    /// The path in this commit, which differs from the requested one for
+    /// commits made before a rename.
+    pub path: String,
+    /// The previous path, when this commit renamed the file.
+    pub renamed_from: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PathCommitPage {
+    pub path: String,
+    pub commits: Vec<PathCommit>,
+    pub page: usize,
+    pub per_page: usize,
+    pub has_previous: bool,
+    pub has_next: bool,
+}
+
+/// Clamps a page size requested by a client to `1..=MAX_PER_PAGE`.
+#[must_use]
+pub fn clamp_per_page(per_page: Option<usize>) -> usize {
+    per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE)
+}
+
+/// Lists one page of the history reachable from `HEAD`, newest first.
+///
+/// Pages are numbered from 1. A repository without commits yields an empty page.
+///
+/// # Errors
+/// Returns `RepoHistoryError::GitError` if the history cannot be walked.
+pub fn list_commits(
+    repo: &Repository,
+    page: usize,
+    per_page: usize,
+) -> Result<CommitPage, RepoHistoryError> {
+    let page = page.max(1);
+    let per_page = per_page.max(1);
+
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)?;
+    match repo.head() {
+        Ok(_) => revwalk.push_head()?,
+        // An empty repository has an unborn HEAD: there is simply no history yet.
+        Err(e) if e.code() == ErrorCode::UnbornBranch || e.code() == ErrorCode::NotFound => {
+            return Ok(CommitPage {
+                commits: Vec::new(),
+                page,
+                per_page,
+                has_previous: page > 1,
+                has_next: false,
+            });
+        }
+        Err(e) => return Err(e.into()),
+    }
+
+    // Take one extra commit to find out whether there is a next page.
+    let mut commits = Vec::with_capacity(per_page + 1);
+    for oid in revwalk.skip(page.saturating_sub(1).saturating_mul(per_page)).take(per_page + 1) {
+        let commit = repo.find_commit(oid?)?;
+        commits.push(summarize(&commit));
+    }
+    let has_next = commits.len() > per_page;
+    commits.truncate(per_page);
+
+    Ok(CommitPage {
+        commits,
+        page,
+        per_page,
+        has_previous: page > 1,
+        has_next,
+    })
+}
+
+/// Loads a single commit with its parents, full message and its diff against
+/// the first parent (or the empty tree for a root commit).
+///
+/// # Errors
+/// Returns `RepoHistoryError::InvalidOid` if `oid` is not a hex object id,
+/// `RepoHistoryError::CommitNotFound` if no such commit exists, and
+/// `RepoHistoryError::DiffError` if the diff cannot be computed.
+pub fn commit_detail(
+    repo: &Repository,
+    oid: &str,
+    limits: &DiffLimits,
+) -> Result<CommitDetail, RepoHistoryError> {
+    commit_detail_at(repo, oid, None, limits)
+}
+
+/// Like [`commit_detail`], but the diff only shows the files at or below
+/// `path` when a path is given.
+///
+/// # Errors
+/// See [`commit_detail`].
+pub fn commit_detail_at(
+    repo: &Repository,
+    oid: &str,
+    path: Option<&str>,
+    limits: &DiffLimits,
+) -> Result<CommitDetail, RepoHistoryError> {
+    let parsed = Oid::from_str(oid).map_err(|_| RepoHistoryError::InvalidOid(oid.to_string()))?;
+    let commit = repo
+        .find_commit(parsed)
+        .map_err(|_| RepoHistoryError::CommitNotFound(oid.to_string()))?;
+
+    let diff = diff_commit_at(repo, &commit, path, limits)?;
+    let committer = commit.committer();
+
+    Ok(CommitDetail {
+        summary: summarize(&commit),
+        message: commit.message().unwrap_or_default().to_string(),
+        committer_name: committer.name().unwrap_or_default().to_string(),
+        committer_email: committer.email().unwrap_or_default().to_string(),
+        committer_date: format_time(&committer.when()),
+        parents: commit.parent_ids().map(|p| p.to_string()).collect(),
+        diff,
+    })
+}
+
+/// Lists one page of the commits reachable from `start` that changed the file
+/// or directory at `path`, newest first.
+///
+/// A commit changed the path when its entry there differs from the one in
+/// each of its parents, so merges that only brought in one side are skipped.
+/// Renames of files are followed: once the commit that added the file under
+/// its current name is reached, older commits are matched against the name
+/// it was renamed from.
+///
+/// # Errors
+/// Returns `RepoHistoryError::GitError` if the history cannot be walked.
+pub fn list_path_commits(
+    repo: &Repository,
+    start: &Commit,
+    path: &str,
+    page: usize,
+    per_page: usize,
+) -> Result<PathCommitPage, RepoHistoryError> {
+    let page = page.max(1);
+    let per_page = per_page.max(1);
+    let path = path.trim_matches('/');
+
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)?;
+    revwalk.push(start.id())?;
+
+    // Every earlier commit is still checked, to follow renames on the way.
+    let mut skip = page.saturating_sub(1).saturating_mul(per_page);
+    let mut current = path.to_string();
+    let mut commits = Vec::with_capacity(per_page + 1);
+    for oid in revwalk {
+        let commit = repo.find_commit(oid?)?;
+        let Some(change) = path_change(repo, &commit, &current)? else {
+            continue;
+        };
+        if skip > 0 {
+            skip -= 1;
+        } else {
+            commits.push(PathCommit {
+                summary: summarize(&commit),
+                path: current.clone(),
+                renamed_from: change.clone(),
+            });
+            // Take one extra commit to find out whether there is a next page.
+            if commits.len() > per_page {
+                break;
+            }
+        }
+        if let Some(old_path) = change {
+            current = old_path;
+        }
+    }
+    let has_next = commits.len() > per_page;
+    commits.truncate(per_page);
+
+    Ok(PathCommitPage {
+        path: path.to_string(),
+        commits,
+        page,
+        per_page,
+        has_previous: page > 1,
+        has_next,
+    })
+}
+
+/// Checks whether `commit` changed `path`. Returns `None` if it did not, and
+/// otherwise the path the file was renamed from, if it was.
+#[allow(clippy::option_option)]
+fn path_change(
+    repo: &Repository,
+    commit: &Commit,
+    path: &str,
+) -> Result<Option<Option<String>>, RepoHistoryError> {
+    let tree = commit.tree()?;
+    let entry = entry_id(&tree, path);
+    let parent_trees = commit
+        .parents()
+        .map(|parent| parent.tree())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if parent_trees.is_empty() {
+        return Ok(entry.map(|_| None));
+    }
+    if parent_trees.iter().any(|parent| entry_id(parent, path) == entry) {
+        return Ok(None);
+    }
+
+    // A file that is new compared to the first parent may have been renamed.
+    let is_file = tree
+        .get_path(Path::new(path))
+        .is_ok_and(|e| e.kind() == Some(git2::ObjectType::Blob));
+    if !is_file || entry_id(&parent_trees[0], path).is_some() {
+        return Ok(Some(None));
+    }
+    let mut diff = repo.diff_tree_to_tree(Some(&parent_trees[0]), Some(&tree), None)?;
+    diff.find_similar(Some(DiffFindOptions::new().renames(true)))?;
+    let renamed_from = diff
+        .deltas()
+        .find(|delta| {
+            delta.status() == Delta::Renamed && delta.new_file().path() == Some(Path::new(path))
+        })
+        .and_then(|delta| delta.old_file().path().map(|p| p.to_string_lossy().to_string()));
+    Ok(Some(renamed_from))
+}
+
+/// The id of the object at `path` in `tree`; the tree itself for the root.
+fn entry_id(tree: &Tree, path: &str) -> Option<Oid> {
+    if path.is_empty() {
+        return Some(tree.id());
+    }
+    tree.get_path(Path::new(path)).ok().map(|entry| entry.id())
+}
+
+/// Summarizes a commit for history listings.
+#[must_use]
+pub fn summarize(commit: &Commit) -> CommitSummary {
+    let oid = commit.id().to_string();
+    let author = commit.author();
+    CommitSummary {
+        short_oid: oid.chars().take(7).collect(),
+        oid,
+        author_name: author.name().unwrap_or_default().to_string(),
+        author_email: author.email().unwrap_or_default().to_string(),
+        date: format_time(&author.when()),
+        timestamp: author.when().seconds(),
+        summary: commit.summary().unwrap_or_default().to_string(),
+    }
+}
+
+/// Formats a git timestamp in the author's own timezone.
+#[must_use]
+pub fn format_time(time: &git2::Time) -> String {
+    to_datetime(time).map_or_else(
+        || time.seconds().to_string(),
+        |dt| dt.format("%Y-%m-%d %H:%M:%S %z").to_string(),
+    )
+}
+
+fn to_datetime(time: &git2::Time) -> Option<DateTime<FixedOffset>> {
+    let offset = FixedOffset::east_opt(time.offset_minutes() * 60)?;
+    offset.timestamp_opt(time.seconds(), 0).single()
+}
+
+/// Describes how long before `now` the unix timestamp `timestamp` was, e.g.
+/// `3 days ago`. Timestamps in the future read as `just now`.
+#[must_use]
+pub fn relative_time(timestamp: i64, now: i64) -> String {
+    const UNITS: &[(i64, &str)] = &[
+        (365 * 24 * 3600, "year"),
+        (30 * 24 * 3600, "month"),
+        (7 * 24 * 3600, "week"),
+        (24 * 3600, "day"),
+        (3600, "hour"),
+        (60, "minute"),
+    ];
+
+    let elapsed = now - timestamp;
+    UNITS
+        .iter()
+        .find(|(seconds, _)| elapsed >= *seconds)
+        .map_or_else(
+            || "just now".to_string(),
+            |(seconds, unit)| {
+                let count = elapsed / seconds;
+                if count == 1 {
+                    format!("1 {} ago", unit)
+                } else {
+                    format!("{} {}s ago", count, unit)
+                }
+            },
+        )
+}
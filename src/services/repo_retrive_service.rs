@@ -1,11 +1,13 @@
 
-use git2::{Repository, ObjectType};
+use git2::{BranchType, Commit, ErrorCode, ObjectType, Oid, Repository, TreeWalkMode, TreeWalkResult};
 use loco_rs::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::{
     path::{Path as StdPath},
 };
 
+use crate::services::{highlight_service::HighlightedFile, markdown_service::Readme};
+
 // Request/Response structures
 #[derive(Debug, Deserialize)]
 pub struct RepoParams {
@@ -14,8 +16,38 @@ pub struct RepoParams {
 
 #[derive(Debug, Deserialize)]
 pub struct FileContentParams {
-    pub repo_id: String,
-    pub file_path: String,
+    /// The branch, tag or commit to read from; the default branch when missing.
+    #[serde(rename = "ref")]
+    pub reference: Option<String>,
+    pub path: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TreeParams {
+    /// The branch, tag or commit to read from; the default branch when missing.
+    #[serde(rename = "ref")]
+    pub reference: Option<String>,
+    pub path: Option<String>,
+}
+
+/// Limits for serving file contents.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FileLimits {
+    /// Largest blob, in bytes, whose content is returned.
+    pub max_content_bytes: u64,
+    /// Largest file, in bytes, that is syntax highlighted; larger files are
+    /// shown as plain text.
+    pub max_highlight_bytes: u64,
+}
+
+impl Default for FileLimits {
+    fn default() -> Self {
+        Self {
+            max_content_bytes: 1024 * 1024,
+            max_highlight_bytes: 512 * 1024,
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -34,6 +66,13 @@ pub struct RepoStructure {
 pub struct RepoResponse {
     pub id: String,
     pub name: String,
+    /// The branch, tag or commit being browsed.
+    pub reference: String,
+    /// The directory being browsed, relative to the repository root.
+    pub path: String,
+    pub refs: RefList,
+    /// The rendered README of the browsed directory, if it has one.
+    pub readme: Option<Readme>,
     pub structure: RepoStructure,
     pub total_files: usize,
     pub total_size: u64,
@@ -43,9 +82,32 @@ pub struct RepoResponse {
 pub struct FileContentResponse {
     pub repo_id: String,
     pub file_path: String,
+    /// The blob id; a file with the same oid always has the same content.
+    pub oid: String,
     pub content: String,
     pub size: u64,
     pub is_binary: bool,
+    /// Set when the file is over the size limit and its content was left out.
+    pub too_large: bool,
+    /// The sanitized HTML of a Markdown file.
+    pub html: Option<String>,
+    /// The lines of a source file as highlighted HTML.
+    pub highlighted: Option<HighlightedFile>,
+}
+
+/// One level of a directory, as served to the explorer.
+#[derive(Debug, Serialize)]
+pub struct TreeEntriesResponse {
+    pub reference: String,
+    pub path: String,
+    pub entries: Vec<RepoStructure>,
+}
+
+/// Branch and tag names of a repository, used by the ref switcher.
+#[derive(Debug, Serialize)]
+pub struct RefList {
+    pub branches: Vec<String>,
+    pub tags: Vec<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -53,65 +115,244 @@ pub struct ErrorResponse {
     pub error: String,
     pub message: String,
 }
-/// Reads the structure of a Git repository at its HEAD commit.
-///
-/// This function retrieves the `HEAD` reference, peels it to a commit,
-/// and then obtains the root tree. It delegates to `read_git_tree_structure`
-/// to build a full directory/file hierarchy.
+/// Reads the structure of a Git repository at its default branch.
 ///
 /// # Arguments
 /// * `repo` - A reference to an open `git2::Repository`.
 ///
 /// # Returns
-/// * `Ok(RepoStructure)` containing the full tree structure under `root`.
-/// * `Err(Error)` if any Git operation (head lookup, commit peel, tree peel) fails.
+/// * `Ok(RepoStructure)` containing the top level of the tree under `root`.
+/// * `Err(Error::NotFound)` if the repository has no commits yet.
+/// * `Err(Error)` if any Git operation (commit peel, tree peel) fails.
 pub fn read_git_repository_structure(repo: &Repository) -> Result<RepoStructure> {
+    let reference = default_ref(repo).ok_or(Error::NotFound)?;
+    let commit = resolve_commit(repo, &reference)?;
+    read_git_structure_at(repo, &commit, "")
+}
 
-    // Get the HEAD commit
-    let head = repo.head()
-        .map_err(|e| -> Error {Error::string(&format!("Failed to get HEAD: {}", e))})?;
+/// Reads one level of the directory at `path` in the tree of `commit`.
+///
+/// # Arguments
+/// * `repo`   - A reference to an open `git2::Repository`.
+/// * `commit` - The commit whose tree is read.
+/// * `path`   - A directory path relative to the repository root; empty for the root.
+///
+/// # Returns
+/// * `Ok(RepoStructure)` for the directory and its direct entries.
+/// * `Err(Error::NotFound)` if `path` does not exist or is not a directory.
+pub fn read_git_structure_at(repo: &Repository, commit: &Commit, path: &str) -> Result<RepoStructure> {
+    let tree = commit.tree()
+        .map_err(|e| Error::string(&format!("Failed to get tree: {}", e)))?;
+
+    let path = path.trim_matches('/');
+    if path.is_empty() {
+        return read_git_tree_structure(repo, &tree, "", "");
+    }
+
+    let entry = tree.get_path(StdPath::new(path)).map_err(|_| Error::NotFound)?;
+    if entry.kind() != Some(ObjectType::Tree) {
+        return Err(Error::NotFound);
+    }
+    let subtree = entry.to_object(repo)
+        .and_then(|object| object.peel_to_tree())
+        .map_err(|e| Error::string(&format!("Failed to peel to tree: {}", e)))?;
+    read_git_tree_structure(repo, &subtree, entry.name().unwrap_or(""), path)
+}
 
-    let commit = head.peel_to_commit()
-        .map_err(|e| -> Error {Error::string(&format!("Failed to get commit: {}", e))})?;
-    
+/// Reads the file at `path` in the tree of `commit`.
+///
+/// # Arguments
+/// * `repo`    - A reference to an open `git2::Repository`.
+/// * `commit`  - The commit whose tree is read.
+/// * `path`    - A file path relative to the repository root.
+/// * `repo_id` - The id of the repository record, echoed in the response.
+/// * `limits`  - The largest file whose content is returned.
+///
+/// # Returns
+/// * `Ok(FileContentResponse)`; binary files and files over the size limit are
+///   returned without content.
+/// * `Err(Error::NotFound)` if `path` does not exist or is not a file.
+pub fn read_git_file_at(
+    repo: &Repository,
+    commit: &Commit,
+    path: &str,
+    repo_id: &str,
+    limits: &FileLimits,
+) -> Result<FileContentResponse> {
+    let path = path.trim_matches('/');
+    let entry = find_blob_entry(commit, path)?;
+
+    let mut response = FileContentResponse {
+        repo_id: repo_id.to_string(),
+        file_path: path.to_string(),
+        oid: entry.id().to_string(),
+        content: String::new(),
+        size: blob_size(repo, entry.id())?,
+        is_binary: false,
+        too_large: false,
+        html: None,
+        highlighted: None,
+    };
+
+    // Check the size from the object header so large blobs are never loaded.
+    if response.size > limits.max_content_bytes {
+        response.too_large = true;
+        return Ok(response);
+    }
+
+    let blob = repo.find_blob(entry.id())
+        .map_err(|e| Error::string(&format!("Failed to get blob: {}", e)))?;
+    response.is_binary = blob.is_binary();
+    if !response.is_binary {
+        response.content = String::from_utf8_lossy(blob.content()).to_string();
+    }
+    Ok(response)
+}
+
+/// Finds the blob at `path` in the tree of `commit`, without loading it.
+///
+/// # Arguments
+/// * `repo`   - A reference to an open `git2::Repository`.
+/// * `commit` - The commit whose tree is read.
+/// * `path`   - A file path relative to the repository root.
+///
+/// # Returns
+/// * `Ok((Oid, u64))` with the id and size of the blob.
+/// * `Err(Error::NotFound)` if `path` does not exist or is not a file.
+pub fn find_git_blob_at(repo: &Repository, commit: &Commit, path: &str) -> Result<(Oid, u64)> {
+    let entry = find_blob_entry(commit, path.trim_matches('/'))?;
+    Ok((entry.id(), blob_size(repo, entry.id())?))
+}
+
+/// Finds the tree entry of the file at `path`, which must be a blob.
+fn find_blob_entry(commit: &Commit, path: &str) -> Result<git2::TreeEntry<'static>> {
     let tree = commit.tree()
-        .map_err(|e| -> Error {Error::string(&format!("Failed to get tree: {}", e))})?;
+        .map_err(|e| Error::string(&format!("Failed to get tree: {}", e)))?;
+    let entry = tree.get_path(StdPath::new(path)).map_err(|_| Error::NotFound)?;
+    if entry.kind() != Some(ObjectType::Blob) {
+        return Err(Error::NotFound);
+    }
+    Ok(entry)
+}
+
+/// Reads the size of a blob from its object header, without loading its content.
+fn blob_size(repo: &Repository, oid: git2::Oid) -> Result<u64> {
+    let (size, _) = repo.odb()
+        .and_then(|odb| odb.read_header(oid))
+        .map_err(|e| Error::string(&format!("Failed to read object header: {}", e)))?;
+    Ok(size as u64)
+}
+
+/// Resolves a branch name, tag name, or full or abbreviated commit id to a commit.
+///
+/// # Arguments
+/// * `repo`     - A reference to an open `git2::Repository`.
+/// * `revision` - Anything `revparse_single` understands.
+///
+/// # Returns
+/// * `Ok(Commit)` the revision points at (tags are peeled).
+/// * `Err(Error::NotFound)` if it does not resolve to a commit.
+pub fn resolve_commit<'r>(repo: &'r Repository, revision: &str) -> Result<Commit<'r>> {
+    repo.revparse_single(revision)
+        .and_then(|object| object.peel_to_commit())
+        .map_err(|_| Error::NotFound)
+}
+
+/// Splits `<ref>/<path>` into the revision and the path inside it.
+///
+/// Branch names may contain slashes themselves, so the longest prefix that
+/// resolves to a commit wins.
+///
+/// # Arguments
+/// * `repo` - A reference to an open `git2::Repository`.
+/// * `spec` - The part of the URL after `/tree/` or `/blob/`.
+///
+/// # Returns
+/// * `Ok((revision, path))`, where `path` may be empty.
+/// * `Err(Error::NotFound)` if no prefix of `spec` is a revision.
+pub fn split_ref_path(repo: &Repository, spec: &str) -> Result<(String, String)> {
+    let segments: Vec<&str> = spec.trim_matches('/').split('/').collect();
+    (1..=segments.len())
+        .rev()
+        .map(|i| (segments[..i].join("/"), segments[i..].join("/")))
+        .find(|(revision, _)| resolve_commit(repo, revision).is_ok())
+        .ok_or(Error::NotFound)
+}
+
+/// Picks the revision to show when none is given.
+///
+/// # Arguments
+/// * `repo` - A reference to an open `git2::Repository`.
+///
+/// # Returns
+/// * The branch HEAD points at, when it exists.
+/// * Otherwise the first local branch, for repositories whose HEAD points at a
+///   branch that was never pushed.
+/// * `None` if the repository has no branches at all.
+pub fn default_ref(repo: &Repository) -> Option<String> {
+    match repo.head() {
+        Ok(head) => return head.shorthand().map(str::to_string),
+        Err(e) if e.code() != ErrorCode::UnbornBranch && e.code() != ErrorCode::NotFound => return None,
+        Err(_) => {}
+    }
+    list_refs(repo).ok()?.branches.into_iter().next()
+}
 
-    // Build structure from the root tree
-    read_git_tree_structure(repo, &tree, "", "")
+/// Lists the local branches and tags of a repository, sorted by name.
+///
+/// # Arguments
+/// * `repo` - A reference to an open `git2::Repository`.
+///
+/// # Returns
+/// * `Ok(RefList)` with the short names of all branches and tags.
+/// * `Err(Error)` if the references cannot be read.
+pub fn list_refs(repo: &Repository) -> Result<RefList> {
+    let mut branches = Vec::new();
+    for branch in repo.branches(Some(BranchType::Local))
+        .map_err(|e| Error::string(&format!("Failed to list branches: {}", e)))?
+    {
+        let (branch, _) = branch.map_err(|e| Error::string(&format!("Failed to read branch: {}", e)))?;
+        if let Ok(Some(name)) = branch.name() {
+            branches.push(name.to_string());
+        }
+    }
+    branches.sort();
 
+    let mut tags: Vec<String> = repo.tag_names(None)
+        .map_err(|e| Error::string(&format!("Failed to list tags: {}", e)))?
+        .iter()
+        .flatten()
+        .map(str::to_string)
+        .collect();
+    tags.sort();
+
+    Ok(RefList { branches, tags })
 }
 
-/// Recursively traverses a Git tree to build a nested `RepoStructure`.
+/// Lists one level of a Git tree as a `RepoStructure`.
 ///
 /// # Arguments
 /// * `repo`  - A reference to the `git2::Repository`.
-/// * `tree`  - The current `git2::Tree` node to process.
+/// * `tree`  - The `git2::Tree` to list.
 /// * `name`  - The name of this tree entry (empty for root).
 /// * `path`  - The full path from the repository root to this entry.
 ///
 /// # Returns
-/// * `Ok(RepoStructure)` representing this directory or file and its children.
-/// * `Err(Error)` if any object lookup or type peel fails.
-///
-/// This function:
-/// 1. Iterates entries in `tree`.
-/// 2. Skips ignored names (via `should_ignore_git_entry`).
-/// 3. For subtrees, recurses and accumulates size and children.
-/// 4. For blobs, reads size, determines binary/text, and captures content+metadata.
-/// 5. Sorts children alphabetically before returning.
+/// * `Ok(RepoStructure)` for this directory, whose children are its direct entries.
+/// * `Err(Error)` if an object header cannot be read.
+///
+/// Subdirectories are returned without children and file contents are never
+/// read, so the cost does not depend on the size of the repository: the
+/// explorer fetches deeper levels and file contents on demand. Entries are
+/// sorted with directories first, then by name.
 pub fn read_git_tree_structure(
     repo: &Repository,
     tree: &git2::Tree,
     name: &str,
     path: &str,
-
 ) -> Result<RepoStructure> {
-
     let mut children = Vec::new();
-    let mut total_size = 0u64;
 
-    // Iterate through tree entries
     for entry in tree.iter() {
         let entry_name = entry.name().unwrap_or("");
         let entry_path = if path.is_empty() {
@@ -125,75 +366,73 @@ pub fn read_git_tree_structure(
             continue;
         }
 
-        match entry.kind() {
-            Some(ObjectType::Tree) => {
-
-                // It's a directory
-                let subtree = entry.to_object(repo)
-                    .map_err(|e| Error::string(&format!("Failed to get tree object: {}", e)))?
-                    .peel_to_tree()
-                    .map_err(|e| Error::string(&format!("Failed to peel to tree: {}", e)))?;
-                let child_structure = read_git_tree_structure(repo, &subtree, entry_name, &entry_path)?;
-                total_size += child_structure.size.unwrap_or(0);
-                children.push(child_structure);
-            }
-
-            Some(ObjectType::Blob) => {
-                // It's a file
-                let blob = entry.to_object(repo)
-                    .map_err(|e| Error::string(&format!("Failed to get blob object: {}", e)))?
-                    .peel_to_blob()
-                    .map_err(|e| Error::string(&format!("Failed to peel to blob: {}", e)))?;
-
-                let file_size = blob.size() as u64;
-                total_size += file_size;
-
-                // Handle file content
-                let is_binary = blob.is_binary();
-                let file_content = if is_binary {
-                    None // Skip binary files
-                } else {
-                    String::from_utf8(blob.content().to_vec()).ok()
-                };
-                let sanitized_id = path.replace("/", "_").replace("\\", "_");
-                children.push(RepoStructure {
-                    name: entry_name.to_string(),
-                    path: entry_path,
-                    id: sanitized_id,
-                    is_file: true,
-                    size: Some(file_size),
-                    children: Vec::new(),
-                    extension: get_file_extension_from_name(entry_name),
-                    content: file_content,
-                });
-            }
-
-            _ => {
-                // Skip other object types (commits, tags)
-                continue;
-            }
-
-        }
+        let is_file = match entry.kind() {
+            Some(ObjectType::Tree) => false,
+            Some(ObjectType::Blob) => true,
+            // Skip other object types (submodule commits)
+            _ => continue,
+        };
 
+        children.push(RepoStructure {
+            name: entry_name.to_string(),
+            id: entry_path.replace(['/', '\\'], "_"),
+            path: entry_path,
+            is_file,
+            size: if is_file { Some(blob_size(repo, entry.id())?) } else { None },
+            children: Vec::new(),
+            extension: if is_file { get_file_extension_from_name(entry_name) } else { None },
+            content: None,
+        });
     }
-    let sanitized_id = path.replace("/", "_").replace("\\", "_");
 
-    // Sort children by name
-    children.sort_by(|a, b| a.name.cmp(&b.name));
+    children.sort_by(|a, b| a.is_file.cmp(&b.is_file).then_with(|| a.name.cmp(&b.name)));
     let structure_name = if name.is_empty() { "root" } else { name };
 
     Ok(RepoStructure {
         name: structure_name.to_string(),
         path: path.to_string(),
-        id: sanitized_id,
+        id: path.replace(['/', '\\'], "_"),
         is_file: false,
-        size: Some(total_size),
+        size: Some(children.iter().filter_map(|c| c.size).sum()),
         children,
         extension: None,
         content: None,
-
     })
+}
+
+/// A file found by [`walk_files`].
+#[derive(Debug, Clone)]
+pub struct TreeFile {
+    /// The path relative to the repository root.
+    pub path: String,
+    pub oid: Oid,
+}
 
+/// Lists every file in `tree` and its subdirectories, leaving out the entries
+/// the explorer hides, in the order of a depth-first walk.
+///
+/// # Arguments
+/// * `tree` - The root tree to walk.
+///
+/// # Returns
+/// * `Ok(Vec<TreeFile>)` with the path and blob id of each file.
+/// * `Err(git2::Error)` if a tree cannot be read.
+pub fn walk_files(tree: &git2::Tree) -> std::result::Result<Vec<TreeFile>, git2::Error> {
+    let mut files = Vec::new();
+    tree.walk(TreeWalkMode::PreOrder, |root, entry| {
+        let name = entry.name().unwrap_or_default();
+        if should_ignore_git_entry(name) {
+            return TreeWalkResult::Skip;
+        }
+        if entry.kind() == Some(ObjectType::Blob) {
+            files.push(TreeFile {
+                path: format!("{}{}", root, name),
+                oid: entry.id(),
+            });
+        }
+        TreeWalkResult::Ok
+    })?;
+    Ok(files)
 }
 
 /// Determines if a Git tree entry should be ignored.
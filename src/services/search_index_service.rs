@@ -0,0 +1,523 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    io::{Read, Write},
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock, Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant, SystemTime},
+};
+
+use git2::{Delta, Oid, Repository, Tree};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use tempfile::NamedTempFile;
+use thiserror::Error;
+
+use crate::services::{
+    highlight_service::language_for,
+    repo_retrive_service::{default_ref, should_ignore_git_entry, walk_files},
+    search_service::{search_lines, FileMatches, SearchSettings},
+};
+
+/// Parsed indexes by file, with the modification time and size they were read
+/// at. Global searches read the index of every repository, which is far too
+/// slow to parse again each time.
+static INDEX_CACHE: LazyLock<Mutex<HashMap<PathBuf, CachedIndex>>> =
+    LazyLock::new(Mutex::default);
+
+struct CachedIndex {
+    modified: SystemTime,
+    len: u64,
+    index: Arc<RepoIndex>,
+}
+
+/// Represents a custom error for building and querying the search index.
+#[derive(Debug, Error)]
+pub enum SearchIndexError {
+    #[error("Invalid search: {0}")]
+    InvalidQuery(String),
+    #[error("Git error: {0}")]
+    GitError(#[from] git2::Error),
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Invalid index file: {0}")]
+    InvalidIndex(#[from] serde_json::Error),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexedFile {
+    pub path: String,
+    /// The blob the content was indexed from.
+    pub oid: String,
+    pub language: Option<String>,
+}
+
+/// The search index of the default branch of one repository.
+///
+/// Every file is broken into trigrams, runs of three bytes compared without
+/// ASCII case. A file can only contain a text if it contains all of the
+/// text's trigrams, so the postings narrow a search down to a few candidate
+/// files, which are then searched for real.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RepoIndex {
+    /// The commit the index was built from, `None` for empty repositories.
+    pub commit: Option<String>,
+    /// Indexed files by id. Ids of removed files are left empty and reused.
+    pub files: Vec<Option<IndexedFile>>,
+    /// The ids of the files containing each trigram, sorted.
+    pub trigrams: HashMap<u32, Vec<u32>>,
+}
+
+/// What an index update did.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct IndexUpdate {
+    pub added: usize,
+    pub removed: usize,
+    /// Set when the whole index was rebuilt rather than updated.
+    pub rebuilt: bool,
+}
+
+/// A search across the indexed repositories.
+#[derive(Debug, Clone, Default)]
+pub struct IndexQuery {
+    /// The text to find, matched without case.
+    pub text: String,
+    /// Only searches files matching this glob. Globs without a `/` match the
+    /// file name, others the whole path; `*` stays within a directory and
+    /// `**` crosses them.
+    pub path_glob: Option<String>,
+    /// Only searches files in this language, e.g. `Rust`.
+    pub language: Option<String>,
+}
+
+/// A repository to search, with the id its index is stored under.
+#[derive(Debug, Clone)]
+pub struct IndexedRepo {
+    pub id: i32,
+    pub name: String,
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RepoFileMatches {
+    pub repo_id: i32,
+    pub repo_name: String,
+    /// The commit the index was built from, which the line numbers refer to.
+    pub reference: String,
+    #[serde(flatten)]
+    pub matches: FileMatches,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct IndexResults {
+    pub files: Vec<RepoFileMatches>,
+    pub total_matches: usize,
+    /// Set when the match limit was reached.
+    pub truncated: bool,
+    /// Set when the search ran out of time; some repositories were not searched.
+    pub timed_out: bool,
+}
+
+/// The file the index of repository `repo_id` is stored in.
+#[must_use]
+pub fn index_path(index_dir: &Path, repo_id: i32) -> PathBuf {
+    index_dir.join(format!("{}.json", repo_id))
+}
+
+/// Reads the stored index of a repository; a missing index is empty.
+///
+/// # Errors
+/// Returns `SearchIndexError::IoError` or `SearchIndexError::InvalidIndex` if
+/// the index exists but cannot be read.
+pub fn load_index(index_dir: &Path, repo_id: i32) -> Result<RepoIndex, SearchIndexError> {
+    match fs::read(index_path(index_dir, repo_id)) {
+        Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(RepoIndex::default()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Like [`load_index`], but keeps the parsed index in memory and only reads
+/// the file again once it was replaced.
+///
+/// # Errors
+/// Returns `SearchIndexError::IoError` or `SearchIndexError::InvalidIndex` if
+/// the index exists but cannot be read.
+pub fn cached_index(index_dir: &Path, repo_id: i32) -> Result<Arc<RepoIndex>, SearchIndexError> {
+    let path = index_path(index_dir, repo_id);
+    let mut file = match fs::File::open(&path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            lock_cache().remove(&path);
+            return Ok(Arc::default());
+        }
+        Err(e) => return Err(e.into()),
+    };
+    // The metadata of the open file, so it describes what is read below even
+    // if the index is replaced in between.
+    let metadata = file.metadata()?;
+    let (modified, len) = (metadata.modified()?, metadata.len());
+    if let Some(cached) = lock_cache().get(&path) {
+        if cached.modified == modified && cached.len == len {
+            return Ok(cached.index.clone());
+        }
+    }
+
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+    let index: Arc<RepoIndex> = Arc::new(serde_json::from_slice(&bytes)?);
+    lock_cache().insert(
+        path,
+        CachedIndex {
+            modified,
+            len,
+            index: index.clone(),
+        },
+    );
+    Ok(index)
+}
+
+fn lock_cache() -> MutexGuard<'static, HashMap<PathBuf, CachedIndex>> {
+    // The cache holds no invariants a panic could break.
+    INDEX_CACHE.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Deletes the index of a repository, if it has one.
+///
+/// # Errors
+/// Returns `SearchIndexError::IoError` if the index cannot be removed.
+pub fn remove_index(index_dir: &Path, repo_id: i32) -> Result<(), SearchIndexError> {
+    let path = index_path(index_dir, repo_id);
+    lock_cache().remove(&path);
+    match fs::remove_file(&path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// The commit the default branch points at, which is what gets indexed.
+fn default_branch_commit(repo: &Repository) -> Option<git2::Commit<'_>> {
+    default_ref(repo)
+        .and_then(|branch| repo.revparse_single(&format!("refs/heads/{}", branch)).ok())
+        .and_then(|object| object.peel_to_commit().ok())
+}
+
+/// Reads the commit the default branch of the repository at `repo_path`
+/// points at. A push only needs the index updated when this changes.
+#[must_use]
+pub fn default_branch_head(repo_path: &Path) -> Option<Oid> {
+    let repo = Repository::open_bare(repo_path).ok()?;
+    let head = default_branch_commit(&repo)?;
+    Some(head.id())
+}
+
+/// Brings the index of a repository up to date with its default branch.
+///
+/// Only the files that changed since the indexed commit are read again. The
+/// index is rebuilt from scratch when there is no index yet or its commit is
+/// gone, e.g. after a force push. Binary files and files larger than
+/// `settings.max_file_bytes` are left out.
+///
+/// # Errors
+/// Returns a `SearchIndexError` if the repository cannot be read or the index
+/// cannot be written.
+pub fn update_index(
+    index_dir: &Path,
+    repo_id: i32,
+    repo: &Repository,
+    settings: &SearchSettings,
+) -> Result<IndexUpdate, SearchIndexError> {
+    let mut index = load_index(index_dir, repo_id)?;
+    let head = default_branch_commit(repo);
+    let head_oid = head.as_ref().map(|commit| commit.id().to_string());
+    if index.commit == head_oid && index_path(index_dir, repo_id).exists() {
+        return Ok(IndexUpdate::default());
+    }
+
+    let new_tree = head.as_ref().map(git2::Commit::tree).transpose()?;
+    let old_tree = index
+        .commit
+        .as_deref()
+        .and_then(|oid| Oid::from_str(oid).ok())
+        .and_then(|oid| repo.find_commit(oid).ok())
+        .and_then(|commit| commit.tree().ok());
+
+    let mut update = IndexUpdate::default();
+    let (removed, added) = match (&old_tree, &new_tree) {
+        (Some(old), new) => changed_paths(repo, old, new.as_ref())?,
+        (None, new) => {
+            update.rebuilt = true;
+            let removed = index.files.iter().flatten().map(|f| f.path.clone()).collect();
+            let added = match new {
+                Some(tree) => walk_files(tree)?.into_iter().map(|f| f.path).collect(),
+                None => Vec::new(),
+            };
+            (removed, added)
+        }
+    };
+
+    update.removed = remove_files(&mut index, &removed);
+    if let Some(tree) = &new_tree {
+        let odb = repo.odb()?;
+        for path in added {
+            // Submodules show up in diffs but have no content here.
+            let Some(entry) = tree
+                .get_path(Path::new(&path))
+                .ok()
+                .filter(|entry| entry.kind() == Some(git2::ObjectType::Blob))
+            else {
+                continue;
+            };
+            let (size, _) = odb.read_header(entry.id())?;
+            if size as u64 > settings.max_file_bytes {
+                continue;
+            }
+            let blob = repo.find_blob(entry.id())?;
+            if blob.is_binary() {
+                continue;
+            }
+            let content = String::from_utf8_lossy(blob.content());
+            add_file(
+                &mut index,
+                IndexedFile {
+                    language: language_for(&path, &content).map(str::to_string),
+                    oid: entry.id().to_string(),
+                    path,
+                },
+                blob.content(),
+            );
+            update.added += 1;
+        }
+    }
+    index.commit = head_oid;
+
+    fs::create_dir_all(index_dir)?;
+    // Replace the index in one step, so searches never read a partial file.
+    // The temporary file is unique, as the worker and a task started by an
+    // SSH push may update the same repository at once.
+    let mut partial = NamedTempFile::new_in(index_dir)?;
+    partial.write_all(&serde_json::to_vec(&index)?)?;
+    // Temporary files are private; the index is read by whichever user runs the server.
+    partial.as_file().set_permissions(fs::Permissions::from_mode(0o644))?;
+    partial
+        .persist(index_path(index_dir, repo_id))
+        .map_err(|e| e.error)?;
+    Ok(update)
+}
+
+/// The paths that were removed and added between two trees, a modified file
+/// counting as both. Only paths the explorer shows are included.
+fn changed_paths(
+    repo: &Repository,
+    old: &Tree,
+    new: Option<&Tree>,
+) -> Result<(Vec<String>, Vec<String>), SearchIndexError> {
+    let diff = repo.diff_tree_to_tree(Some(old), new, None)?;
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+    for delta in diff.deltas() {
+        let old_path = delta.old_file().path().map(|p| p.to_string_lossy().to_string());
+        let new_path = delta.new_file().path().map(|p| p.to_string_lossy().to_string());
+        if !matches!(delta.status(), Delta::Added) {
+            removed.extend(old_path.filter(|p| is_indexed_path(p)));
+        }
+        if !matches!(delta.status(), Delta::Deleted) {
+            added.extend(new_path.filter(|p| is_indexed_path(p)));
+        }
+    }
+    Ok((removed, added))
+}
+
+fn is_indexed_path(path: &str) -> bool {
+    !path.split('/').any(should_ignore_git_entry)
+}
+
+/// Removes the files at `paths` from the index, returning how many were indexed.
+fn remove_files(index: &mut RepoIndex, paths: &[String]) -> usize {
+    let paths: BTreeSet<&str> = paths.iter().map(String::as_str).collect();
+    let mut ids = BTreeSet::new();
+    for (id, slot) in index.files.iter_mut().enumerate() {
+        if slot.as_ref().is_some_and(|file| paths.contains(file.path.as_str())) {
+            *slot = None;
+            ids.insert(u32::try_from(id).unwrap_or(u32::MAX));
+        }
+    }
+    if !ids.is_empty() {
+        index.trigrams.retain(|_, postings| {
+            postings.retain(|id| !ids.contains(id));
+            !postings.is_empty()
+        });
+    }
+    ids.len()
+}
+
+fn add_file(index: &mut RepoIndex, file: IndexedFile, content: &[u8]) {
+    let id = match index.files.iter().position(Option::is_none) {
+        Some(free) => {
+            index.files[free] = Some(file);
+            free
+        }
+        None => {
+            index.files.push(Some(file));
+            index.files.len() - 1
+        }
+    };
+    let id = u32::try_from(id).unwrap_or(u32::MAX);
+    for trigram in trigrams(content) {
+        let postings = index.trigrams.entry(trigram).or_default();
+        if let Err(position) = postings.binary_search(&id) {
+            postings.insert(position, id);
+        }
+    }
+}
+
+/// The distinct trigrams of `bytes`, ignoring ASCII case.
+fn trigrams(bytes: &[u8]) -> BTreeSet<u32> {
+    bytes
+        .windows(3)
+        .map(|w| {
+            u32::from(w[0].to_ascii_lowercase()) << 16
+                | u32::from(w[1].to_ascii_lowercase()) << 8
+                | u32::from(w[2].to_ascii_lowercase())
+        })
+        .collect()
+}
+
+/// The ids of the files in `index` that may contain `text`.
+fn candidates(index: &RepoIndex, text: &str) -> Vec<u32> {
+    let wanted = trigrams(text.as_bytes());
+    let all = || {
+        (0..index.files.len())
+            .filter(|&id| index.files[id].is_some())
+            .filter_map(|id| u32::try_from(id).ok())
+            .collect()
+    };
+    if wanted.is_empty() {
+        return all();
+    }
+
+    let mut lists: Vec<&Vec<u32>> = Vec::with_capacity(wanted.len());
+    for trigram in &wanted {
+        match index.trigrams.get(trigram) {
+            Some(postings) => lists.push(postings),
+            None => return Vec::new(),
+        }
+    }
+    // Intersect starting from the rarest trigram.
+    lists.sort_by_key(|postings| postings.len());
+    let mut result = lists[0].clone();
+    for postings in &lists[1..] {
+        result.retain(|id| postings.binary_search(id).is_ok());
+    }
+    result
+}
+
+/// Turns a path glob into a regular expression, see [`IndexQuery::path_glob`].
+///
+/// # Errors
+/// Returns `SearchIndexError::InvalidQuery` if the glob cannot be compiled.
+pub fn glob_to_regex(glob: &str) -> Result<Regex, SearchIndexError> {
+    let mut pattern = String::from(if glob.contains('/') { "^" } else { "(^|/)" });
+    let mut chars = glob.trim_start_matches('/').chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                // `**/` also matches no directory at all.
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    pattern.push_str("(.*/)?");
+                } else {
+                    pattern.push_str(".*");
+                }
+            }
+            '*' => pattern.push_str("[^/]*"),
+            '?' => pattern.push_str("[^/]"),
+            c => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    pattern.push('$');
+    Regex::new(&pattern).map_err(|e| SearchIndexError::InvalidQuery(e.to_string()))
+}
+
+/// Searches the indexes of `repos` for lines containing `query.text`.
+///
+/// Candidate files come from the index, kept in memory between searches, and
+/// are then searched in the blob they were indexed from. The search stops once `settings.max_matches` lines
+/// matched or `settings.timeout_ms` passed.
+///
+/// # Errors
+/// Returns `SearchIndexError::InvalidQuery` for an empty text or an invalid
+/// glob, and other errors if an index or repository cannot be read.
+pub fn search_index(
+    index_dir: &Path,
+    repos: &[IndexedRepo],
+    query: &IndexQuery,
+    settings: &SearchSettings,
+) -> Result<IndexResults, SearchIndexError> {
+    let text = query.text.trim();
+    if text.is_empty() {
+        return Err(SearchIndexError::InvalidQuery("the search is empty".to_string()));
+    }
+    let regex = RegexBuilder::new(&regex::escape(text))
+        .case_insensitive(true)
+        .build()
+        .map_err(|e| SearchIndexError::InvalidQuery(e.to_string()))?;
+    let glob = query.path_glob.as_deref().map(str::trim).filter(|g| !g.is_empty());
+    let glob = glob.map(glob_to_regex).transpose()?;
+    let language = query.language.as_deref().map(str::trim).filter(|l| !l.is_empty());
+    let deadline = Instant::now() + Duration::from_millis(settings.timeout_ms);
+
+    let mut results = IndexResults::default();
+    'repos: for indexed_repo in repos {
+        let index = cached_index(index_dir, indexed_repo.id)?;
+        let files: Vec<&IndexedFile> = candidates(&index, text)
+            .into_iter()
+            .filter_map(|id| index.files.get(id as usize).and_then(Option::as_ref))
+            .filter(|file| glob.as_ref().is_none_or(|glob| glob.is_match(&file.path)))
+            .filter(|file| {
+                language.is_none_or(|language| {
+                    file.language.as_deref().is_some_and(|l| l.eq_ignore_ascii_case(language))
+                })
+            })
+            .collect();
+        if files.is_empty() {
+            continue;
+        }
+
+        let repo = Repository::open_bare(&indexed_repo.path)?;
+        let reference = index.commit.clone().unwrap_or_default();
+        for file in files {
+            if Instant::now() > deadline {
+                results.timed_out = true;
+                break 'repos;
+            }
+            // The blob may be gone if the repository was rewritten since.
+            let Ok(blob) = Oid::from_str(&file.oid).and_then(|oid| repo.find_blob(oid)) else {
+                continue;
+            };
+            let content = String::from_utf8_lossy(blob.content());
+            let remaining = settings.max_matches - results.total_matches;
+            let Some(matches) = search_lines(&regex, &content, settings.context_lines, remaining)
+            else {
+                continue;
+            };
+            results.total_matches += matches.match_count;
+            results.files.push(RepoFileMatches {
+                repo_id: indexed_repo.id,
+                repo_name: indexed_repo.name.clone(),
+                reference: reference.clone(),
+                matches: FileMatches {
+                    path: file.path.clone(),
+                    ..matches
+                },
+            });
+            if results.total_matches >= settings.max_matches {
+                results.truncated = true;
+                break 'repos;
+            }
+        }
+    }
+    Ok(results)
+}
@@ -0,0 +1,328 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use git2::{Commit, Repository};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::services::repo_retrive_service::walk_files;
+
+/// Longest line, in characters, shown in search results; minified files
+/// would otherwise fill the page.
+const MAX_LINE_CHARS: usize = 500;
+
+/// Represents a custom error for searching a repository.
+#[derive(Debug, Error)]
+pub enum SearchError {
+    #[error("Invalid search pattern: {0}")]
+    InvalidPattern(String),
+    #[error("Git error: {0}")]
+    GitError(#[from] git2::Error),
+}
+
+/// Limits for searching the files of a repository.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchSettings {
+    /// Files larger than this, in bytes, are skipped.
+    pub max_file_bytes: u64,
+    /// Searches are stopped after this many milliseconds and return what was
+    /// found so far.
+    pub timeout_ms: u64,
+    /// Most matching lines returned by a search.
+    pub max_matches: usize,
+    /// Lines shown before and after every matching line.
+    pub context_lines: usize,
+    /// Most files returned by the file finder.
+    pub max_files: usize,
+    /// Where the instance-wide search index is kept, one file per repository.
+    pub index_dir: PathBuf,
+}
+
+fn default_index_dir() -> PathBuf {
+    let base = Path::new(env!("REPO_BASE_PATH"));
+    base.parent().unwrap_or(base).join("search-index")
+}
+
+impl Default for SearchSettings {
+    fn default() -> Self {
+        Self {
+            max_file_bytes: 1024 * 1024,
+            timeout_ms: 5_000,
+            max_matches: 200,
+            context_lines: 2,
+            max_files: 50,
+            index_dir: default_index_dir(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchMode {
+    #[default]
+    Literal,
+    Regex,
+}
+
+/// What to search for.
+#[derive(Debug, Clone)]
+pub struct SearchQuery {
+    pub pattern: String,
+    pub mode: SearchMode,
+    pub case_sensitive: bool,
+}
+
+impl SearchQuery {
+    /// Compiles the query into a regular expression.
+    ///
+    /// # Errors
+    /// Returns `SearchError::InvalidPattern` for empty or invalid patterns.
+    pub fn to_regex(&self) -> Result<Regex, SearchError> {
+        if self.pattern.is_empty() {
+            return Err(SearchError::InvalidPattern("the pattern is empty".to_string()));
+        }
+        let pattern = match self.mode {
+            SearchMode::Literal => regex::escape(&self.pattern),
+            SearchMode::Regex => self.pattern.clone(),
+        };
+        RegexBuilder::new(&pattern)
+            .case_insensitive(!self.case_sensitive)
+            .build()
+            .map_err(|e| SearchError::InvalidPattern(e.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchLine {
+    /// The line number, counting from 1.
+    pub number: usize,
+    pub text: String,
+    /// Whether this line matched; the others are context.
+    pub is_match: bool,
+}
+
+/// Consecutive lines of a file: matching lines with the context around them.
+/// Matches close to each other share one chunk.
+#[derive(Debug, Serialize)]
+pub struct SearchChunk {
+    pub lines: Vec<SearchLine>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FileMatches {
+    pub path: String,
+    pub match_count: usize,
+    pub chunks: Vec<SearchChunk>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct SearchResults {
+    pub files: Vec<FileMatches>,
+    pub total_matches: usize,
+    /// Files left out because they are larger than the limit.
+    pub skipped_files: usize,
+    /// Set when the match limit was reached.
+    pub truncated: bool,
+    /// Set when the search ran out of time; files not yet searched are missing.
+    pub timed_out: bool,
+}
+
+/// A file found by the file finder.
+#[derive(Debug, Serialize)]
+pub struct FileHit {
+    pub path: String,
+    pub score: i64,
+    /// The indices of the characters of `path` that matched the query.
+    pub positions: Vec<usize>,
+}
+
+/// The results of either kind of search, for display.
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SearchOutcome {
+    Code(SearchResults),
+    Files { files: Vec<FileHit> },
+}
+
+/// Searches the lines of every text file at `commit`.
+///
+/// Binary files and files over `settings.max_file_bytes` are skipped. The
+/// search stops once `settings.max_matches` lines matched or
+/// `settings.timeout_ms` passed, and flags the results accordingly.
+///
+/// # Errors
+/// Returns `SearchError::InvalidPattern` for patterns that do not compile and
+/// `SearchError::GitError` if the tree cannot be read.
+pub fn search_code(
+    repo: &Repository,
+    commit: &Commit,
+    query: &SearchQuery,
+    settings: &SearchSettings,
+) -> Result<SearchResults, SearchError> {
+    let regex = query.to_regex()?;
+    let deadline = Instant::now() + Duration::from_millis(settings.timeout_ms);
+    let odb = repo.odb()?;
+    let mut results = SearchResults::default();
+
+    for file in walk_files(&commit.tree()?)? {
+        if Instant::now() > deadline {
+            results.timed_out = true;
+            break;
+        }
+        let (size, _) = odb.read_header(file.oid)?;
+        if size as u64 > settings.max_file_bytes {
+            results.skipped_files += 1;
+            continue;
+        }
+        let blob = repo.find_blob(file.oid)?;
+        if blob.is_binary() {
+            continue;
+        }
+
+        let content = String::from_utf8_lossy(blob.content());
+        let remaining = settings.max_matches - results.total_matches;
+        let Some(matches) = search_lines(&regex, &content, settings.context_lines, remaining) else {
+            continue;
+        };
+        results.total_matches += matches.match_count;
+        results.files.push(FileMatches {
+            path: file.path,
+            ..matches
+        });
+        if results.total_matches >= settings.max_matches {
+            results.truncated = true;
+            break;
+        }
+    }
+    Ok(results)
+}
+
+/// Finds the lines of `content` that match `regex`, at most `limit`, with
+/// `context` lines around each. Returns `None` when nothing matched; the path
+/// of the result is left for the caller to fill in.
+#[must_use]
+pub fn search_lines(regex: &Regex, content: &str, context: usize, limit: usize) -> Option<FileMatches> {
+    let lines: Vec<&str> = content.lines().collect();
+    let matching: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| regex.is_match(line))
+        .map(|(index, _)| index)
+        .take(limit)
+        .collect();
+    if matching.is_empty() {
+        return None;
+    }
+
+    let mut chunks: Vec<SearchChunk> = Vec::new();
+    let mut shown_until = 0;
+    for &index in &matching {
+        let start = index.saturating_sub(context);
+        let end = (index + context + 1).min(lines.len());
+        // Continue the previous chunk when the context overlaps or touches it.
+        let start = match chunks.last() {
+            Some(_) if start <= shown_until => shown_until,
+            _ => {
+                chunks.push(SearchChunk { lines: Vec::new() });
+                start
+            }
+        };
+        let chunk = chunks.last_mut()?;
+        for (number, line) in lines.iter().enumerate().take(end).skip(start) {
+            chunk.lines.push(SearchLine {
+                number: number + 1,
+                text: line.chars().take(MAX_LINE_CHARS).collect(),
+                is_match: false,
+            });
+        }
+        if let Some(line) = chunk.lines.iter_mut().find(|line| line.number == index + 1) {
+            line.is_match = true;
+        }
+        shown_until = shown_until.max(end);
+    }
+
+    Some(FileMatches {
+        path: String::new(),
+        match_count: matching.len(),
+        chunks,
+    })
+}
+
+/// Finds the files at `commit` whose path contains the characters of `query`
+/// in order, best matches first.
+///
+/// Matches are ranked higher when the characters are consecutive, start a
+/// path segment or word, or fall in the file name rather than its directory.
+///
+/// # Errors
+/// Returns `SearchError::GitError` if the tree cannot be read.
+pub fn find_files(commit: &Commit, query: &str, limit: usize) -> Result<Vec<FileHit>, SearchError> {
+    let query: Vec<char> = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect();
+
+    let mut hits: Vec<FileHit> = walk_files(&commit.tree()?)?
+        .into_iter()
+        .filter_map(|file| {
+            let (score, positions) = fuzzy_match(&file.path, &query)?;
+            Some(FileHit {
+                path: file.path,
+                score,
+                positions,
+            })
+        })
+        .collect();
+    hits.sort_by(|a, b| {
+        b.score
+            .cmp(&a.score)
+            .then_with(|| a.path.len().cmp(&b.path.len()))
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    hits.truncate(limit);
+    Ok(hits)
+}
+
+/// Scores `path` against the lowercase characters of a query, returning the
+/// score and the matched character indices, or `None` if it does not match.
+///
+/// Characters are matched greedily from the end, so the file name is
+/// preferred over directories that happen to contain the same letters.
+fn fuzzy_match(path: &str, query: &[char]) -> Option<(i64, Vec<usize>)> {
+    let chars: Vec<char> = path.chars().collect();
+    let name_start = chars.iter().rposition(|c| *c == '/').map_or(0, |i| i + 1);
+
+    let mut positions = Vec::with_capacity(query.len());
+    let mut next = chars.len();
+    for wanted in query.iter().rev() {
+        let index = chars[..next]
+            .iter()
+            .rposition(|c| c.to_lowercase().eq(std::iter::once(*wanted)))?;
+        positions.push(index);
+        next = index;
+    }
+    positions.reverse();
+
+    let mut score: i64 = 0;
+    for (i, &index) in positions.iter().enumerate() {
+        score += 1;
+        if i > 0 && positions[i - 1] + 1 == index {
+            score += 5;
+        }
+        let starts_word = index == 0
+            || matches!(chars[index - 1], '/' | '_' | '-' | '.' | ' ')
+            || (chars[index].is_uppercase() && chars[index - 1].is_lowercase());
+        if starts_word {
+            score += 8;
+        }
+        if index >= name_start {
+            score += 2;
+        }
+    }
+    Some((score, positions))
+}
@@ -1,11 +1,75 @@
 use std::fs::{OpenOptions, read_to_string};
 use std::io::Write;
 use std::path::PathBuf;
-use anyhow::{Result, Context};
+use anyhow::{bail, Result, Context};
 use tracing::{event, Level};
 
 use crate::models::sshes::Model;
 
+/// Forced command run by sshd for every key, see `src/bin/gitcrab_serve.rs`.
+const SERVE_COMMAND: &str = "gitcrab-serve";
+/// Options restricting a key to the forced command.
+const KEY_RESTRICTIONS: &str = "no-port-forwarding,no-X11-forwarding,no-agent-forwarding,no-pty";
+/// Key types accepted by `validate_public_key`.
+const KEY_TYPES: [&str; 8] = [
+    "ssh-ed25519",
+    "ssh-rsa",
+    "ssh-dss",
+    "ecdsa-sha2-nistp256",
+    "ecdsa-sha2-nistp384",
+    "ecdsa-sha2-nistp521",
+    "sk-ssh-ed25519@openssh.com",
+    "sk-ecdsa-sha2-nistp256@openssh.com",
+];
+
+/// Checks that `key` is a single OpenSSH public key line (`<type> <base64> [comment]`).
+///
+/// Rejecting anything else matters because the key is written verbatim into
+/// `authorized_keys`: a newline or a leading option list would let a user grant
+/// themselves a shell or bypass the forced command.
+///
+/// # Errors
+/// Returns an `Err` describing why the key was rejected.
+pub fn validate_public_key(key: &str) -> Result<()> {
+    let key = key.trim();
+    if key.contains(['\n', '\r']) {
+        bail!("public key must be a single line");
+    }
+
+    let mut parts = key.split_whitespace();
+    let key_type = parts.next().unwrap_or_default();
+    if !KEY_TYPES.contains(&key_type) {
+        bail!("unsupported key type '{}'", key_type);
+    }
+
+    let body = parts.next().unwrap_or_default();
+    if body.is_empty()
+        || !body
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '/' || c == '=')
+    {
+        bail!("public key data is not valid base64");
+    }
+
+    Ok(())
+}
+
+/// Builds the `authorized_keys` line for a stored key, pinning it to the forced command.
+fn authorized_keys_line(key: &Model) -> String {
+    format!(
+        "command=\"{} --key-id {}\",{} {}\n",
+        SERVE_COMMAND,
+        key.id,
+        KEY_RESTRICTIONS,
+        key.public_key.clone().unwrap_or_default().trim()
+    )
+}
+
+/// The marker identifying the line written for a stored key.
+fn key_marker(key: &Model) -> String {
+    format!("--key-id {}\"", key.id)
+}
+
 
 pub struct SshKeyService {
     authorized_keys_path: PathBuf,
@@ -28,35 +92,36 @@ impl SshKeyService {
     /// Appends a public SSH key to the `authorized_keys` file.
     ///
     /// This will create the file if it does not exist, then open it
-    /// for appending and write the key (plus a newline).
+    /// for appending and write the key prefixed with
+    /// `command="gitcrab-serve --key-id N"`, so every connection made with it
+    /// goes through the per-repository authorization of `gitcrab-serve`.
     ///
     /// # Arguments
-    /// * `key` – A `Model` containing at least the `public_key` string.
+    /// * `key` – A saved `Model` containing at least the `id` and `public_key`.
     ///
     /// # Errors
-    /// Returns an `Err` if the file cannot be opened or written.
+    /// Returns an `Err` if the key is invalid or the file cannot be opened or written.
     pub fn add_key(&self, key: &Model) -> Result<()> {
-        event!(Level::INFO, "something has happened!");
+        validate_public_key(&key.public_key.clone().unwrap_or_default())?;
+        event!(Level::INFO, key_id = key.id, "adding key to authorized_keys");
 
         let mut file = OpenOptions::new()
             .create(true)
             .append(true)
             .open(&self.authorized_keys_path)
             .with_context(|| format!("failed to open {:?}", self.authorized_keys_path))?;
-        let modified_string = key.public_key.clone().unwrap_or_default() + "\n";
-        let bytes = modified_string.as_bytes();
-        file.write_all(bytes)
+        file.write_all(authorized_keys_line(key).as_bytes())
             .context("failed to write ssh key")?;
         Ok(())
     }
 
-    /// Removes all occurrences of the specified public key from `authorized_keys`.
+    /// Removes the line written for the specified key from `authorized_keys`.
     ///
-    /// Reads the entire file, filters out lines containing the key,
-    /// and writes the filtered contents back.
+    /// Reads the entire file, filters out lines carrying the key's
+    /// `--key-id` marker, and writes the filtered contents back.
     ///
     /// # Arguments
-    /// * `key` – A `Model` containing the `public_key` to remove.
+    /// * `key` – The saved `Model` whose line should be removed.
     ///
     /// # Errors
     /// Returns an `Err` if the file cannot be read or the updated content cannot be written.
@@ -64,9 +129,10 @@ impl SshKeyService {
         let contents = read_to_string(&self.authorized_keys_path)
             .with_context(|| "failed to read authorized_keys")?;
 
+        let marker = key_marker(key);
         let mut new_contents = String::new();
         for line in contents.lines() {
-            if !(line.contains(&key.public_key.clone().unwrap_or_default()))
+            if !line.contains(&marker)
             {
                 new_contents.push_str(line);
                 new_contents.push('\n');
@@ -78,6 +144,46 @@ impl SshKeyService {
         Ok(())
     }
 
+    /// Rewrites `authorized_keys` so that it holds exactly the given keys.
+    ///
+    /// Every other line is dropped, notably keys written before they were
+    /// pinned to `gitcrab-serve`, which would otherwise keep unrestricted
+    /// access to every repository through the git shell.
+    ///
+    /// # Arguments
+    /// * `keys` – Every stored key.
+    ///
+    /// # Returns
+    /// The number of lines dropped.
+    ///
+    /// # Errors
+    /// Returns an `Err` if the file cannot be read or written.
+    pub fn sync_keys(&self, keys: &[Model]) -> Result<usize> {
+        let contents = if self.authorized_keys_path.exists() {
+            read_to_string(&self.authorized_keys_path)
+                .with_context(|| "failed to read authorized_keys")?
+        } else {
+            String::new()
+        };
+
+        let mut new_contents = String::new();
+        for key in keys {
+            match validate_public_key(&key.public_key.clone().unwrap_or_default()) {
+                Ok(()) => new_contents.push_str(&authorized_keys_line(key)),
+                Err(err) => event!(Level::WARN, key_id = key.id, "skipping invalid key: {}", err),
+            }
+        }
+        let kept = new_contents.lines().collect::<Vec<_>>();
+        let dropped = contents
+            .lines()
+            .filter(|line| !line.trim().is_empty() && !kept.contains(line))
+            .count();
+
+        std::fs::write(&self.authorized_keys_path, &new_contents)
+            .with_context(|| "failed to write updated authorized_keys")?;
+        Ok(dropped)
+    }
+
     /// Updates an existing SSH key by removing the old entry and adding the new one.
     ///
     /// This is a convenience wrapper that calls `remove_key` then `add_key`.
@@ -0,0 +1,187 @@
+//! Slowing down password guessing and request floods.
+//!
+//! Failed logins are counted per account, in the database, and per client
+//! address, in the application cache. Past a few free attempts every further
+//! failure has to wait twice as long as the previous one, and an account that
+//! keeps failing gets locked for a while. Endpoints that send emails or create
+//! accounts are rate limited per address instead.
+use std::{net::IpAddr, time::Duration};
+
+use chrono::Local;
+use loco_rs::cache::Cache;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// Limits on failed logins.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ThrottleSettings {
+    /// Failed logins of an account allowed before backoff starts.
+    pub account_free_attempts: u32,
+    /// Failed logins from one address, to any account, allowed before backoff starts.
+    pub ip_free_attempts: u32,
+    /// Wait after the first throttled failure, in seconds; it doubles with every failure after it.
+    pub backoff_base_secs: u64,
+    /// Longest wait between two attempts, in seconds.
+    pub max_backoff_secs: u64,
+    /// Failed logins after which an account is locked and its owner notified.
+    pub lockout_threshold: u32,
+    /// How long a locked account stays locked, in seconds.
+    pub lockout_secs: u64,
+    /// Failures from an address are forgotten after it made no attempt for this long, in seconds.
+    pub ip_reset_secs: u64,
+}
+
+impl Default for ThrottleSettings {
+    fn default() -> Self {
+        Self {
+            account_free_attempts: 3,
+            ip_free_attempts: 10,
+            backoff_base_secs: 1,
+            max_backoff_secs: 300,
+            lockout_threshold: 10,
+            lockout_secs: 900,
+            ip_reset_secs: 3600,
+        }
+    }
+}
+
+/// At most `max` requests per `window_secs` seconds.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct RateLimit {
+    pub max: u32,
+    pub window_secs: u64,
+}
+
+/// Rate limits of the endpoints that are cheap to call but expensive to serve.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RateLimitSettings {
+    /// Accounts created per address.
+    pub register: RateLimit,
+    /// Password reset emails requested per address, and per account.
+    pub forgot: RateLimit,
+    /// Magic links requested per address, and per account.
+    pub magic_link: RateLimit,
+}
+
+impl Default for RateLimitSettings {
+    fn default() -> Self {
+        Self {
+            register: RateLimit { max: 10, window_secs: 3600 },
+            forgot: RateLimit { max: 5, window_secs: 3600 },
+            magic_link: RateLimit { max: 5, window_secs: 3600 },
+        }
+    }
+}
+
+/// What a failed login costs an account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Penalty {
+    /// Nothing yet, the next attempt may come right away.
+    None,
+    /// The next attempt has to wait this long.
+    Backoff(Duration),
+    /// The account is locked for this long, and its owner should be told.
+    Lockout(Duration),
+}
+
+/// How long to wait after the `failures`-th consecutive failure, when the
+/// first `free` ones are not throttled.
+#[must_use]
+pub fn backoff(failures: u32, free: u32, settings: &ThrottleSettings) -> Option<Duration> {
+    let throttled = failures.checked_sub(free).filter(|n| *n > 0)?;
+    let factor = 2u64.saturating_pow(throttled - 1);
+    let secs = settings
+        .backoff_base_secs
+        .saturating_mul(factor)
+        .min(settings.max_backoff_secs);
+    Some(Duration::from_secs(secs))
+}
+
+/// The penalty for the `failures`-th consecutive failed login of an account.
+#[must_use]
+pub fn account_penalty(failures: u32, settings: &ThrottleSettings) -> Penalty {
+    if failures >= settings.lockout_threshold {
+        return Penalty::Lockout(Duration::from_secs(settings.lockout_secs));
+    }
+    backoff(failures, settings.account_free_attempts, settings).map_or(Penalty::None, Penalty::Backoff)
+}
+
+/// Failed logins from one address, as kept in the cache.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct IpFailures {
+    failures: u32,
+    /// Unix time before which the address may not try again.
+    blocked_until: i64,
+}
+
+fn ip_key(ip: Option<IpAddr>) -> String {
+    format!("login-failures:{}", ip.map_or_else(|| "unknown".to_string(), |ip| ip.to_string()))
+}
+
+async fn load_ip_failures(cache: &Cache, key: &str) -> IpFailures {
+    match cache.get(key).await {
+        Ok(Some(value)) => serde_json::from_str(&value).unwrap_or_default(),
+        Ok(None) => IpFailures::default(),
+        Err(e) => {
+            warn!("Failed to read login failures from the cache: {}", e);
+            IpFailures::default()
+        }
+    }
+}
+
+/// How long the address has to wait before its next login attempt, if at all.
+pub async fn ip_wait(cache: &Cache, ip: Option<IpAddr>) -> Option<Duration> {
+    let state = load_ip_failures(cache, &ip_key(ip)).await;
+    let remaining = state.blocked_until - Local::now().timestamp();
+    u64::try_from(remaining).ok().filter(|secs| *secs > 0).map(Duration::from_secs)
+}
+
+/// Records a failed login from the address, blocking it for a while once it
+/// failed too often.
+pub async fn record_ip_failure(cache: &Cache, ip: Option<IpAddr>, settings: &ThrottleSettings) {
+    let key = ip_key(ip);
+    let mut state = load_ip_failures(cache, &key).await;
+    state.failures = state.failures.saturating_add(1);
+    if let Some(wait) = backoff(state.failures, settings.ip_free_attempts, settings) {
+        let wait = i64::try_from(wait.as_secs()).unwrap_or(i64::MAX);
+        state.blocked_until = Local::now().timestamp().saturating_add(wait);
+        warn!(ip = ?ip, failures = state.failures, "throttling logins from address");
+    }
+    let value = serde_json::to_string(&state).unwrap_or_default();
+    if let Err(e) = cache
+        .insert_with_expiry(&key, &value, Duration::from_secs(settings.ip_reset_secs))
+        .await
+    {
+        warn!("Failed to record a login failure in the cache: {}", e);
+    }
+}
+
+/// Counts a request against `limit` for `subject` (an address or an account)
+/// in `bucket`, returning how long to wait when the limit is exceeded.
+pub async fn check_rate_limit(cache: &Cache, bucket: &str, subject: &str, limit: RateLimit) -> Option<Duration> {
+    let window_secs = limit.window_secs.max(1);
+    let now = u64::try_from(Local::now().timestamp()).unwrap_or_default();
+    let window = now / window_secs;
+    let key = format!("rate-limit:{bucket}:{subject}:{window}");
+
+    let count = match cache.get(&key).await {
+        Ok(value) => value.and_then(|value| value.parse::<u32>().ok()).unwrap_or(0),
+        Err(e) => {
+            warn!("Failed to read a rate limit from the cache: {}", e);
+            0
+        }
+    };
+    if count >= limit.max {
+        warn!(bucket, subject, "rate limit exceeded");
+        return Some(Duration::from_secs((window + 1) * window_secs - now));
+    }
+    if let Err(e) = cache
+        .insert_with_expiry(&key, &(count + 1).to_string(), Duration::from_secs(window_secs))
+        .await
+    {
+        warn!("Failed to update a rate limit in the cache: {}", e);
+    }
+    None
+}
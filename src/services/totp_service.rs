@@ -0,0 +1,165 @@
+//! Time-based one-time passwords (RFC 6238), as generated by authenticator
+//! apps, and the recovery codes that stand in for them when the device is lost.
+use base32::Alphabet;
+use hmac::{Hmac, Mac};
+use qrcode::{render::svg, QrCode};
+use rand::Rng;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+/// Length of the codes, which is what every authenticator app expects.
+pub const DIGITS: u32 = 6;
+/// How long each code is valid for, in seconds.
+pub const PERIOD_SECS: u64 = 30;
+/// Codes of the steps just before and after the current one are accepted too,
+/// to allow for clocks drifting apart.
+pub const ALLOWED_DRIFT_STEPS: u64 = 1;
+/// Number of recovery codes handed out when two-factor authentication is enabled.
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+/// 160 bits, the size RFC 4226 recommends for HMAC-SHA1 secrets.
+const SECRET_BYTES: usize = 20;
+const SECRET_ALPHABET: Alphabet = Alphabet::Rfc4648 { padding: false };
+/// Recovery codes avoid characters that are easily mistaken for each other.
+const RECOVERY_CODE_CHARS: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+const RECOVERY_CODE_HALF_LENGTH: usize = 5;
+
+/// Represents a custom error for `TotpService` operations.
+#[derive(Debug, Error)]
+pub enum TotpError {
+    #[error("Invalid secret: {0}")]
+    InvalidSecret(String),
+    #[error("Failed to render the QR code: {0}")]
+    QrCode(String),
+}
+
+/// Generates a new random secret, base32 encoded as authenticator apps expect.
+#[must_use]
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; SECRET_BYTES];
+    rand::thread_rng().fill(&mut bytes);
+    base32::encode(SECRET_ALPHABET, &bytes)
+}
+
+fn decode_secret(secret: &str) -> Result<Vec<u8>, TotpError> {
+    base32::decode(SECRET_ALPHABET, &secret.trim().to_uppercase())
+        .filter(|bytes| !bytes.is_empty())
+        .ok_or_else(|| TotpError::InvalidSecret("secret is not valid base32".to_string()))
+}
+
+/// The time step `unix_time` falls in.
+#[must_use]
+pub const fn step_at(unix_time: u64) -> u64 {
+    unix_time / PERIOD_SECS
+}
+
+/// Computes the code of `secret` for the given time step.
+///
+/// # Errors
+/// Returns `TotpError::InvalidSecret` if the secret is not valid base32.
+pub fn code_at(secret: &str, step: u64) -> Result<String, TotpError> {
+    let key = decode_secret(secret)?;
+    let mut mac = Hmac::<Sha1>::new_from_slice(&key)
+        .map_err(|e| TotpError::InvalidSecret(e.to_string()))?;
+    mac.update(&step.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    // Dynamic truncation, RFC 4226 section 5.3.
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+    Ok(format!(
+        "{:0width$}",
+        binary % 10u32.pow(DIGITS),
+        width = DIGITS as usize
+    ))
+}
+
+/// Checks `code` against `secret` at `unix_time`, returning the time step it
+/// belongs to.
+///
+/// Codes of steps up to `last_step` are rejected, so a code cannot be used
+/// twice even while it is still valid.
+///
+/// # Errors
+/// Returns `TotpError::InvalidSecret` if the secret is not valid base32.
+pub fn verify(
+    secret: &str,
+    code: &str,
+    unix_time: u64,
+    last_step: Option<u64>,
+) -> Result<Option<u64>, TotpError> {
+    let code: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+    if code.len() != DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+        return Ok(None);
+    }
+
+    let current = step_at(unix_time);
+    for step in current.saturating_sub(ALLOWED_DRIFT_STEPS)..=current + ALLOWED_DRIFT_STEPS {
+        if last_step.is_some_and(|last| step <= last) {
+            continue;
+        }
+        if code_at(secret, step)? == code {
+            return Ok(Some(step));
+        }
+    }
+    Ok(None)
+}
+
+/// Builds the `otpauth://` URI authenticator apps enroll from, usually by
+/// scanning it as a QR code.
+#[must_use]
+pub fn provisioning_uri(secret: &str, account: &str, issuer: &str) -> String {
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        urlencoding::encode(issuer),
+        urlencoding::encode(account),
+        secret,
+        urlencoding::encode(issuer),
+        DIGITS,
+        PERIOD_SECS
+    )
+}
+
+/// Renders `uri` as an SVG QR code.
+///
+/// # Errors
+/// Returns `TotpError::QrCode` if the URI is too long to fit in a QR code.
+pub fn qr_code_svg(uri: &str) -> Result<String, TotpError> {
+    let code = QrCode::new(uri.as_bytes()).map_err(|e| TotpError::QrCode(e.to_string()))?;
+    Ok(code
+        .render::<svg::Color<'_>>()
+        .min_dimensions(200, 200)
+        .build())
+}
+
+/// Generates a fresh set of recovery codes, formatted as `xxxxx-xxxxx`.
+#[must_use]
+pub fn generate_recovery_codes() -> Vec<String> {
+    let mut rng = rand::thread_rng();
+    let mut half = || -> String {
+        (0..RECOVERY_CODE_HALF_LENGTH)
+            .map(|_| RECOVERY_CODE_CHARS[rng.gen_range(0..RECOVERY_CODE_CHARS.len())] as char)
+            .collect()
+    };
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| format!("{}-{}", half(), half()))
+        .collect()
+}
+
+/// Hashes a recovery code for storage. Case, spaces and dashes are ignored,
+/// so the code can be typed back however it was written down.
+#[must_use]
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    format!("{:x}", Sha256::digest(normalized.as_bytes()))
+}
@@ -0,0 +1,34 @@
+//! `cargo loco task authorized_keys`
+//!
+//! Rewrites the git user's `authorized_keys` from the stored SSH keys. Keys
+//! added before they were pinned to `gitcrab-serve`, or whose row is gone,
+//! are dropped, so that every key left goes through per-repository
+//! authorization. Run it once after migrating, and whenever the file was
+//! edited by hand.
+use loco_rs::{
+    prelude::*,
+    task::{Task, TaskInfo, Vars},
+};
+
+use crate::{models::sshes, services::ssh_service::SshKeyService};
+
+pub struct AuthorizedKeys;
+
+#[async_trait]
+impl Task for AuthorizedKeys {
+    fn task(&self) -> TaskInfo {
+        TaskInfo {
+            name: "authorized_keys".to_string(),
+            detail: "Rewrite the git user's authorized_keys from the stored SSH keys".to_string(),
+        }
+    }
+
+    async fn run(&self, ctx: &AppContext, _vars: &Vars) -> Result<()> {
+        let keys = sshes::Entity::find().all(&ctx.db).await?;
+        let dropped = SshKeyService::new(env!("GIT_HOME"))
+            .sync_keys(&keys)
+            .map_err(|e| Error::string(&e.to_string()))?;
+        println!("Wrote {} keys, dropped {dropped} other lines", keys.len());
+        Ok(())
+    }
+}
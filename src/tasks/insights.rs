@@ -0,0 +1,50 @@
+//! `cargo loco task insights [repo:<owner>/<name>]`
+//!
+//! Computes the insights of one repository, or of every repository. Pushes
+//! over SSH run this, as the push happens outside of the server.
+use loco_rs::{
+    bgworker::BackgroundWorker,
+    prelude::*,
+    task::{Task, TaskInfo, Vars},
+};
+
+use crate::{
+    models::git_repos,
+    services::git_access_service::parse_repository_path,
+    workers::insights::{InsightsWorker, InsightsWorkerArgs},
+};
+
+pub struct Insights;
+
+#[async_trait]
+impl Task for Insights {
+    fn task(&self) -> TaskInfo {
+        TaskInfo {
+            name: "insights".to_string(),
+            detail: "Compute the insights of a repository (repo:<owner>/<name>) or of all repositories"
+                .to_string(),
+        }
+    }
+
+    async fn run(&self, ctx: &AppContext, vars: &Vars) -> Result<()> {
+        let repo_ids = match vars.cli_arg("repo") {
+            Ok(path) => {
+                let (owner, name) =
+                    parse_repository_path(path).map_err(|e| Error::string(&e.to_string()))?;
+                vec![git_repos::Model::find_by_namespace(&ctx.db, &owner, &name).await?.id]
+            }
+            Err(_) => git_repos::Entity::find()
+                .all(&ctx.db)
+                .await?
+                .into_iter()
+                .map(|item| item.id)
+                .collect(),
+        };
+
+        let worker = InsightsWorker::build(ctx);
+        for repo_id in repo_ids {
+            worker.perform(InsightsWorkerArgs { repo_id }).await?;
+        }
+        Ok(())
+    }
+}
@@ -1 +1,5 @@
-
+pub mod insights;
+pub mod search_index;
+pub mod reset_two_factor;
+pub mod namespace_repos;
+pub mod authorized_keys;
@@ -0,0 +1,69 @@
+//! `cargo loco task namespace_repos`
+//!
+//! Moves repositories to `<REPO_BASE_PATH>/<owner>/<name>.git`, where they are
+//! looked up now, from `<REPO_BASE_PATH>/<name>.git`, where they were created
+//! before they had owners, and from `<REPO_BASE_PATH>/<pid>/<name>.git`, where
+//! they were created before namespaces were user names. Run it once after
+//! migrating; repositories already in place are left alone, so running it
+//! again is harmless.
+use loco_rs::{
+    prelude::*,
+    task::{Task, TaskInfo, Vars},
+};
+
+use crate::{
+    models::{git_repos, users},
+    services::git_service::GitService,
+};
+
+pub struct NamespaceRepos;
+
+#[async_trait]
+impl Task for NamespaceRepos {
+    fn task(&self) -> TaskInfo {
+        TaskInfo {
+            name: "namespace_repos".to_string(),
+            detail: "Move repositories from older layouts into their owner's namespace".to_string(),
+        }
+    }
+
+    async fn run(&self, ctx: &AppContext, _vars: &Vars) -> Result<()> {
+        let service = GitService::new(env!("REPO_BASE_PATH").into(), env!("GIT_USER"));
+        let repos = git_repos::Entity::find()
+            .find_also_related(users::Entity)
+            .all(&ctx.db)
+            .await?;
+
+        let mut failed = 0;
+        for (item, owner) in repos {
+            let (Some(owner), Some(name)) = (owner, item.name.clone()) else {
+                continue;
+            };
+            let moved = match service.move_into_namespace(None, &owner.namespace(), &name).await {
+                Ok(None) => {
+                    let pid = owner.pid.to_string();
+                    service.move_into_namespace(Some(&pid), &owner.namespace(), &name).await
+                }
+                moved => moved,
+            };
+            match moved {
+                Ok(Some(path)) => {
+                    let mut item = item.into_active_model();
+                    item.path = ActiveValue::set(Some(path.to_string_lossy().to_string()));
+                    item.update(&ctx.db).await?;
+                    println!("Moved {name} to {}", path.display());
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    failed += 1;
+                    println!("Could not move {name}: {err}");
+                }
+            }
+        }
+
+        if failed > 0 {
+            return Err(Error::string(&format!("{failed} repositories could not be moved")));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,38 @@
+//! `cargo loco task reset_two_factor email:<email>`
+//!
+//! Turns two-factor authentication off for a user who lost both their
+//! authenticator app and their recovery codes, after checking who they are
+//! some other way. They can log in with their password alone afterwards.
+use loco_rs::{
+    prelude::*,
+    task::{Task, TaskInfo, Vars},
+};
+
+use crate::models::users;
+
+pub struct ResetTwoFactor;
+
+#[async_trait]
+impl Task for ResetTwoFactor {
+    fn task(&self) -> TaskInfo {
+        TaskInfo {
+            name: "reset_two_factor".to_string(),
+            detail: "Turn two-factor authentication off for a locked-out user (email:<email>)"
+                .to_string(),
+        }
+    }
+
+    async fn run(&self, ctx: &AppContext, vars: &Vars) -> Result<()> {
+        let email = vars.cli_arg("email")?;
+        let user = users::Model::find_by_email(&ctx.db, email).await?;
+        if user.totp_secret.is_none() {
+            println!("{email} does not use two-factor authentication");
+            return Ok(());
+        }
+
+        let user = user.into_active_model().disable_totp(&ctx.db).await?;
+        tracing::warn!(user_pid = user.pid.to_string(), "two-factor authentication reset by an admin");
+        println!("Two-factor authentication was turned off for {email}");
+        Ok(())
+    }
+}
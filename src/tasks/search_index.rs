@@ -0,0 +1,50 @@
+//! `cargo loco task search_index [repo:<owner>/<name>]`
+//!
+//! Brings the search index of one repository, or of every repository, up to
+//! date. Pushes over SSH run this, as the push happens outside of the server.
+use loco_rs::{
+    bgworker::BackgroundWorker,
+    prelude::*,
+    task::{Task, TaskInfo, Vars},
+};
+
+use crate::{
+    models::git_repos,
+    services::git_access_service::parse_repository_path,
+    workers::search_index::{SearchIndexWorker, SearchIndexWorkerArgs},
+};
+
+pub struct SearchIndex;
+
+#[async_trait]
+impl Task for SearchIndex {
+    fn task(&self) -> TaskInfo {
+        TaskInfo {
+            name: "search_index".to_string(),
+            detail: "Update the search index of a repository (repo:<owner>/<name>) or of all repositories"
+                .to_string(),
+        }
+    }
+
+    async fn run(&self, ctx: &AppContext, vars: &Vars) -> Result<()> {
+        let repo_ids = match vars.cli_arg("repo") {
+            Ok(path) => {
+                let (owner, name) =
+                    parse_repository_path(path).map_err(|e| Error::string(&e.to_string()))?;
+                vec![git_repos::Model::find_by_namespace(&ctx.db, &owner, &name).await?.id]
+            }
+            Err(_) => git_repos::Entity::find()
+                .all(&ctx.db)
+                .await?
+                .into_iter()
+                .map(|item| item.id)
+                .collect(),
+        };
+
+        let worker = SearchIndexWorker::build(ctx);
+        for repo_id in repo_ids {
+            worker.perform(SearchIndexWorkerArgs { repo_id }).await?;
+        }
+        Ok(())
+    }
+}
@@ -2,6 +2,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::models::_entities::users;
 use loco_rs::prelude::*;
+use sea_orm::prelude::DateTimeWithTimeZone;
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct LoginResponse {
@@ -23,6 +24,26 @@ impl LoginResponse {
     }
 }
 
+/// Returned instead of a [`LoginResponse`] to users with two-factor
+/// authentication, who exchange it for one at `/api/auth/login/2fa`.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TwoFactorChallengeResponse {
+    pub two_factor_required: bool,
+    pub challenge_token: String,
+    pub expires_at: Option<DateTimeWithTimeZone>,
+}
+
+impl TwoFactorChallengeResponse {
+    #[must_use]
+    pub fn new(user: &users::Model) -> Self {
+        Self {
+            two_factor_required: true,
+            challenge_token: user.totp_challenge_token.clone().unwrap_or_default(),
+            expires_at: user.totp_challenge_expiration,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct CurrentResponse {
     pub pid: String,
@@ -1,14 +1,39 @@
+use std::collections::HashMap;
+
 use loco_rs::prelude::*;
 
-use crate::{models::_entities::git_repos, services::repo_retrive_service::RepoResponse};
+use crate::{
+    models::{_entities::git_repos, users},
+    services::{
+        blame_service::Blame,
+        diff_service::Comparison,
+        language_stats_service::LanguageStats,
+        refs_service::{BranchList, TagInfo},
+        repo_history_service::{CommitDetail, CommitPage, PathCommitPage},
+        repo_retrive_service::{FileContentResponse, RefList, RepoResponse},
+    },
+};
 
 /// Render a list view of `git_repos`.
 ///
 /// # Errors
 ///
 /// When there is an issue with rendering the view.
-pub fn list(v: &impl ViewRenderer, items: &Vec<git_repos::Model>) -> Result<Response> {
-    format::render().view(v, "git_repo/list.html", data!({"items": items}))
+pub fn list(v: &impl ViewRenderer, items: &Vec<git_repos::Model>, languages: &HashMap<String, LanguageStats>, owner: &str, http_base: &str) -> Result<Response> {
+    format::render().view(v, "git_repo/list.html", data!({"items": items, "languages": languages, "owner": owner, "http_base": http_base}))
+}
+
+/// Render the `git_repos` a visitor can read, with their owners.
+///
+/// # Errors
+///
+/// When there is an issue with rendering the view.
+pub fn explore(v: &impl ViewRenderer, items: &[(git_repos::Model, users::Model)], http_base: &str) -> Result<Response> {
+    let items: Vec<_> = items
+        .iter()
+        .map(|(item, owner)| serde_json::json!({"item": item, "owner": owner.name, "namespace": owner.namespace()}))
+        .collect();
+    format::render().view(v, "git_repo/explore.html", data!({"items": items, "http_base": http_base}))
 }
 
 /// Render a single `git_repo` view.
@@ -16,8 +41,35 @@ pub fn list(v: &impl ViewRenderer, items: &Vec<git_repos::Model>) -> Result<Resp
 /// # Errors
 ///
 /// When there is an issue with rendering the view.
-pub fn show(v: &impl ViewRenderer, item: &git_repos::Model, data: RepoResponse) -> Result<Response> {
-    format::render().view(v, "git_repo/show.html", data!({"item": item,"data": data}))
+pub fn show(v: &impl ViewRenderer, item: &git_repos::Model, data: RepoResponse, languages: Option<&LanguageStats>, owner: &str, http_base: &str) -> Result<Response> {
+    format::render().view(v, "git_repo/show.html", data!({"item": item,"data": data, "languages": languages, "owner": owner, "http_base": http_base}))
+}
+
+/// Render the page of a `git_repo` nothing has been pushed to yet.
+///
+/// # Errors
+///
+/// When there is an issue with rendering the view.
+pub fn empty(v: &impl ViewRenderer, item: &git_repos::Model, owner: &str, http_base: &str) -> Result<Response> {
+    format::render().view(v, "git_repo/empty.html", data!({"item": item, "owner": owner, "http_base": http_base}))
+}
+
+/// Render a single file of a `git_repo` at `reference`.
+///
+/// # Errors
+///
+/// When there is an issue with rendering the view.
+pub fn blob(v: &impl ViewRenderer, item: &git_repos::Model, file: &FileContentResponse, reference: &str, refs: &RefList) -> Result<Response> {
+    format::render().view(v, "git_repo/blob.html", data!({"item": item, "file": file, "reference": reference, "refs": refs}))
+}
+
+/// Render the blame of a single file of a `git_repo` at `reference`.
+///
+/// # Errors
+///
+/// When there is an issue with rendering the view.
+pub fn blame(v: &impl ViewRenderer, item: &git_repos::Model, blame: &Blame, reference: &str, refs: &RefList) -> Result<Response> {
+    format::render().view(v, "git_repo/blame.html", data!({"item": item, "blame": blame, "reference": reference, "refs": refs}))
 }
 
 /// Render a `git_repo` create form.
@@ -34,6 +86,60 @@ pub fn create(v: &impl ViewRenderer) -> Result<Response> {
 /// # Errors
 ///
 /// When there is an issue with rendering the view.
-pub fn edit(v: &impl ViewRenderer, item: &git_repos::Model) -> Result<Response> {
-    format::render().view(v, "git_repo/edit.html", data!({"item": item}))
+pub fn edit(v: &impl ViewRenderer, item: &git_repos::Model, branches: &[String], default_branch: Option<&str>) -> Result<Response> {
+    format::render().view(v, "git_repo/edit.html", data!({"item": item, "branches": branches, "default_branch": default_branch}))
+}
+
+/// Render a page of the commit history of a `git_repo`.
+///
+/// # Errors
+///
+/// When there is an issue with rendering the view.
+pub fn commits(v: &impl ViewRenderer, item: &git_repos::Model, page: &CommitPage) -> Result<Response> {
+    format::render().view(v, "git_repo/commits.html", data!({"item": item, "page": page}))
+}
+
+/// Render a page of the commits that changed one path of a `git_repo`.
+///
+/// # Errors
+///
+/// When there is an issue with rendering the view.
+pub fn history(v: &impl ViewRenderer, item: &git_repos::Model, page: &PathCommitPage, reference: &str) -> Result<Response> {
+    format::render().view(v, "git_repo/history.html", data!({"item": item, "page": page, "reference": reference}))
+}
+
+/// Render a single commit of a `git_repo`.
+///
+/// # Errors
+///
+/// When there is an issue with rendering the view.
+pub fn commit(v: &impl ViewRenderer, item: &git_repos::Model, commit: &CommitDetail, path: Option<&str>) -> Result<Response> {
+    format::render().view(v, "git_repo/commit.html", data!({"item": item, "commit": commit, "path": path}))
+}
+
+/// Render the comparison of two revisions of a `git_repo`.
+///
+/// # Errors
+///
+/// When there is an issue with rendering the view.
+pub fn compare(v: &impl ViewRenderer, item: &git_repos::Model, comparison: &Comparison) -> Result<Response> {
+    format::render().view(v, "git_repo/compare.html", data!({"item": item, "comparison": comparison}))
+}
+
+/// Render the branches of a `git_repo`.
+///
+/// # Errors
+///
+/// When there is an issue with rendering the view.
+pub fn branches(v: &impl ViewRenderer, item: &git_repos::Model, list: &BranchList, can_write: bool) -> Result<Response> {
+    format::render().view(v, "git_repo/branches.html", data!({"item": item, "list": list, "can_write": can_write}))
+}
+
+/// Render the tags of a `git_repo`.
+///
+/// # Errors
+///
+/// When there is an issue with rendering the view.
+pub fn tags(v: &impl ViewRenderer, item: &git_repos::Model, tags: &[TagInfo], branches: &BranchList, can_write: bool) -> Result<Response> {
+    format::render().view(v, "git_repo/tags.html", data!({"item": item, "tags": tags, "branches": branches, "can_write": can_write}))
 }
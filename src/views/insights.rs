@@ -0,0 +1,47 @@
+use loco_rs::prelude::*;
+
+use crate::{
+    models::{_entities::git_repos, repo_insights},
+    services::insights_service::{punchcard_grid, Insights},
+};
+
+/// Render the insights page of a `git_repo`. `outdated` is set while newer
+/// insights are being computed.
+///
+/// # Errors
+///
+/// When there is an issue with rendering the view.
+pub fn show(
+    v: &impl ViewRenderer,
+    item: &git_repos::Model,
+    stored: Option<&(repo_insights::Model, Insights)>,
+    outdated: bool,
+) -> Result<Response> {
+    let computed_at = stored.map(|(row, _)| row.updated_at.format("%Y-%m-%d %H:%M").to_string());
+    let insights = stored.map(|(_, insights)| insights);
+    let punchcard = insights.map(|insights| punchcard_grid(&insights.punchcard));
+    // The charts are scaled to their largest value.
+    let max_week = insights
+        .and_then(|insights| insights.weeks.iter().map(|week| week.commits).max())
+        .unwrap_or_default();
+    let max_slot = punchcard
+        .iter()
+        .flatten()
+        .flatten()
+        .copied()
+        .max()
+        .unwrap_or_default();
+    format::render().view(
+        v,
+        "insights/show.html",
+        data!({
+            "item": item,
+            "insights": insights,
+            "computed_at": computed_at,
+            "outdated": outdated,
+            "punchcard": punchcard,
+            "max_week": max_week,
+            "max_slot": max_slot,
+        }),
+    )
+}
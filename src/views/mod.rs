@@ -2,3 +2,8 @@ pub mod auth;
 pub mod git_repo;
 pub mod home;
 pub mod ssh;
+pub mod search;
+pub mod insights;
+pub mod token;
+pub mod two_factor;
+pub mod session;
@@ -0,0 +1,45 @@
+use loco_rs::prelude::*;
+
+use crate::{
+    controllers::search::{GlobalSearchParams, SearchParams},
+    models::_entities::git_repos,
+    services::{search_index_service::IndexResults, search_service::SearchOutcome},
+};
+
+/// Render the search page of a `git_repo`, with the results once a query was given.
+///
+/// # Errors
+///
+/// When there is an issue with rendering the view.
+pub fn repo(
+    v: &impl ViewRenderer,
+    item: &git_repos::Model,
+    params: &SearchParams,
+    reference: &str,
+    outcome: Option<&SearchOutcome>,
+    error: Option<&str>,
+) -> Result<Response> {
+    format::render().view(
+        v,
+        "search/repo.html",
+        data!({"item": item, "params": params, "reference": reference, "outcome": outcome, "error": error}),
+    )
+}
+
+/// Render the search across all repositories, with the results once a query was given.
+///
+/// # Errors
+///
+/// When there is an issue with rendering the view.
+pub fn global(
+    v: &impl ViewRenderer,
+    params: &GlobalSearchParams,
+    results: Option<&IndexResults>,
+    error: Option<&str>,
+) -> Result<Response> {
+    format::render().view(
+        v,
+        "search/global.html",
+        data!({"params": params, "results": results, "error": error}),
+    )
+}
@@ -0,0 +1,44 @@
+use loco_rs::prelude::*;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::models::sessions;
+
+/// A session as shown to its owner.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SessionResponse {
+    pub pid: String,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTimeWithTimeZone,
+    pub last_seen_at: DateTimeWithTimeZone,
+    pub expires_at: DateTimeWithTimeZone,
+    /// Whether this is the session the request was made with.
+    pub current: bool,
+}
+
+impl SessionResponse {
+    #[must_use]
+    pub fn new(item: &sessions::Model, current: Option<Uuid>) -> Self {
+        Self {
+            pid: item.pid.to_string(),
+            user_agent: item.user_agent.clone(),
+            ip_address: item.ip_address.clone(),
+            created_at: item.created_at,
+            last_seen_at: item.last_seen_at,
+            expires_at: item.expires_at,
+            current: current == Some(item.pid),
+        }
+    }
+}
+
+/// Render the sessions of a user.
+///
+/// # Errors
+///
+/// When there is an issue with rendering the view.
+pub fn list(v: &impl ViewRenderer, items: &[sessions::Model], current: Option<Uuid>) -> Result<Response> {
+    let items: Vec<_> = items.iter().map(|item| SessionResponse::new(item, current)).collect();
+    format::render().view(v, "session/list.html", data!({"items": items}))
+}
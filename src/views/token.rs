@@ -0,0 +1,68 @@
+use chrono::Local;
+use loco_rs::prelude::*;
+use sea_orm::prelude::DateTimeWithTimeZone;
+use serde::{Deserialize, Serialize};
+
+use crate::models::personal_access_tokens::{self, Scope};
+
+/// A personal access token as shown to its owner. The secret is only
+/// included right after the token was created.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TokenResponse {
+    pub id: i32,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    /// The last characters of the secret, to tell tokens apart.
+    pub token_hint: String,
+    pub created_at: DateTimeWithTimeZone,
+    pub expires_at: Option<DateTimeWithTimeZone>,
+    pub last_used_at: Option<DateTimeWithTimeZone>,
+    pub expired: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl TokenResponse {
+    #[must_use]
+    pub fn new(item: &personal_access_tokens::Model) -> Self {
+        Self {
+            id: item.id,
+            name: item.name.clone(),
+            scopes: item.scopes(),
+            token_hint: item.token_hint.clone(),
+            created_at: item.created_at,
+            expires_at: item.expires_at,
+            last_used_at: item.last_used_at,
+            expired: item.is_expired(Local::now().fixed_offset()),
+            token: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_secret(item: &personal_access_tokens::Model, secret: String) -> Self {
+        Self {
+            token: Some(secret),
+            ..Self::new(item)
+        }
+    }
+}
+
+/// Render the personal access tokens of a user, with the form to create one.
+///
+/// # Errors
+///
+/// When there is an issue with rendering the view.
+pub fn list(v: &impl ViewRenderer, items: &[personal_access_tokens::Model], scopes: &[Scope]) -> Result<Response> {
+    let items: Vec<_> = items.iter().map(TokenResponse::new).collect();
+    format::render().view(v, "token/list.html", data!({"items": items, "scopes": scopes}))
+}
+
+/// Render a token that was just created, with its secret.
+///
+/// # Errors
+///
+/// When there is an issue with rendering the view.
+pub fn created(v: &impl ViewRenderer, item: &personal_access_tokens::Model, secret: &str) -> Result<Response> {
+    let item = TokenResponse::with_secret(item, secret.to_string());
+    format::render().view(v, "token/created.html", data!({"item": item}))
+}
@@ -0,0 +1,65 @@
+use loco_rs::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::models::users;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TwoFactorStatusResponse {
+    pub enabled: bool,
+    /// An authenticator app was set up but no code was confirmed yet.
+    pub pending: bool,
+    pub recovery_codes_left: usize,
+}
+
+impl TwoFactorStatusResponse {
+    #[must_use]
+    pub fn new(user: &users::Model) -> Self {
+        let enabled = user.two_factor_enabled();
+        Self {
+            enabled,
+            pending: !enabled && user.totp_secret.is_some(),
+            recovery_codes_left: user.recovery_codes_left(),
+        }
+    }
+}
+
+/// What an authenticator app needs to be enrolled.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TwoFactorSetupResponse {
+    /// The secret, for apps that cannot scan QR codes.
+    pub secret: String,
+    pub provisioning_uri: String,
+    pub qr_code_svg: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RecoveryCodesResponse {
+    pub recovery_codes: Vec<String>,
+}
+
+/// Render the two-factor authentication settings of a user.
+///
+/// # Errors
+///
+/// When there is an issue with rendering the view.
+pub fn show(v: &impl ViewRenderer, status: &TwoFactorStatusResponse) -> Result<Response> {
+    format::render().view(v, "two_factor/show.html", data!({"status": status}))
+}
+
+/// Render the QR code and secret to enroll an authenticator app with.
+///
+/// # Errors
+///
+/// When there is an issue with rendering the view.
+pub fn setup(v: &impl ViewRenderer, setup: &TwoFactorSetupResponse) -> Result<Response> {
+    format::render().view(v, "two_factor/setup.html", data!({"setup": setup}))
+}
+
+/// Render recovery codes, which are only ever shown this once.
+///
+/// # Errors
+///
+/// When there is an issue with rendering the view.
+pub fn recovery_codes(v: &impl ViewRenderer, codes: &[String]) -> Result<Response> {
+    format::render().view(v, "two_factor/recovery_codes.html", data!({"codes": codes}))
+}
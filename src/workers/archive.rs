@@ -0,0 +1,32 @@
+use std::path::PathBuf;
+
+use loco_rs::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::services::archive_service::{build_cached_archive, ArchiveTarget};
+
+/// Builds archives too large to stream on request into the archive cache.
+pub struct ArchiveWorker {
+    pub ctx: AppContext,
+}
+
+#[derive(Deserialize, Debug, Serialize)]
+pub struct ArchiveWorkerArgs {
+    pub target: ArchiveTarget,
+    /// Where the finished archive is stored.
+    pub cache_path: PathBuf,
+}
+
+#[async_trait]
+impl BackgroundWorker<ArchiveWorkerArgs> for ArchiveWorker {
+    fn build(ctx: &AppContext) -> Self {
+        Self { ctx: ctx.clone() }
+    }
+
+    async fn perform(&self, args: ArchiveWorkerArgs) -> Result<()> {
+        tokio::task::spawn_blocking(move || build_cached_archive(&args.target, &args.cache_path))
+            .await
+            .map_err(|e| Error::string(&e.to_string()))?
+            .map_err(|e| Error::string(&e.to_string()))
+    }
+}
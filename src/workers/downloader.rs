@@ -1,23 +0,0 @@
-use loco_rs::prelude::*;
-use serde::{Deserialize, Serialize};
-
-pub struct DownloadWorker {
-    pub ctx: AppContext,
-}
-
-#[derive(Deserialize, Debug, Serialize)]
-pub struct DownloadWorkerArgs {
-    pub user_guid: String,
-}
-
-#[async_trait]
-impl BackgroundWorker<DownloadWorkerArgs> for DownloadWorker {
-    fn build(ctx: &AppContext) -> Self {
-        Self { ctx: ctx.clone() }
-    }
-    async fn perform(&self, _args: DownloadWorkerArgs) -> Result<()> {
-        // TODO: Some actual work goes here...
-
-        Ok(())
-    }
-}
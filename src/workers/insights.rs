@@ -0,0 +1,91 @@
+use std::time::Duration;
+
+use chrono::Local;
+use loco_rs::prelude::*;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{
+    models::{git_repos, repo_insights, users},
+    services::{git_service::GitService, insights_service::compute_insights},
+};
+
+/// How long a queued update keeps views from queueing another one. A job that
+/// failed or was lost is queued again once this passed.
+const PENDING_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// The cache key marking an update of the insights of `repo_id` as queued.
+#[must_use]
+pub fn pending_key(repo_id: i32) -> String {
+    format!("insights:pending:{}", repo_id)
+}
+
+/// Computes the insights of a repository after a push and stores them, so the
+/// insights page never walks the history itself.
+pub struct InsightsWorker {
+    pub ctx: AppContext,
+}
+
+#[derive(Deserialize, Debug, Serialize)]
+pub struct InsightsWorkerArgs {
+    pub repo_id: i32,
+}
+
+impl InsightsWorker {
+    /// Queues an update of the insights of a repository unless one is queued
+    /// already. Views of outdated insights use this, so that refreshing a page
+    /// does not queue a history walk each time; pushes always queue one.
+    pub async fn queue_if_idle(ctx: &AppContext, repo_id: i32) {
+        let key = pending_key(repo_id);
+        if let Ok(true) = ctx.cache.contains_key(&key).await {
+            return;
+        }
+        if let Err(e) = ctx.cache.insert_with_expiry(&key, "", PENDING_TTL).await {
+            warn!("Failed to mark insights update of repository {} as queued: {}", repo_id, e);
+        }
+        if let Err(e) = Self::perform_later(ctx, InsightsWorkerArgs { repo_id }).await {
+            warn!("Failed to queue insights update for repository {}: {}", repo_id, e);
+        }
+    }
+}
+
+#[async_trait]
+impl BackgroundWorker<InsightsWorkerArgs> for InsightsWorker {
+    fn build(ctx: &AppContext) -> Self {
+        Self { ctx: ctx.clone() }
+    }
+
+    async fn perform(&self, args: InsightsWorkerArgs) -> Result<()> {
+        let item = git_repos::Entity::find_by_id(args.repo_id)
+            .one(&self.ctx.db)
+            .await?
+            .ok_or_else(|| Error::string(&format!("Repository {} not found", args.repo_id)))?;
+        let owner = users::Entity::find_by_id(item.user_id)
+            .one(&self.ctx.db)
+            .await?
+            .ok_or_else(|| Error::string(&format!("Owner of repository {} not found", item.id)))?;
+
+        let name = item.name.clone().unwrap_or_default();
+        let insights = tokio::task::spawn_blocking(move || {
+            let path = GitService::new(env!("REPO_BASE_PATH").into(), env!("GIT_USER"))
+                .get_repository_path(&owner.namespace(), &name)
+                .map_err(|e| Error::string(&e.to_string()))?;
+            let repo = git2::Repository::open_bare(path).map_err(|e| Error::string(&e.to_string()))?;
+            compute_insights(&repo, Local::now().timestamp()).map_err(|e| Error::string(&e.to_string()))
+        })
+        .await
+        .map_err(|e| Error::string(&e.to_string()))??;
+
+        repo_insights::ActiveModel::replace(&self.ctx.db, item.id, &insights).await?;
+        if let Err(e) = self.ctx.cache.remove(&pending_key(item.id)).await {
+            warn!("Failed to clear queued insights update of repository {}: {}", item.id, e);
+        }
+        info!(
+            repo_id = args.repo_id,
+            commits = insights.total_commits,
+            contributors = insights.contributors.len(),
+            "insights updated"
+        );
+        Ok(())
+    }
+}
@@ -1 +1,3 @@
-pub mod downloader;
+pub mod archive;
+pub mod search_index;
+pub mod insights;
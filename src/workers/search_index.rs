@@ -0,0 +1,58 @@
+use loco_rs::prelude::*;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::{
+    common::settings::Settings,
+    models::{git_repos, users},
+    services::{git_service::GitService, search_index_service::update_index},
+};
+
+/// Brings the search index of a repository up to date after a push.
+pub struct SearchIndexWorker {
+    pub ctx: AppContext,
+}
+
+#[derive(Deserialize, Debug, Serialize)]
+pub struct SearchIndexWorkerArgs {
+    pub repo_id: i32,
+}
+
+#[async_trait]
+impl BackgroundWorker<SearchIndexWorkerArgs> for SearchIndexWorker {
+    fn build(ctx: &AppContext) -> Self {
+        Self { ctx: ctx.clone() }
+    }
+
+    async fn perform(&self, args: SearchIndexWorkerArgs) -> Result<()> {
+        let item = git_repos::Entity::find_by_id(args.repo_id)
+            .one(&self.ctx.db)
+            .await?
+            .ok_or_else(|| Error::string(&format!("Repository {} not found", args.repo_id)))?;
+        let owner = users::Entity::find_by_id(item.user_id)
+            .one(&self.ctx.db)
+            .await?
+            .ok_or_else(|| Error::string(&format!("Owner of repository {} not found", item.id)))?;
+        let settings = Settings::from_context(&self.ctx).search;
+
+        let update = tokio::task::spawn_blocking(move || {
+            let path = GitService::new(env!("REPO_BASE_PATH").into(), env!("GIT_USER"))
+                .get_repository_path(&owner.namespace(), &item.name.clone().unwrap_or_default())
+                .map_err(|e| Error::string(&e.to_string()))?;
+            let repo = git2::Repository::open_bare(path).map_err(|e| Error::string(&e.to_string()))?;
+            update_index(&settings.index_dir, item.id, &repo, &settings)
+                .map_err(|e| Error::string(&e.to_string()))
+        })
+        .await
+        .map_err(|e| Error::string(&e.to_string()))??;
+
+        info!(
+            repo_id = args.repo_id,
+            added = update.added,
+            removed = update.removed,
+            rebuilt = update.rebuilt,
+            "search index updated"
+        );
+        Ok(())
+    }
+}
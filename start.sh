@@ -13,9 +13,15 @@ chmod 700 /home/git/.ssh
 chmod 600 /home/git/.ssh/authorized_keys
 chmod 755 /home/git/repositories
 chmod 755 /home/git/git-shell-commands
-chmod +x /home/git/git-shell-commands/git-serve
+chmod +x /home/git/git-shell-commands/gitcrab-serve
 ln -s /home/git/repositories /repositories
 
+# gitcrab-serve runs inside SSH sessions, which do not inherit the container
+# environment: hand it the database and configuration location explicitly.
+if ! grep -q "^SetEnv" /etc/ssh/sshd_config; then
+    echo "SetEnv DATABASE_URL=${DATABASE_URL} LOCO_ENV=${LOCO_ENV:-development} LOCO_CONFIG_FOLDER=/usr/app/config GITCRAB_CLI=/usr/app/gitcrab-cli" >> /etc/ssh/sshd_config
+fi
+
 # Start SSH server
 log_message "Starting SSH server..."
 /usr/sbin/sshd -D &
@@ -1,4 +1,5 @@
 mod models;
 mod requests;
+mod services;
 mod tasks;
 mod workers;
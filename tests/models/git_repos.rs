@@ -1,5 +1,12 @@
-use gitcrab::app::App;
-use loco_rs::testing::prelude::*;
+use gitcrab::{
+    app::App,
+    models::{
+        git_repos::{self, Access, Visibility},
+        users,
+    },
+};
+use loco_rs::{model::ModelError, testing::prelude::*};
+use sea_orm::{ActiveModelTrait, ActiveValue};
 use serial_test::serial;
 
 macro_rules! configure_insta {
@@ -12,20 +19,115 @@ macro_rules! configure_insta {
 
 #[tokio::test]
 #[serial]
-async fn test_model() {
+async fn can_find_by_owner() {
     configure_insta!();
 
     let boot = boot_test::<App>().await.unwrap();
     seed::<App>(&boot.app_context).await.unwrap();
 
-    // query your model, e.g.:
-    //
-    // let item = models::posts::Model::find_by_pid(
-    //     &boot.app_context.db,
-    //     "11111111-1111-1111-1111-111111111111",
-    // )
-    // .await;
+    let owner = users::Model::find_by_pid(&boot.app_context.db, "11111111-1111-1111-1111-111111111111")
+        .await
+        .unwrap();
+    let other = users::Model::find_by_pid(&boot.app_context.db, "22222222-2222-2222-2222-222222222222")
+        .await
+        .unwrap();
 
-    // snapshot the result:
-    // assert_debug_snapshot!(item);
+    let repo = git_repos::ActiveModel {
+        name: ActiveValue::set(Some("api".to_string())),
+        path: ActiveValue::set(Some(format!("/tmp/{}/api.git", owner.namespace()))),
+        user_id: ActiveValue::set(owner.id),
+        ..Default::default()
+    }
+    .insert(&boot.app_context.db)
+    .await
+    .unwrap();
+
+    let found = git_repos::Model::find_by_owner(&boot.app_context.db, owner.id, repo.id).await;
+    assert_eq!(found.unwrap().id, repo.id);
+
+    let hidden = git_repos::Model::find_by_owner(&boot.app_context.db, other.id, repo.id).await;
+    assert!(matches!(hidden, Err(ModelError::EntityNotFound)));
+
+    let owned = git_repos::Model::list_by_owner(&boot.app_context.db, owner.id)
+        .await
+        .unwrap();
+    assert_eq!(owned.len(), 1);
+    assert!(git_repos::Model::list_by_owner(&boot.app_context.db, other.id)
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test]
+#[serial]
+async fn access_follows_visibility() {
+    configure_insta!();
+
+    let boot = boot_test::<App>().await.unwrap();
+    seed::<App>(&boot.app_context).await.unwrap();
+
+    let owner = users::Model::find_by_pid(&boot.app_context.db, "11111111-1111-1111-1111-111111111111")
+        .await
+        .unwrap();
+    let other = users::Model::find_by_pid(&boot.app_context.db, "22222222-2222-2222-2222-222222222222")
+        .await
+        .unwrap();
+
+    let repo = git_repos::ActiveModel {
+        name: ActiveValue::set(Some("api".to_string())),
+        path: ActiveValue::set(Some(format!("/tmp/{}/api.git", owner.namespace()))),
+        user_id: ActiveValue::set(owner.id),
+        ..Default::default()
+    }
+    .insert(&boot.app_context.db)
+    .await
+    .unwrap();
+
+    // Repositories are private unless made visible.
+    assert_eq!(repo.visibility(), Visibility::Private);
+    assert_eq!(repo.access_for(Some(&owner)), Access::Write);
+    assert_eq!(repo.access_for(Some(&other)), Access::None);
+    assert_eq!(repo.access_for(None), Access::None);
+
+    let internal = git_repos::Model {
+        visibility: "internal".to_string(),
+        ..repo.clone()
+    };
+    assert_eq!(internal.access_for(Some(&owner)), Access::Write);
+    assert_eq!(internal.access_for(Some(&other)), Access::Read);
+    assert_eq!(internal.access_for(None), Access::None);
+
+    let public = git_repos::Model {
+        visibility: "public".to_string(),
+        ..repo.clone()
+    };
+    assert_eq!(public.access_for(Some(&other)), Access::Read);
+    assert_eq!(public.access_for(None), Access::Read);
+
+    let (found, found_owner) = git_repos::Model::find_with_owner(&boot.app_context.db, repo.id)
+        .await
+        .unwrap();
+    assert_eq!(found.id, repo.id);
+    assert_eq!(found_owner.id, owner.id);
+}
+
+#[test]
+fn can_normalize_topics() {
+    assert_eq!(
+        git_repos::normalize_topics("Rust, web  git,rust,,-cli-, c++"),
+        vec!["rust", "web", "git", "cli", "c"]
+    );
+    assert!(git_repos::normalize_topics(" , ").is_empty());
+    assert_eq!(Visibility::parse("PUBLIC"), Visibility::Public);
+    assert_eq!(Visibility::parse("secret"), Visibility::Private);
+}
+
+#[test]
+fn can_validate_homepages() {
+    assert!(git_repos::is_valid_homepage("https://example.com"));
+    assert!(git_repos::is_valid_homepage("http://example.com/docs?page=1"));
+    assert!(!git_repos::is_valid_homepage("javascript:alert(1)"));
+    assert!(!git_repos::is_valid_homepage("https://"));
+    assert!(!git_repos::is_valid_homepage("https://exa mple.com"));
+    assert!(!git_repos::is_valid_homepage("example.com"));
 }
@@ -4,3 +4,6 @@ mod git_repos;
 mod logins;
 mod homes;
 mod sshes;
+mod repo_insights;
+mod personal_access_tokens;
+mod sessions;
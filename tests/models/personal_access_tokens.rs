@@ -0,0 +1,102 @@
+use chrono::{Duration, Local};
+use gitcrab::{
+    app::App,
+    models::{
+        personal_access_tokens::{self, hash_token, parse_scopes, Scope, TOKEN_PREFIX},
+        users,
+    },
+};
+use loco_rs::testing::prelude::*;
+use serial_test::serial;
+
+const USER1_PID: &str = "11111111-1111-1111-1111-111111111111";
+
+#[test]
+fn can_parse_scopes() {
+    assert_eq!(
+        parse_scopes(&["repo:write", "bogus", " ssh_keys ", "repo:write"]),
+        vec![Scope::RepoWrite, Scope::SshKeys]
+    );
+    assert!(parse_scopes::<&str>(&[]).is_empty());
+}
+
+#[tokio::test]
+#[serial]
+async fn can_create_and_find_by_token() {
+    let boot = boot_test::<App>().await.unwrap();
+    seed::<App>(&boot.app_context).await.unwrap();
+    let db = &boot.app_context.db;
+    let user = users::Model::find_by_pid(db, USER1_PID).await.unwrap();
+
+    let (item, secret) = personal_access_tokens::ActiveModel::create_for_user(
+        db,
+        user.id,
+        " ci ",
+        &[Scope::RepoRead],
+        None,
+    )
+    .await
+    .unwrap();
+
+    assert!(secret.starts_with(TOKEN_PREFIX));
+    assert_eq!(item.name, "ci");
+    assert_eq!(item.token_hash, hash_token(&secret));
+    assert_ne!(item.token_hash, secret);
+    assert!(secret.ends_with(&item.token_hint));
+    assert_eq!(item.scopes(), vec![Scope::RepoRead]);
+
+    let found = personal_access_tokens::Model::find_by_token(db, &secret).await.unwrap();
+    assert_eq!(found.id, item.id);
+    assert!(personal_access_tokens::Model::find_by_token(db, "gcp_unknown").await.is_err());
+    assert!(personal_access_tokens::Model::find_by_token(db, &secret[TOKEN_PREFIX.len()..])
+        .await
+        .is_err());
+
+    let touched = found.touch(db).await.unwrap();
+    assert!(touched.last_used_at.is_some());
+}
+
+#[tokio::test]
+#[serial]
+async fn expired_tokens_are_not_found() {
+    let boot = boot_test::<App>().await.unwrap();
+    seed::<App>(&boot.app_context).await.unwrap();
+    let db = &boot.app_context.db;
+    let user = users::Model::find_by_pid(db, USER1_PID).await.unwrap();
+
+    let yesterday = (Local::now() - Duration::days(1)).fixed_offset();
+    let (item, secret) = personal_access_tokens::ActiveModel::create_for_user(
+        db,
+        user.id,
+        "old",
+        &[Scope::RepoRead],
+        Some(yesterday),
+    )
+    .await
+    .unwrap();
+
+    assert!(item.is_expired(Local::now().fixed_offset()));
+    assert!(personal_access_tokens::Model::find_by_token(db, &secret).await.is_err());
+}
+
+#[tokio::test]
+#[serial]
+async fn scopes_imply_narrower_ones() {
+    let boot = boot_test::<App>().await.unwrap();
+    seed::<App>(&boot.app_context).await.unwrap();
+    let db = &boot.app_context.db;
+    let user = users::Model::find_by_pid(db, USER1_PID).await.unwrap();
+
+    let create = |scopes: &'static [Scope]| personal_access_tokens::ActiveModel::create_for_user(db, user.id, "t", scopes, None);
+    let (read, _) = create(&[Scope::RepoRead]).await.unwrap();
+    let (write, _) = create(&[Scope::RepoWrite]).await.unwrap();
+    let (admin, _) = create(&[Scope::Admin]).await.unwrap();
+
+    assert!(read.allows(Scope::RepoRead));
+    assert!(!read.allows(Scope::RepoWrite));
+    assert!(!read.allows(Scope::SshKeys));
+    assert!(write.allows(Scope::RepoRead));
+    assert!(write.allows(Scope::RepoWrite));
+    assert!(!write.allows(Scope::Admin));
+    assert!(Scope::ALL.iter().all(|scope| admin.allows(*scope)));
+}
@@ -0,0 +1,61 @@
+use chrono::NaiveDate;
+use gitcrab::{
+    app::App,
+    models::{git_repos, repo_insights, users},
+    services::insights_service::{Contributor, Insights, PunchcardCell, WeekActivity},
+};
+use loco_rs::testing::prelude::*;
+use sea_orm::{ActiveModelTrait, ActiveValue};
+use serial_test::serial;
+
+fn insights(head: &str, commits: i32) -> Insights {
+    Insights {
+        head_commit: Some(head.to_string()),
+        total_commits: commits,
+        weeks: vec![WeekActivity {
+            week_start: NaiveDate::from_ymd_opt(2026, 10, 12).unwrap(),
+            commits,
+        }],
+        contributors: vec![Contributor {
+            name: "Alice".to_string(),
+            email: "alice@example.com".to_string(),
+            commits,
+            additions: 10,
+            deletions: 2,
+        }],
+        punchcard: vec![PunchcardCell {
+            weekday: 0,
+            hour: 9,
+            commits,
+        }],
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn can_replace_and_load_insights() {
+    let boot = boot_test::<App>().await.unwrap();
+    seed::<App>(&boot.app_context).await.unwrap();
+    let db = &boot.app_context.db;
+
+    let owner = users::Model::find_by_pid(db, "11111111-1111-1111-1111-111111111111")
+        .await
+        .unwrap();
+    let repo = git_repos::ActiveModel {
+        name: ActiveValue::set(Some("stats".to_string())),
+        user_id: ActiveValue::set(owner.id),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .unwrap();
+
+    assert!(repo_insights::Model::find_by_repo(db, repo.id).await.unwrap().is_none());
+
+    repo_insights::ActiveModel::replace(db, repo.id, &insights("aaa", 1)).await.unwrap();
+    repo_insights::ActiveModel::replace(db, repo.id, &insights("bbb", 5)).await.unwrap();
+
+    let (row, loaded) = repo_insights::Model::find_by_repo(db, repo.id).await.unwrap().unwrap();
+    assert_eq!(row.head_commit.as_deref(), Some("bbb"));
+    assert_eq!(loaded, insights("bbb", 5));
+}
@@ -0,0 +1,122 @@
+use chrono::{Duration, Local};
+use gitcrab::{
+    app::App,
+    common::session_store::SessionDbPool,
+    models::{sessions, users},
+};
+use axum_session::DatabasePool;
+use loco_rs::testing::prelude::*;
+use sea_orm::{ActiveModelTrait, ActiveValue, IntoActiveModel};
+use serial_test::serial;
+
+const USER1_PID: &str = "11111111-1111-1111-1111-111111111111";
+const USER2_PID: &str = "22222222-2222-2222-2222-222222222222";
+
+#[tokio::test]
+#[serial]
+async fn can_create_and_find_active_sessions() {
+    let boot = boot_test::<App>().await.unwrap();
+    seed::<App>(&boot.app_context).await.unwrap();
+    let db = &boot.app_context.db;
+    let user = users::Model::find_by_pid(db, USER1_PID).await.unwrap();
+
+    let item = sessions::ActiveModel::create_for_user(
+        db,
+        user.id,
+        Some("curl/8.0"),
+        Some("10.0.0.1".parse().unwrap()),
+        3600,
+    )
+    .await
+    .unwrap();
+    assert_eq!(item.user_agent.as_deref(), Some("curl/8.0"));
+    assert_eq!(item.ip_address.as_deref(), Some("10.0.0.1"));
+
+    let found = sessions::Model::find_active(db, &item.pid.to_string()).await.unwrap();
+    assert_eq!(found.id, item.id);
+    assert!(sessions::Model::find_active(db, "not-a-uuid").await.is_err());
+
+    // Expired sessions are neither accepted nor listed.
+    let mut expired = item.into_active_model();
+    expired.expires_at = ActiveValue::set((Local::now() - Duration::minutes(1)).into());
+    let expired = expired.update(db).await.unwrap();
+    assert!(sessions::Model::find_active(db, &expired.pid.to_string()).await.is_err());
+    assert!(sessions::Model::list_by_owner(db, user.id).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+#[serial]
+async fn can_revoke_sessions() {
+    let boot = boot_test::<App>().await.unwrap();
+    seed::<App>(&boot.app_context).await.unwrap();
+    let db = &boot.app_context.db;
+    let user = users::Model::find_by_pid(db, USER1_PID).await.unwrap();
+    let other = users::Model::find_by_pid(db, USER2_PID).await.unwrap();
+
+    let mut created = Vec::new();
+    for _ in 0..3 {
+        created.push(
+            sessions::ActiveModel::create_for_user(db, user.id, None, None, 3600)
+                .await
+                .unwrap(),
+        );
+    }
+    let foreign = sessions::ActiveModel::create_for_user(db, other.id, None, None, 3600)
+        .await
+        .unwrap();
+
+    // Only the owner can revoke a session.
+    assert!(!sessions::ActiveModel::revoke(db, user.id, foreign.pid).await.unwrap());
+    assert!(sessions::ActiveModel::revoke(db, user.id, created[0].pid).await.unwrap());
+
+    let revoked = sessions::ActiveModel::revoke_others(db, user.id, created[1].pid)
+        .await
+        .unwrap();
+    assert_eq!(revoked, 1);
+    let left = sessions::Model::list_by_owner(db, user.id).await.unwrap();
+    assert_eq!(left.len(), 1);
+    assert_eq!(left[0].pid, created[1].pid);
+
+    assert_eq!(sessions::ActiveModel::revoke_all(db, user.id).await.unwrap(), 1);
+    assert_eq!(sessions::Model::list_by_owner(db, other.id).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+#[serial]
+async fn resetting_the_password_revokes_all_sessions() {
+    let boot = boot_test::<App>().await.unwrap();
+    seed::<App>(&boot.app_context).await.unwrap();
+    let db = &boot.app_context.db;
+    let user = users::Model::find_by_pid(db, USER1_PID).await.unwrap();
+    sessions::ActiveModel::create_for_user(db, user.id, None, None, 3600)
+        .await
+        .unwrap();
+
+    let user = user.into_active_model().reset_password(db, "new-password").await.unwrap();
+    assert!(sessions::Model::list_by_owner(db, user.id).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+#[serial]
+async fn session_store_keeps_data_in_the_database() {
+    let boot = boot_test::<App>().await.unwrap();
+    let pool = SessionDbPool::new(boot.app_context.db.clone());
+    let table = "session_store";
+    pool.delete_all(table).await.unwrap();
+    let now = Local::now().timestamp();
+
+    pool.store("live", "{\"a\":1}", now + 3600, table).await.unwrap();
+    pool.store("live", "{\"a\":2}", now + 3600, table).await.unwrap();
+    pool.store("stale", "{}", now - 10, table).await.unwrap();
+
+    assert_eq!(pool.load("live", table).await.unwrap().as_deref(), Some("{\"a\":2}"));
+    assert_eq!(pool.load("stale", table).await.unwrap(), None);
+    assert!(pool.exists("live", table).await.unwrap());
+    assert!(!pool.exists("stale", table).await.unwrap());
+    assert_eq!(pool.count(table).await.unwrap(), 2);
+    assert_eq!(pool.get_ids(table).await.unwrap(), vec!["live".to_string()]);
+
+    assert_eq!(pool.delete_by_expiry(table).await.unwrap(), vec!["stale".to_string()]);
+    pool.delete_one_by_id("live", table).await.unwrap();
+    assert_eq!(pool.count(table).await.unwrap(), 0);
+}
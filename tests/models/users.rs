@@ -85,6 +85,32 @@ async fn handle_create_with_password_with_duplicate() {
     assert_debug_snapshot!(new_user);
 }
 
+#[tokio::test]
+#[serial]
+async fn names_are_unique_namespaces() {
+    let boot = boot_test::<App>()
+        .await
+        .expect("Failed to boot test application");
+    seed::<App>(&boot.app_context)
+        .await
+        .expect("Failed to seed database");
+
+    let register = |name: &str| RegisterParams {
+        email: format!("{name}@example.org"),
+        password: "1234".to_string(),
+        name: name.to_string(),
+    };
+    let db = &boot.app_context.db;
+    assert!(Model::create_with_password(db, &register("user1")).await.is_err());
+    assert!(Model::create_with_password(db, &register("two words")).await.is_err());
+    assert!(Model::create_with_password(db, &register("../etc")).await.is_err());
+
+    let user = Model::create_with_password(db, &register("new_user-1")).await.unwrap();
+    assert_eq!(user.namespace(), "new_user-1");
+    let found = Model::find_by_username(db, "new_user-1").await.unwrap();
+    assert_eq!(found.id, user.id);
+}
+
 #[tokio::test]
 #[serial]
 async fn can_find_by_email() {
@@ -123,7 +123,7 @@ async fn can_login_with_verify(#[case] test_name: &str, #[case] password: &str)
 
 #[tokio::test]
 #[serial]
-async fn can_login_without_verify() {
+async fn cannot_login_without_verify() {
     configure_insta!();
 
     request::<App, _, _>(|request, _ctx| async move {
@@ -158,15 +158,11 @@ async fn can_login_without_verify() {
 
         assert_eq!(
             login_response.status_code(),
-            200,
-            "Login request should succeed"
+            403,
+            "Login request should be rejected until the email is verified"
         );
 
-        with_settings!({
-            filters => cleanup_user_model()
-        }, {
-            assert_debug_snapshot!(login_response.text());
-        });
+        assert_debug_snapshot!(login_response.text());
     })
     .await;
 }
@@ -0,0 +1,140 @@
+use axum::http::{header, HeaderValue};
+use gitcrab::app::App;
+use loco_rs::{testing::prelude::*, TestServer};
+use serial_test::serial;
+
+use super::prepare_data;
+
+/// The cookies a page sets, as a `Cookie` header, along with the CSRF token
+/// its forms carry.
+async fn load_form(request: &TestServer, token: &str) -> (String, String) {
+    let (key, value) = prepare_data::auth_header(token);
+    let res = request.get("/tokens").add_header(key, value).await;
+    assert_eq!(res.status_code(), 200);
+
+    let cookies: Vec<String> = res
+        .headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .filter_map(|value| value.split(';').next())
+        .map(ToString::to_string)
+        .collect();
+    let csrf_cookie = cookies
+        .iter()
+        .find_map(|cookie| cookie.strip_prefix("csrf-token="))
+        .expect("the page should set the CSRF cookie")
+        .to_string();
+
+    let html = res.text();
+    assert!(html.contains(&format!(r#"name="csrf_token" value="{csrf_cookie}""#)));
+    assert!(html.contains(&format!(r#"<meta name="csrf-token" content="{csrf_cookie}">"#)));
+
+    let cookie_header = format!("{}; auth-token={token}", cookies.join("; "));
+    (cookie_header, csrf_cookie)
+}
+
+fn form(csrf_token: Option<&str>) -> serde_json::Value {
+    let mut form = serde_json::json!({"name": "laptop", "scopes": "repo:read", "expires_in_days": "7"});
+    if let Some(csrf_token) = csrf_token {
+        form["csrf_token"] = csrf_token.into();
+    }
+    form
+}
+
+#[tokio::test]
+#[serial]
+async fn cookie_authenticated_forms_need_the_token() {
+    request::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (cookies, csrf_token) = load_form(&request, &user.token).await;
+        let cookie = HeaderValue::from_str(&cookies).unwrap();
+
+        let res = request
+            .post("/tokens")
+            .add_header(header::COOKIE, cookie.clone())
+            .form(&form(None))
+            .await;
+        assert_eq!(res.status_code(), 403);
+
+        let res = request
+            .post("/tokens")
+            .add_header(header::COOKIE, cookie.clone())
+            .form(&form(Some("forged")))
+            .await;
+        assert_eq!(res.status_code(), 403);
+
+        let res = request
+            .delete("/tokens/1")
+            .add_header(header::COOKIE, cookie.clone())
+            .await;
+        assert_eq!(res.status_code(), 403);
+
+        // With the token the request gets past the check; the test
+        // configuration reads the JWT from the `Authorization` header, so it
+        // is then sent to log in.
+        let res = request
+            .post("/tokens")
+            .add_header(header::COOKIE, cookie.clone())
+            .form(&form(Some(&csrf_token)))
+            .await;
+        assert_eq!(res.status_code(), 303);
+
+        let res = request
+            .delete("/tokens/1")
+            .add_header(header::COOKIE, cookie)
+            .add_header(
+                axum::http::HeaderName::from_static("x-csrf-token"),
+                HeaderValue::from_str(&csrf_token).unwrap(),
+            )
+            .await;
+        assert_eq!(res.status_code(), 303);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn the_token_must_match_the_session() {
+    request::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (_, csrf_token) = load_form(&request, &user.token).await;
+
+        // A token without the session it belongs to is worthless, even when
+        // the double-submit cookie is forged to match.
+        let cookies = format!("csrf-token={csrf_token}; auth-token={}", user.token);
+        let res = request
+            .post("/tokens")
+            .add_header(header::COOKIE, HeaderValue::from_str(&cookies).unwrap())
+            .form(&form(Some(&csrf_token)))
+            .await;
+        assert_eq!(res.status_code(), 403);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn requests_from_other_sites_are_refused() {
+    request::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (key, value) = prepare_data::auth_header(&user.token);
+
+        let res = request
+            .post("/api/tokens")
+            .add_header(key.clone(), value.clone())
+            .add_header(header::ORIGIN, HeaderValue::from_static("https://evil.example"))
+            .json(&serde_json::json!({"name": "ci", "scopes": ["repo:read"]}))
+            .await;
+        assert_eq!(res.status_code(), 403);
+
+        // Requests with a token in `Authorization` need no CSRF token.
+        let res = request
+            .post("/api/tokens")
+            .add_header(key, value)
+            .json(&serde_json::json!({"name": "ci", "scopes": ["repo:read"]}))
+            .await;
+        assert_eq!(res.status_code(), 201);
+    })
+    .await;
+}
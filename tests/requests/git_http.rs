@@ -0,0 +1,124 @@
+use axum::http::{HeaderName, HeaderValue};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use flate2::{write::GzEncoder, Compression};
+use gitcrab::{
+    app::App,
+    models::{git_repos, users},
+};
+use loco_rs::testing::prelude::*;
+use sea_orm::{ActiveModelTrait, ActiveValue};
+use serial_test::serial;
+use std::io::Write;
+
+const USER1_PID: &str = "11111111-1111-1111-1111-111111111111";
+const USER1_API_KEY: &str = "lo-95ec80d7-cb60-4b70-9b4b-9ef74cb88758";
+
+fn basic_auth(password: &str) -> (HeaderName, HeaderValue) {
+    let encoded = STANDARD.encode(format!("git:{password}"));
+    (
+        HeaderName::from_static("authorization"),
+        HeaderValue::from_str(&format!("Basic {encoded}")).unwrap(),
+    )
+}
+
+#[tokio::test]
+#[serial]
+async fn challenges_anonymous_clients() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+
+        let res = request
+            .get("/user1/api.git/info/refs?service=git-upload-pack")
+            .await;
+
+        assert_eq!(res.status_code(), 401);
+        assert!(res.headers().contains_key("www-authenticate"));
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn rejects_invalid_credentials() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+
+        let (name, value) = basic_auth("not-a-key");
+        let res = request
+            .get("/user1/api.git/info/refs?service=git-receive-pack")
+            .add_header(name, value)
+            .await;
+
+        assert_eq!(res.status_code(), 401);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn reports_missing_repositories_to_authenticated_users() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+
+        let (name, value) = basic_auth(USER1_API_KEY);
+        let res = request
+            .get("/user1/missing.git/info/refs?service=git-upload-pack")
+            .add_header(name, value)
+            .await;
+
+        assert_eq!(res.status_code(), 404);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn only_serves_the_smart_protocol() {
+    request::<App, _, _>(|request, _ctx| async move {
+        let res = request.get("/user1/api.git/info/refs").await;
+        assert_eq!(res.status_code(), 403);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn refuses_compressed_requests_that_inflate_past_the_limit() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+        let owner = users::Model::find_by_pid(&ctx.db, USER1_PID).await.unwrap();
+        let path = std::path::PathBuf::from(env!("REPO_BASE_PATH"))
+            .join(owner.namespace())
+            .join("big.git");
+        let _ = std::fs::remove_dir_all(&path);
+        git2::Repository::init_bare(&path).unwrap();
+        git_repos::ActiveModel {
+            name: ActiveValue::set(Some("big".to_string())),
+            path: ActiveValue::set(Some(path.to_string_lossy().to_string())),
+            user_id: ActiveValue::set(owner.id),
+            ..Default::default()
+        }
+        .insert(&ctx.db)
+        .await
+        .unwrap();
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+        encoder.write_all(&vec![0; 64 * 1024 * 1024 + 1]).unwrap();
+        let (name, value) = basic_auth(USER1_API_KEY);
+        let res = request
+            .post(&format!("/{}/big.git/git-upload-pack", owner.namespace()))
+            .add_header(name, value)
+            .add_header(
+                HeaderName::from_static("content-encoding"),
+                HeaderValue::from_static("gzip"),
+            )
+            .bytes(encoder.finish().unwrap().into())
+            .await;
+        assert_eq!(res.status_code(), 413);
+
+        let _ = std::fs::remove_dir_all(
+            std::path::PathBuf::from(env!("REPO_BASE_PATH")).join(owner.namespace()),
+        );
+    })
+    .await;
+}
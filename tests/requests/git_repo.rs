@@ -0,0 +1,247 @@
+use gitcrab::{
+    app::App,
+    models::{
+        git_repos,
+        personal_access_tokens::{self, Scope},
+        repo_insights, users,
+    },
+    workers::insights::pending_key,
+};
+use loco_rs::{app::AppContext, testing::prelude::*};
+use sea_orm::{ActiveModelTrait, ActiveValue};
+use serial_test::serial;
+
+const USER1_PID: &str = "11111111-1111-1111-1111-111111111111";
+
+/// Creates an empty bare repository owned by `owner`, on disk and in the database.
+async fn create_repo(ctx: &AppContext, owner: &users::Model, name: &str, visibility: &str) -> git_repos::Model {
+    let path = std::path::PathBuf::from(env!("REPO_BASE_PATH"))
+        .join(owner.namespace())
+        .join(format!("{name}.git"));
+    let _ = std::fs::remove_dir_all(&path);
+    git2::Repository::init_bare(&path).unwrap();
+
+    git_repos::ActiveModel {
+        name: ActiveValue::set(Some(name.to_string())),
+        path: ActiveValue::set(Some(path.to_string_lossy().to_string())),
+        user_id: ActiveValue::set(owner.id),
+        visibility: ActiveValue::set(visibility.to_string()),
+        ..Default::default()
+    }
+    .insert(&ctx.db)
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+#[serial]
+async fn anonymous_visitors_can_read_public_repositories() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+        let owner = users::Model::find_by_pid(&ctx.db, USER1_PID).await.unwrap();
+        let public = create_repo(&ctx, &owner, "open-crate", "public").await;
+        let private = create_repo(&ctx, &owner, "secret-crate", "private").await;
+        let internal = create_repo(&ctx, &owner, "team-crate", "internal").await;
+
+        let res = request.get("/explore").await;
+        assert_eq!(res.status_code(), 200);
+        assert!(res.text().contains("open-crate"));
+        assert!(!res.text().contains("secret-crate"));
+        assert!(!res.text().contains("team-crate"));
+
+        let res = request.get(&format!("/git_repos/{}", public.id)).await;
+        assert_eq!(res.status_code(), 200);
+        let res = request.get(&format!("/git_repos/{}/branches", public.id)).await;
+        assert_eq!(res.status_code(), 200);
+        assert!(!res.text().contains("New branch"));
+
+        // Anything else asks the visitor to log in, whether it exists or not.
+        for id in [private.id, internal.id, 0] {
+            let res = request.get(&format!("/git_repos/{id}")).await;
+            assert_eq!(res.status_code(), 303);
+            assert_eq!(res.headers().get("location").unwrap(), "/login");
+        }
+
+        // Writing always requires logging in.
+        let res = request
+            .post(&format!("/git_repos/{}/branches", public.id))
+            .form(&serde_json::json!({"name": "feature", "from": "main"}))
+            .await;
+        assert_eq!(res.headers().get("location").unwrap(), "/login");
+
+        let res = request
+            .get("/user1/open-crate.git/info/refs?service=git-upload-pack")
+            .await;
+        assert_eq!(res.status_code(), 200);
+        let res = request
+            .get("/user1/open-crate.git/info/refs?service=git-receive-pack")
+            .await;
+        assert_eq!(res.status_code(), 401);
+        let res = request
+            .get("/user1/secret-crate.git/info/refs?service=git-upload-pack")
+            .await;
+        assert_eq!(res.status_code(), 401);
+
+        let _ = std::fs::remove_dir_all(
+            std::path::PathBuf::from(env!("REPO_BASE_PATH")).join(owner.namespace()),
+        );
+    })
+    .await;
+}
+
+/// Commits `files` onto `main` of the bare repository backing `item`.
+fn commit_files(item: &git_repos::Model, files: &[(&str, &str)], message: &str) -> git2::Oid {
+    let repo = git2::Repository::open_bare(item.path.as_deref().unwrap()).unwrap();
+    let parent = repo.find_reference("refs/heads/main").ok().and_then(|r| r.peel_to_commit().ok());
+    let mut tree = repo.treebuilder(parent.as_ref().map(|p| p.tree().unwrap()).as_ref()).unwrap();
+    for (name, content) in files {
+        let blob = repo.blob(content.as_bytes()).unwrap();
+        tree.insert(name, blob, 0o100_644).unwrap();
+    }
+    let tree = repo.find_tree(tree.write().unwrap()).unwrap();
+    let signature = git2::Signature::now("Alice", "alice@example.com").unwrap();
+    let parents: Vec<&git2::Commit> = parent.iter().collect();
+    let oid = repo
+        .commit(Some("refs/heads/main"), &signature, &signature, message, &tree, &parents)
+        .unwrap();
+    repo.set_head("refs/heads/main").unwrap();
+    oid
+}
+
+#[tokio::test]
+#[serial]
+async fn renders_the_repository_pages() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+        let owner = users::Model::find_by_pid(&ctx.db, USER1_PID).await.unwrap();
+        let item = create_repo(&ctx, &owner, "pages-crate", "public").await;
+        let first = commit_files(&item, &[("lib.rs", "fn first() {}\n")], "First");
+        let second = commit_files(&item, &[("lib.rs", "fn first() {}\nfn second() {}\n")], "Second");
+
+        let res = request.get(&format!("/git_repos/{}", item.id)).await;
+        assert_eq!(res.status_code(), 200);
+        assert!(res.text().contains("lib.rs"));
+        let res = request.get(&format!("/git_repos/{}/tree/{first}", item.id)).await;
+        assert_eq!(res.status_code(), 200);
+        let res = request.get(&format!("/git_repos/{}/blob/main/lib.rs", item.id)).await;
+        assert_eq!(res.status_code(), 200);
+        assert!(res.text().contains("second"));
+
+        let res = request.get(&format!("/git_repos/{}/raw/main/lib.rs", item.id)).await;
+        assert_eq!(res.status_code(), 200);
+        assert_eq!(res.text(), "fn first() {}\nfn second() {}\n");
+        let res = request.get(&format!("/git_repos/{}/raw/main/missing.rs", item.id)).await;
+        assert_eq!(res.status_code(), 404);
+
+        let res = request.get(&format!("/git_repos/{}/entries?ref=main", item.id)).await;
+        assert_eq!(res.status_code(), 200);
+        assert!(res.text().contains("lib.rs"));
+        let res = request.get(&format!("/git_repos/{}/file?path=lib.rs", item.id)).await;
+        assert_eq!(res.status_code(), 200);
+        assert!(res.text().contains("second"));
+
+        let res = request.get(&format!("/git_repos/{}/blame/main/lib.rs", item.id)).await;
+        assert_eq!(res.status_code(), 200);
+        assert!(res.text().contains("Second"));
+        let res = request.get(&format!("/git_repos/{}/blame/main/missing.rs", item.id)).await;
+        assert_eq!(res.status_code(), 404);
+
+        let res = request.get(&format!("/git_repos/{}/history/main/lib.rs", item.id)).await;
+        assert_eq!(res.status_code(), 200);
+        assert!(res.text().contains("First") && res.text().contains("Second"));
+
+        let res = request.get(&format!("/git_repos/{}/commits", item.id)).await;
+        assert_eq!(res.status_code(), 200);
+        assert!(res.text().contains("Second"));
+        let res = request.get(&format!("/git_repos/{}/commit/{second}?path=lib.rs", item.id)).await;
+        assert_eq!(res.status_code(), 200);
+        assert!(res.text().contains("second"));
+        let res = request.get(&format!("/git_repos/{}/commit/{}", item.id, "0".repeat(40))).await;
+        assert_eq!(res.status_code(), 404);
+
+        let res = request.get(&format!("/git_repos/{}/compare/{first}...main", item.id)).await;
+        assert_eq!(res.status_code(), 200);
+        assert!(res.text().contains("second"));
+        let res = request.get(&format!("/git_repos/{}/compare/{first}...nowhere", item.id)).await;
+        assert_eq!(res.status_code(), 404);
+
+        let _ = std::fs::remove_dir_all(
+            std::path::PathBuf::from(env!("REPO_BASE_PATH")).join(owner.namespace()),
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn lists_and_creates_references() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+        let owner = users::Model::find_by_pid(&ctx.db, USER1_PID).await.unwrap();
+        let item = create_repo(&ctx, &owner, "refs-crate", "public").await;
+        commit_files(&item, &[("lib.rs", "fn first() {}\n")], "First");
+        let (_, token) = personal_access_tokens::ActiveModel::create_for_user(
+            &ctx.db,
+            owner.id,
+            "test",
+            &[Scope::RepoWrite],
+            None,
+        )
+        .await
+        .unwrap();
+        let (name, value) = super::prepare_data::auth_header(&token);
+
+        let res = request
+            .post(&format!("/api/git_repos/{}/branches", item.id))
+            .add_header(name.clone(), value.clone())
+            .json(&serde_json::json!({"name": "feature", "from": "main"}))
+            .await;
+        assert_eq!(res.status_code(), 201);
+        let res = request
+            .post(&format!("/api/git_repos/{}/tags", item.id))
+            .add_header(name, value)
+            .json(&serde_json::json!({"name": "v1.0", "target": "feature", "message": "First release"}))
+            .await;
+        assert_eq!(res.status_code(), 201);
+
+        let res = request.get(&format!("/api/git_repos/{}/branches", item.id)).await;
+        assert_eq!(res.status_code(), 200);
+        assert!(res.text().contains("feature"));
+        let res = request.get(&format!("/git_repos/{}/tags", item.id)).await;
+        assert_eq!(res.status_code(), 200);
+        assert!(res.text().contains("v1.0"));
+
+        let _ = std::fs::remove_dir_all(
+            std::path::PathBuf::from(env!("REPO_BASE_PATH")).join(owner.namespace()),
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn views_queue_one_insights_update_at_a_time() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+        let owner = users::Model::find_by_pid(&ctx.db, USER1_PID).await.unwrap();
+        let item = create_repo(&ctx, &owner, "insights-crate", "public").await;
+        commit_files(&item, &[("lib.rs", "fn first() {}\n")], "First");
+
+        // While an update is queued, views do not queue another one.
+        ctx.cache.insert(&pending_key(item.id), "").await.unwrap();
+        let res = request.get(&format!("/git_repos/{}/insights", item.id)).await;
+        assert_eq!(res.status_code(), 200);
+        assert!(repo_insights::Model::find_by_repo(&ctx.db, item.id).await.unwrap().is_none());
+
+        // Workers run in the foreground in tests, so the update is done at once.
+        ctx.cache.remove(&pending_key(item.id)).await.unwrap();
+        request.get(&format!("/api/git_repos/{}/insights", item.id)).await;
+        assert!(repo_insights::Model::find_by_repo(&ctx.db, item.id).await.unwrap().is_some());
+        assert!(!ctx.cache.contains_key(&pending_key(item.id)).await.unwrap());
+
+        let _ = std::fs::remove_dir_all(
+            std::path::PathBuf::from(env!("REPO_BASE_PATH")).join(owner.namespace()),
+        );
+    })
+    .await;
+}
@@ -1,4 +1,11 @@
 mod auth;
+mod csrf;
+mod git_http;
+mod git_repo;
 mod prepare_data;
+mod sessions;
+mod throttle;
+mod token;
+mod two_factor;
 
 pub mod mysession;
\ No newline at end of file
@@ -26,11 +26,10 @@ pub async fn init_user_login(request: &TestServer, ctx: &AppContext) -> LoggedIn
         .await
         .unwrap();
 
-    let verify_payload = serde_json::json!({
-        "token": user.email_verification_token,
-    });
-
-    request.post("/api/auth/verify").json(&verify_payload).await;
+    let email_verification_token = user.email_verification_token.unwrap();
+    request
+        .get(&format!("/api/auth/verify/{email_verification_token}"))
+        .await;
 
     let response = request
         .post("/api/auth/login")
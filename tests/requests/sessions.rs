@@ -0,0 +1,152 @@
+use gitcrab::{app::App, models::users, views::auth::LoginResponse};
+use loco_rs::{testing::prelude::*, TestServer};
+use serial_test::serial;
+
+use super::prepare_data;
+
+/// Logs in again as the user of `prepare_data`, returning a fresh JWT.
+async fn log_in(request: &TestServer, user_agent: &str) -> String {
+    let res = request
+        .post("/api/auth/login")
+        .add_header(
+            axum::http::header::USER_AGENT,
+            axum::http::HeaderValue::from_str(user_agent).unwrap(),
+        )
+        .json(&serde_json::json!({"email": "test@loco.com", "password": "1234"}))
+        .await;
+    assert_eq!(res.status_code(), 200);
+    let login: LoginResponse = res.json();
+    login.token
+}
+
+async fn current_status(request: &TestServer, token: &str) -> u16 {
+    let (key, value) = prepare_data::auth_header(token);
+    request
+        .get("/api/auth/current")
+        .add_header(key, value)
+        .await
+        .status_code()
+        .as_u16()
+}
+
+#[tokio::test]
+#[serial]
+async fn can_list_sessions() {
+    request::<App, _, _>(|request, ctx| async move {
+        prepare_data::init_user_login(&request, &ctx).await;
+        let token = log_in(&request, "laptop-browser").await;
+        let (key, value) = prepare_data::auth_header(&token);
+
+        let res = request.get("/api/sessions").add_header(key, value).await;
+        assert_eq!(res.status_code(), 200);
+        let items: Vec<serde_json::Value> = res.json();
+        assert_eq!(items.len(), 2);
+        let current: Vec<_> = items.iter().filter(|item| item["current"] == true).collect();
+        assert_eq!(current.len(), 1);
+        assert_eq!(current[0]["user_agent"], "laptop-browser");
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn can_sign_out_other_sessions() {
+    request::<App, _, _>(|request, ctx| async move {
+        let first = prepare_data::init_user_login(&request, &ctx).await.token;
+        let second = log_in(&request, "phone").await;
+        assert_eq!(current_status(&request, &first).await, 200);
+
+        let (key, value) = prepare_data::auth_header(&second);
+        let res = request
+            .post("/api/sessions/sign-out-others")
+            .add_header(key, value)
+            .await;
+        assert_eq!(res.status_code(), 200);
+        assert_eq!(res.json::<serde_json::Value>()["revoked"], 1);
+
+        // The JWT of a revoked session is refused although it has not expired.
+        assert_eq!(current_status(&request, &first).await, 303);
+        assert_eq!(current_status(&request, &second).await, 200);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn can_revoke_a_session() {
+    request::<App, _, _>(|request, ctx| async move {
+        let first = prepare_data::init_user_login(&request, &ctx).await.token;
+        let second = log_in(&request, "phone").await;
+        let (key, value) = prepare_data::auth_header(&second);
+
+        let items: Vec<serde_json::Value> = request
+            .get("/api/sessions")
+            .add_header(key.clone(), value.clone())
+            .await
+            .json();
+        let other = items.iter().find(|item| item["current"] == false).unwrap();
+        let res = request
+            .delete(&format!("/api/sessions/{}", other["pid"].as_str().unwrap()))
+            .add_header(key.clone(), value.clone())
+            .await;
+        assert_eq!(res.status_code(), 200);
+        assert_eq!(current_status(&request, &first).await, 303);
+
+        let res = request
+            .delete(&format!("/api/sessions/{}", other["pid"].as_str().unwrap()))
+            .add_header(key, value)
+            .await;
+        assert_eq!(res.status_code(), 404);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn logout_ends_the_session() {
+    request::<App, _, _>(|request, ctx| async move {
+        let token = prepare_data::init_user_login(&request, &ctx).await.token;
+        let (key, value) = prepare_data::auth_header(&token);
+
+        let res = request.post("/api/auth/logout").add_header(key, value).await;
+        assert_eq!(res.status_code(), 200);
+        assert_eq!(current_status(&request, &token).await, 303);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn password_reset_ends_all_sessions() {
+    request::<App, _, _>(|request, ctx| async move {
+        let token = prepare_data::init_user_login(&request, &ctx).await.token;
+
+        request
+            .post("/api/auth/forgot")
+            .json(&serde_json::json!({"email": "test@loco.com"}))
+            .await;
+        let user = users::Model::find_by_email(&ctx.db, "test@loco.com").await.unwrap();
+        let res = request
+            .post("/api/auth/reset")
+            .json(&serde_json::json!({"token": user.reset_token, "password": "new-password"}))
+            .await;
+        assert_eq!(res.status_code(), 200);
+
+        assert_eq!(current_status(&request, &token).await, 303);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn sessions_page_lists_sessions() {
+    request::<App, _, _>(|request, ctx| async move {
+        let token = prepare_data::init_user_login(&request, &ctx).await.token;
+        let (key, value) = prepare_data::auth_header(&token);
+
+        let res = request.get("/sessions").add_header(key, value).await;
+        assert_eq!(res.status_code(), 200);
+        assert!(res.text().contains("this session"));
+    })
+    .await;
+}
@@ -0,0 +1,134 @@
+use gitcrab::{app::App, models::users};
+use loco_rs::testing::prelude::*;
+use sea_orm::{ActiveModelTrait, ActiveValue, IntoActiveModel};
+use serial_test::serial;
+
+use super::prepare_data;
+
+const USER_EMAIL: &str = "test@loco.com";
+
+fn login(email: &str, password: &str) -> serde_json::Value {
+    serde_json::json!({"email": email, "password": password})
+}
+
+#[tokio::test]
+#[serial]
+async fn locks_the_account_and_emails_its_owner() {
+    request::<App, _, _>(|request, ctx| async move {
+        let logged_in = prepare_data::init_user_login(&request, &ctx).await;
+        let mut user = logged_in.user.into_active_model();
+        user.failed_login_attempts = ActiveValue::set(9);
+        user.update(&ctx.db).await.unwrap();
+        let sent_before = ctx.mailer.as_ref().unwrap().deliveries().count;
+
+        let res = request.post("/api/auth/login").json(&login(USER_EMAIL, "wrong")).await;
+        assert_eq!(res.status_code(), 303);
+
+        let user = users::Model::find_by_email(&ctx.db, USER_EMAIL).await.unwrap();
+        assert!(user.locked_for().is_some());
+        assert_eq!(user.failed_login_attempts, 0);
+        assert_eq!(ctx.mailer.as_ref().unwrap().deliveries().count, sent_before + 1);
+
+        // Even the right password is refused while the account is locked, and
+        // the answer is the one an unknown email gets.
+        let res = request.post("/api/auth/login").json(&login(USER_EMAIL, "1234")).await;
+        let unknown = request.post("/api/auth/login").json(&login("nobody@loco.com", "1234")).await;
+        assert_eq!(res.status_code(), unknown.status_code());
+        assert_eq!(res.text(), unknown.text());
+        assert!(!res.headers().contains_key("retry-after"));
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn magic_links_do_not_get_around_a_lockout() {
+    request::<App, _, _>(|request, ctx| async move {
+        let logged_in = prepare_data::init_user_login(&request, &ctx).await;
+        let user = logged_in.user.into_active_model().create_magic_link(&ctx.db).await.unwrap();
+        let token = user.magic_link_token.clone().unwrap();
+        let mut user = user.into_active_model();
+        let until = chrono::Local::now() + chrono::Duration::minutes(10);
+        user.locked_until = ActiveValue::set(Some(until.into()));
+        user.update(&ctx.db).await.unwrap();
+
+        let res = request.get(&format!("/api/auth/magic-link/{token}")).await;
+        assert_ne!(res.status_code(), 200);
+        assert!(!res.text().contains("token"));
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn successful_login_forgets_failures() {
+    request::<App, _, _>(|request, ctx| async move {
+        prepare_data::init_user_login(&request, &ctx).await;
+
+        for _ in 0..2 {
+            request.post("/api/auth/login").json(&login(USER_EMAIL, "wrong")).await;
+        }
+        let user = users::Model::find_by_email(&ctx.db, USER_EMAIL).await.unwrap();
+        assert_eq!(user.failed_login_attempts, 2);
+
+        let res = request.post("/api/auth/login").json(&login(USER_EMAIL, "1234")).await;
+        assert_eq!(res.status_code(), 200);
+        let user = users::Model::find_by_email(&ctx.db, USER_EMAIL).await.unwrap();
+        assert_eq!(user.failed_login_attempts, 0);
+        assert!(user.locked_until.is_none());
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn throttles_an_address_guessing_accounts() {
+    request::<App, _, _>(|request, _ctx| async move {
+        // Unknown accounts have no counter of their own, only the address does.
+        for i in 0..11 {
+            let res = request
+                .post("/api/auth/login")
+                .json(&login(&format!("nobody{i}@loco.com"), "wrong"))
+                .await;
+            assert_eq!(res.status_code(), 303);
+        }
+
+        let res = request.post("/api/auth/login").json(&login("nobody@loco.com", "wrong")).await;
+        assert_eq!(res.status_code(), 429);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn rate_limits_password_reset_emails() {
+    request::<App, _, _>(|request, ctx| async move {
+        prepare_data::init_user_login(&request, &ctx).await;
+
+        let forgot = serde_json::json!({"email": USER_EMAIL});
+        for _ in 0..5 {
+            let res = request.post("/api/auth/forgot").json(&forgot).await;
+            assert_eq!(res.status_code(), 200);
+        }
+        let res = request.post("/api/auth/forgot").json(&forgot).await;
+        assert_eq!(res.status_code(), 429);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn rate_limits_registrations() {
+    request::<App, _, _>(|request, _ctx| async move {
+        let register = |i: u32| {
+            serde_json::json!({"name": format!("loco{i}"), "email": format!("user{i}@loco.com"), "password": "1234"})
+        };
+        for i in 0..10 {
+            let res = request.post("/api/auth/register").json(&register(i)).await;
+            assert_eq!(res.status_code(), 200);
+        }
+        let res = request.post("/api/auth/register").json(&register(10)).await;
+        assert_eq!(res.status_code(), 429);
+    })
+    .await;
+}
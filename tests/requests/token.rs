@@ -0,0 +1,246 @@
+use axum::http::{HeaderName, HeaderValue};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use gitcrab::{
+    app::App,
+    models::{
+        git_repos,
+        personal_access_tokens::{self, Scope},
+        users,
+    },
+};
+use loco_rs::{app::AppContext, testing::prelude::*};
+use sea_orm::{ActiveModelTrait, ActiveValue};
+use serial_test::serial;
+
+use super::prepare_data;
+
+const USER1_PID: &str = "11111111-1111-1111-1111-111111111111";
+
+fn basic_auth(password: &str) -> (HeaderName, HeaderValue) {
+    let encoded = STANDARD.encode(format!("git:{password}"));
+    (
+        HeaderName::from_static("authorization"),
+        HeaderValue::from_str(&format!("Basic {encoded}")).unwrap(),
+    )
+}
+
+async fn create_token(ctx: &AppContext, user: &users::Model, scopes: &[Scope]) -> String {
+    personal_access_tokens::ActiveModel::create_for_user(&ctx.db, user.id, "test", scopes, None)
+        .await
+        .unwrap()
+        .1
+}
+
+#[tokio::test]
+#[serial]
+async fn can_manage_tokens_through_the_api() {
+    request::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+
+        let res = request
+            .post("/api/tokens")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({"name": "ci", "scopes": ["repo:read"], "expires_in_days": 30}))
+            .await;
+        assert_eq!(res.status_code(), 201);
+        let created: serde_json::Value = res.json();
+        let secret = created["token"].as_str().unwrap().to_string();
+        assert!(secret.starts_with("gcp_"));
+        assert_eq!(created["scopes"], serde_json::json!(["repo:read"]));
+        assert!(created["expires_at"].is_string());
+        assert!(created.get("token_hash").is_none());
+
+        let res = request
+            .post("/api/tokens")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({"name": "ci", "scopes": ["bogus"]}))
+            .await;
+        assert_eq!(res.status_code(), 400);
+
+        // The token authenticates as its owner...
+        let (token_key, token_value) = prepare_data::auth_header(&secret);
+        let res = request
+            .get("/api/auth/current")
+            .add_header(token_key.clone(), token_value.clone())
+            .await;
+        assert_eq!(res.status_code(), 200);
+        assert!(res.text().contains(&user.user.pid.to_string()));
+
+        // ...but only within its scopes.
+        let res = request
+            .get("/api/tokens")
+            .add_header(token_key.clone(), token_value.clone())
+            .await;
+        assert_eq!(res.status_code(), 403);
+
+        let res = request
+            .get("/api/tokens")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        assert_eq!(res.status_code(), 200);
+        let list: Vec<serde_json::Value> = res.json();
+        assert_eq!(list.len(), 1);
+        assert!(list[0].get("token").is_none());
+        assert!(list[0]["last_used_at"].is_string());
+
+        let res = request
+            .delete(&format!("/api/tokens/{}", list[0]["id"]))
+            .add_header(auth_key, auth_value)
+            .await;
+        assert_eq!(res.status_code(), 200);
+
+        let res = request
+            .get("/api/auth/current")
+            .add_header(token_key, token_value)
+            .await;
+        assert_ne!(res.status_code(), 200);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn git_http_enforces_token_scopes() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+        let owner = users::Model::find_by_pid(&ctx.db, USER1_PID).await.unwrap();
+        let path = std::path::PathBuf::from(env!("REPO_BASE_PATH"))
+            .join(owner.namespace())
+            .join("token-crate.git");
+        let _ = std::fs::remove_dir_all(&path);
+        git2::Repository::init_bare(&path).unwrap();
+        git_repos::ActiveModel {
+            name: ActiveValue::set(Some("token-crate".to_string())),
+            path: ActiveValue::set(Some(path.to_string_lossy().to_string())),
+            user_id: ActiveValue::set(owner.id),
+            ..Default::default()
+        }
+        .insert(&ctx.db)
+        .await
+        .unwrap();
+
+        let read = create_token(&ctx, &owner, &[Scope::RepoRead]).await;
+        let write = create_token(&ctx, &owner, &[Scope::RepoWrite]).await;
+        let ssh = create_token(&ctx, &owner, &[Scope::SshKeys]).await;
+
+        let refs = |service: &str| format!("/user1/token-crate.git/info/refs?service={service}");
+        let cases = [
+            (&read, "git-upload-pack", 200),
+            (&read, "git-receive-pack", 403),
+            (&write, "git-upload-pack", 200),
+            (&write, "git-receive-pack", 200),
+            (&ssh, "git-upload-pack", 403),
+        ];
+        for (token, service, status) in cases {
+            let (name, value) = basic_auth(token);
+            let res = request.get(&refs(service)).add_header(name, value).await;
+            assert_eq!(res.status_code(), status, "{service}");
+        }
+
+        let (name, value) = basic_auth("gcp_not-a-token");
+        let res = request.get(&refs("git-upload-pack")).add_header(name, value).await;
+        assert_eq!(res.status_code(), 401);
+
+        let _ = std::fs::remove_dir_all(&path);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn tokens_without_repo_read_browse_like_anonymous_visitors() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+        let owner = users::Model::find_by_pid(&ctx.db, USER1_PID).await.unwrap();
+        let mut ids = Vec::new();
+        for (name, visibility) in [("open-crate", "public"), ("secret-crate", "private")] {
+            let path = std::path::PathBuf::from(env!("REPO_BASE_PATH"))
+                .join(owner.namespace())
+                .join(format!("{name}.git"));
+            let _ = std::fs::remove_dir_all(&path);
+            git2::Repository::init_bare(&path).unwrap();
+            let repo = git_repos::ActiveModel {
+                name: ActiveValue::set(Some(name.to_string())),
+                path: ActiveValue::set(Some(path.to_string_lossy().to_string())),
+                user_id: ActiveValue::set(owner.id),
+                visibility: ActiveValue::set(visibility.to_string()),
+                ..Default::default()
+            }
+            .insert(&ctx.db)
+            .await
+            .unwrap();
+            ids.push(repo.id);
+        }
+
+        let ssh = create_token(&ctx, &owner, &[Scope::SshKeys]).await;
+        let (name, value) = prepare_data::auth_header(&ssh);
+        let res = request
+            .get(&format!("/git_repos/{}", ids[0]))
+            .add_header(name.clone(), value.clone())
+            .await;
+        assert_eq!(res.status_code(), 200);
+
+        // Even the owner's own private repository stays hidden from the token.
+        let res = request.get(&format!("/git_repos/{}", ids[1])).add_header(name, value).await;
+        assert_eq!(res.status_code(), 303);
+        assert_eq!(res.headers().get("location").unwrap(), "/login");
+
+        let _ = std::fs::remove_dir_all(
+            std::path::PathBuf::from(env!("REPO_BASE_PATH")).join(owner.namespace()),
+        );
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn ssh_key_pages_need_the_ssh_keys_scope() {
+    request::<App, _, _>(|request, ctx| async move {
+        seed::<App>(&ctx).await.unwrap();
+        let owner = users::Model::find_by_pid(&ctx.db, USER1_PID).await.unwrap();
+
+        let read = create_token(&ctx, &owner, &[Scope::RepoRead]).await;
+        let (name, value) = prepare_data::auth_header(&read);
+        let res = request.get("/sshes/new").add_header(name, value).await;
+        assert_eq!(res.status_code(), 403);
+
+        let ssh = create_token(&ctx, &owner, &[Scope::SshKeys]).await;
+        let (name, value) = prepare_data::auth_header(&ssh);
+        let res = request.get("/sshes/new").add_header(name, value).await;
+        assert_eq!(res.status_code(), 200);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn can_manage_tokens_through_the_web() {
+    request::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+
+        let res = request
+            .post("/tokens")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .form(&serde_json::json!({"name": "laptop", "scopes": "repo:write", "expires_in_days": ""}))
+            .await;
+        assert_eq!(res.status_code(), 200);
+        assert!(res.text().contains("gcp_"));
+        assert!(res.text().contains("never expires"));
+
+        let res = request
+            .post("/tokens")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .form(&serde_json::json!({"name": "", "scopes": "repo:write", "expires_in_days": "7"}))
+            .await;
+        assert_eq!(res.status_code(), 303);
+        assert!(res.headers().get("location").unwrap().to_str().unwrap().contains("error="));
+
+        let res = request.get("/tokens").add_header(auth_key, auth_value).await;
+        assert_eq!(res.status_code(), 200);
+        assert!(res.text().contains("laptop"));
+        assert!(res.text().contains("repo:write"));
+    })
+    .await;
+}
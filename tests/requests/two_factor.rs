@@ -0,0 +1,258 @@
+use chrono::Local;
+use gitcrab::{
+    app::App,
+    models::users,
+    services::totp_service::{code_at, step_at},
+    views::auth::LoginResponse,
+};
+use loco_rs::testing::prelude::*;
+use sea_orm::{ActiveModelTrait, ActiveValue, IntoActiveModel};
+use serial_test::serial;
+
+use super::prepare_data;
+
+/// The code the authenticator app shows `steps` periods from now.
+fn code(secret: &str, steps: u64) -> String {
+    let now = u64::try_from(Local::now().timestamp()).unwrap();
+    code_at(secret, step_at(now) + steps).unwrap()
+}
+
+#[tokio::test]
+#[serial]
+async fn can_log_in_with_two_factor_authentication() {
+    request::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+
+        // Enrolling takes a code from the app before anything changes.
+        let res = request
+            .post("/api/auth/2fa/setup")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        assert_eq!(res.status_code(), 200);
+        let setup: serde_json::Value = res.json();
+        let secret = setup["secret"].as_str().unwrap().to_string();
+        assert!(setup["provisioning_uri"].as_str().unwrap().starts_with("otpauth://totp/"));
+
+        let res = request
+            .post("/api/auth/2fa/enable")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({"code": "000000"}))
+            .await;
+        assert_eq!(res.status_code(), 400);
+        let first_code = code(&secret, 0);
+        let res = request
+            .post("/api/auth/2fa/enable")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({"code": first_code}))
+            .await;
+        assert_eq!(res.status_code(), 200);
+        let codes: serde_json::Value = res.json();
+        let recovery_codes = codes["recovery_codes"].as_array().unwrap();
+        assert_eq!(recovery_codes.len(), 10);
+        let recovery_code = recovery_codes[0].as_str().unwrap().to_string();
+
+        // The password alone now only gets a challenge.
+        let login = || async {
+            let res = request
+                .post("/api/auth/login")
+                .json(&serde_json::json!({"email": "test@loco.com", "password": "1234"}))
+                .await;
+            assert_eq!(res.status_code(), 200);
+            let challenge: serde_json::Value = res.json();
+            assert_eq!(challenge["two_factor_required"], true);
+            assert!(challenge.get("token").is_none());
+            challenge["challenge_token"].as_str().unwrap().to_string()
+        };
+        let second_step = |challenge: String, code: String| {
+            request
+                .post("/api/auth/login/2fa")
+                .json(&serde_json::json!({"challenge_token": challenge, "code": code}))
+        };
+
+        let challenge = login().await;
+        let res = second_step(challenge.clone(), "000000".to_string()).await;
+        assert_ne!(res.status_code(), 200);
+        // The code used to enable two-factor authentication cannot be replayed.
+        let res = second_step(challenge.clone(), first_code).await;
+        assert_ne!(res.status_code(), 200);
+        let res = second_step(challenge.clone(), code(&secret, 1)).await;
+        assert_eq!(res.status_code(), 200);
+        let login_response: LoginResponse = serde_json::from_str(&res.text()).unwrap();
+        assert_eq!(login_response.pid, user.user.pid.to_string());
+
+        // Challenges are single use.
+        let res = second_step(challenge, code(&secret, 1)).await;
+        assert_ne!(res.status_code(), 200);
+
+        // Recovery codes work once.
+        let res = second_step(login().await, recovery_code.to_uppercase()).await;
+        assert_eq!(res.status_code(), 200);
+        let res = second_step(login().await, recovery_code.clone()).await;
+        assert_ne!(res.status_code(), 200);
+
+        let res = request
+            .get("/api/auth/2fa")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        let status: serde_json::Value = res.json();
+        assert_eq!(status["enabled"], true);
+        assert_eq!(status["recovery_codes_left"], 9);
+
+        // Disabling takes the password and a second factor.
+        let res = request
+            .post("/api/auth/2fa/disable")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({"password": "wrong", "code": recovery_codes[1]}))
+            .await;
+        assert_eq!(res.status_code(), 400);
+        let res = request
+            .post("/api/auth/2fa/disable")
+            .add_header(auth_key, auth_value)
+            .json(&serde_json::json!({"password": "1234", "code": recovery_codes[1]}))
+            .await;
+        assert_eq!(res.status_code(), 200);
+
+        let res = request
+            .post("/api/auth/login")
+            .json(&serde_json::json!({"email": "test@loco.com", "password": "1234"}))
+            .await;
+        let login_response: LoginResponse = serde_json::from_str(&res.text()).unwrap();
+        assert!(!login_response.token.is_empty());
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn challenges_expire_after_too_many_wrong_codes() {
+    request::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (user, _) = user
+            .user
+            .into_active_model()
+            .start_totp_enrollment(&ctx.db)
+            .await
+            .unwrap()
+            .into_active_model()
+            .enable_totp(&ctx.db, 0)
+            .await
+            .unwrap();
+        let secret = user.totp_secret.clone().unwrap();
+
+        let res = request
+            .post("/api/auth/login")
+            .json(&serde_json::json!({"email": "test@loco.com", "password": "1234"}))
+            .await;
+        let challenge: serde_json::Value = res.json();
+        let challenge = challenge["challenge_token"].as_str().unwrap().to_string();
+
+        for _ in 0..users::TOTP_CHALLENGE_MAX_ATTEMPTS {
+            request
+                .post("/api/auth/login/2fa")
+                .json(&serde_json::json!({"challenge_token": challenge, "code": "000000"}))
+                .await;
+        }
+        let res = request
+            .post("/api/auth/login/2fa")
+            .json(&serde_json::json!({"challenge_token": challenge, "code": code(&secret, 0)}))
+            .await;
+        assert_ne!(res.status_code(), 200);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn wrong_codes_count_as_failed_logins() {
+    request::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (user, _) = user
+            .user
+            .into_active_model()
+            .start_totp_enrollment(&ctx.db)
+            .await
+            .unwrap()
+            .into_active_model()
+            .enable_totp(&ctx.db, 0)
+            .await
+            .unwrap();
+        let secret = user.totp_secret.clone().unwrap();
+        let mut user = user.into_active_model();
+        user.failed_login_attempts = ActiveValue::set(9);
+        user.update(&ctx.db).await.unwrap();
+        let sent_before = ctx.mailer.as_ref().unwrap().deliveries().count;
+
+        // The right password alone does not forget earlier failures.
+        let res = request
+            .post("/api/auth/login")
+            .json(&serde_json::json!({"email": "test@loco.com", "password": "1234"}))
+            .await;
+        let challenge: serde_json::Value = res.json();
+        let challenge = challenge["challenge_token"].as_str().unwrap().to_string();
+        let user = users::Model::find_by_email(&ctx.db, "test@loco.com").await.unwrap();
+        assert_eq!(user.failed_login_attempts, 9);
+
+        let res = request
+            .post("/api/auth/login/2fa")
+            .json(&serde_json::json!({"challenge_token": challenge, "code": "000000"}))
+            .await;
+        assert_ne!(res.status_code(), 200);
+        let user = users::Model::find_by_email(&ctx.db, "test@loco.com").await.unwrap();
+        assert!(user.locked_for().is_some());
+        assert_eq!(ctx.mailer.as_ref().unwrap().deliveries().count, sent_before + 1);
+
+        // Not even the right code gets past the lockout.
+        let res = request
+            .post("/api/auth/login/2fa")
+            .json(&serde_json::json!({"challenge_token": challenge, "code": code(&secret, 0)}))
+            .await;
+        assert_ne!(res.status_code(), 200);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn can_manage_two_factor_authentication_through_the_web() {
+    request::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+
+        let res = request
+            .get("/two_factor")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        assert_eq!(res.status_code(), 200);
+        assert!(res.text().contains("disabled"));
+
+        let res = request
+            .post("/two_factor/setup")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        assert_eq!(res.status_code(), 200);
+        assert!(res.text().contains("<svg"));
+        let user = users::Model::find_by_pid(&ctx.db, &user.user.pid.to_string()).await.unwrap();
+        let secret = user.totp_secret.unwrap();
+
+        let res = request
+            .post("/two_factor/enable")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .form(&serde_json::json!({"code": "000000"}))
+            .await;
+        assert_eq!(res.status_code(), 303);
+        assert!(res.headers().get("location").unwrap().to_str().unwrap().contains("error="));
+
+        let res = request
+            .post("/two_factor/enable")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .form(&serde_json::json!({"code": code(&secret, 0)}))
+            .await;
+        assert_eq!(res.status_code(), 200);
+        assert!(res.text().contains("Recovery codes"));
+
+        let res = request.get("/two_factor").add_header(auth_key, auth_value).await;
+        assert!(res.text().contains("10 recovery codes left"));
+    })
+    .await;
+}
@@ -0,0 +1,74 @@
+use std::io::{Cursor, Read};
+
+use flate2::read::GzDecoder;
+use gitcrab::services::archive_service::{
+    archive_prefix, build_cached_archive, cache_path, write_archive, ArchiveFormat, ArchiveTarget,
+};
+
+use super::test_repo::TestRepo;
+
+fn target(test: &TestRepo, format: ArchiveFormat) -> ArchiveTarget {
+    let commit = test.repo.head().unwrap().peel_to_commit().unwrap().id().to_string();
+    ArchiveTarget {
+        repo_path: test.repo.path().to_path_buf(),
+        prefix: archive_prefix("api", &commit),
+        commit,
+        format,
+    }
+}
+
+#[test]
+fn parses_archive_file_names() {
+    assert_eq!(
+        ArchiveFormat::from_file_name("release/1.0.tar.gz"),
+        Some(("release/1.0", ArchiveFormat::TarGz))
+    );
+    assert_eq!(ArchiveFormat::from_file_name("main.zip"), Some(("main", ArchiveFormat::Zip)));
+    assert_eq!(ArchiveFormat::from_file_name(".zip"), None);
+    assert_eq!(ArchiveFormat::from_file_name("main.rar"), None);
+}
+
+#[test]
+fn writes_tar_gz_under_the_prefix() {
+    let mut test = TestRepo::new();
+    test.commit(&[("README.md", Some("hello\n")), ("src/lib.rs", Some("// lib\n"))], "Initial commit");
+    let target = target(&test, ArchiveFormat::TarGz);
+    assert!(target.prefix.starts_with("api-") && target.prefix.len() == 11);
+
+    let bytes = write_archive(&target, Vec::new()).unwrap();
+    let mut archive = tar::Archive::new(GzDecoder::new(Cursor::new(bytes)));
+    let mut files = Vec::new();
+    for entry in archive.entries().unwrap() {
+        let mut entry = entry.unwrap();
+        let path = entry.path().unwrap().to_string_lossy().to_string();
+        let mut content = String::new();
+        entry.read_to_string(&mut content).unwrap();
+        files.push((path, content));
+    }
+
+    let prefix = &target.prefix;
+    assert!(files.contains(&(format!("{prefix}/"), String::new())));
+    assert!(files.contains(&(format!("{prefix}/src/"), String::new())));
+    assert!(files.contains(&(format!("{prefix}/README.md"), "hello\n".to_string())));
+    assert!(files.contains(&(format!("{prefix}/src/lib.rs"), "// lib\n".to_string())));
+}
+
+#[test]
+fn caches_zip_archives_by_commit() {
+    let mut test = TestRepo::new();
+    test.commit(&[("README.md", Some("hello\n"))], "Initial commit");
+    let target = target(&test, ArchiveFormat::Zip);
+
+    let cache_dir = test.path.join("archive-cache");
+    let path = cache_path(&cache_dir, &target);
+    build_cached_archive(&target, &path).unwrap();
+    assert!(path.ends_with(format!("{}/{}.zip", target.commit, target.prefix)));
+
+    let mut zip = zip::ZipArchive::new(std::fs::File::open(&path).unwrap()).unwrap();
+    let mut content = String::new();
+    zip.by_name(&format!("{}/README.md", target.prefix))
+        .unwrap()
+        .read_to_string(&mut content)
+        .unwrap();
+    assert_eq!(content, "hello\n");
+}
@@ -0,0 +1,68 @@
+use gitcrab::services::{
+    blame_service::{blame_file, BlameError},
+    repo_history_service::relative_time,
+    repo_retrive_service::FileLimits,
+};
+
+use super::test_repo::TestRepo;
+
+#[test]
+fn groups_lines_by_the_commit_that_last_changed_them() {
+    let mut test = TestRepo::new();
+    let first = test.commit(&[("notes.txt", Some("one\ntwo\nthree\nfour\n"))], "Add notes");
+    let second = test.commit_as(
+        "Bob",
+        "bob@example.com",
+        &[("notes.txt", Some("one\n2\n3\nfour\n"))],
+        "Use digits",
+    );
+
+    let commit = test.repo.find_commit(second).unwrap();
+    let blame = blame_file(&test.repo, &commit, "notes.txt", &FileLimits::default(), 1_800_000_000).unwrap();
+
+    let hunks: Vec<_> = blame
+        .hunks
+        .iter()
+        .map(|h| (h.commit.oid.clone(), h.start_line, h.lines.clone()))
+        .collect();
+    assert_eq!(
+        hunks,
+        vec![
+            (first.to_string(), 1, vec!["one".to_string()]),
+            (second.to_string(), 2, vec!["2".to_string(), "3".to_string()]),
+            (first.to_string(), 4, vec!["four".to_string()]),
+        ]
+    );
+    assert_eq!(blame.hunks[1].commit.author_name, "Bob");
+    assert_eq!(blame.hunks[1].parent, Some(first.to_string()));
+    // The root commit has nothing to go back to.
+    assert_eq!(blame.hunks[0].parent, None);
+}
+
+#[test]
+fn blames_as_of_an_older_commit() {
+    let mut test = TestRepo::new();
+    let first = test.commit(&[("notes.txt", Some("one\n"))], "Add notes");
+    test.commit(&[("notes.txt", Some("uno\n"))], "Translate");
+
+    let commit = test.repo.find_commit(first).unwrap();
+    let blame = blame_file(&test.repo, &commit, "notes.txt", &FileLimits::default(), 0).unwrap();
+    assert_eq!(blame.hunks.len(), 1);
+    assert_eq!(blame.hunks[0].commit.oid, first.to_string());
+    assert_eq!(blame.hunks[0].lines, vec!["one".to_string()]);
+
+    assert!(matches!(
+        blame_file(&test.repo, &commit, "missing.txt", &FileLimits::default(), 0),
+        Err(BlameError::NotFound(_))
+    ));
+}
+
+#[test]
+fn describes_times_relative_to_now() {
+    let now = 1_700_000_000;
+    assert_eq!(relative_time(now - 30, now), "just now");
+    assert_eq!(relative_time(now - 60, now), "1 minute ago");
+    assert_eq!(relative_time(now - 3 * 3600, now), "3 hours ago");
+    assert_eq!(relative_time(now - 40 * 24 * 3600, now), "1 month ago");
+    assert_eq!(relative_time(now + 100, now), "just now");
+}
@@ -0,0 +1,112 @@
+use gitcrab::services::diff_service::{compare, diff_commit, parse_compare_spec, DiffLimits};
+
+use super::test_repo::TestRepo;
+
+const LONG_FILE: &str = "alpha\nbravo\ncharlie\ndelta\necho\nfoxtrot\ngolf\nhotel\nindia\njuliet\n";
+
+#[test]
+fn renders_hunks_with_line_numbers() {
+    let mut test = TestRepo::new();
+    test.commit(&[("notes.txt", Some("one\ntwo\nthree\n"))], "Add notes");
+    let oid = test.commit(&[("notes.txt", Some("one\n2\nthree\n"))], "Edit notes");
+
+    let commit = test.repo.find_commit(oid).unwrap();
+    let diff = diff_commit(&test.repo, &commit, &DiffLimits::default()).unwrap();
+    let file = &diff.files[0];
+    assert_eq!(file.hunks.len(), 1);
+
+    let lines: Vec<_> = file.hunks[0]
+        .lines
+        .iter()
+        .map(|l| (l.kind, l.old_lineno, l.new_lineno, l.content.as_str()))
+        .collect();
+    assert_eq!(
+        lines,
+        vec![
+            ("context", Some(1), Some(1), "one"),
+            ("deletion", Some(2), None, "two"),
+            ("addition", None, Some(2), "2"),
+            ("context", Some(3), Some(3), "three"),
+        ]
+    );
+
+    // The split view pairs the removed line with its replacement.
+    let changed = &file.hunks[0].rows[1];
+    assert_eq!(changed.left.as_ref().unwrap().content, "two");
+    assert_eq!(changed.right.as_ref().unwrap().content, "2");
+}
+
+#[test]
+fn detects_renames_and_binary_files() {
+    let mut test = TestRepo::new();
+    test.commit(&[("src/old.txt", Some(LONG_FILE))], "Add file");
+    let oid = test.commit(
+        &[
+            ("src/old.txt", None),
+            ("src/new.txt", Some(LONG_FILE)),
+            ("logo.png", Some("\u{0}\u{1}\u{2}binary")),
+        ],
+        "Rename and add a binary",
+    );
+
+    let commit = test.repo.find_commit(oid).unwrap();
+    let diff = diff_commit(&test.repo, &commit, &DiffLimits::default()).unwrap();
+
+    let renamed = diff.files.iter().find(|f| f.path == "src/new.txt").unwrap();
+    assert_eq!(renamed.status, "renamed");
+    assert_eq!(renamed.old_path.as_deref(), Some("src/old.txt"));
+
+    let binary = diff.files.iter().find(|f| f.path == "logo.png").unwrap();
+    assert!(binary.is_binary);
+    assert!(binary.hunks.is_empty());
+}
+
+#[test]
+fn stops_at_size_limits() {
+    let mut test = TestRepo::new();
+    let oid = test.commit(
+        &[("a.txt", Some(LONG_FILE)), ("b.txt", Some("b\n")), ("c.txt", Some("c\n"))],
+        "Add files",
+    );
+    let commit = test.repo.find_commit(oid).unwrap();
+
+    let limits = DiffLimits {
+        max_file_lines: 5,
+        ..DiffLimits::default()
+    };
+    let diff = diff_commit(&test.repo, &commit, &limits).unwrap();
+    let large = diff.files.iter().find(|f| f.path == "a.txt").unwrap();
+    assert!(large.too_large);
+    assert!(large.hunks.is_empty());
+    assert_eq!(large.additions, 10);
+
+    let limits = DiffLimits {
+        max_files: 2,
+        ..DiffLimits::default()
+    };
+    let diff = diff_commit(&test.repo, &commit, &limits).unwrap();
+    assert_eq!(diff.files.len(), 2);
+    assert!(diff.truncated);
+}
+
+#[test]
+fn compares_a_branch_with_its_merge_base() {
+    let mut test = TestRepo::new();
+    let base = test.commit(&[("README.md", Some("hello\n"))], "Initial commit");
+    let head = test.commit(&[("feature.txt", Some("new\n"))], "Add feature");
+    test.repo
+        .branch("feature", &test.repo.find_commit(head).unwrap(), false)
+        .unwrap();
+    test.repo.branch("main-line", &test.repo.find_commit(base).unwrap(), false).unwrap();
+
+    let (from, to) = parse_compare_spec("main-line...feature").unwrap();
+    let comparison = compare(&test.repo, &from, &to, &DiffLimits::default()).unwrap();
+    assert_eq!(comparison.merge_base, Some(base.to_string()));
+    assert_eq!(comparison.commits.len(), 1);
+    assert_eq!(comparison.commits[0].summary, "Add feature");
+    assert_eq!(comparison.diff.files.len(), 1);
+    assert_eq!(comparison.diff.files[0].path, "feature.txt");
+
+    assert!(parse_compare_spec("main-line").is_err());
+    assert!(compare(&test.repo, "main-line", "missing", &DiffLimits::default()).is_err());
+}
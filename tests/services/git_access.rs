@@ -0,0 +1,98 @@
+use std::{
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+use gitcrab::services::git_access_service::{
+    cli_path, parse_repository_path, parse_ssh_command, post_push_tasks, spawn_task, GitCommand,
+    GitRequest, CLI_ENV,
+};
+use serial_test::serial;
+
+/// A stand-in for `gitcrab-cli` that appends its arguments to `calls` next to it.
+fn fake_cli() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("gitcrab-cli-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&dir).unwrap();
+    let cli = dir.join("gitcrab-cli");
+    fs::write(&cli, "#!/bin/sh\necho \"$@\" >> \"$(dirname \"$0\")/calls\"\n").unwrap();
+    fs::set_permissions(&cli, fs::Permissions::from_mode(0o755)).unwrap();
+    cli
+}
+
+/// Runs the tasks of a push like `gitcrab-serve` does and returns the recorded calls.
+fn push(cli: &Path, default_branch_moved: bool) -> Vec<String> {
+    let request = parse_ssh_command("git-receive-pack 'alice/api.git'").unwrap();
+    for task in post_push_tasks(default_branch_moved) {
+        spawn_task(cli, task, &request).unwrap().wait().unwrap();
+    }
+    let calls = fs::read_to_string(cli.with_file_name("calls")).unwrap_or_default();
+    let _ = fs::remove_dir_all(cli.parent().unwrap());
+    calls.lines().map(str::to_string).collect()
+}
+
+#[test]
+fn can_parse_ssh_commands() {
+    let request = parse_ssh_command("git-upload-pack '/alice/api.git'").unwrap();
+    assert_eq!(
+        request,
+        GitRequest {
+            command: GitCommand::UploadPack,
+            owner: "alice".to_string(),
+            name: "api".to_string(),
+        }
+    );
+
+    let request = parse_ssh_command("git-receive-pack 'owner/api'").unwrap();
+    assert_eq!(request.command, GitCommand::ReceivePack);
+    assert_eq!(request.display_path(), "owner/api.git");
+}
+
+#[test]
+fn rejects_unsupported_commands() {
+    assert!(parse_ssh_command("").is_err());
+    assert!(parse_ssh_command("bash -i").is_err());
+    assert!(parse_ssh_command("git-upload-archive 'owner/api.git'").is_err());
+}
+
+#[test]
+fn rejects_paths_outside_a_namespace() {
+    assert!(parse_repository_path("api.git").is_err());
+    assert!(parse_repository_path("/owner/../other/api.git").is_err());
+    assert!(parse_repository_path("/home/git/repositories/owner/api.git").is_err());
+    assert!(parse_repository_path("owner/a;pi.git").is_err());
+    assert_eq!(
+        parse_repository_path("/owner/api.git").unwrap(),
+        ("owner".to_string(), "api".to_string())
+    );
+}
+
+#[test]
+fn a_push_moving_the_default_branch_updates_the_search_index() {
+    assert!(push(&fake_cli(), true).contains(&"task search_index repo:alice/api".to_string()));
+    assert!(!push(&fake_cli(), false).iter().any(|call| call.contains("search_index")));
+}
+
+#[test]
+#[serial]
+fn cli_path_can_be_configured() {
+    std::env::set_var(CLI_ENV, "/usr/app/gitcrab-cli");
+    assert_eq!(cli_path().unwrap(), PathBuf::from("/usr/app/gitcrab-cli"));
+
+    std::env::remove_var(CLI_ENV);
+    assert_eq!(cli_path().unwrap().file_name().unwrap(), "gitcrab-cli");
+}
+
+#[test]
+fn every_push_updates_the_insights() {
+    for default_branch_moved in [true, false] {
+        assert!(push(&fake_cli(), default_branch_moved)
+            .contains(&"task insights repo:alice/api".to_string()));
+    }
+}
+
+#[test]
+fn a_missing_cli_fails_to_start_the_task() {
+    let request = parse_ssh_command("git-receive-pack 'alice/api.git'").unwrap();
+    assert!(spawn_task(Path::new("/nonexistent/gitcrab-cli"), "insights", &request).is_err());
+}
@@ -0,0 +1,45 @@
+use gitcrab::services::highlight_service::{detect_syntax, highlight};
+
+fn language(path: &str, first_line: &str) -> Option<String> {
+    detect_syntax(path, first_line).map(|syntax| syntax.name.clone())
+}
+
+#[test]
+fn detects_languages_by_name_extension_and_shebang() {
+    assert_eq!(language("src/main.rs", "").as_deref(), Some("Rust"));
+    assert_eq!(language("Cargo.toml", "").as_deref(), Some("TOML"));
+    assert_eq!(language("Cargo.lock", "").as_deref(), Some("TOML"));
+    assert_eq!(language("config/app.yaml", "").as_deref(), Some("YAML"));
+    assert_eq!(language("migrations/init.sql", "").as_deref(), Some("SQL"));
+    assert_eq!(language("Dockerfile", "").as_deref(), Some("Dockerfile"));
+    assert_eq!(language("Makefile", "").as_deref(), Some("Makefile"));
+    assert!(language("scripts/deploy", "#!/usr/bin/env bash").unwrap().contains("bash"));
+    assert_eq!(language("NOTES", "just some notes"), None);
+}
+
+#[test]
+fn produces_one_balanced_fragment_per_line() {
+    let source = "/* a comment\n   spanning <lines> */\nfn main() {}\n";
+    let highlighted = highlight("main.rs", source, 1024);
+    assert_eq!(highlighted.language.as_deref(), Some("Rust"));
+    assert_eq!(highlighted.lines.len(), 3);
+
+    for line in &highlighted.lines {
+        assert_eq!(line.matches("<span").count(), line.matches("</span>").count());
+        assert!(!line.contains('\n'));
+    }
+    // The comment is reopened on its second line.
+    assert!(highlighted.lines[1].starts_with(r#"<span class="hl-source hl-rust"><span class="hl-comment"#));
+    assert!(highlighted.lines[1].contains("&lt;lines&gt;"));
+}
+
+#[test]
+fn escapes_plain_text_and_large_files() {
+    let highlighted = highlight("notes.unknown", "<b>bold</b>\nsecond\n", 1024);
+    assert_eq!(highlighted.language, None);
+    assert_eq!(highlighted.lines, vec!["&lt;b&gt;bold&lt;/b&gt;", "second"]);
+
+    let highlighted = highlight("main.rs", "fn main() {}\n", 4);
+    assert_eq!(highlighted.language, None);
+    assert_eq!(highlighted.lines, vec!["fn main() {}"]);
+}
@@ -0,0 +1,78 @@
+use chrono::NaiveDate;
+use gitcrab::services::insights_service::{compute_insights, punchcard_grid, Contributor, PunchcardCell, ACTIVITY_WEEKS};
+
+use super::test_repo::TestRepo;
+
+#[test]
+fn unifies_contributors_with_the_mailmap() {
+    let mut test = TestRepo::new();
+    // 2023-11-14 23:13 on a Tuesday, then one commit every hour.
+    test.commit(
+        &[
+            ("a.txt", Some("1\n2\n3\n")),
+            (".mailmap", Some("Bob <bob@new.example> <bob@old.example>\n")),
+        ],
+        "Add a",
+    );
+    test.commit_as("bobby", "bob@old.example", &[("b.txt", Some("x\n"))], "Add b");
+    test.commit_as("Bob", "bob@new.example", &[("a.txt", Some("1\n2\n"))], "Shorten a");
+    let head = test.commit_as("Bob", "bob@new.example", &[("b.txt", Some("y\nz\n"))], "Change b");
+
+    let insights = compute_insights(&test.repo, 1_700_020_000).unwrap();
+    assert_eq!(insights.head_commit, Some(head.to_string()));
+    assert_eq!(insights.total_commits, 4);
+    assert_eq!(
+        insights.contributors,
+        vec![
+            Contributor {
+                name: "Bob".to_string(),
+                email: "bob@new.example".to_string(),
+                commits: 3,
+                additions: 3,
+                deletions: 2,
+            },
+            Contributor {
+                name: "Alice".to_string(),
+                email: "alice@example.com".to_string(),
+                commits: 1,
+                additions: 4,
+                deletions: 0,
+            },
+        ]
+    );
+
+    assert_eq!(insights.weeks.len(), ACTIVITY_WEEKS as usize);
+    let last = insights.weeks.last().unwrap();
+    assert_eq!(last.week_start, NaiveDate::from_ymd_opt(2023, 11, 13).unwrap());
+    assert_eq!(last.commits, 4);
+    assert_eq!(insights.weeks.iter().map(|week| week.commits).sum::<i32>(), 4);
+
+    let slots: Vec<(i16, i16, i32)> = insights
+        .punchcard
+        .iter()
+        .map(|cell| (cell.weekday, cell.hour, cell.commits))
+        .collect();
+    assert_eq!(slots, vec![(1, 23, 1), (2, 0, 1), (2, 1, 1), (2, 2, 1)]);
+}
+
+#[test]
+fn lays_out_the_punchcard() {
+    let grid = punchcard_grid(&[
+        PunchcardCell { weekday: 0, hour: 9, commits: 2 },
+        PunchcardCell { weekday: 6, hour: 23, commits: 1 },
+    ]);
+    assert_eq!(grid.len(), 7);
+    assert!(grid.iter().all(|row| row.len() == 24));
+    assert_eq!(grid[0][9], 2);
+    assert_eq!(grid[6][23], 1);
+    assert_eq!(grid.iter().flatten().sum::<i32>(), 3);
+}
+
+#[test]
+fn handles_empty_repositories() {
+    let test = TestRepo::new();
+    let insights = compute_insights(&test.repo, 1_700_000_000).unwrap();
+    assert_eq!(insights.head_commit, None);
+    assert_eq!(insights.total_commits, 0);
+    assert_eq!(insights.weeks.len(), ACTIVITY_WEEKS as usize);
+}
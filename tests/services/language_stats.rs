@@ -0,0 +1,46 @@
+use gitcrab::services::language_stats_service::{classify, is_excluded, language_stats};
+
+use super::test_repo::TestRepo;
+
+#[test]
+fn classifies_by_name_and_extension() {
+    assert_eq!(classify("src/main.rs").map(|l| l.name), Some("Rust"));
+    assert_eq!(classify("web/App.TSX").map(|l| l.name), Some("TypeScript"));
+    assert_eq!(classify("build/Makefile").map(|l| l.name), Some("Makefile"));
+    assert_eq!(classify("LICENSE"), None);
+
+    assert!(is_excluded("vendor/lib.c"));
+    assert!(is_excluded("crates/a/target/debug/build.rs"));
+    assert!(is_excluded("Cargo.lock"));
+    assert!(!is_excluded("src/vendors.rs"));
+}
+
+#[test]
+fn computes_shares_without_vendored_and_generated_files() {
+    let mut test = TestRepo::new();
+    let oid = test.commit(
+        &[
+            ("src/main.rs", Some(&"x".repeat(300))),
+            ("scripts/run.py", Some(&"x".repeat(100))),
+            ("vendor/dep.py", Some(&"x".repeat(1000))),
+            ("Cargo.lock", Some(&"x".repeat(1000))),
+            ("gen/api.rs", Some(&"x".repeat(1000))),
+            ("gen/keep.rs", Some(&"x".repeat(100))),
+            ("gen/.gitattributes", Some("keep.rs -linguist-generated\n")),
+            (".gitattributes", Some("# generated code\ngen/** linguist-generated=true\n")),
+            ("notes.txt", Some("unknown")),
+        ],
+        "Add files",
+    );
+    let commit = test.repo.find_commit(oid).unwrap();
+
+    let stats = language_stats(&test.repo, &commit).unwrap();
+    assert_eq!(stats.commit, oid.to_string());
+    assert_eq!(stats.total_bytes, 500);
+    let shares: Vec<(&str, u64, f64)> = stats
+        .languages
+        .iter()
+        .map(|l| (l.name.as_str(), l.bytes, l.percentage))
+        .collect();
+    assert_eq!(shares, vec![("Rust", 400, 80.0), ("Python", 100, 20.0)]);
+}
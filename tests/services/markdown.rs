@@ -0,0 +1,75 @@
+use gitcrab::services::markdown_service::{find_readme, render_markdown, render_readme, LinkContext};
+
+use super::test_repo::TestRepo;
+
+fn context<'a>(base_dir: &'a str, tree: Option<&'a git2::Tree<'a>>) -> LinkContext<'a> {
+    LinkContext {
+        repo_id: "7",
+        reference: "main",
+        base_dir,
+        tree,
+    }
+}
+
+#[test]
+fn renders_gfm_and_strips_scripts() {
+    let source = "# Title\n\n| a | b |\n|---|---|\n| 1 | 2 |\n\n- [x] done\n- [ ] todo\n\n<script>alert(1)</script>\n[x](javascript:alert(1))\n";
+    let html = render_markdown(source, &context("", None));
+
+    assert!(html.contains(r##"<h1 id="user-content-title"><a class="anchor" href="#user-content-title""##));
+    assert!(html.contains("<table>"));
+    assert!(html.contains(r#"<input disabled="" type="checkbox" checked="">"#));
+    assert!(!html.contains("<script>"));
+    assert!(!html.contains("javascript:"));
+}
+
+#[test]
+fn numbers_repeated_heading_anchors() {
+    let html = render_markdown("## Usage\n## Usage\n## What's `new`?\n", &context("", None));
+    assert!(html.contains(r#"id="user-content-usage""#));
+    assert!(html.contains(r#"id="user-content-usage-1""#));
+    assert!(html.contains(r#"id="user-content-whats-new""#));
+}
+
+#[test]
+fn rewrites_relative_links() {
+    let mut test = TestRepo::new();
+    test.commit(
+        &[("docs/guide.md", Some("guide\n")), ("docs/img/logo.png", Some("png")), ("LICENSE", Some("MIT\n"))],
+        "Add docs",
+    );
+    let tree = test.repo.head().unwrap().peel_to_tree().unwrap();
+    let source = "[license](../LICENSE) [img dir](img) [guide](guide.md#setup) [top](#intro) [site](https://example.com)\n\n![logo](./img/logo.png)\n";
+    let html = render_markdown(source, &context("docs", Some(&tree)));
+
+    assert!(html.contains(r#"href="/git_repos/7/blob/main/LICENSE""#));
+    assert!(html.contains(r#"href="/git_repos/7/tree/main/docs/img""#));
+    assert!(html.contains(r#"href="/git_repos/7/blob/main/docs/guide.md#user-content-setup""#));
+    assert!(html.contains(r##"href="#user-content-intro""##));
+    assert!(html.contains(r#"href="https://example.com""#));
+    assert!(html.contains(r#"src="/git_repos/7/raw/main/docs/img/logo.png""#));
+}
+
+#[test]
+fn finds_the_readme_of_a_directory() {
+    let mut test = TestRepo::new();
+    test.commit(
+        &[
+            ("readme.txt", Some("plain text\n")),
+            ("README.md", Some("# Hello\n")),
+            ("sub/README", Some("sub <readme>\n")),
+        ],
+        "Add readmes",
+    );
+    let tree = test.repo.head().unwrap().peel_to_tree().unwrap();
+    assert_eq!(find_readme(&tree).as_deref(), Some("README.md"));
+
+    let root = render_readme(&test.repo, &tree, "", "7", "main", 1024).unwrap();
+    assert!(root.html.contains("Hello</h1>"));
+
+    let sub = render_readme(&test.repo, &tree, "sub", "7", "main", 1024).unwrap();
+    assert_eq!(sub.path, "sub/README");
+    assert_eq!(sub.html, "<pre>sub &lt;readme&gt;\n</pre>");
+
+    assert!(render_readme(&test.repo, &tree, "", "7", "main", 2).is_none());
+}
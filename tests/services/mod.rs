@@ -0,0 +1,18 @@
+mod archive;
+mod blame;
+mod diff;
+mod git_access;
+mod highlight;
+mod insights;
+mod language_stats;
+mod markdown;
+mod raw;
+mod refs;
+mod repo_browse;
+mod repo_history;
+mod search;
+mod search_index;
+mod ssh_keys;
+mod test_repo;
+mod throttle;
+mod totp;
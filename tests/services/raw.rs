@@ -0,0 +1,64 @@
+use gitcrab::services::raw_service::{
+    content_disposition, guess_content_type, is_inline_type, parse_range, stream_blob, ByteRange,
+};
+
+use super::test_repo::TestRepo;
+
+#[test]
+fn guesses_safe_content_types() {
+    assert_eq!(guess_content_type("logo.PNG", b"\x89PNG"), "image/png");
+    assert_eq!(guess_content_type("manual.pdf", b"%PDF"), "application/pdf");
+    assert_eq!(guess_content_type("index.html", b"<script>alert(1)</script>"), "text/plain; charset=utf-8");
+    assert_eq!(guess_content_type("Makefile", b"all:\n\tcargo build\n"), "text/plain; charset=utf-8");
+    assert_eq!(guess_content_type("release.bin", b"\x7fELF\x00\x01"), "application/octet-stream");
+
+    assert!(is_inline_type("image/png"));
+    assert!(!is_inline_type("application/octet-stream"));
+}
+
+#[test]
+fn encodes_file_names_in_content_disposition() {
+    assert_eq!(
+        content_disposition("notes.txt", false),
+        "inline; filename=\"notes.txt\"; filename*=UTF-8''notes.txt"
+    );
+    assert_eq!(
+        content_disposition("rapport \"été\".pdf", true),
+        "attachment; filename=\"rapport __t__.pdf\"; filename*=UTF-8''rapport%20%22%C3%A9t%C3%A9%22.pdf"
+    );
+}
+
+#[test]
+fn parses_byte_ranges() {
+    assert_eq!(parse_range(None, 100), ByteRange::Full);
+    assert_eq!(parse_range(Some("bytes=0-9"), 100), ByteRange::Partial(0..=9));
+    assert_eq!(parse_range(Some("bytes=90-"), 100), ByteRange::Partial(90..=99));
+    assert_eq!(parse_range(Some("bytes=-10"), 100), ByteRange::Partial(90..=99));
+    assert_eq!(parse_range(Some("bytes=50-500"), 100), ByteRange::Partial(50..=99));
+    assert_eq!(parse_range(Some("bytes=100-"), 100), ByteRange::Unsatisfiable);
+    assert_eq!(parse_range(Some("bytes=0-1,5-6"), 100), ByteRange::Full);
+    assert_eq!(parse_range(Some("items=0-1"), 100), ByteRange::Full);
+    assert_eq!(parse_range(Some("bytes=9-1"), 100), ByteRange::Full);
+}
+
+#[tokio::test]
+async fn streams_only_the_requested_window_of_a_blob() {
+    let mut repo = TestRepo::new();
+    repo.commit(&[("notes.txt", Some("0123456789"))], "notes");
+    let oid = repo
+        .repo
+        .head()
+        .unwrap()
+        .peel_to_tree()
+        .unwrap()
+        .get_name("notes.txt")
+        .unwrap()
+        .id();
+
+    let (content_type, body) = stream_blob(repo.repo.path().to_path_buf(), oid, "notes.txt".to_string(), 2..5);
+    assert_eq!(content_type.await.unwrap(), "text/plain; charset=utf-8");
+    assert_eq!(&axum::body::to_bytes(body, usize::MAX).await.unwrap()[..], b"234");
+
+    let (_, body) = stream_blob(repo.repo.path().to_path_buf(), oid, "notes.txt".to_string(), 0..10);
+    assert_eq!(&axum::body::to_bytes(body, usize::MAX).await.unwrap()[..], b"0123456789");
+}
@@ -0,0 +1,91 @@
+use git2::{BranchType, Signature};
+use gitcrab::services::refs_service::{
+    create_branch, create_tag, delete_branch, list_branches, list_tags, set_default_branch,
+    RefsError,
+};
+
+use super::test_repo::TestRepo;
+
+#[test]
+fn lists_branches_with_ahead_and_behind_counts() {
+    let mut test = TestRepo::new();
+    test.commit(&[("README.md", Some("hello\n"))], "Initial commit");
+    let default = test.repo.head().unwrap().shorthand().unwrap().to_string();
+    create_branch(&test.repo, "feature/login", &default).unwrap();
+    test.commit(&[("main.txt", Some("main\n"))], "Work on the default branch");
+
+    let list = list_branches(&test.repo).unwrap();
+    assert_eq!(list.default_branch.as_deref(), Some(default.as_str()));
+    assert!(list.branches[0].is_default);
+
+    let feature = list.branches.iter().find(|b| b.name == "feature/login").unwrap();
+    assert_eq!((feature.ahead, feature.behind), (0, 1));
+    assert!(feature.merged);
+    assert_eq!(feature.commit.summary, "Initial commit");
+
+    assert!(matches!(
+        create_branch(&test.repo, "feature/login", &default),
+        Err(RefsError::AlreadyExists(_))
+    ));
+    assert!(matches!(
+        create_branch(&test.repo, "bad..name", &default),
+        Err(RefsError::InvalidName(_))
+    ));
+    assert!(matches!(
+        create_branch(&test.repo, "other", "missing"),
+        Err(RefsError::NotFound(_))
+    ));
+}
+
+#[test]
+fn deletes_only_merged_branches() {
+    let mut test = TestRepo::new();
+    let base = test.commit(&[("README.md", Some("hello\n"))], "Initial commit");
+    let default = test.repo.head().unwrap().shorthand().unwrap().to_string();
+    create_branch(&test.repo, "merged", &default).unwrap();
+    let head = test.commit(&[("unmerged.txt", Some("work\n"))], "Unmerged work");
+    test.repo
+        .branch("unmerged", &test.repo.find_commit(head).unwrap(), false)
+        .unwrap();
+    test.repo
+        .reference(&format!("refs/heads/{default}"), base, true, "reset")
+        .unwrap();
+
+    assert!(matches!(delete_branch(&test.repo, "unmerged"), Err(RefsError::NotMerged(_))));
+    assert!(matches!(delete_branch(&test.repo, &default), Err(RefsError::DefaultBranch(_))));
+    assert!(matches!(delete_branch(&test.repo, "missing"), Err(RefsError::NotFound(_))));
+    delete_branch(&test.repo, "merged").unwrap();
+    assert!(test.repo.find_branch("merged", BranchType::Local).is_err());
+}
+
+#[test]
+fn creates_lightweight_and_annotated_tags() {
+    let mut test = TestRepo::new();
+    test.commit(&[("README.md", Some("hello\n"))], "Initial commit");
+    let tagger = Signature::now("Alice", "alice@example.com").unwrap();
+
+    create_tag(&test.repo, "v1.0", "HEAD", None, &tagger).unwrap();
+    create_tag(&test.repo, "v1.1", "HEAD", Some("Release 1.1\n"), &tagger).unwrap();
+    assert!(matches!(
+        create_tag(&test.repo, "v1.0", "HEAD", None, &tagger),
+        Err(RefsError::AlreadyExists(_))
+    ));
+
+    let tags = list_tags(&test.repo).unwrap();
+    assert_eq!(tags.len(), 2);
+    assert!(!tags[0].annotated);
+    assert!(tags[1].annotated);
+    assert_eq!(tags[1].message.as_deref(), Some("Release 1.1"));
+    assert_eq!(tags[1].tagger_name.as_deref(), Some("Alice"));
+}
+
+#[test]
+fn changes_the_default_branch() {
+    let mut test = TestRepo::new();
+    test.commit(&[("README.md", Some("hello\n"))], "Initial commit");
+    create_branch(&test.repo, "develop", "HEAD").unwrap();
+
+    set_default_branch(&test.repo, "develop").unwrap();
+    assert_eq!(test.repo.head().unwrap().shorthand(), Some("develop"));
+    assert!(matches!(set_default_branch(&test.repo, "missing"), Err(RefsError::NotFound(_))));
+}
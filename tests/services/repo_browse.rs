@@ -0,0 +1,124 @@
+use gitcrab::services::repo_retrive_service::{
+    default_ref, list_refs, read_git_file_at, read_git_repository_structure,
+    read_git_structure_at, resolve_commit, split_ref_path, FileLimits,
+};
+
+use super::test_repo::TestRepo;
+
+#[test]
+fn handles_empty_repositories() {
+    let test = TestRepo::new();
+    assert_eq!(default_ref(&test.repo), None);
+    assert!(read_git_repository_structure(&test.repo).is_err());
+}
+
+#[test]
+fn falls_back_to_an_existing_branch() {
+    let mut test = TestRepo::new();
+    let oid = test.commit(&[("README.md", Some("hello\n"))], "Initial commit");
+    let head = test.repo.head().unwrap().shorthand().unwrap().to_string();
+    assert_eq!(default_ref(&test.repo), Some(head.clone()));
+
+    // HEAD pointing at a branch that does not exist.
+    test.repo
+        .branch("main", &test.repo.find_commit(oid).unwrap(), false)
+        .unwrap();
+    test.repo.set_head("refs/heads/missing").unwrap();
+    test.repo
+        .find_branch(&head, git2::BranchType::Local)
+        .unwrap()
+        .delete()
+        .unwrap();
+    assert_eq!(default_ref(&test.repo), Some("main".to_string()));
+}
+
+#[test]
+fn resolves_branches_tags_and_oids() {
+    let mut test = TestRepo::new();
+    let first = test.commit(&[("docs/guide.md", Some("v1\n"))], "First");
+    {
+        let commit = test.repo.find_commit(first).unwrap();
+        test.repo.branch("feature/docs", &commit, false).unwrap();
+        test.repo.tag_lightweight("v1.0", commit.as_object(), false).unwrap();
+    }
+    test.commit(&[("docs/guide.md", Some("v2\n"))], "Second");
+
+    for revision in ["feature/docs", "v1.0", &first.to_string(), &first.to_string()[..7]] {
+        assert_eq!(resolve_commit(&test.repo, revision).unwrap().id(), first);
+    }
+    assert!(resolve_commit(&test.repo, "nope").is_err());
+
+    assert_eq!(
+        split_ref_path(&test.repo, "feature/docs/docs/guide.md").unwrap(),
+        ("feature/docs".to_string(), "docs/guide.md".to_string())
+    );
+    assert_eq!(
+        split_ref_path(&test.repo, "v1.0").unwrap(),
+        ("v1.0".to_string(), String::new())
+    );
+    assert!(split_ref_path(&test.repo, "nope/docs").is_err());
+
+    let refs = list_refs(&test.repo).unwrap();
+    assert!(refs.branches.contains(&"feature/docs".to_string()));
+    assert_eq!(refs.tags, vec!["v1.0".to_string()]);
+}
+
+#[test]
+fn reads_one_directory_level_at_a_time() {
+    let mut test = TestRepo::new();
+    let oid = test.commit(
+        &[
+            ("src/lib.rs", Some("pub fn it() {}\n")),
+            ("src/nested/mod.rs", Some("\n")),
+            ("README.md", Some("hi\n")),
+        ],
+        "Initial commit",
+    );
+    let commit = test.repo.find_commit(oid).unwrap();
+
+    let root = read_git_structure_at(&test.repo, &commit, "").unwrap();
+    let names: Vec<_> = root.children.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, vec!["src", "README.md"]);
+    assert!(root.children[0].children.is_empty());
+    assert_eq!(root.children[1].size, Some(3));
+    assert!(root.children.iter().all(|c| c.content.is_none()));
+
+    let src = read_git_structure_at(&test.repo, &commit, "src").unwrap();
+    assert_eq!(src.name, "src");
+    let paths: Vec<_> = src.children.iter().map(|c| c.path.as_str()).collect();
+    assert_eq!(paths, vec!["src/nested", "src/lib.rs"]);
+    assert!(read_git_structure_at(&test.repo, &commit, "README.md").is_err());
+}
+
+#[test]
+fn reads_files_within_limits() {
+    let mut test = TestRepo::new();
+    let oid = test.commit(
+        &[("src/lib.rs", Some("pub fn it() {}\n")), ("logo.png", Some("\u{0}\u{1}png"))],
+        "Initial commit",
+    );
+    let commit = test.repo.find_commit(oid).unwrap();
+    let limits = FileLimits::default();
+
+    let file = read_git_file_at(&test.repo, &commit, "src/lib.rs", "1", &limits).unwrap();
+    assert_eq!(file.content, "pub fn it() {}\n");
+    assert_eq!(file.size, 15);
+    assert!(!file.is_binary && !file.too_large);
+    let blob_oid = commit.tree().unwrap().get_path("src/lib.rs".as_ref()).unwrap().id();
+    assert_eq!(file.oid, blob_oid.to_string());
+
+    let binary = read_git_file_at(&test.repo, &commit, "logo.png", "1", &limits).unwrap();
+    assert!(binary.is_binary);
+    assert!(binary.content.is_empty());
+
+    let small = FileLimits {
+        max_content_bytes: 4,
+        ..FileLimits::default()
+    };
+    let large = read_git_file_at(&test.repo, &commit, "src/lib.rs", "1", &small).unwrap();
+    assert!(large.too_large);
+    assert!(large.content.is_empty());
+
+    assert!(read_git_file_at(&test.repo, &commit, "src", "1", &limits).is_err());
+    assert!(read_git_file_at(&test.repo, &commit, "missing.rs", "1", &limits).is_err());
+}
@@ -0,0 +1,141 @@
+use gitcrab::services::{
+    diff_service::DiffLimits,
+    repo_history_service::{
+        commit_detail, commit_detail_at, list_commits, list_path_commits, RepoHistoryError,
+    },
+};
+
+use super::test_repo::TestRepo;
+
+#[test]
+fn lists_an_empty_repository() {
+    let test = TestRepo::new();
+    let page = list_commits(&test.repo, 1, 10).unwrap();
+    assert!(page.commits.is_empty());
+    assert!(!page.has_next);
+}
+
+#[test]
+fn paginates_history_newest_first() {
+    let mut test = TestRepo::new();
+    let oids: Vec<_> = (0..5)
+        .map(|i| test.commit(&[("counter.txt", Some(&i.to_string()))], &format!("commit {}", i)))
+        .collect();
+
+    let first = list_commits(&test.repo, 1, 2).unwrap();
+    assert_eq!(first.commits.len(), 2);
+    assert_eq!(first.commits[0].oid, oids[4].to_string());
+    assert_eq!(first.commits[0].summary, "commit 4");
+    assert_eq!(first.commits[0].short_oid.len(), 7);
+    assert!(!first.has_previous);
+    assert!(first.has_next);
+
+    let last = list_commits(&test.repo, 3, 2).unwrap();
+    assert_eq!(last.commits.len(), 1);
+    assert_eq!(last.commits[0].oid, oids[0].to_string());
+    assert!(last.has_previous);
+    assert!(!last.has_next);
+
+    let beyond = list_commits(&test.repo, usize::MAX, 2).unwrap();
+    assert!(beyond.commits.is_empty());
+    assert!(!beyond.has_next);
+}
+
+#[test]
+fn counts_changed_lines_per_file() {
+    let mut test = TestRepo::new();
+    let root = test.commit(
+        &[("README.md", Some("one\ntwo\nthree\n")), ("old.txt", Some("bye\n"))],
+        "Initial commit",
+    );
+    let oid = test.commit(
+        &[("README.md", Some("one\n2\nthree\nfour\n")), ("old.txt", None)],
+        "Update readme\n\nAnd remove old.txt",
+    );
+
+    let detail = commit_detail(&test.repo, &oid.to_string(), &DiffLimits::default()).unwrap();
+    assert_eq!(detail.parents, vec![root.to_string()]);
+    assert_eq!(detail.message, "Update readme\n\nAnd remove old.txt");
+    assert_eq!(detail.diff.files.len(), 2);
+
+    let readme = detail.diff.files.iter().find(|f| f.path == "README.md").unwrap();
+    assert_eq!((readme.status.as_str(), readme.additions, readme.deletions), ("modified", 2, 1));
+    let old = detail.diff.files.iter().find(|f| f.path == "old.txt").unwrap();
+    assert_eq!((old.status.as_str(), old.additions, old.deletions), ("deleted", 0, 1));
+    assert_eq!((detail.diff.total_additions, detail.diff.total_deletions), (2, 2));
+
+    let root = commit_detail(&test.repo, &root.to_string(), &DiffLimits::default()).unwrap();
+    assert!(root.parents.is_empty());
+    assert_eq!(root.diff.total_additions, 4);
+}
+
+#[test]
+fn rejects_unknown_commits() {
+    let test = TestRepo::new();
+    assert!(matches!(
+        commit_detail(&test.repo, "not-an-oid", &DiffLimits::default()),
+        Err(RepoHistoryError::InvalidOid(_))
+    ));
+    assert!(matches!(
+        commit_detail(
+            &test.repo,
+            "0123456789012345678901234567890123456789",
+            &DiffLimits::default()
+        ),
+        Err(RepoHistoryError::CommitNotFound(_))
+    ));
+}
+
+#[test]
+fn lists_commits_of_a_path_across_renames() {
+    let mut test = TestRepo::new();
+    let body = "fn main() {\n    println!(\"hello\");\n}\n";
+    let added = test.commit(&[("old.rs", Some(body)), ("other.txt", Some("a\n"))], "Add old.rs");
+    test.commit(&[("other.txt", Some("b\n"))], "Touch other.txt");
+    let renamed = test.commit(&[("old.rs", None), ("src/new.rs", Some(body))], "Move to src");
+    let edited = test.commit(
+        &[("src/new.rs", Some("fn main() {\n    println!(\"hello, world\");\n}\n"))],
+        "Greet the world",
+    );
+
+    let head = test.repo.find_commit(edited).unwrap();
+    let page = list_path_commits(&test.repo, &head, "src/new.rs", 1, 10).unwrap();
+    let commits: Vec<_> = page
+        .commits
+        .iter()
+        .map(|c| (c.summary.oid.clone(), c.path.as_str(), c.renamed_from.as_deref()))
+        .collect();
+    assert_eq!(
+        commits,
+        vec![
+            (edited.to_string(), "src/new.rs", None),
+            (renamed.to_string(), "src/new.rs", Some("old.rs")),
+            (added.to_string(), "old.rs", None),
+        ]
+    );
+
+    // Directories match every commit that changed something inside them.
+    let dir = list_path_commits(&test.repo, &head, "src", 1, 1).unwrap();
+    assert_eq!(dir.commits[0].summary.oid, edited.to_string());
+    assert!(dir.has_next);
+    let dir = list_path_commits(&test.repo, &head, "src", 2, 1).unwrap();
+    assert_eq!(dir.commits[0].summary.oid, renamed.to_string());
+    assert!(!dir.has_next);
+
+    let dir = list_path_commits(&test.repo, &head, "src", usize::MAX, 1).unwrap();
+    assert!(dir.commits.is_empty());
+    assert!(!dir.has_next);
+}
+
+#[test]
+fn limits_a_commit_diff_to_a_path() {
+    let mut test = TestRepo::new();
+    test.commit(&[("a.txt", Some("a\n"))], "Add a");
+    let oid = test.commit(&[("a.txt", Some("b\n")), ("dir/c.txt", Some("c\n"))], "Change both");
+
+    let detail =
+        commit_detail_at(&test.repo, &oid.to_string(), Some("dir"), &DiffLimits::default()).unwrap();
+    let paths: Vec<_> = detail.diff.files.iter().map(|f| f.path.as_str()).collect();
+    assert_eq!(paths, vec!["dir/c.txt"]);
+    assert_eq!((detail.diff.total_additions, detail.diff.total_deletions), (1, 0));
+}
@@ -0,0 +1,106 @@
+use gitcrab::services::search_service::{
+    find_files, search_code, SearchError, SearchMode, SearchQuery, SearchSettings,
+};
+
+use super::test_repo::TestRepo;
+
+fn query(pattern: &str, mode: SearchMode) -> SearchQuery {
+    SearchQuery {
+        pattern: pattern.to_string(),
+        mode,
+        case_sensitive: false,
+    }
+}
+
+#[test]
+fn finds_lines_with_context() {
+    let mut test = TestRepo::new();
+    let oid = test.commit(
+        &[
+            ("src/lib.rs", Some("one\ntwo\nfn Answer() {}\nthree\nfour\nfive\nsix\nfn answer_again() {}\n")),
+            ("README.md", Some("no match here\n")),
+            ("logo.png", Some("\0\u{1}answer")),
+        ],
+        "Add files",
+    );
+    let commit = test.repo.find_commit(oid).unwrap();
+    let settings = SearchSettings {
+        context_lines: 1,
+        ..SearchSettings::default()
+    };
+
+    let results = search_code(&test.repo, &commit, &query("answer", SearchMode::Literal), &settings).unwrap();
+    assert_eq!(results.total_matches, 2);
+    assert_eq!(results.files.len(), 1);
+    let file = &results.files[0];
+    assert_eq!(file.path, "src/lib.rs");
+    let chunks: Vec<Vec<(usize, bool)>> = file
+        .chunks
+        .iter()
+        .map(|chunk| chunk.lines.iter().map(|l| (l.number, l.is_match)).collect())
+        .collect();
+    assert_eq!(
+        chunks,
+        vec![vec![(2, false), (3, true), (4, false)], vec![(7, false), (8, true)]]
+    );
+
+    let regex = search_code(&test.repo, &commit, &query(r"fn \w+_again", SearchMode::Regex), &settings).unwrap();
+    assert_eq!(regex.total_matches, 1);
+    assert!(matches!(
+        search_code(&test.repo, &commit, &query("(", SearchMode::Regex), &settings),
+        Err(SearchError::InvalidPattern(_))
+    ));
+}
+
+#[test]
+fn stops_at_the_limits() {
+    let mut test = TestRepo::new();
+    let oid = test.commit(
+        &[("a.txt", Some("x\nx\nx\n")), ("big.txt", Some(&"x\n".repeat(100)))],
+        "Add files",
+    );
+    let commit = test.repo.find_commit(oid).unwrap();
+    let settings = SearchSettings {
+        max_file_bytes: 10,
+        max_matches: 2,
+        ..SearchSettings::default()
+    };
+
+    let results = search_code(&test.repo, &commit, &query("x", SearchMode::Literal), &settings).unwrap();
+    assert_eq!(results.total_matches, 2);
+    assert!(results.truncated);
+    assert!(!results.timed_out);
+
+    let settings = SearchSettings {
+        max_file_bytes: 10,
+        ..SearchSettings::default()
+    };
+    let results = search_code(&test.repo, &commit, &query("x", SearchMode::Literal), &settings).unwrap();
+    assert_eq!(results.skipped_files, 1);
+    assert_eq!(results.total_matches, 3);
+}
+
+#[test]
+fn ranks_file_names_by_fuzzy_match() {
+    let mut test = TestRepo::new();
+    let oid = test.commit(
+        &[
+            ("src/controllers/git_repo.rs", Some("a")),
+            ("src/services/repo_retrive_service.rs", Some("b")),
+            ("assets/views/git_repo/show.html", Some("c")),
+        ],
+        "Add files",
+    );
+    let commit = test.repo.find_commit(oid).unwrap();
+
+    let hits = find_files(&commit, "gitrepo.rs", 10).unwrap();
+    assert_eq!(hits[0].path, "src/controllers/git_repo.rs");
+    assert_eq!(hits.len(), 1);
+
+    let hits = find_files(&commit, "show", 10).unwrap();
+    assert_eq!(hits[0].path, "assets/views/git_repo/show.html");
+    let matched: String = hits[0].positions.iter().map(|&i| hits[0].path.chars().nth(i).unwrap()).collect();
+    assert_eq!(matched, "show");
+
+    assert!(find_files(&commit, "zzz", 10).unwrap().is_empty());
+}
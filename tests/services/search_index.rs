@@ -0,0 +1,205 @@
+use std::sync::Arc;
+
+use gitcrab::services::{
+    search_index_service::{
+        cached_index, default_branch_head, glob_to_regex, load_index, remove_index, search_index,
+        update_index, IndexQuery, IndexUpdate, IndexedRepo,
+    },
+    search_service::SearchSettings,
+};
+
+use super::test_repo::TestRepo;
+
+fn settings(test: &TestRepo) -> SearchSettings {
+    SearchSettings {
+        context_lines: 0,
+        // Inside `.git`, so commits leave it out.
+        index_dir: test.path.join(".git/search-index"),
+        ..SearchSettings::default()
+    }
+}
+
+fn query(text: &str) -> IndexQuery {
+    IndexQuery {
+        text: text.to_string(),
+        ..IndexQuery::default()
+    }
+}
+
+fn repos(test: &TestRepo) -> Vec<IndexedRepo> {
+    vec![IndexedRepo {
+        id: 1,
+        name: "alice/demo".to_string(),
+        path: test.path.join(".git"),
+    }]
+}
+
+fn found(test: &TestRepo, settings: &SearchSettings, query: &IndexQuery) -> Vec<String> {
+    search_index(&settings.index_dir, &repos(test), query, settings)
+        .unwrap()
+        .files
+        .into_iter()
+        .map(|file| file.matches.path)
+        .collect()
+}
+
+#[test]
+fn updates_incrementally() {
+    let mut test = TestRepo::new();
+    let settings = settings(&test);
+    test.commit(
+        &[("src/lib.rs", Some("fn needle() {}\n")), ("notes.txt", Some("hay\n"))],
+        "Add files",
+    );
+    let update = update_index(&settings.index_dir, 1, &test.repo, &settings).unwrap();
+    assert_eq!(
+        update,
+        IndexUpdate {
+            added: 2,
+            removed: 0,
+            rebuilt: true
+        }
+    );
+    assert_eq!(found(&test, &settings, &query("NEEDLE")), vec!["src/lib.rs"]);
+
+    let head = test.commit(
+        &[("src/lib.rs", None), ("notes.txt", Some("a needle in the hay\n"))],
+        "Move the needle",
+    );
+    let update = update_index(&settings.index_dir, 1, &test.repo, &settings).unwrap();
+    assert_eq!(
+        update,
+        IndexUpdate {
+            added: 1,
+            removed: 2,
+            rebuilt: false
+        }
+    );
+    assert_eq!(found(&test, &settings, &query("needle")), vec!["notes.txt"]);
+
+    let index = load_index(&settings.index_dir, 1).unwrap();
+    assert_eq!(index.commit, Some(head.to_string()));
+    assert_eq!(
+        update_index(&settings.index_dir, 1, &test.repo, &settings).unwrap(),
+        IndexUpdate::default()
+    );
+}
+
+#[test]
+fn keeps_indexes_in_memory_until_they_change() {
+    let mut test = TestRepo::new();
+    let settings = settings(&test);
+    test.commit(&[("a.txt", Some("first\n"))], "First");
+    update_index(&settings.index_dir, 1, &test.repo, &settings).unwrap();
+    let first = cached_index(&settings.index_dir, 1).unwrap();
+    assert!(Arc::ptr_eq(&first, &cached_index(&settings.index_dir, 1).unwrap()));
+
+    let head = test.commit(&[("b.txt", Some("second\n"))], "Second");
+    update_index(&settings.index_dir, 1, &test.repo, &settings).unwrap();
+    let second = cached_index(&settings.index_dir, 1).unwrap();
+    assert!(!Arc::ptr_eq(&first, &second));
+    assert_eq!(second.commit, Some(head.to_string()));
+
+    remove_index(&settings.index_dir, 1).unwrap();
+    assert_eq!(cached_index(&settings.index_dir, 1).unwrap().commit, None);
+}
+
+#[test]
+fn concurrent_updates_of_one_index_all_succeed() {
+    let mut test = TestRepo::new();
+    let settings = settings(&test);
+    let head = test.commit(&[("a.txt", Some("needle\n"))], "Add a file");
+    let git_dir = test.repo.path().to_path_buf();
+
+    let updates: Vec<_> = (0..8)
+        .map(|_| {
+            let (git_dir, settings) = (git_dir.clone(), settings.clone());
+            std::thread::spawn(move || {
+                let repo = git2::Repository::open(&git_dir).unwrap();
+                update_index(&settings.index_dir, 1, &repo, &settings)
+            })
+        })
+        .collect();
+    for update in updates {
+        update.join().unwrap().unwrap();
+    }
+
+    assert_eq!(load_index(&settings.index_dir, 1).unwrap().commit, Some(head.to_string()));
+    let leftovers = std::fs::read_dir(&settings.index_dir).unwrap().count();
+    assert_eq!(leftovers, 1);
+}
+
+#[test]
+fn filters_by_path_and_language() {
+    let mut test = TestRepo::new();
+    let settings = settings(&test);
+    test.commit(
+        &[
+            ("src/main.rs", Some("let target = 1;\n")),
+            ("src/deep/util.py", Some("target = 1\n")),
+            ("docs/target.md", Some("The target.\n")),
+        ],
+        "Add files",
+    );
+    update_index(&settings.index_dir, 1, &test.repo, &settings).unwrap();
+
+    let by_glob = |glob: &str| {
+        let mut paths = found(
+            &test,
+            &settings,
+            &IndexQuery {
+                path_glob: Some(glob.to_string()),
+                ..query("target")
+            },
+        );
+        paths.sort();
+        paths
+    };
+    assert_eq!(by_glob("*.rs"), vec!["src/main.rs"]);
+    assert_eq!(by_glob("src/**"), vec!["src/deep/util.py", "src/main.rs"]);
+    assert_eq!(by_glob("src/*"), vec!["src/main.rs"]);
+
+    let python = IndexQuery {
+        language: Some("python".to_string()),
+        ..query("target")
+    };
+    assert_eq!(found(&test, &settings, &python), vec!["src/deep/util.py"]);
+    assert!(found(&test, &settings, &query("no such text")).is_empty());
+}
+
+#[test]
+fn translates_globs() {
+    let regex = glob_to_regex("*.rs").unwrap();
+    assert!(regex.is_match("main.rs"));
+    assert!(regex.is_match("src/main.rs"));
+    assert!(!regex.is_match("main.rsx"));
+
+    let regex = glob_to_regex("src/**/mod.rs").unwrap();
+    assert!(regex.is_match("src/mod.rs"));
+    assert!(regex.is_match("src/a/b/mod.rs"));
+    assert!(!regex.is_match("lib/src/mod.rs"));
+}
+
+#[test]
+fn default_branch_head_follows_the_default_branch_only() {
+    let mut test = TestRepo::new();
+    let git_dir = test.repo.path().to_path_buf();
+    assert_eq!(default_branch_head(&git_dir), None);
+
+    let first = test.commit(&[("a.txt", Some("a\n"))], "first");
+    assert_eq!(default_branch_head(&git_dir), Some(first));
+
+    // A push to another branch leaves the default branch where it was.
+    {
+        let commit = test.repo.find_commit(first).unwrap();
+        let tree = commit.tree().unwrap();
+        let signature = git2::Signature::now("Bob", "bob@example.com").unwrap();
+        test.repo
+            .commit(Some("refs/heads/topic"), &signature, &signature, "topic", &tree, &[&commit])
+            .unwrap();
+    }
+    assert_eq!(default_branch_head(&git_dir), Some(first));
+
+    let second = test.commit(&[("a.txt", Some("b\n"))], "second");
+    assert_eq!(default_branch_head(&git_dir), Some(second));
+}
@@ -0,0 +1,47 @@
+use gitcrab::{models::sshes, services::ssh_service::{validate_public_key, SshKeyService}};
+
+#[test]
+fn accepts_openssh_public_keys() {
+    assert!(validate_public_key(
+        "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIGx3bS9FvH1BkqYXN1UmBR2e8wq0u5m6zmh6XaRlEk3b me@host"
+    )
+    .is_ok());
+}
+
+#[test]
+fn rejects_keys_that_could_inject_options() {
+    assert!(validate_public_key("").is_err());
+    assert!(validate_public_key("command=\"/bin/sh\" ssh-ed25519 AAAA").is_err());
+    assert!(validate_public_key("ssh-ed25519 AAAA\nssh-rsa BBBB").is_err());
+    assert!(validate_public_key("ssh-ed25519 AAAA\"bad").is_err());
+}
+
+#[test]
+fn sync_keys_pins_stored_keys_and_drops_the_rest() {
+    let home = std::env::temp_dir().join(format!("gitcrab-test-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(home.join(".ssh")).unwrap();
+    let authorized_keys = home.join(".ssh/authorized_keys");
+    std::fs::write(
+        &authorized_keys,
+        "ssh-ed25519 AAAAbare legacy@host\ncommand=\"gitcrab-serve --key-id 99\",no-pty ssh-rsa BBBB gone@host\n",
+    )
+    .unwrap();
+
+    let now = chrono::Local::now().fixed_offset();
+    let key = sshes::Model {
+        created_at: now,
+        updated_at: now,
+        id: 7,
+        public_key: Some("ssh-ed25519 AAAAC3Nz me@host".to_string()),
+        title: None,
+        user_id: 1,
+    };
+    let dropped = SshKeyService::new(home.to_str().unwrap()).sync_keys(&[key]).unwrap();
+
+    assert_eq!(dropped, 2);
+    let contents = std::fs::read_to_string(&authorized_keys).unwrap();
+    assert_eq!(contents.lines().count(), 1);
+    assert!(contents.starts_with("command=\"gitcrab-serve --key-id 7\","));
+    assert!(contents.trim_end().ends_with("ssh-ed25519 AAAAC3Nz me@host"));
+    std::fs::remove_dir_all(home).unwrap();
+}
@@ -0,0 +1,68 @@
+use std::{fs, path::PathBuf};
+
+use git2::{IndexAddOption, Oid, Repository, Signature, Time};
+
+/// A throwaway repository with a working tree, removed again when dropped.
+pub struct TestRepo {
+    pub path: PathBuf,
+    pub repo: Repository,
+    clock: i64,
+}
+
+impl TestRepo {
+    pub fn new() -> Self {
+        let path = std::env::temp_dir().join(format!("gitcrab-test-{}", uuid::Uuid::new_v4()));
+        let repo = Repository::init(&path).unwrap();
+        Self {
+            path,
+            repo,
+            clock: 1_700_000_000,
+        }
+    }
+
+    /// Writes (`Some`) or deletes (`None`) the given files and commits all changes on HEAD.
+    pub fn commit(&mut self, files: &[(&str, Option<&str>)], message: &str) -> Oid {
+        self.commit_as("Alice", "alice@example.com", files, message)
+    }
+
+    pub fn commit_as(
+        &mut self,
+        name: &str,
+        email: &str,
+        files: &[(&str, Option<&str>)],
+        message: &str,
+    ) -> Oid {
+        for (file, content) in files {
+            let target = self.path.join(file);
+            match content {
+                Some(content) => {
+                    fs::create_dir_all(target.parent().unwrap()).unwrap();
+                    fs::write(&target, content).unwrap();
+                }
+                None => fs::remove_file(&target).unwrap(),
+            }
+        }
+
+        let mut index = self.repo.index().unwrap();
+        index
+            .add_all(["*"].iter(), IndexAddOption::DEFAULT, None)
+            .unwrap();
+        index.update_all(["*"].iter(), None).unwrap();
+        index.write().unwrap();
+        let tree = self.repo.find_tree(index.write_tree().unwrap()).unwrap();
+
+        self.clock += 3600;
+        let signature = Signature::new(name, email, &Time::new(self.clock, 0)).unwrap();
+        let parent = self.repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+        self.repo
+            .commit(Some("HEAD"), &signature, &signature, message, &tree, &parents)
+            .unwrap()
+    }
+}
+
+impl Drop for TestRepo {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
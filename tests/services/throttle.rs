@@ -0,0 +1,34 @@
+use std::time::Duration;
+
+use gitcrab::services::throttle_service::{account_penalty, backoff, Penalty, ThrottleSettings};
+
+#[test]
+fn backoff_doubles_after_the_free_attempts() {
+    let settings = ThrottleSettings::default();
+    assert_eq!(backoff(0, 3, &settings), None);
+    assert_eq!(backoff(3, 3, &settings), None);
+    assert_eq!(backoff(4, 3, &settings), Some(Duration::from_secs(1)));
+    assert_eq!(backoff(5, 3, &settings), Some(Duration::from_secs(2)));
+    assert_eq!(backoff(7, 3, &settings), Some(Duration::from_secs(8)));
+}
+
+#[test]
+fn backoff_is_capped() {
+    let settings = ThrottleSettings {
+        max_backoff_secs: 60,
+        ..ThrottleSettings::default()
+    };
+    assert_eq!(backoff(20, 3, &settings), Some(Duration::from_secs(60)));
+    assert_eq!(backoff(u32::MAX, 0, &settings), Some(Duration::from_secs(60)));
+}
+
+#[test]
+fn locks_the_account_at_the_threshold() {
+    let settings = ThrottleSettings::default();
+    assert_eq!(account_penalty(1, &settings), Penalty::None);
+    assert_eq!(account_penalty(4, &settings), Penalty::Backoff(Duration::from_secs(1)));
+    assert_eq!(
+        account_penalty(settings.lockout_threshold, &settings),
+        Penalty::Lockout(Duration::from_secs(settings.lockout_secs))
+    );
+}
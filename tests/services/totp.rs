@@ -0,0 +1,77 @@
+use gitcrab::services::totp_service::{
+    code_at, generate_recovery_codes, generate_secret, hash_recovery_code, provisioning_uri,
+    qr_code_svg, step_at, verify, RECOVERY_CODE_COUNT,
+};
+
+/// The secret of the RFC 6238 test vectors, "12345678901234567890" in base32.
+const RFC_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+#[test]
+fn matches_the_rfc_6238_test_vectors() {
+    // The RFC lists 8 digit codes; 6 digit codes are their last 6 digits.
+    assert_eq!(code_at(RFC_SECRET, step_at(59)).unwrap(), "287082");
+    assert_eq!(code_at(RFC_SECRET, step_at(1_111_111_109)).unwrap(), "081804");
+    assert_eq!(code_at(RFC_SECRET, step_at(1_234_567_890)).unwrap(), "005924");
+    assert_eq!(code_at(RFC_SECRET, step_at(2_000_000_000)).unwrap(), "279037");
+}
+
+#[test]
+fn accepts_codes_within_the_drift_window() {
+    let now = 1_234_567_890;
+    let step = step_at(now);
+    let code = code_at(RFC_SECRET, step).unwrap();
+
+    assert_eq!(verify(RFC_SECRET, &code, now, None).unwrap(), Some(step));
+    assert_eq!(verify(RFC_SECRET, "005 924", now, None).unwrap(), Some(step));
+    assert_eq!(verify(RFC_SECRET, &code, now + 30, None).unwrap(), Some(step));
+    assert_eq!(verify(RFC_SECRET, &code, now - 30, None).unwrap(), Some(step));
+    assert_eq!(verify(RFC_SECRET, &code, now + 90, None).unwrap(), None);
+    assert_eq!(verify(RFC_SECRET, "000000", now, None).unwrap(), None);
+    assert_eq!(verify(RFC_SECRET, "12345", now, None).unwrap(), None);
+    assert_eq!(verify(RFC_SECRET, "abcdef", now, None).unwrap(), None);
+}
+
+#[test]
+fn rejects_codes_that_were_already_used() {
+    let now = 1_234_567_890;
+    let step = step_at(now);
+    let code = code_at(RFC_SECRET, step).unwrap();
+    let next = code_at(RFC_SECRET, step + 1).unwrap();
+
+    assert_eq!(verify(RFC_SECRET, &code, now, Some(step)).unwrap(), None);
+    assert_eq!(verify(RFC_SECRET, &next, now, Some(step)).unwrap(), Some(step + 1));
+}
+
+#[test]
+fn generates_usable_secrets() {
+    let secret = generate_secret();
+    assert_eq!(secret.len(), 32);
+    assert_ne!(secret, generate_secret());
+    assert!(code_at(&secret, 1).is_ok());
+    assert!(code_at("not base32!", 1).is_err());
+}
+
+#[test]
+fn can_build_the_provisioning_uri() {
+    let uri = provisioning_uri(RFC_SECRET, "user1@example.com", "GitCrab");
+    assert_eq!(
+        uri,
+        "otpauth://totp/GitCrab:user1%40example.com?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=GitCrab&algorithm=SHA1&digits=6&period=30"
+    );
+    assert!(qr_code_svg(&uri).unwrap().contains("<svg"));
+}
+
+#[test]
+fn recovery_codes_are_unique_and_forgiving() {
+    let codes = generate_recovery_codes();
+    assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+    let mut unique = codes.clone();
+    unique.sort();
+    unique.dedup();
+    assert_eq!(unique.len(), RECOVERY_CODE_COUNT);
+    assert!(codes.iter().all(|code| code.len() == 11 && code.chars().nth(5) == Some('-')));
+
+    assert_eq!(hash_recovery_code("abcde-fghjk"), hash_recovery_code(" ABCDE FGHJK "));
+    assert_eq!(hash_recovery_code("abcde-fghjk"), hash_recovery_code("abcdefghjk"));
+    assert_ne!(hash_recovery_code("abcde-fghjk"), hash_recovery_code("abcde-fghjm"));
+}
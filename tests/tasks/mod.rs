@@ -1 +1,2 @@
-
+mod namespace_repos;
+mod reset_two_factor;
@@ -0,0 +1,46 @@
+use gitcrab::{
+    app::App,
+    models::{git_repos, users},
+};
+use loco_rs::{boot::run_task, task, testing::prelude::*};
+use sea_orm::{ActiveModelTrait, ActiveValue, EntityTrait};
+use serial_test::serial;
+
+const USER1_PID: &str = "11111111-1111-1111-1111-111111111111";
+
+#[tokio::test]
+#[serial]
+async fn moves_repositories_out_of_pid_namespaces() {
+    let boot = boot_test::<App>().await.unwrap();
+    seed::<App>(&boot.app_context).await.unwrap();
+    let db = &boot.app_context.db;
+
+    let owner = users::Model::find_by_pid(db, USER1_PID).await.unwrap();
+    let base = std::path::PathBuf::from(env!("REPO_BASE_PATH"));
+    let old_path = base.join(USER1_PID).join("moved-crate.git");
+    let _ = std::fs::remove_dir_all(base.join(USER1_PID));
+    let _ = std::fs::remove_dir_all(base.join(owner.namespace()));
+    git2::Repository::init_bare(&old_path).unwrap();
+    let item = git_repos::ActiveModel {
+        name: ActiveValue::set(Some("moved-crate".to_string())),
+        path: ActiveValue::set(Some(old_path.to_string_lossy().to_string())),
+        user_id: ActiveValue::set(owner.id),
+        ..Default::default()
+    }
+    .insert(db)
+    .await
+    .unwrap();
+
+    run_task::<App>(&boot.app_context, Some(&"namespace_repos".to_string()), &task::Vars::default())
+        .await
+        .unwrap();
+
+    let new_path = base.join("user1").join("moved-crate.git");
+    assert!(new_path.join("HEAD").exists());
+    assert!(!old_path.exists());
+    let item = git_repos::Entity::find_by_id(item.id).one(db).await.unwrap().unwrap();
+    assert_eq!(item.path, Some(new_path.to_string_lossy().to_string()));
+
+    let _ = std::fs::remove_dir_all(base.join(USER1_PID));
+    let _ = std::fs::remove_dir_all(base.join(owner.namespace()));
+}
@@ -0,0 +1,36 @@
+use gitcrab::{app::App, models::users};
+use loco_rs::{boot::run_task, task, testing::prelude::*};
+use sea_orm::IntoActiveModel;
+use serial_test::serial;
+
+const USER1_PID: &str = "11111111-1111-1111-1111-111111111111";
+
+#[tokio::test]
+#[serial]
+async fn resets_two_factor_authentication() {
+    let boot = boot_test::<App>().await.unwrap();
+    seed::<App>(&boot.app_context).await.unwrap();
+    let db = &boot.app_context.db;
+
+    let user = users::Model::find_by_pid(db, USER1_PID).await.unwrap();
+    let (user, _) = user
+        .into_active_model()
+        .start_totp_enrollment(db)
+        .await
+        .unwrap()
+        .into_active_model()
+        .enable_totp(db, 0)
+        .await
+        .unwrap();
+    assert!(user.two_factor_enabled());
+
+    let vars = task::Vars::from_cli_args(vec![("email".to_string(), user.email.clone())]);
+    run_task::<App>(&boot.app_context, Some(&"reset_two_factor".to_string()), &vars)
+        .await
+        .unwrap();
+
+    let user = users::Model::find_by_pid(db, USER1_PID).await.unwrap();
+    assert!(!user.two_factor_enabled());
+    assert!(user.totp_secret.is_none());
+    assert_eq!(user.recovery_codes_left(), 0);
+}